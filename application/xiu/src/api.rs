@@ -2,18 +2,28 @@ use {
     anyhow::Result,
     axum::{
         extract::Query,
+        http::{header, StatusCode},
+        response::IntoResponse,
         routing::{get, post},
-        Json, Router,
+        Extension, Json, Router,
     },
+    commonlib::{
+        acl::{AccessControl, ConnectionAccess},
+        errors::AclErrorValue,
+    },
+    hyper::server::conn::AddrStream,
+    record::define::{RecordCommand, RecordCommandSender},
     serde::Deserialize,
     serde_json::Value,
-    std::sync::Arc,
+    std::{convert::Infallible, future::ready, sync::Arc},
     streamhub::{
         define::{self, RelayType, StreamHubEventSender},
+        statistics::metrics,
         stream::StreamIdentifier,
         utils::Uuid,
     },
     tokio::{self, sync::oneshot},
+    tower::service_fn,
 };
 
 #[derive(serde::Serialize)]
@@ -51,9 +61,17 @@ struct RelayStreamParams {
     relay_type: RelayType,
 }
 
+#[derive(Deserialize)]
+struct RecordStreamParams {
+    app_name: String,
+    stream_name: String,
+}
+
 #[derive(Clone)]
 struct ApiService {
     channel_event_producer: StreamHubEventSender,
+    //none if the record is not enabled
+    record_command_sender: Option<RecordCommandSender>,
 }
 
 impl ApiService {
@@ -62,7 +80,10 @@ impl ApiService {
             "Usage of xiu http api:
                 ./api/query_whole_streams(get) query whole streams' information or top streams' information.
                 ./api/query_stream(post) query stream information by identifier and uuid.
-                ./api/kick_off_client(post) kick off client by publish/subscribe id.\n",
+                ./api/kick_off_client(post) kick off client by publish/subscribe id.
+                ./api/start_record(post) start recording a stream by app_name and stream_name.
+                ./api/stop_record(post) stop recording a stream by app_name and stream_name.
+                ./metrics(get) query the metrics of the streams and sessions in OpenMetrics format.\n",
         )
     }
 
@@ -141,6 +162,27 @@ impl ApiService {
         }
     }
 
+    async fn metrics(&self) -> impl IntoResponse {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let hub_event = define::StreamHubEvent::ApiMetrics { result_sender };
+        if let Err(err) = self.channel_event_producer.send(hub_event) {
+            log::error!("send api metrics event error: {}", err);
+        }
+
+        match result_receiver.await {
+            Ok(text) => (
+                StatusCode::OK,
+                [(header::CONTENT_TYPE, metrics::CONTENT_TYPE)],
+                text,
+            ),
+            Err(err) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CONTENT_TYPE, "text/plain")],
+                err.to_string(),
+            ),
+        }
+    }
+
     async fn kick_off_client(&self, id: KickOffClientParams) -> Result<String> {
         let id_result = Uuid::from_str2(&id.uuid);
 
@@ -251,11 +293,80 @@ impl ApiService {
             }
         }
     }
+
+    async fn record_stream(
+        &self,
+        params: RecordStreamParams,
+        is_start: bool,
+    ) -> Json<ApiResponse<Value>> {
+        let record_command_sender = if let Some(sender) = &self.record_command_sender {
+            sender
+        } else {
+            let api_response = ApiResponse {
+                error_code: -1,
+                desp: String::from("record is not enabled"),
+                data: Value::Null,
+            };
+            return Json(api_response);
+        };
+
+        let (result_sender, result_receiver) = oneshot::channel();
+        let command = if is_start {
+            RecordCommand::Start {
+                app_name: params.app_name,
+                stream_name: params.stream_name,
+                result_sender,
+            }
+        } else {
+            RecordCommand::Stop {
+                app_name: params.app_name,
+                stream_name: params.stream_name,
+                result_sender,
+            }
+        };
+
+        if record_command_sender.send(command).is_err() {
+            log::error!("send api record command error");
+        }
+
+        match result_receiver.await {
+            Ok(Ok(())) => {
+                let api_response = ApiResponse {
+                    error_code: 0,
+                    desp: String::from("succ"),
+                    data: Value::Null,
+                };
+                Json(api_response)
+            }
+            Ok(Err(err)) => {
+                let api_response = ApiResponse {
+                    error_code: -1,
+                    desp: String::from("failed"),
+                    data: serde_json::json!(err.to_string()),
+                };
+                Json(api_response)
+            }
+            Err(err) => {
+                let api_response = ApiResponse {
+                    error_code: -1,
+                    desp: String::from("failed"),
+                    data: serde_json::json!(err.to_string()),
+                };
+                Json(api_response)
+            }
+        }
+    }
 }
 
-pub async fn run(producer: StreamHubEventSender, port: usize) {
+pub async fn run(
+    producer: StreamHubEventSender,
+    record_command_sender: Option<RecordCommandSender>,
+    port: usize,
+    access_control: Option<Arc<AccessControl>>,
+) {
     let api = Arc::new(ApiService {
         channel_event_producer: producer,
+        record_command_sender,
     });
 
     let api_root = api.clone();
@@ -271,6 +382,9 @@ pub async fn run(producer: StreamHubEventSender, port: usize) {
         api_query_stream.query_stream(stream).await
     };
 
+    let api_metrics = api.clone();
+    let metrics = move || async move { api_metrics.metrics().await };
+
     let api_kick_off = api.clone();
     let kick_off = move |Json(id): Json<KickOffClientParams>| async move {
         match api_kick_off.kick_off_client(id).await {
@@ -289,17 +403,55 @@ pub async fn run(producer: StreamHubEventSender, port: usize) {
         api_stop_relay_stream.stop_relay_stream(params).await
     };
 
+    let api_start_record = api.clone();
+    let start_record = move |Json(params): Json<RecordStreamParams>| async move {
+        api_start_record.record_stream(params, true).await
+    };
+
+    let api_stop_record = api.clone();
+    let stop_record = move |Json(params): Json<RecordStreamParams>| async move {
+        api_stop_record.record_stream(params, false).await
+    };
+
     let app = Router::new()
         .route("/", get(root))
         .route("/api/query_whole_streams", get(query_streams))
         .route("/api/query_stream", post(query_stream))
         .route("/api/kick_off_client", post(kick_off))
         .route("/api/start_relay_stream", post(start_relay_stream))
-        .route("/api/stop_relay_stream", post(stop_relay_stream));
+        .route("/api/stop_relay_stream", post(stop_relay_stream))
+        .route("/api/start_record", post(start_record))
+        .route("/api/stop_record", post(stop_record))
+        .route("/metrics", get(metrics));
+
+    /*The connections are checked by the publish rules of the access control when they
+    are accepted, the requests of a rejected connection are answered with the rejection.*/
+    let make_service = service_fn(move |conn: &AddrStream| {
+        let remote_addr = conn.remote_addr();
+        let access = ConnectionAccess::check(&access_control, remote_addr.ip(), false);
+        let router = match access.result() {
+            //the connection is counted while the router of the connection is alive
+            Ok(()) => app.clone().layer(Extension(access)),
+            Err(err) => {
+                log::warn!(
+                    "http api connection from {} is rejected: {}",
+                    remote_addr,
+                    err
+                );
+                metrics::inc_acl_rejection("api");
+                let status = match err.value {
+                    AclErrorValue::IpNotAllowed => StatusCode::FORBIDDEN,
+                    _ => StatusCode::TOO_MANY_REQUESTS,
+                };
+                Router::new().fallback(move || async move { status })
+            }
+        };
+        ready(Ok::<_, Infallible>(router))
+    });
 
     log::info!("Http api server listening on http://0.0.0.0:{}", port);
     axum::Server::bind(&([0, 0, 0, 0], port as u16).into())
-        .serve(app.into_make_service())
+        .serve(make_service)
         .await
         .unwrap();
 }
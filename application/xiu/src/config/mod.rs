@@ -2,9 +2,13 @@ pub mod errors;
 
 use commonlib::auth::AuthAlgorithm;
 use errors::ConfigError;
+use record::define::RecordFormat;
 use serde_derive::Deserialize;
 use std::fs;
+use std::net::Ipv4Addr;
 use std::vec::Vec;
+use streamhub::cluster::ClusterMode;
+use streamhub::queue::BackpressurePolicy;
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
@@ -13,8 +17,13 @@ pub struct Config {
     pub webrtc: Option<WebRTCConfig>,
     pub httpflv: Option<HttpFlvConfig>,
     pub hls: Option<HlsConfig>,
+    pub dash: Option<DashConfig>,
+    pub record: Option<RecordConfig>,
+    pub vod: Option<VodConfig>,
     pub httpapi: Option<HttpApiConfig>,
     pub httpnotify: Option<HttpNotifierConfig>,
+    pub streamhub: Option<StreamHubConfig>,
+    pub cluster: Option<ClusterConfig>,
     pub authsecret: AuthSecretConfig,
     pub log: Option<LogConfig>,
 }
@@ -37,6 +46,7 @@ impl Config {
                 pull: None,
                 push: None,
                 auth: None,
+                acl: None,
             });
         }
 
@@ -47,6 +57,11 @@ impl Config {
                 relay_enabled: false,
                 port: rtsp_port,
                 auth: None,
+                credential_auth: None,
+                acl: None,
+                rtmp_remux_enabled: None,
+                session_timeout: None,
+                multicast: None,
             });
         }
 
@@ -56,6 +71,8 @@ impl Config {
                 enabled: true,
                 port: webrtc_port,
                 auth: None,
+                acl: None,
+                rtmp_remux_enabled: None,
             });
         }
 
@@ -65,6 +82,7 @@ impl Config {
                 enabled: true,
                 port: httpflv_port,
                 auth: None,
+                acl: None,
             });
         }
 
@@ -74,7 +92,18 @@ impl Config {
                 enabled: true,
                 port: hls_port,
                 need_record: false,
+                low_latency: None,
+                part_duration: None,
+                fmp4: None,
+                storage: None,
+                root_dir: None,
+                memory_capacity: None,
+                dvr_window: None,
+                sign_secret: None,
+                sign_ttl: None,
+                sign_bind_ip: None,
                 auth: None,
+                acl: None,
             });
         }
 
@@ -89,8 +118,13 @@ impl Config {
             webrtc: webrtc_config,
             httpflv: httpflv_config,
             hls: hls_config,
+            dash: None,
+            record: None,
+            vod: None,
             httpapi: None,
             httpnotify: None,
+            streamhub: None,
+            cluster: None,
             authsecret: AuthSecretConfig::default(),
             log: log_config,
         }
@@ -105,18 +139,35 @@ pub struct RtmpConfig {
     pub pull: Option<RtmpPullConfig>,
     pub push: Option<Vec<RtmpPushConfig>>,
     pub auth: Option<AuthConfig>,
+    pub acl: Option<AclConfig>,
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct RtmpPullConfig {
     pub enabled: bool,
     pub address: String,
     pub port: u16,
+    //the backup origins(host:port) tried in order if the address cannot be connected
+    pub backup_addresses: Option<Vec<String>>,
+    //how long the players wait for the stream pulled from the origin, in seconds
+    pub wait_timeout: Option<u64>,
+    //stop pulling after the stream has no players for the time, in seconds
+    pub idle_timeout: Option<u64>,
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct RtmpPushConfig {
     pub enabled: bool,
     pub address: String,
     pub port: usize,
+    //only push the streams matching the pattern of app_name/stream_name
+    pub stream_pattern: Option<String>,
+    //rewrite the app/stream names on the remote server
+    pub remote_app_name: Option<String>,
+    pub remote_stream_name: Option<String>,
+    //appended to the remote stream name, e.g. the auth parameters
+    pub query: Option<String>,
+    //the reconnect intervals in milliseconds
+    pub reconnect_interval_min: Option<u64>,
+    pub reconnect_interval_max: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -124,7 +175,45 @@ pub struct RtspConfig {
     pub enabled: bool,
     pub port: usize,
     pub auth: Option<AuthConfig>,
+    //the Digest/Basic challenge of the rtsp clients(cameras, NVRs etc.)
+    pub credential_auth: Option<RtspCredentialAuthConfig>,
+    pub acl: Option<AclConfig>,
     pub relay_enabled: bool,
+    //remux the rtmp streams to rtsp
+    pub rtmp_remux_enabled: Option<bool>,
+    //the idle sessions are torn down after it(in seconds), 60 by default
+    pub session_timeout: Option<u64>,
+    //serve the players requesting multicast
+    pub multicast: Option<RtspMulticastConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtspMulticastConfig {
+    pub enabled: bool,
+    pub address_start: Ipv4Addr,
+    pub address_end: Ipv4Addr,
+    pub port_start: u16,
+    pub port_end: u16,
+    //16 by default
+    pub ttl: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RtspCredentialAuthConfig {
+    pub enabled: bool,
+    //digest by default
+    pub scheme: Option<RtspAuthScheme>,
+    pub realm: Option<String>,
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum RtspAuthScheme {
+    #[serde(rename = "basic")]
+    Basic,
+    #[serde(rename = "digest")]
+    Digest,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -132,6 +221,9 @@ pub struct WebRTCConfig {
     pub enabled: bool,
     pub port: usize,
     pub auth: Option<AuthConfig>,
+    pub acl: Option<AclConfig>,
+    //remux the rtmp streams to webrtc for whep playback
+    pub rtmp_remux_enabled: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -139,6 +231,7 @@ pub struct HttpFlvConfig {
     pub enabled: bool,
     pub port: usize,
     pub auth: Option<AuthConfig>,
+    pub acl: Option<AclConfig>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -147,7 +240,74 @@ pub struct HlsConfig {
     pub port: usize,
     //record or not
     pub need_record: bool,
+    //low-latency hls(partial segments, preload hints and blocking playlist reload)
+    pub low_latency: Option<bool>,
+    //the partial segment duration(ms) of low-latency hls
+    pub part_duration: Option<i64>,
+    //fMP4(CMAF) segments with #EXT-X-MAP instead of MPEG-TS segments
+    pub fmp4: Option<bool>,
+    //where the playlists and segments are stored, file by default
+    pub storage: Option<SegmentStorageType>,
+    //the root directory of the file storage
+    pub root_dir: Option<String>,
+    //the max bytes of the segments kept per stream by the memory storage
+    pub memory_capacity: Option<usize>,
+    //the duration(seconds) of the segments retained for timeshift
+    pub dvr_window: Option<u64>,
+    //every playlist/segment url must be signed by the secret if set
+    pub sign_secret: Option<String>,
+    //the seconds the signed segment urls are valid for
+    pub sign_ttl: Option<i64>,
+    //sign the client ip, disable it if the players are behind proxies
+    pub sign_bind_ip: Option<bool>,
+    pub auth: Option<AuthConfig>,
+    pub acl: Option<AclConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentStorageType {
+    #[serde(rename = "file")]
+    File,
+    #[serde(rename = "memory")]
+    Memory,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DashConfig {
+    pub enabled: bool,
+    pub port: usize,
+    //where the mpds and segments are stored, file by default
+    pub storage: Option<SegmentStorageType>,
+    //the root directory of the file storage
+    pub root_dir: Option<String>,
+    //the max bytes of the segments kept per stream by the memory storage
+    pub memory_capacity: Option<usize>,
     pub auth: Option<AuthConfig>,
+    pub acl: Option<AclConfig>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RecordConfig {
+    pub enabled: bool,
+    //record every published stream, or only the streams started by the http api
+    pub auto_record: Option<bool>,
+    //flv or mp4(fragmented), flv by default
+    pub format: Option<RecordFormat>,
+    //the path template of the record files(without the extension)
+    pub path: Option<String>,
+    //roll to a new file after the duration(seconds)
+    pub max_duration: Option<u64>,
+    //roll to a new file after the size(bytes)
+    pub max_size: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct VodConfig {
+    pub enabled: bool,
+    //the app name used by the rtmp/httpflv players to play the files
+    pub app_name: Option<String>,
+    //the directory of the flv files
+    pub root_dir: Option<String>,
 }
 
 pub enum LogLevel {
@@ -174,6 +334,8 @@ pub struct LogFile {
 #[derive(Debug, Deserialize, Clone)]
 pub struct HttpApiConfig {
     pub port: usize,
+    //the managers are checked by the publish rules
+    pub acl: Option<AclConfig>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -183,6 +345,46 @@ pub struct HttpNotifierConfig {
     pub on_unpublish: Option<String>,
     pub on_play: Option<String>,
     pub on_stop: Option<String>,
+    pub on_record: Option<String>,
+    pub on_hls_segment: Option<String>,
+    //a non-2xx response of on_publish/on_play rejects the session
+    pub sync: Option<bool>,
+    //sign the request bodies with HMAC-SHA256
+    pub secret: Option<String>,
+    //the timeout(ms) of every request
+    pub timeout: Option<u64>,
+    pub retries: Option<u32>,
+    //the first retry interval(ms), doubled after every retry
+    pub retry_interval: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct StreamHubConfig {
+    //the max count of frames(packets) queued for one subscriber
+    pub subscriber_queue_size: Option<usize>,
+    //what to do when a subscriber cannot keep up with the stream
+    pub backpressure_policy: Option<BackpressurePolicy>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct ClusterConfig {
+    pub enabled: bool,
+    //the unique id of the node in the cluster
+    pub node_id: String,
+    //the tcp port which receives the announcements of the other nodes
+    pub port: usize,
+    //the registry addresses(host:port) of the other nodes
+    pub peers: Vec<String>,
+    //the secret shared by the nodes to sign the announcements
+    pub secret: String,
+    //relay or redirect the players of the streams published on other nodes
+    pub mode: Option<ClusterMode>,
+    //the interval of announcing the local streams, in milliseconds
+    pub announce_interval: Option<u64>,
+    //the addresses(host:port) announced to the other nodes
+    pub rtmp_address: Option<String>,
+    pub httpflv_address: Option<String>,
+    pub hls_address: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -190,6 +392,10 @@ pub struct AuthSecretConfig {
     pub key: String,
     pub password: String,
     pub push_password: Option<String>,
+    //used for jwt(HS256) authentication
+    pub jwt_secret: Option<String>,
+    //the PEM public key file used for jwt(RS256) authentication
+    pub jwt_public_key: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -199,6 +405,23 @@ pub struct AuthConfig {
     pub algorithm: AuthAlgorithm,
 }
 
+//the ip access control of a listener
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AclConfig {
+    //the CIDR blocks(or ips) allowed/denied to publish, all are allowed if empty
+    pub publish_allow: Option<Vec<String>>,
+    pub publish_deny: Option<Vec<String>>,
+    //the CIDR blocks(or ips) allowed/denied to play
+    pub play_allow: Option<Vec<String>>,
+    pub play_deny: Option<Vec<String>>,
+    //the max concurrent connections of one ip
+    pub max_connections_per_ip: Option<usize>,
+    //the new connections per second of one ip
+    pub connection_rate: Option<f64>,
+    //the new connections allowed at once, connection_rate by default
+    pub connection_burst: Option<f64>,
+}
+
 pub fn load(cfg_path: &String) -> Result<Config, ConfigError> {
     let content = fs::read_to_string(cfg_path)?;
     let decoded_config = toml::from_str(&content[..]).unwrap();
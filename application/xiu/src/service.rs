@@ -1,35 +1,83 @@
-use crate::config::{AuthConfig, AuthSecretConfig};
-use commonlib::auth::AuthType;
+use crate::config::{AclConfig, AuthConfig, AuthSecretConfig, RtspAuthScheme, SegmentStorageType};
+use commonlib::auth::{AuthAlgorithm, AuthType};
 use rtmp::remuxer::RtmpRemuxer;
 use std::sync::Arc;
+use std::time::Duration;
 use xrtsp::relay::pull_client_manager::RtspPullClientManager;
+use xrtsp::remuxer::RtspRemuxer;
+use xwebrtc::remuxer::WebRTCRemuxer;
 
 use {
     super::api,
     super::config::Config,
     //https://rustcc.cn/article?id=6dcbf032-0483-4980-8bfe-c64a7dfb33c7
     anyhow::Result,
-    commonlib::auth::Auth,
+    commonlib::{
+        acl::{AccessControl, IpRule},
+        auth::Auth,
+        jwt::JwtVerifier,
+    },
+    dash::remuxer::DashRemuxer,
+    dash::server as dash_server,
+    hls::define::{HLS_MEMORY_STORAGE_CAPACITY, LL_HLS_PART_DURATION},
+    hls::playlist_notifier::PlaylistNotifier,
     hls::remuxer::HlsRemuxer,
     hls::server as hls_server,
+    hls::storage::{FileStorage, MemoryStorage, SharedSegmentStorage},
+    hls::url_signer::{UrlSigner, DEFAULT_SIGN_TTL},
     httpflv::server as httpflv_server,
+    record::{
+        define::{
+            RecordCommandSender, RecordSetting, VodSetting, DEFAULT_RECORD_PATH,
+            DEFAULT_VOD_APP_NAME, DEFAULT_VOD_ROOT_DIR,
+        },
+        manager::RecordManager,
+    },
     rtmp::{
-        relay::{pull_client::PullClient, push_client::PushClient},
+        relay::{
+            pull_client::PullClient,
+            push_client::PushClient,
+            push_target::{PushTarget, ReconnectBackoff},
+        },
         rtmp::RtmpServer,
     },
-    streamhub::{notify::http::HttpNotifier, notify::Notifier, StreamsHub},
+    streamhub::{
+        cluster::{gossip::GossipRegistry, ClusterMode, NodeInfo, StreamRegistry},
+        notify::http::{self, HttpNotifier},
+        notify::Notifier,
+        origin_pull::{DEFAULT_PULL_IDLE_TIMEOUT, DEFAULT_PULL_WAIT_TIMEOUT},
+        queue::DEFAULT_SUBSCRIBER_QUEUE_SIZE,
+        StreamsHub,
+    },
     tokio,
-    xrtsp::rtsp::RtspServer,
+    xrtsp::{
+        rtsp::RtspServer,
+        rtsp_auth::{AuthScheme, Credentials, ServerAuthenticator},
+        rtsp_multicast::MulticastConfig,
+    },
     xwebrtc::webrtc::WebRTCServer,
 };
 
 pub struct Service {
     cfg: Config,
+    //used by the http api to start/stop the recordings
+    record_command_sender: Option<RecordCommandSender>,
+    //notifies the record files and the hls segments besides the stream hub events
+    notifier: Option<Arc<dyn Notifier>>,
+    //the cluster registry, players are redirected by it in the redirect mode
+    registry: Option<Arc<dyn StreamRegistry>>,
+    cluster_mode: ClusterMode,
 }
 
 impl Service {
     pub fn new(cfg: Config) -> Self {
-        Service { cfg }
+        Service {
+            cfg,
+            record_command_sender: None,
+            notifier: None,
+            registry: None,
+            cluster_mode: ClusterMode::default(),
+        }
     }
 
     fn gen_auth(auth_config: &Option<AuthConfig>, authsecret: &AuthSecretConfig) -> Option<Auth> {
@@ -50,41 +98,120 @@ impl Service {
                     false => AuthType::None,
                 }
             };
-            Some(Auth::new(
+            let mut auth = Auth::new(
                 authsecret.key.clone(),
                 authsecret.password.clone(),
                 authsecret.push_password.clone(),
                 cfg.algorithm.clone(),
                 auth_type,
-            ))
+            );
+            if let AuthAlgorithm::Jwt = cfg.algorithm {
+                auth.set_jwt_verifier(Self::gen_jwt_verifier(authsecret));
+            }
+            Some(auth)
         } else {
             None
         }
     }
 
+    fn gen_jwt_verifier(authsecret: &AuthSecretConfig) -> JwtVerifier {
+        let mut verifier = JwtVerifier::new();
+        //an empty secret would accept the tokens signed by anyone
+        if let Some(secret) = authsecret.jwt_secret.as_ref().filter(|s| !s.is_empty()) {
+            verifier.set_hs256_secret(secret);
+        }
+        if let Some(public_key_path) = &authsecret.jwt_public_key {
+            match std::fs::read(public_key_path) {
+                Ok(pem) => {
+                    if let Err(err) = verifier.set_rs256_public_key(&pem) {
+                        log::error!("load jwt public key {} error: {}", public_key_path, err);
+                    }
+                }
+                Err(err) => {
+                    log::error!("read jwt public key {} error: {}", public_key_path, err);
+                }
+            }
+        }
+        verifier
+    }
+
+    //an invalid CIDR fails the startup rather than leaving the listener open
+    fn gen_access_control(acl_config: &Option<AclConfig>) -> Result<Option<Arc<AccessControl>>> {
+        let Some(cfg) = acl_config else {
+            return Ok(None);
+        };
+        let ip_rule = |allow: &Option<Vec<String>>, deny: &Option<Vec<String>>| {
+            IpRule::new(
+                allow.as_deref().unwrap_or_default(),
+                deny.as_deref().unwrap_or_default(),
+            )
+            .map_err(|err| anyhow::anyhow!("invalid acl configuration: {}", err))
+        };
+
+        let mut access_control = AccessControl::new(
+            ip_rule(&cfg.publish_allow, &cfg.publish_deny)?,
+            ip_rule(&cfg.play_allow, &cfg.play_deny)?,
+        );
+        if let Some(max_connections) = cfg.max_connections_per_ip {
+            access_control.set_max_connections_per_ip(max_connections);
+        }
+        if let Some(rate) = cfg.connection_rate {
+            access_control.set_connection_rate(rate, cfg.connection_burst.unwrap_or(rate));
+        }
+        Ok(Some(Arc::new(access_control)))
+    }
+
     pub async fn run(&mut self) -> Result<()> {
         let notifier: Option<Arc<dyn Notifier>> = if let Some(httpnotifier) = &self.cfg.httpnotify {
             if !httpnotifier.enabled {
                 None
             } else {
-                Some(Arc::new(HttpNotifier::new(
+                let mut http_notifier = HttpNotifier::new(
                     httpnotifier.on_publish.clone(),
                     httpnotifier.on_unpublish.clone(),
                     httpnotifier.on_play.clone(),
                     httpnotifier.on_stop.clone(),
-                )))
+                );
+                http_notifier.set_on_record_url(httpnotifier.on_record.clone());
+                http_notifier.set_on_hls_segment_url(httpnotifier.on_hls_segment.clone());
+                http_notifier.set_sync(httpnotifier.sync.unwrap_or(false));
+                http_notifier.set_secret(httpnotifier.secret.clone());
+                if let Some(timeout) = httpnotifier.timeout {
+                    http_notifier.set_timeout(Duration::from_millis(timeout));
+                }
+                http_notifier.set_retry(
+                    httpnotifier.retries.unwrap_or(http::DEFAULT_RETRIES),
+                    httpnotifier
+                        .retry_interval
+                        .map(Duration::from_millis)
+                        .unwrap_or(http::DEFAULT_RETRY_INTERVAL),
+                );
+                Some(Arc::new(http_notifier))
             }
         } else {
             None
         };
+        self.notifier = notifier.clone();
 
         let mut stream_hub = StreamsHub::new(notifier);
 
+        if let Some(streamhub_cfg) = &self.cfg.streamhub {
+            stream_hub.set_subscriber_queue(
+                streamhub_cfg
+                    .subscriber_queue_size
+                    .unwrap_or(DEFAULT_SUBSCRIBER_QUEUE_SIZE),
+                streamhub_cfg.backpressure_policy.unwrap_or_default(),
+            );
+        }
+
+        self.start_cluster(&mut stream_hub).await?;
         self.start_httpflv(&mut stream_hub).await?;
         self.start_hls(&mut stream_hub).await?;
+        self.start_dash(&mut stream_hub).await?;
         self.start_rtmp(&mut stream_hub).await?;
         self.start_rtsp(&mut stream_hub).await?;
         self.start_webrtc(&mut stream_hub).await?;
+        self.start_record(&mut stream_hub).await?;
         self.start_http_api_server(&mut stream_hub).await?;
         self.start_rtmp_remuxer(&mut stream_hub).await?;
 
@@ -95,17 +222,87 @@ impl Service {
         Ok(())
     }
 
+    async fn start_cluster(&mut self, stream_hub: &mut StreamsHub) -> Result<()> {
+        let cluster_cfg = &self.cfg.cluster;
+
+        if let Some(cluster_cfg_value) = cluster_cfg {
+            if !cluster_cfg_value.enabled {
+                return Ok(());
+            }
+
+            if cluster_cfg_value.secret.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "the secret of the cluster registry is not configured"
+                ));
+            }
+
+            let node = NodeInfo {
+                id: cluster_cfg_value.node_id.clone(),
+                rtmp_address: cluster_cfg_value.rtmp_address.clone(),
+                httpflv_address: cluster_cfg_value.httpflv_address.clone(),
+                hls_address: cluster_cfg_value.hls_address.clone(),
+            };
+            let mut registry = GossipRegistry::new(
+                node,
+                cluster_cfg_value.port,
+                cluster_cfg_value.peers.clone(),
+                cluster_cfg_value.secret.clone(),
+            );
+            if let Some(interval) = cluster_cfg_value.announce_interval {
+                registry.set_interval(Duration::from_millis(interval));
+            }
+            let registry = Arc::new(registry);
+
+            let registry_clone = registry.clone();
+            tokio::spawn(async move {
+                if let Err(err) = registry_clone.run().await {
+                    log::error!("cluster registry error: {}", err);
+                }
+            });
+
+            let mode = cluster_cfg_value.mode.unwrap_or_default();
+            stream_hub.set_registry(registry.clone(), mode);
+            self.registry = Some(registry);
+            self.cluster_mode = mode;
+        }
+
+        Ok(())
+    }
+
+    fn gen_vod_setting(&self) -> Option<VodSetting> {
+        match &self.cfg.vod {
+            Some(vod_cfg) if vod_cfg.enabled => Some(VodSetting {
+                app_name: vod_cfg
+                    .app_name
+                    .clone()
+                    .unwrap_or(String::from(DEFAULT_VOD_APP_NAME)),
+                root_dir: vod_cfg
+                    .root_dir
+                    .clone()
+                    .unwrap_or(String::from(DEFAULT_VOD_ROOT_DIR)),
+            }),
+            _ => None,
+        }
+    }
+
     async fn start_http_api_server(&mut self, stream_hub: &mut StreamsHub) -> Result<()> {
         let producer = stream_hub.get_hub_event_sender();
 
-        let http_api_port = if let Some(httpapi) = &self.cfg.httpapi {
-            httpapi.port
+        let (http_api_port, access_control) = if let Some(httpapi) = &self.cfg.httpapi {
+            (httpapi.port, Self::gen_access_control(&httpapi.acl)?)
         } else {
-            8000
+            (8000, None)
         };
 
+        let record_command_sender = self.record_command_sender.clone();
         tokio::spawn(async move {
-            api::run(producer, http_api_port).await;
+            api::run(
+                producer,
+                record_command_sender,
+                http_api_port,
+                access_control,
+            )
+            .await;
         });
         Ok(())
     }
@@ -118,11 +315,7 @@ impl Service {
                 return Ok(());
             }
 
-            let gop_num = if let Some(gop_num_val) = rtmp_cfg_value.gop_num {
-                gop_num_val
-            } else {
-                1
-            };
+            let gop_num = rtmp_cfg_value.gop_num.unwrap_or(1);
 
             let producer = stream_hub.get_hub_event_sender();
 
@@ -139,8 +332,31 @@ impl Service {
                         port = push_value.port
                     );
 
+                    let mut push_target = PushTarget::new(address);
+                    if let Some(stream_pattern) = &push_value.stream_pattern {
+                        push_target.set_stream_pattern(stream_pattern.clone());
+                    }
+                    if let Some(remote_app_name) = &push_value.remote_app_name {
+                        push_target.set_remote_app_name(remote_app_name.clone());
+                    }
+                    if let Some(remote_stream_name) = &push_value.remote_stream_name {
+                        push_target.set_remote_stream_name(remote_stream_name.clone());
+                    }
+                    if let Some(query) = &push_value.query {
+                        push_target.set_query(query.clone());
+                    }
+                    let backoff = ReconnectBackoff::default();
+                    push_target.set_backoff(ReconnectBackoff::new(
+                        push_value
+                            .reconnect_interval_min
+                            .map_or(backoff.min, Duration::from_millis),
+                        push_value
+                            .reconnect_interval_max
+                            .map_or(backoff.max, Duration::from_millis),
+                    ));
+
                     let mut push_client = PushClient::new(
-                        address,
+                        push_target,
                         stream_hub.get_client_event_consumer(),
                         producer.clone(),
                     );
@@ -153,29 +369,44 @@ impl Service {
                     stream_hub.set_rtmp_push_enabled(true);
                 }
             }
-            /*static pull*/
-            if let Some(pull_cfg_value) = &rtmp_cfg_value.pull {
-                if pull_cfg_value.enabled {
+            /*static pull, the pull client also pulls the streams from the other nodes of the cluster*/
+            let pull_cfg = rtmp_cfg_value.pull.as_ref().filter(|cfg| cfg.enabled);
+            if pull_cfg.is_some() || self.registry.is_some() {
+                let mut addresses = Vec::new();
+                if let Some(pull_cfg_value) = pull_cfg {
                     let address = format!(
                         "{ip}:{port}",
                         ip = pull_cfg_value.address,
                         port = pull_cfg_value.port
                     );
                     log::info!("start rtmp pull client from address: {}", address);
-                    let mut pull_client = PullClient::new(
-                        address,
-                        stream_hub.get_client_event_consumer(),
-                        producer.clone(),
-                    );
-
-                    tokio::spawn(async move {
-                        if let Err(err) = pull_client.run().await {
-                            log::error!("pull client error {}", err);
-                        }
-                    });
+                    addresses.push(address);
+                    if let Some(backup_addresses) = &pull_cfg_value.backup_addresses {
+                        addresses.extend(backup_addresses.iter().cloned());
+                    }
 
                     stream_hub.set_rtmp_pull_enabled(true);
+                    stream_hub.set_rtmp_pull_timeout(
+                        pull_cfg_value
+                            .wait_timeout
+                            .map_or(DEFAULT_PULL_WAIT_TIMEOUT, Duration::from_secs),
+                        pull_cfg_value
+                            .idle_timeout
+                            .map_or(DEFAULT_PULL_IDLE_TIMEOUT, Duration::from_secs),
+                    );
                 }
+
+                let mut pull_client = PullClient::new(
+                    addresses,
+                    stream_hub.get_client_event_consumer(),
+                    producer.clone(),
+                );
+
+                tokio::spawn(async move {
+                    if let Err(err) = pull_client.run().await {
+                        log::error!("pull client error {}", err);
+                    }
+                });
             }
 
             let listen_port = rtmp_cfg_value.port;
@@ -183,6 +414,12 @@ impl Service {
 
             let auth = Self::gen_auth(&rtmp_cfg_value.auth, &self.cfg.authsecret);
             let mut rtmp_server = RtmpServer::new(address, producer, gop_num, auth);
+            if let Some(vod_setting) = self.gen_vod_setting() {
+                rtmp_server.set_vod_setting(vod_setting);
+            }
+            if let Some(access_control) = Self::gen_access_control(&rtmp_cfg_value.acl)? {
+                rtmp_server.set_access_control(access_control);
+            }
             tokio::spawn(async move {
                 if let Err(err) = rtmp_server.run().await {
                     log::error!("rtmp server error: {}", err);
@@ -251,6 +488,42 @@ impl Service {
 
             let auth = Self::gen_auth(&rtsp_cfg_value.auth, &self.cfg.authsecret);
             let mut rtsp_server = RtspServer::new(address, producer, auth);
+            if let Some(access_control) = Self::gen_access_control(&rtsp_cfg_value.acl)? {
+                rtsp_server.set_access_control(access_control);
+            }
+            if let Some(session_timeout) = rtsp_cfg_value.session_timeout {
+                rtsp_server.set_session_timeout(Duration::from_secs(session_timeout));
+            }
+            if let Some(multicast) = &rtsp_cfg_value.multicast {
+                if multicast.enabled {
+                    rtsp_server.set_multicast(MulticastConfig {
+                        address_start: multicast.address_start,
+                        address_end: multicast.address_end,
+                        port_start: multicast.port_start,
+                        port_end: multicast.port_end,
+                        ttl: multicast.ttl.unwrap_or(16),
+                    });
+                }
+            }
+            if let Some(credential_auth) = &rtsp_cfg_value.credential_auth {
+                if credential_auth.enabled {
+                    let scheme = match credential_auth.scheme {
+                        Some(RtspAuthScheme::Basic) => AuthScheme::Basic,
+                        _ => AuthScheme::Digest,
+                    };
+                    rtsp_server.set_authenticator(ServerAuthenticator::new(
+                        scheme,
+                        credential_auth
+                            .realm
+                            .clone()
+                            .unwrap_or_else(|| String::from("xiu")),
+                        Credentials {
+                            username: credential_auth.username.clone(),
+                            password: credential_auth.password.clone(),
+                        },
+                    ));
+                }
+            }
             tokio::spawn(async move {
                 if let Err(err) = rtsp_server.run().await {
                     log::error!("rtsp server error: {}", err);
@@ -269,6 +542,21 @@ impl Service {
                     }
                 });
             }
+
+            let rtmp_enabled = self.cfg.rtmp.as_ref().is_some_and(|cfg| cfg.enabled);
+            if rtsp_cfg_value.rtmp_remux_enabled.unwrap_or(false) && rtmp_enabled {
+                let mut rtsp_remuxer = RtspRemuxer::new(
+                    stream_hub.get_client_event_consumer(),
+                    stream_hub.get_hub_event_sender(),
+                );
+                stream_hub.set_rtsp_remuxer_enabled(true);
+
+                tokio::spawn(async move {
+                    if let Err(err) = rtsp_remuxer.run().await {
+                        log::error!("rtsp remuxer error: {}", err);
+                    }
+                });
+            }
         }
 
         Ok(())
@@ -289,11 +577,29 @@ impl Service {
 
             let auth = Self::gen_auth(&webrtc_cfg_value.auth, &self.cfg.authsecret);
             let mut webrtc_server = WebRTCServer::new(address, producer, auth);
+            if let Some(access_control) = Self::gen_access_control(&webrtc_cfg_value.acl)? {
+                webrtc_server.set_access_control(access_control);
+            }
             tokio::spawn(async move {
                 if let Err(err) = webrtc_server.run().await {
                     log::error!("webrtc server error: {}", err);
                 }
             });
+
+            let rtmp_enabled = self.cfg.rtmp.as_ref().is_some_and(|cfg| cfg.enabled);
+            if webrtc_cfg_value.rtmp_remux_enabled.unwrap_or(false) && rtmp_enabled {
+                let mut webrtc_remuxer = WebRTCRemuxer::new(
+                    stream_hub.get_client_event_consumer(),
+                    stream_hub.get_hub_event_sender(),
+                );
+                stream_hub.set_webrtc_remuxer_enabled(true);
+
+                tokio::spawn(async move {
+                    if let Err(err) = webrtc_remuxer.run().await {
+                        log::error!("webrtc remuxer error: {}", err);
+                    }
+                });
+            }
         }
 
         Ok(())
@@ -310,8 +616,13 @@ impl Service {
             let event_producer = stream_hub.get_hub_event_sender();
 
             let auth = Self::gen_auth(&httpflv_cfg_value.auth, &self.cfg.authsecret);
+            let vod_setting = self.gen_vod_setting();
+            let access_control = Self::gen_access_control(&httpflv_cfg_value.acl)?;
             tokio::spawn(async move {
-                if let Err(err) = httpflv_server::run(event_producer, port, auth).await {
+                if let Err(err) =
+                    httpflv_server::run(event_producer, port, auth, vod_setting, access_control)
+                        .await
+                {
                     log::error!("httpflv server error: {}", err);
                 }
             });
@@ -320,6 +631,21 @@ impl Service {
         Ok(())
     }
 
+    fn gen_segment_storage(
+        storage_type: Option<SegmentStorageType>,
+        root_dir: &Option<String>,
+        memory_capacity: Option<usize>,
+    ) -> SharedSegmentStorage {
+        match storage_type {
+            Some(SegmentStorageType::Memory) => Arc::new(MemoryStorage::new(
+                memory_capacity.unwrap_or(HLS_MEMORY_STORAGE_CAPACITY),
+            )),
+            Some(SegmentStorageType::File) | None => {
+                Arc::new(FileStorage::new(root_dir.as_deref().unwrap_or(".")))
+            }
+        }
+    }
+
     async fn start_hls(&mut self, stream_hub: &mut StreamsHub) -> Result<()> {
         let hls_cfg = &self.cfg.hls;
 
@@ -328,13 +654,35 @@ impl Service {
                 return Ok(());
             }
 
+            let storage = Self::gen_segment_storage(
+                hls_cfg_value.storage,
+                &hls_cfg_value.root_dir,
+                hls_cfg_value.memory_capacity,
+            );
+
             let event_producer = stream_hub.get_hub_event_sender();
             let cient_event_consumer = stream_hub.get_client_event_consumer();
             let mut hls_remuxer = HlsRemuxer::new(
                 cient_event_consumer,
                 event_producer,
                 hls_cfg_value.need_record,
+                storage.clone(),
             );
+            hls_remuxer.set_fmp4(hls_cfg_value.fmp4.unwrap_or(false));
+            if let Some(dvr_window) = hls_cfg_value.dvr_window {
+                hls_remuxer.set_dvr_window(dvr_window as i64 * 1000);
+            }
+            if let Some(notifier) = &self.notifier {
+                hls_remuxer.set_notifier(notifier.clone());
+            }
+
+            let playlist_notifier = PlaylistNotifier::new();
+            if hls_cfg_value.low_latency.unwrap_or(false) {
+                hls_remuxer.set_low_latency(
+                    hls_cfg_value.part_duration.unwrap_or(LL_HLS_PART_DURATION),
+                    playlist_notifier.clone(),
+                );
+            }
 
             tokio::spawn(async move {
                 if let Err(err) = hls_remuxer.run().await {
@@ -344,8 +692,29 @@ impl Service {
 
             let port = hls_cfg_value.port;
             let auth = Self::gen_auth(&hls_cfg_value.auth, &self.cfg.authsecret);
+            let registry = self
+                .registry
+                .clone()
+                .filter(|_| self.cluster_mode == ClusterMode::Redirect);
+            let url_signer = hls_cfg_value.sign_secret.clone().map(|secret| {
+                let mut url_signer = UrlSigner::new(secret);
+                url_signer.set_ttl(hls_cfg_value.sign_ttl.unwrap_or(DEFAULT_SIGN_TTL));
+                url_signer.set_bind_ip(hls_cfg_value.sign_bind_ip.unwrap_or(true));
+                url_signer
+            });
+            let access_control = Self::gen_access_control(&hls_cfg_value.acl)?;
             tokio::spawn(async move {
-                if let Err(err) = hls_server::run(port, auth).await {
+                if let Err(err) = hls_server::run(
+                    port,
+                    auth,
+                    playlist_notifier,
+                    storage,
+                    registry,
+                    url_signer,
+                    access_control,
+                )
+                .await
+                {
                     log::error!("hls server error: {}", err);
                 }
             });
@@ -354,4 +723,82 @@ impl Service {
 
         Ok(())
     }
+
+    async fn start_dash(&mut self, stream_hub: &mut StreamsHub) -> Result<()> {
+        let dash_cfg = &self.cfg.dash;
+
+        if let Some(dash_cfg_value) = dash_cfg {
+            if !dash_cfg_value.enabled {
+                return Ok(());
+            }
+
+            let storage = Self::gen_segment_storage(
+                dash_cfg_value.storage,
+                &dash_cfg_value.root_dir,
+                dash_cfg_value.memory_capacity,
+            );
+
+            let event_producer = stream_hub.get_hub_event_sender();
+            let cient_event_consumer = stream_hub.get_client_event_consumer();
+            let mut dash_remuxer =
+                DashRemuxer::new(cient_event_consumer, event_producer, storage.clone());
+
+            tokio::spawn(async move {
+                if let Err(err) = dash_remuxer.run().await {
+                    log::error!("dash remuxer error: {}", err);
+                }
+            });
+
+            let port = dash_cfg_value.port;
+            let auth = Self::gen_auth(&dash_cfg_value.auth, &self.cfg.authsecret);
+            let access_control = Self::gen_access_control(&dash_cfg_value.acl)?;
+            tokio::spawn(async move {
+                if let Err(err) = dash_server::run(port, auth, access_control, storage).await {
+                    log::error!("dash server error: {}", err);
+                }
+            });
+            stream_hub.set_dash_enabled(true);
+        }
+
+        Ok(())
+    }
+
+    async fn start_record(&mut self, stream_hub: &mut StreamsHub) -> Result<()> {
+        let record_cfg = &self.cfg.record;
+
+        if let Some(record_cfg_value) = record_cfg {
+            if !record_cfg_value.enabled {
+                return Ok(());
+            }
+
+            let setting = RecordSetting {
+                format: record_cfg_value.format.unwrap_or_default(),
+                path: record_cfg_value
+                    .path
+                    .clone()
+                    .unwrap_or(String::from(DEFAULT_RECORD_PATH)),
+                max_duration: record_cfg_value.max_duration.unwrap_or(0),
+                max_size: record_cfg_value.max_size.unwrap_or(0),
+            };
+
+            let event_producer = stream_hub.get_hub_event_sender();
+            let cient_event_consumer = stream_hub.get_client_event_consumer();
+            let mut record_manager =
+                RecordManager::new(cient_event_consumer, event_producer, setting);
+            record_manager.set_auto_record(record_cfg_value.auto_record.unwrap_or(true));
+            if let Some(notifier) = &self.notifier {
+                record_manager.set_notifier(notifier.clone());
+            }
+            self.record_command_sender = Some(record_manager.get_command_sender());
+
+            tokio::spawn(async move {
+                if let Err(err) = record_manager.run().await {
+                    log::error!("record manager error: {}", err);
+                }
+            });
+            stream_hub.set_record_enabled(true);
+        }
+
+        Ok(())
+    }
 }
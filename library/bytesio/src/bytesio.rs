@@ -68,6 +68,12 @@ impl UdpIO {
         None
     }
 
+    //the hops of the datagrams sent to a multicast group
+    pub fn set_multicast_ttl(&self, ttl: u32) -> Result<(), BytesIOError> {
+        self.socket.set_multicast_ttl_v4(ttl)?;
+        Ok(())
+    }
+
     pub fn get_local_port(&self) -> Option<u16> {
         if let Ok(local_addr) = self.socket.local_addr() {
             log::info!("local address: {}", local_addr);
@@ -0,0 +1,164 @@
+//ITU-T G.711: the 16 bit linear pcm is compressed to 8 bit A-law or mu-law samples
+
+//the sample rate of G.711 is always 8000, and one byte is one sample
+pub const G711_SAMPLE_RATE: u32 = 8000;
+
+const SIGN_BIT: u8 = 0x80;
+const QUANT_MASK: u8 = 0x0F;
+const SEG_SHIFT: u8 = 4;
+const SEG_MASK: u8 = 0x70;
+
+//the bias of mu-law, and the max linear value which can be encoded
+const ULAW_BIAS: i32 = 0x84;
+const ULAW_CLIP: i32 = 32635;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum G711Law {
+    //PCMA
+    ALaw,
+    //PCMU
+    MuLaw,
+}
+
+impl G711Law {
+    pub fn decode(&self, data: &[u8]) -> Vec<i16> {
+        match self {
+            G711Law::ALaw => data.iter().map(|sample| alaw_to_linear(*sample)).collect(),
+            G711Law::MuLaw => data.iter().map(|sample| ulaw_to_linear(*sample)).collect(),
+        }
+    }
+
+    pub fn encode(&self, pcm: &[i16]) -> Vec<u8> {
+        match self {
+            G711Law::ALaw => pcm.iter().map(|sample| linear_to_alaw(*sample)).collect(),
+            G711Law::MuLaw => pcm.iter().map(|sample| linear_to_ulaw(*sample)).collect(),
+        }
+    }
+}
+
+//the segment(exponent) of a 13/14 bit magnitude
+fn segment(magnitude: i32, first_segment_end: i32) -> u8 {
+    let mut segment_end = first_segment_end;
+    for segment in 0..8 {
+        if magnitude <= segment_end {
+            return segment;
+        }
+        segment_end = (segment_end << 1) | 1;
+    }
+    8
+}
+
+pub fn alaw_to_linear(sample: u8) -> i16 {
+    let sample = sample ^ 0x55;
+
+    let mut linear = ((sample & QUANT_MASK) as i32) << 4;
+    match (sample & SEG_MASK) >> SEG_SHIFT {
+        0 => linear += 8,
+        1 => linear += 0x108,
+        segment => {
+            linear += 0x108;
+            linear <<= segment - 1;
+        }
+    }
+
+    if sample & SIGN_BIT > 0 {
+        linear as i16
+    } else {
+        -linear as i16
+    }
+}
+
+pub fn linear_to_alaw(sample: i16) -> u8 {
+    //the A-law works on the 13 bit magnitude
+    let (mask, magnitude) = if sample >= 0 {
+        (0xD5, (sample >> 3) as i32)
+    } else {
+        (0x55, -(sample as i32 >> 3) - 1)
+    };
+
+    let segment = segment(magnitude, 0x1F);
+    if segment >= 8 {
+        return 0x7F ^ mask;
+    }
+
+    let quant = if segment < 2 {
+        (magnitude >> 1) as u8
+    } else {
+        (magnitude >> segment) as u8
+    } & QUANT_MASK;
+    ((segment << SEG_SHIFT) | quant) ^ mask
+}
+
+pub fn ulaw_to_linear(sample: u8) -> i16 {
+    let sample = !sample;
+
+    let mut linear =
+        ((((sample & QUANT_MASK) as i32) << 3) + ULAW_BIAS) << ((sample & SEG_MASK) >> SEG_SHIFT);
+    linear -= ULAW_BIAS;
+
+    if sample & SIGN_BIT > 0 {
+        -linear as i16
+    } else {
+        linear as i16
+    }
+}
+
+pub fn linear_to_ulaw(sample: i16) -> u8 {
+    let (mask, magnitude) = if sample < 0 {
+        (0x7F, -(sample as i32))
+    } else {
+        (0xFF, sample as i32)
+    };
+    let magnitude = magnitude.min(ULAW_CLIP) + ULAW_BIAS;
+
+    let segment = segment(magnitude >> 7, 0x01);
+    let quant = ((magnitude >> (segment + 3)) as u8) & QUANT_MASK;
+    ((segment << SEG_SHIFT) | quant) ^ mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alaw_to_linear, linear_to_alaw, linear_to_ulaw, ulaw_to_linear, G711Law};
+
+    #[test]
+    fn test_decode() {
+        //the silence of the two laws
+        assert_eq!(alaw_to_linear(0xD5), 8);
+        assert_eq!(alaw_to_linear(0x55), -8);
+        assert_eq!(ulaw_to_linear(0xFF), 0);
+        assert_eq!(ulaw_to_linear(0x7F), 0);
+        //the max values
+        assert_eq!(alaw_to_linear(0xAA), 32256);
+        assert_eq!(alaw_to_linear(0x2A), -32256);
+        assert_eq!(ulaw_to_linear(0x80), 32124);
+        assert_eq!(ulaw_to_linear(0x00), -32124);
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(linear_to_alaw(0), 0xD5);
+        assert_eq!(linear_to_alaw(i16::MAX), 0xAA);
+        assert_eq!(linear_to_alaw(i16::MIN), 0x2A);
+        assert_eq!(linear_to_ulaw(0), 0xFF);
+        assert_eq!(linear_to_ulaw(i16::MAX), 0x80);
+        assert_eq!(linear_to_ulaw(i16::MIN), 0x00);
+
+        //every code is decoded and encoded back to itself
+        for code in 0..=u8::MAX {
+            assert_eq!(linear_to_alaw(alaw_to_linear(code)), code);
+            //0x7F(-0) is encoded to 0xFF(+0)
+            if code != 0x7F {
+                assert_eq!(linear_to_ulaw(ulaw_to_linear(code)), code);
+            }
+        }
+
+        let pcm = [0, 1000, -1000, 20000];
+        for law in [G711Law::ALaw, G711Law::MuLaw] {
+            let decoded = law.decode(&law.encode(&pcm));
+            for (sample, decoded) in pcm.iter().zip(decoded) {
+                //the quantization error is less than 1/16 of the value
+                assert!((*sample as i32 - decoded as i32).abs() <= (*sample as i32).abs() / 16 + 8);
+            }
+        }
+    }
+}
@@ -0,0 +1,392 @@
+use {
+    crate::errors::{AclError, AclErrorValue},
+    std::{
+        collections::HashMap,
+        net::IpAddr,
+        str::FromStr,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+};
+
+//the idle rate limit buckets are removed at this interval
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+fn acl_error(value: AclErrorValue) -> AclError {
+    AclError { value }
+}
+
+//an ip address or a CIDR block, e.g. 192.168.0.0/16 or 2001:db8::/32
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        //the ipv4-mapped ipv6 addresses of the dual-stack listeners are matched as ipv4
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_len as u32)
+                    .unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = AclError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || acl_error(AclErrorValue::InvalidCidr(s.to_string()));
+
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s.trim(), None),
+        };
+        let addr = IpAddr::from_str(addr)
+            .map_err(|_| invalid())?
+            .to_canonical();
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse::<u8>().map_err(|_| invalid())?,
+            None => max_prefix_len,
+        };
+        if prefix_len > max_prefix_len {
+            return Err(invalid());
+        }
+
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+/*The deny list has priority, and only the addresses in the allow list are
+allowed if it is not empty.*/
+#[derive(Debug, Clone, Default)]
+pub struct IpRule {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl IpRule {
+    pub fn new(allow: &[String], deny: &[String]) -> Result<Self, AclError> {
+        let parse = |nets: &[String]| {
+            nets.iter()
+                .map(|net| net.parse::<IpNet>())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Self {
+            allow: parse(allow)?,
+            deny: parse(deny)?,
+        })
+    }
+
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        !self.deny.iter().any(|net| net.contains(ip))
+            && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(ip)))
+    }
+}
+
+//token bucket, refilled by rate tokens per second up to burst tokens
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Default)]
+struct AccessState {
+    connections: HashMap<IpAddr, usize>,
+    buckets: HashMap<IpAddr, Bucket>,
+    last_prune: Option<Instant>,
+}
+
+/*The access control of one listener: the ip rules of the publish/play directions,
+the max concurrent connections and the new connection rate of every ip.*/
+#[derive(Default)]
+pub struct AccessControl {
+    publish: IpRule,
+    play: IpRule,
+    max_connections_per_ip: Option<usize>,
+    //(connections per second, burst)
+    connection_rate: Option<(f64, f64)>,
+    state: Mutex<AccessState>,
+}
+
+impl AccessControl {
+    pub fn new(publish: IpRule, play: IpRule) -> Self {
+        Self {
+            publish,
+            play,
+            ..Default::default()
+        }
+    }
+
+    pub fn set_max_connections_per_ip(&mut self, max_connections: usize) {
+        self.max_connections_per_ip = Some(max_connections);
+    }
+
+    //the burst is the connections allowed at once after an idle period
+    pub fn set_connection_rate(&mut self, rate: f64, burst: f64) {
+        self.connection_rate = Some((rate, burst.max(1.0)));
+    }
+
+    //check the direction once the session is known to publish or play
+    pub fn check(&self, ip: IpAddr, is_pull: bool) -> Result<(), AclError> {
+        let rule = if is_pull { &self.play } else { &self.publish };
+        if rule.is_allowed(ip) {
+            Ok(())
+        } else {
+            Err(acl_error(AclErrorValue::IpNotAllowed))
+        }
+    }
+
+    /*Check a new connection at accept time, the peer must be allowed to publish or play.
+    The connection is counted until the returned guard is dropped.*/
+    pub fn check_connection(self: &Arc<Self>, ip: IpAddr) -> Result<ConnectionGuard, AclError> {
+        self.check_connection_at(ip, Instant::now())
+    }
+
+    fn check_connection_at(
+        self: &Arc<Self>,
+        ip: IpAddr,
+        now: Instant,
+    ) -> Result<ConnectionGuard, AclError> {
+        let ip = ip.to_canonical();
+        if !self.publish.is_allowed(ip) && !self.play.is_allowed(ip) {
+            return Err(acl_error(AclErrorValue::IpNotAllowed));
+        }
+
+        let mut state = self.state.lock().unwrap();
+
+        if let Some(max_connections) = self.max_connections_per_ip {
+            if state.connections.get(&ip).copied().unwrap_or_default() >= max_connections {
+                return Err(acl_error(AclErrorValue::TooManyConnections));
+            }
+        }
+
+        if let Some((rate, burst)) = self.connection_rate {
+            if state
+                .last_prune
+                .is_none_or(|last_prune| now - last_prune >= PRUNE_INTERVAL)
+            {
+                state.buckets.retain(|_, bucket| {
+                    bucket.tokens + (now - bucket.last_refill).as_secs_f64() * rate < burst
+                });
+                state.last_prune = Some(now);
+            }
+
+            let bucket = state.buckets.entry(ip).or_insert(Bucket {
+                tokens: burst,
+                last_refill: now,
+            });
+            bucket.tokens =
+                (bucket.tokens + (now - bucket.last_refill).as_secs_f64() * rate).min(burst);
+            bucket.last_refill = now;
+            if bucket.tokens < 1.0 {
+                return Err(acl_error(AclErrorValue::ConnectionRateExceeded));
+            }
+            bucket.tokens -= 1.0;
+        }
+
+        if self.max_connections_per_ip.is_some() {
+            *state.connections.entry(ip).or_default() += 1;
+        }
+
+        Ok(ConnectionGuard {
+            access_control: self.clone(),
+            ip,
+        })
+    }
+}
+
+pub struct ConnectionGuard {
+    access_control: Arc<AccessControl>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if self.access_control.max_connections_per_ip.is_none() {
+            return;
+        }
+        let mut state = self.access_control.state.lock().unwrap();
+        if let Some(count) = state.connections.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                state.connections.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/*The access of an accepted HTTP connection. It is checked once at accept time and
+shared by the requests of the connection(keep-alive), so that the requests are not
+counted as new connections. The connection is counted until all its clones are dropped.*/
+#[derive(Clone)]
+pub struct ConnectionAccess {
+    result: Result<Option<Arc<ConnectionGuard>>, Arc<AclError>>,
+}
+
+impl ConnectionAccess {
+    //is_pull is the direction of the connections of the listener
+    pub fn check(access_control: &Option<Arc<AccessControl>>, ip: IpAddr, is_pull: bool) -> Self {
+        let result = match access_control {
+            Some(access_control) => access_control
+                .check(ip, is_pull)
+                .and_then(|_| access_control.check_connection(ip))
+                .map(|guard| Some(Arc::new(guard)))
+                .map_err(Arc::new),
+            None => Ok(None),
+        };
+        Self { result }
+    }
+
+    pub fn result(&self) -> Result<(), &AclError> {
+        match &self.result {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccessControl, ConnectionAccess, IpNet, IpRule};
+    use crate::errors::AclErrorValue;
+    use std::{
+        net::IpAddr,
+        sync::Arc,
+        time::{Duration, Instant},
+    };
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn rule(allow: &[&str], deny: &[&str]) -> IpRule {
+        let strings = |nets: &[&str]| nets.iter().map(|net| net.to_string()).collect::<Vec<_>>();
+        IpRule::new(&strings(allow), &strings(deny)).unwrap()
+    }
+
+    #[test]
+    fn test_ip_rule() {
+        let net: IpNet = "192.168.0.0/16".parse().unwrap();
+        assert!(net.contains(ip("192.168.10.1")));
+        assert!(net.contains(ip("::ffff:192.168.10.1")));
+        assert!(!net.contains(ip("192.169.0.1")));
+        assert!("0.0.0.0/0"
+            .parse::<IpNet>()
+            .unwrap()
+            .contains(ip("8.8.8.8")));
+        assert!("2001:db8::/32"
+            .parse::<IpNet>()
+            .unwrap()
+            .contains(ip("2001:db8::1")));
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("10.0.0/8".parse::<IpNet>().is_err());
+
+        let rule = rule(&["10.0.0.0/8"], &["10.0.0.1"]);
+        assert!(rule.is_allowed(ip("10.1.2.3")));
+        assert!(!rule.is_allowed(ip("10.0.0.1")));
+        assert!(!rule.is_allowed(ip("192.168.0.1")));
+        assert!(IpRule::default().is_allowed(ip("192.168.0.1")));
+    }
+
+    #[test]
+    fn test_check() {
+        let acl = Arc::new(AccessControl::new(
+            rule(&["10.0.0.0/8"], &[]),
+            rule(&[], &["192.168.0.0/16"]),
+        ));
+        assert!(acl.check(ip("10.0.0.1"), false).is_ok());
+        assert!(acl.check(ip("172.16.0.1"), false).is_err());
+        assert!(acl.check(ip("172.16.0.1"), true).is_ok());
+        assert!(acl.check(ip("192.168.0.1"), true).is_err());
+
+        //neither publishing nor playing is allowed
+        assert!(matches!(
+            acl.check_connection(ip("192.168.0.1")).err().unwrap().value,
+            AclErrorValue::IpNotAllowed
+        ));
+        assert!(acl.check_connection(ip("172.16.0.1")).is_ok());
+    }
+
+    #[test]
+    fn test_connection_limits() {
+        let mut acl = AccessControl::default();
+        acl.set_max_connections_per_ip(2);
+        acl.set_connection_rate(1.0, 3.0);
+        let acl = Arc::new(acl);
+        let now = Instant::now();
+
+        let first = acl.check_connection_at(ip("10.0.0.1"), now).unwrap();
+        let _second = acl.check_connection_at(ip("10.0.0.1"), now).unwrap();
+        assert!(matches!(
+            acl.check_connection_at(ip("10.0.0.1"), now)
+                .err()
+                .unwrap()
+                .value,
+            AclErrorValue::TooManyConnections
+        ));
+        //the other ips are limited separately
+        assert!(acl.check_connection_at(ip("10.0.0.2"), now).is_ok());
+
+        drop(first);
+        let third = acl.check_connection_at(ip("10.0.0.1"), now).unwrap();
+        drop(third);
+        //the burst is used up
+        assert!(matches!(
+            acl.check_connection_at(ip("10.0.0.1"), now)
+                .err()
+                .unwrap()
+                .value,
+            AclErrorValue::ConnectionRateExceeded
+        ));
+        assert!(acl
+            .check_connection_at(ip("10.0.0.1"), now + Duration::from_secs(1))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_connection_access() {
+        let mut acl = AccessControl::new(IpRule::default(), rule(&[], &["192.168.0.0/16"]));
+        acl.set_max_connections_per_ip(1);
+        let acl = Some(Arc::new(acl));
+
+        assert!(ConnectionAccess::check(&None, ip("10.0.0.1"), true)
+            .result()
+            .is_ok());
+        assert!(matches!(
+            ConnectionAccess::check(&acl, ip("192.168.0.1"), true)
+                .result()
+                .err()
+                .unwrap()
+                .value,
+            AclErrorValue::IpNotAllowed
+        ));
+
+        //the requests of the connection share the access
+        let access = ConnectionAccess::check(&acl, ip("10.0.0.1"), true);
+        let request_access = access.clone();
+        assert!(request_access.result().is_ok());
+        assert!(ConnectionAccess::check(&acl, ip("10.0.0.1"), true)
+            .result()
+            .is_err());
+
+        drop(access);
+        drop(request_access);
+        assert!(ConnectionAccess::check(&acl, ip("10.0.0.1"), true)
+            .result()
+            .is_ok());
+    }
+}
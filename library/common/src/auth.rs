@@ -3,6 +3,7 @@ use md5;
 use serde_derive::Deserialize;
 
 use crate::errors::{AuthError, AuthErrorValue};
+use crate::jwt::JwtVerifier;
 use crate::scanf;
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -12,6 +13,9 @@ pub enum AuthAlgorithm {
     Simple,
     #[serde(rename = "md5")]
     Md5,
+    //HS256/RS256 json web token with expiry and claims of app, stream and direction
+    #[serde(rename = "jwt")]
+    Jwt,
 }
 
 pub enum SecretCarrier {
@@ -84,6 +88,7 @@ pub struct Auth {
     password: String,
     push_password: Option<String>,
     pub auth_type: AuthType,
+    jwt_verifier: Option<JwtVerifier>,
 }
 
 impl Auth {
@@ -100,11 +105,17 @@ impl Auth {
             password,
             push_password,
             auth_type,
+            jwt_verifier: None,
         }
     }
 
+    pub fn set_jwt_verifier(&mut self, jwt_verifier: JwtVerifier) {
+        self.jwt_verifier = Some(jwt_verifier);
+    }
+
     pub fn authenticate(
         &self,
+        app_name: &str,
         stream_name: &String,
         secret: &Option<SecretCarrier>,
         is_pull: bool,
@@ -119,11 +130,13 @@ impl Auth {
             /*Here we should do auth and it must be successful. */
             if let Some(secret_value) = secret {
                 let token = get_secret(secret_value)?;
-                if self.check(stream_name, token.as_str(), is_pull) {
-                    return Ok(());
+                match self.check(app_name, stream_name, token.as_str(), is_pull) {
+                    Ok(()) => return Ok(()),
+                    Err(check_err) => {
+                        auth_err_reason = format!("{} token: {}", check_err, token);
+                        err = check_err;
+                    }
                 }
-                auth_err_reason = format!("token is not correct: {}", token);
-                err = AuthErrorValue::TokenIsNotCorrect;
             }
 
             log::error!(
@@ -138,20 +151,47 @@ impl Auth {
         Ok(())
     }
 
-    fn check(&self, stream_name: &String, auth_str: &str, is_pull: bool) -> bool {
+    fn check(
+        &self,
+        app_name: &str,
+        stream_name: &String,
+        auth_str: &str,
+        is_pull: bool,
+    ) -> Result<(), AuthErrorValue> {
         let password = if is_pull {
             &self.password
         } else {
             self.push_password.as_ref().unwrap_or(&self.password)
         };
 
-        match self.algorithm {
+        let correct = match self.algorithm {
             AuthAlgorithm::Simple => password == auth_str,
             AuthAlgorithm::Md5 => {
                 let raw_data = format!("{}{}", self.key, stream_name);
                 let digest_str = format!("{:x}", md5::compute(raw_data));
                 auth_str == digest_str
             }
+            AuthAlgorithm::Jwt => {
+                return match &self.jwt_verifier {
+                    Some(verifier) => verifier
+                        .verify(
+                            auth_str,
+                            app_name,
+                            stream_name,
+                            is_pull,
+                            chrono::Utc::now().timestamp(),
+                        )
+                        .map(|_| ())
+                        .map_err(|err| err.value),
+                    None => Err(AuthErrorValue::TokenIsNotCorrect),
+                };
+            }
+        };
+
+        if correct {
+            Ok(())
+        } else {
+            Err(AuthErrorValue::TokenIsNotCorrect)
         }
     }
 }
@@ -14,7 +14,15 @@ pub enum AuthErrorValue {
     #[fail(display = "no token found.")]
     NoTokenFound,
     #[fail(display = "invalid token format.")]
-    InvalidTokenFormat
+    InvalidTokenFormat,
+    #[fail(display = "token is expired.")]
+    TokenExpired,
+    #[fail(display = "token is not valid yet.")]
+    TokenNotYetValid,
+    #[fail(display = "token is not allowed for the stream.")]
+    TokenNotAllowed,
+    #[fail(display = "invalid public key.")]
+    InvalidPublicKey,
 }
 
 impl fmt::Display for AuthError {
@@ -32,3 +40,36 @@ impl Fail for AuthError {
         self.value.backtrace()
     }
 }
+
+#[derive(Debug)]
+pub struct AclError {
+    pub value: AclErrorValue,
+}
+
+#[derive(Debug, Fail)]
+pub enum AclErrorValue {
+    #[fail(display = "ip is not allowed.")]
+    IpNotAllowed,
+    #[fail(display = "too many connections from the ip.")]
+    TooManyConnections,
+    #[fail(display = "connection rate of the ip is exceeded.")]
+    ConnectionRateExceeded,
+    #[fail(display = "invalid cidr: {}", _0)]
+    InvalidCidr(String),
+}
+
+impl fmt::Display for AclError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
+
+impl Fail for AclError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        self.value.cause()
+    }
+
+    fn backtrace(&self) -> Option<&Backtrace> {
+        self.value.backtrace()
+    }
+}
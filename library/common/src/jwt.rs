@@ -0,0 +1,236 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use openssl::{
+    hash::MessageDigest,
+    memcmp,
+    pkey::{PKey, Public},
+    sign::{Signer, Verifier},
+};
+use serde_derive::Deserialize;
+
+use crate::errors::{AuthError, AuthErrorValue};
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+/*The claims of the stream tokens, the token is allowed for all the apps, streams
+or directions if the corresponding claim is absent.*/
+#[derive(Debug, Deserialize)]
+pub struct JwtClaims {
+    pub exp: i64,
+    pub nbf: Option<i64>,
+    pub app: Option<String>,
+    pub stream: Option<String>,
+    //"push" or "pull"
+    pub direction: Option<String>,
+}
+
+/*Verifies the HS256 tokens by the shared secret and the RS256 tokens by the public
+key, a token is rejected if the key of its algorithm is not configured.*/
+#[derive(Debug, Clone, Default)]
+pub struct JwtVerifier {
+    hs256_secret: Option<Vec<u8>>,
+    rs256_public_key: Option<PKey<Public>>,
+}
+
+fn auth_error(value: AuthErrorValue) -> AuthError {
+    AuthError { value }
+}
+
+impl JwtVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_hs256_secret(&mut self, secret: &str) {
+        self.hs256_secret = Some(secret.as_bytes().to_vec());
+    }
+
+    //the public key is in PEM format
+    pub fn set_rs256_public_key(&mut self, pem: &[u8]) -> Result<(), AuthError> {
+        let public_key = PKey::public_key_from_pem(pem)
+            .map_err(|_| auth_error(AuthErrorValue::InvalidPublicKey))?;
+        self.rs256_public_key = Some(public_key);
+        Ok(())
+    }
+
+    pub fn verify(
+        &self,
+        token: &str,
+        app_name: &str,
+        stream_name: &str,
+        is_pull: bool,
+        now: i64,
+    ) -> Result<JwtClaims, AuthError> {
+        let invalid_format = || auth_error(AuthErrorValue::InvalidTokenFormat);
+
+        let mut parts = token.split('.');
+        let (header, payload, signature) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(header), Some(payload), Some(signature)) if parts.next().is_none() => {
+                (header, payload, signature)
+            }
+            _ => return Err(invalid_format()),
+        };
+        let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| invalid_format());
+
+        let header: JwtHeader =
+            serde_json::from_slice(&decode(header)?).map_err(|_| invalid_format())?;
+        let signing_input = &token[..token.len() - signature.len() - 1];
+        if !self.verify_signature(&header.alg, signing_input.as_bytes(), &decode(signature)?) {
+            return Err(auth_error(AuthErrorValue::TokenIsNotCorrect));
+        }
+
+        let claims: JwtClaims =
+            serde_json::from_slice(&decode(payload)?).map_err(|_| invalid_format())?;
+        if now >= claims.exp {
+            return Err(auth_error(AuthErrorValue::TokenExpired));
+        }
+        if claims.nbf.is_some_and(|nbf| now < nbf) {
+            return Err(auth_error(AuthErrorValue::TokenNotYetValid));
+        }
+
+        //the rtsp stream names are the paths(/app/stream)
+        let stream_name = stream_name.rsplit('/').next().unwrap_or(stream_name);
+        let direction = if is_pull { "pull" } else { "push" };
+        if claims.app.as_ref().is_some_and(|app| app != app_name)
+            || claims
+                .stream
+                .as_ref()
+                .is_some_and(|stream| stream != stream_name)
+            || claims.direction.as_ref().is_some_and(|d| d != direction)
+        {
+            return Err(auth_error(AuthErrorValue::TokenNotAllowed));
+        }
+
+        Ok(claims)
+    }
+
+    fn verify_signature(&self, alg: &str, data: &[u8], signature: &[u8]) -> bool {
+        match alg {
+            "HS256" => {
+                let Some(secret) = &self.hs256_secret else {
+                    return false;
+                };
+                let expected = PKey::hmac(secret).and_then(|key| {
+                    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+                    signer.update(data)?;
+                    signer.sign_to_vec()
+                });
+                matches!(expected, Ok(expected)
+                    if expected.len() == signature.len() && memcmp::eq(&expected, signature))
+            }
+            "RS256" => {
+                let Some(public_key) = &self.rs256_public_key else {
+                    return false;
+                };
+                Verifier::new(MessageDigest::sha256(), public_key)
+                    .and_then(|mut verifier| {
+                        verifier.update(data)?;
+                        verifier.verify(signature)
+                    })
+                    .unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JwtVerifier;
+    use crate::errors::AuthErrorValue;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use openssl::{
+        hash::MessageDigest,
+        pkey::{PKey, Private},
+        rsa::Rsa,
+        sign::Signer,
+    };
+
+    fn encode(alg: &str, claims: &str, key: &PKey<Private>) -> String {
+        let input = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(format!("{{\"alg\":\"{alg}\",\"typ\":\"JWT\"}}")),
+            URL_SAFE_NO_PAD.encode(claims)
+        );
+        let mut signer = Signer::new(MessageDigest::sha256(), key).unwrap();
+        signer.update(input.as_bytes()).unwrap();
+        format!(
+            "{}.{}",
+            input,
+            URL_SAFE_NO_PAD.encode(signer.sign_to_vec().unwrap())
+        )
+    }
+
+    #[test]
+    fn test_hs256() {
+        let mut verifier = JwtVerifier::new();
+        verifier.set_hs256_secret("secret");
+        let key = PKey::hmac(b"secret").unwrap();
+
+        let token = encode(
+            "HS256",
+            r#"{"exp":2000,"nbf":1000,"app":"live","stream":"test","direction":"pull"}"#,
+            &key,
+        );
+        assert!(verifier.verify(&token, "live", "test", true, 1500).is_ok());
+        assert!(verifier
+            .verify(&token, "live", "/live/test", true, 1500)
+            .is_ok());
+
+        let err = |app: &str, stream: &str, is_pull: bool, now: i64| {
+            verifier
+                .verify(&token, app, stream, is_pull, now)
+                .unwrap_err()
+                .value
+        };
+        assert!(matches!(
+            err("live", "test", true, 2000),
+            AuthErrorValue::TokenExpired
+        ));
+        assert!(matches!(
+            err("live", "test", true, 999),
+            AuthErrorValue::TokenNotYetValid
+        ));
+        assert!(matches!(
+            err("live", "other", true, 1500),
+            AuthErrorValue::TokenNotAllowed
+        ));
+        assert!(matches!(
+            err("vod", "test", true, 1500),
+            AuthErrorValue::TokenNotAllowed
+        ));
+        assert!(matches!(
+            err("live", "test", false, 1500),
+            AuthErrorValue::TokenNotAllowed
+        ));
+
+        let forged = encode("HS256", r#"{"exp":2000}"#, &PKey::hmac(b"other").unwrap());
+        assert!(matches!(
+            verifier
+                .verify(&forged, "live", "test", true, 1500)
+                .unwrap_err()
+                .value,
+            AuthErrorValue::TokenIsNotCorrect
+        ));
+    }
+
+    #[test]
+    fn test_rs256() {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut verifier = JwtVerifier::new();
+        verifier
+            .set_rs256_public_key(&key.public_key_to_pem().unwrap())
+            .unwrap();
+
+        let token = encode("RS256", r#"{"exp":2000,"direction":"push"}"#, &key);
+        assert!(verifier.verify(&token, "live", "test", false, 1500).is_ok());
+
+        //the HS256 tokens are rejected without the secret
+        let token = encode("HS256", r#"{"exp":2000}"#, &PKey::hmac(b"secret").unwrap());
+        assert!(verifier
+            .verify(&token, "live", "test", false, 1500)
+            .is_err());
+    }
+}
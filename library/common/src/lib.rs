@@ -1,5 +1,7 @@
+pub mod acl;
 pub mod auth;
 pub mod define;
 pub mod errors;
 pub mod http;
+pub mod jwt;
 pub mod utils;
@@ -49,7 +49,7 @@ impl Amf0Writer {
     }
 
     pub fn write_string(&mut self, value: &String) -> Result<(), Amf0WriteError> {
-        if value.len() > (u16::max_value() as usize) {
+        if value.len() > (u16::MAX as usize) {
             return Err(Amf0WriteError {
                 value: Amf0WriteErrorValue::NormalStringTooLong,
             });
@@ -3,6 +3,8 @@ use serde::Serialize;
 
 #[derive(Debug, Clone, Serialize, Default)]
 pub enum SoundFormat {
+    G711A = 7,
+    G711U = 8,
     #[default]
     AAC = 10,
     OPUS = 13,
@@ -47,6 +49,40 @@ pub fn u8_2_avc_codec_id(codec_id: u8) -> AvcCodecId {
     }
 }
 
+pub fn fourcc_2_avc_codec_id(fourcc: &[u8]) -> AvcCodecId {
+    if fourcc == fourcc::AVC1 {
+        AvcCodecId::H264
+    } else if fourcc == fourcc::HVC1 {
+        AvcCodecId::HEVC
+    } else {
+        AvcCodecId::UNKNOWN
+    }
+}
+
+pub fn avc_codec_id_2_fourcc(codec_id: u8) -> Option<[u8; 4]> {
+    match codec_id {
+        7_u8 => Some(*fourcc::AVC1),
+        12_u8 => Some(*fourcc::HVC1),
+        _ => None,
+    }
+}
+
+//Enhanced RTMP, https://github.com/veovera/enhanced-rtmp
+pub mod fourcc {
+    pub const AVC1: &[u8; 4] = b"avc1";
+    pub const HVC1: &[u8; 4] = b"hvc1";
+}
+
+pub mod ex_video_packet_type {
+    pub const SEQUENCE_START: u8 = 0;
+    pub const CODED_FRAMES: u8 = 1;
+    pub const SEQUENCE_END: u8 = 2;
+    //the composition time is zero and not carried
+    pub const CODED_FRAMES_X: u8 = 3;
+    pub const METADATA: u8 = 4;
+    pub const MPEG2TS_SEQUENCE_START: u8 = 5;
+}
+
 pub mod tag_type {
     pub const AUDIO: u8 = 8;
     pub const VIDEO: u8 = 9;
@@ -59,6 +95,18 @@ pub mod h264_nal_type {
     pub const H264_NAL_PPS: u8 = 8;
     pub const H264_NAL_AUD: u8 = 9;
 }
+
+pub mod hevc_nal_type {
+    pub const HEVC_NAL_BLA_W_LP: u8 = 16;
+    pub const HEVC_NAL_IDR_W_RADL: u8 = 19;
+    pub const HEVC_NAL_IDR_N_LP: u8 = 20;
+    pub const HEVC_NAL_CRA: u8 = 21;
+    pub const HEVC_NAL_RSV_IRAP_23: u8 = 23;
+    pub const HEVC_NAL_VPS: u8 = 32;
+    pub const HEVC_NAL_SPS: u8 = 33;
+    pub const HEVC_NAL_PPS: u8 = 34;
+    pub const HEVC_NAL_AUD: u8 = 35;
+}
 #[derive(Debug, Clone, Serialize, Default)]
 pub enum AacProfile {
     // @see @see ISO_IEC_14496-3-AAC-2001.pdf, page 23
@@ -158,6 +206,7 @@ pub fn u8_2_avc_level(profile: u8) -> AvcLevel {
     }
 }
 
+#[derive(Clone)]
 pub enum FlvData {
     Video { timestamp: u32, data: BytesMut },
     Audio { timestamp: u32, data: BytesMut },
@@ -9,6 +9,7 @@ use {
         errors::FlvDemuxerError,
         mpeg4_aac::Mpeg4AacProcessor,
         mpeg4_avc::Mpeg4AvcProcessor,
+        mpeg4_hevc::Mpeg4HevcProcessor,
     },
     byteorder::BigEndian,
     bytes::BytesMut,
@@ -114,12 +115,14 @@ impl FlvDemuxerVideoData {
 #[derive(Default)]
 pub struct FlvVideoTagDemuxer {
     avc_processor: Mpeg4AvcProcessor,
+    hevc_processor: Mpeg4HevcProcessor,
 }
 
 impl FlvVideoTagDemuxer {
     pub fn new() -> Self {
         Self {
             avc_processor: Mpeg4AvcProcessor::new(),
+            hevc_processor: Mpeg4HevcProcessor::new(),
         }
     }
     pub fn demux(
@@ -153,6 +156,28 @@ impl FlvVideoTagDemuxer {
                 }
                 _ => {}
             }
+        } else if tag_header.codec_id == AvcCodecId::HEVC as u8 {
+            match tag_header.avc_packet_type {
+                avc_packet_type::AVC_SEQHDR => {
+                    self.hevc_processor
+                        .decoder_configuration_record_load(&mut reader)?;
+
+                    return Ok(None);
+                }
+                avc_packet_type::AVC_NALU => {
+                    let data = self.hevc_processor.hevc_mp4toannexb(&mut reader)?;
+
+                    let video_data = FlvDemuxerVideoData {
+                        codec_id: AvcCodecId::HEVC as u8,
+                        pts: timestamp as i64 + tag_header.composition_time as i64,
+                        dts: timestamp as i64,
+                        frame_type: tag_header.frame_type,
+                        data,
+                    };
+                    return Ok(Some(video_data));
+                }
+                _ => {}
+            }
         }
 
         Ok(None)
@@ -179,6 +204,20 @@ impl FlvAudioTagDemuxer {
         let mut reader = BytesReader::new(data);
 
         let tag_header = AudioTagHeader::unmarshal(&mut reader)?;
+
+        //the G.711 samples are returned as they are
+        if tag_header.sound_format == SoundFormat::G711A as u8
+            || tag_header.sound_format == SoundFormat::G711U as u8
+        {
+            return Ok(FlvDemuxerAudioData {
+                has_data: true,
+                sound_format: tag_header.sound_format,
+                pts: timestamp as i64,
+                dts: timestamp as i64,
+                data: reader.extract_remaining_bytes(),
+            });
+        }
+
         self.aac_processor
             .extend_data(reader.extract_remaining_bytes());
 
@@ -212,6 +251,146 @@ impl FlvAudioTagDemuxer {
     }
 }
 
+/*The data demuxed for the fMP4 muxers, the frames are kept as they are in the
+flv tags(length prefixed NALUs and raw AAC frames), and the sequence headers
+are returned as the decoder configurations of the tracks.*/
+pub enum FlvMp4DemuxerData {
+    VideoConfig {
+        codec_id: u8,
+        decoder_configuration_record: BytesMut,
+        width: u32,
+        height: u32,
+    },
+    AudioConfig {
+        audio_specific_config: BytesMut,
+        sampling_frequency: u32,
+        channels: u8,
+    },
+    Video {
+        data: FlvDemuxerVideoData,
+    },
+    Audio {
+        data: FlvDemuxerAudioData,
+    },
+    None,
+}
+
+#[derive(Default)]
+pub struct FlvMp4Demuxer {}
+
+impl FlvMp4Demuxer {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn demux(&mut self, data: FlvData) -> Result<FlvMp4DemuxerData, FlvDemuxerError> {
+        match data {
+            FlvData::Video { timestamp, data } => self.demux_video(timestamp, data),
+            FlvData::Audio { timestamp, data } => self.demux_audio(timestamp, data),
+            _ => Ok(FlvMp4DemuxerData::None),
+        }
+    }
+
+    fn demux_video(
+        &mut self,
+        timestamp: u32,
+        data: BytesMut,
+    ) -> Result<FlvMp4DemuxerData, FlvDemuxerError> {
+        let mut reader = BytesReader::new(data);
+        let tag_header = VideoTagHeader::unmarshal(&mut reader)?;
+
+        if tag_header.codec_id != AvcCodecId::H264 as u8
+            && tag_header.codec_id != AvcCodecId::HEVC as u8
+        {
+            return Ok(FlvMp4DemuxerData::None);
+        }
+
+        match tag_header.avc_packet_type {
+            avc_packet_type::AVC_SEQHDR => {
+                let decoder_configuration_record = reader.extract_remaining_bytes();
+                let mut record_reader = BytesReader::new(decoder_configuration_record.clone());
+
+                /*parse the SPS to get the video resolution*/
+                let (width, height) = if tag_header.codec_id == AvcCodecId::H264 as u8 {
+                    let mut avc_processor = Mpeg4AvcProcessor::new();
+                    avc_processor.decoder_configuration_record_load(&mut record_reader)?;
+                    (
+                        avc_processor.mpeg4_avc.width,
+                        avc_processor.mpeg4_avc.height,
+                    )
+                } else {
+                    let mut hevc_processor = Mpeg4HevcProcessor::new();
+                    hevc_processor.decoder_configuration_record_load(&mut record_reader)?;
+                    (
+                        hevc_processor.mpeg4_hevc.width,
+                        hevc_processor.mpeg4_hevc.height,
+                    )
+                };
+
+                Ok(FlvMp4DemuxerData::VideoConfig {
+                    codec_id: tag_header.codec_id,
+                    decoder_configuration_record,
+                    width,
+                    height,
+                })
+            }
+            avc_packet_type::AVC_NALU => Ok(FlvMp4DemuxerData::Video {
+                data: FlvDemuxerVideoData {
+                    codec_id: tag_header.codec_id,
+                    pts: timestamp as i64 + tag_header.composition_time as i64,
+                    dts: timestamp as i64,
+                    frame_type: tag_header.frame_type,
+                    data: reader.extract_remaining_bytes(),
+                },
+            }),
+            _ => Ok(FlvMp4DemuxerData::None),
+        }
+    }
+
+    fn demux_audio(
+        &mut self,
+        timestamp: u32,
+        data: BytesMut,
+    ) -> Result<FlvMp4DemuxerData, FlvDemuxerError> {
+        let mut reader = BytesReader::new(data);
+        let tag_header = AudioTagHeader::unmarshal(&mut reader)?;
+
+        if tag_header.sound_format != SoundFormat::AAC as u8 {
+            return Ok(FlvMp4DemuxerData::None);
+        }
+
+        match tag_header.aac_packet_type {
+            aac_packet_type::AAC_SEQHDR => {
+                let audio_specific_config = reader.extract_remaining_bytes();
+                if audio_specific_config.len() < 2 {
+                    return Ok(FlvMp4DemuxerData::None);
+                }
+
+                let mut aac_processor = Mpeg4AacProcessor::new();
+                aac_processor
+                    .extend_data(audio_specific_config.clone())
+                    .audio_specific_config_load()?;
+
+                Ok(FlvMp4DemuxerData::AudioConfig {
+                    audio_specific_config,
+                    sampling_frequency: aac_processor.mpeg4_aac.sampling_frequency,
+                    channels: aac_processor.mpeg4_aac.channels,
+                })
+            }
+            aac_packet_type::AAC_RAW => Ok(FlvMp4DemuxerData::Audio {
+                data: FlvDemuxerAudioData {
+                    has_data: true,
+                    sound_format: tag_header.sound_format,
+                    pts: timestamp as i64,
+                    dts: timestamp as i64,
+                    data: reader.extract_remaining_bytes(),
+                },
+            }),
+            _ => Ok(FlvMp4DemuxerData::None),
+        }
+    }
+}
+
 pub struct FlvDemuxer {
     bytes_reader: BytesReader,
 }
@@ -262,6 +441,12 @@ impl FlvDemuxer {
                     data: body,
                 }));
             }
+            tag_type::SCRIPT_DATA_AMF => {
+                return Ok(Some(FlvData::MetaData {
+                    timestamp: dts,
+                    data: body,
+                }));
+            }
 
             _ => {}
         }
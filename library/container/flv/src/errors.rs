@@ -164,6 +164,8 @@ pub enum MpegErrorValue {
     ShouldNotComeHere,
     #[fail(display = "the sps nal unit type is not correct")]
     SPSNalunitTypeNotCorrect,
+    #[fail(display = "the vps/sps/pps of hevc is not found")]
+    HevcParameterSetsNotFound,
     #[fail(display = "not supported sampling frequency")]
     NotSupportedSamplingFrequency,
 }
@@ -188,6 +190,14 @@ impl From<BytesWriteError> for Mpeg4AvcHevcError {
     }
 }
 
+impl From<BitError> for Mpeg4AvcHevcError {
+    fn from(error: BitError) -> Self {
+        Mpeg4AvcHevcError {
+            value: MpegErrorValue::BitError(error),
+        }
+    }
+}
+
 impl From<H264Error> for Mpeg4AvcHevcError {
     fn from(error: H264Error) -> Self {
         Mpeg4AvcHevcError {
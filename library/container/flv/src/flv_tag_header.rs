@@ -145,6 +145,12 @@ pub struct VideoTagHeader {
     */
     pub avc_packet_type: u8,
     pub composition_time: i32,
+    /*
+        Enhanced RTMP: the codec is signaled by a FourCC instead of codec_id,
+        and the PacketType(SequenceStart/CodedFrames/SequenceEnd) is mapped
+        to avc_packet_type, CodedFramesX is read as an AVC NALU.
+    */
+    pub is_ex_header: bool,
 }
 
 impl VideoTagHeader {
@@ -154,10 +160,38 @@ impl VideoTagHeader {
             codec_id: 0,
             avc_packet_type: 0,
             composition_time: 0,
+            is_ex_header: false,
         }
     }
 }
 
+fn read_composition_time(reader: &mut BytesReader) -> Result<i32, FlvDemuxerError> {
+    let mut composition_time: i32 = 0;
+    //bigend 3bytes
+    for _ in 0..3 {
+        let time = reader.read_u8()?;
+        composition_time = (composition_time << 8) + time as i32;
+    }
+    //transfer to signed i24
+    if composition_time & (1 << 23) != 0 {
+        let sign_extend_mask = 0xff_ff << 23;
+        // Sign extend the value
+        composition_time |= sign_extend_mask
+    }
+    Ok(composition_time)
+}
+
+fn write_composition_time(
+    writer: &mut BytesWriter,
+    composition_time: i32,
+) -> Result<(), FlvMuxerError> {
+    //bigend 3bytes
+    for i in (0..3).rev() {
+        writer.write_u8(((composition_time >> (i * 8)) & 0xFF) as u8)?;
+    }
+    Ok(())
+}
+
 impl Unmarshal<&mut BytesReader, Result<Self, FlvDemuxerError>> for VideoTagHeader {
     fn unmarshal(reader: &mut BytesReader) -> Result<Self, FlvDemuxerError>
     where
@@ -166,6 +200,30 @@ impl Unmarshal<&mut BytesReader, Result<Self, FlvDemuxerError>> for VideoTagHead
         let mut tag_header = VideoTagHeader::defalut();
 
         let flags = reader.read_u8()?;
+
+        //IsExHeader: UB[1] FrameType: UB[3] PacketType: UB[4] FourCC: UI32
+        if flags & 0x80 != 0 {
+            tag_header.is_ex_header = true;
+            tag_header.frame_type = (flags >> 4) & 0x07;
+            let packet_type = flags & 0x0f;
+
+            let fourcc = reader.read_bytes(4)?;
+            tag_header.codec_id = define::fourcc_2_avc_codec_id(&fourcc[..]) as u8;
+
+            tag_header.avc_packet_type = match packet_type {
+                define::ex_video_packet_type::CODED_FRAMES => {
+                    if tag_header.codec_id != define::AvcCodecId::UNKNOWN as u8 {
+                        tag_header.composition_time = read_composition_time(reader)?;
+                    }
+                    define::avc_packet_type::AVC_NALU
+                }
+                define::ex_video_packet_type::CODED_FRAMES_X => define::avc_packet_type::AVC_NALU,
+                _ => packet_type,
+            };
+
+            return Ok(tag_header);
+        }
+
         tag_header.frame_type = flags >> 4;
         tag_header.codec_id = flags & 0x0f;
 
@@ -173,21 +231,7 @@ impl Unmarshal<&mut BytesReader, Result<Self, FlvDemuxerError>> for VideoTagHead
             || tag_header.codec_id == define::AvcCodecId::HEVC as u8
         {
             tag_header.avc_packet_type = reader.read_u8()?;
-            tag_header.composition_time = 0;
-
-            //bigend 3bytes
-            for _ in 0..3 {
-                let time = reader.read_u8()?;
-                //print!("==time0=={}\n", time);
-                //print!("==time1=={}\n", self.tag.composition_time);
-                tag_header.composition_time = (tag_header.composition_time << 8) + time as i32;
-            }
-            //transfer to signed i24
-            if tag_header.composition_time & (1 << 23) != 0 {
-                let sign_extend_mask = 0xff_ff << 23;
-                // Sign extend the value
-                tag_header.composition_time |= sign_extend_mask
-            }
+            tag_header.composition_time = read_composition_time(reader)?;
         }
 
         Ok(tag_header)
@@ -198,6 +242,25 @@ impl Marshal<Result<BytesMut, FlvMuxerError>> for VideoTagHeader {
     fn marshal(&self) -> Result<BytesMut, FlvMuxerError> {
         let mut writer = BytesWriter::default();
 
+        if self.is_ex_header {
+            if let Some(fourcc) = define::avc_codec_id_2_fourcc(self.codec_id) {
+                let packet_type = match self.avc_packet_type {
+                    define::avc_packet_type::AVC_NALU if self.composition_time == 0 => {
+                        define::ex_video_packet_type::CODED_FRAMES_X
+                    }
+                    packet_type => packet_type,
+                };
+
+                writer.write_u8(0x80 | (self.frame_type & 0x07) << 4 | packet_type)?;
+                writer.write(&fourcc)?;
+
+                if packet_type == define::ex_video_packet_type::CODED_FRAMES {
+                    write_composition_time(&mut writer, self.composition_time)?;
+                }
+                return Ok(writer.extract_current_bytes());
+            }
+        }
+
         let byte_1st = self.frame_type << 4 | self.codec_id;
         writer.write_u8(byte_1st)?;
 
@@ -205,14 +268,50 @@ impl Marshal<Result<BytesMut, FlvMuxerError>> for VideoTagHeader {
             || self.codec_id == define::AvcCodecId::HEVC as u8
         {
             writer.write_u8(self.avc_packet_type)?;
-
-            let mut cts = self.composition_time;
-            for _ in 0..3 {
-                writer.write_u8((cts & 0xFF) as u8)?;
-                cts >>= 8;
-            }
+            write_composition_time(&mut writer, self.composition_time)?;
         }
 
         Ok(writer.extract_current_bytes())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::VideoTagHeader;
+    use crate::{define, Marshal, Unmarshal};
+    use bytesio::bytes_reader::BytesReader;
+
+    #[test]
+    fn test_ex_video_tag_header() {
+        let mut tag_header = VideoTagHeader {
+            frame_type: define::frame_type::KEY_FRAME,
+            codec_id: define::AvcCodecId::HEVC as u8,
+            avc_packet_type: define::avc_packet_type::AVC_SEQHDR,
+            composition_time: 0,
+            is_ex_header: true,
+        };
+        let data = tag_header.marshal().unwrap();
+        assert_eq!(&data[..], b"\x90hvc1");
+
+        tag_header.frame_type = define::frame_type::INTER_FRAME;
+        tag_header.avc_packet_type = define::avc_packet_type::AVC_NALU;
+        tag_header.composition_time = 40;
+        let data = tag_header.marshal().unwrap();
+        assert_eq!(&data[..], b"\xa1hvc1\x00\x00\x28");
+
+        let mut reader = BytesReader::new(data);
+        let header = VideoTagHeader::unmarshal(&mut reader).unwrap();
+        assert!(header.is_ex_header);
+        assert_eq!(header.frame_type, define::frame_type::INTER_FRAME);
+        assert_eq!(header.codec_id, define::AvcCodecId::HEVC as u8);
+        assert_eq!(header.avc_packet_type, define::avc_packet_type::AVC_NALU);
+        assert_eq!(header.composition_time, 40);
+
+        /* CodedFramesX */
+        let mut reader = BytesReader::new(b"\x93hvc1"[..].into());
+        let header = VideoTagHeader::unmarshal(&mut reader).unwrap();
+        assert_eq!(header.frame_type, define::frame_type::KEY_FRAME);
+        assert_eq!(header.avc_packet_type, define::avc_packet_type::AVC_NALU);
+        assert_eq!(header.composition_time, 0);
+    }
+}
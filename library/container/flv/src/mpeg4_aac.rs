@@ -392,7 +392,7 @@ impl Mpeg4AacProcessor {
             self.mpeg4_bits_copy(&mut pce_bits_vec, 8)?;
         }
 
-        let rv = (pce_bits_vec.len() + 7) / 8;
+        let rv = pce_bits_vec.len().div_ceil(8);
 
         log::trace!(
             "remove warnings: {} {} {} {} {}",
@@ -1,25 +1,42 @@
-use {super::errors::Mpeg4AvcHevcError, byteorder::BigEndian, bytesio::bytes_reader::BytesReader};
-#[allow(dead_code)]
-#[derive(Default)]
+use {
+    super::{
+        define::hevc_nal_type,
+        errors::{Mpeg4AvcHevcError, MpegErrorValue},
+    },
+    byteorder::BigEndian,
+    bytes::BytesMut,
+    bytesio::{bits_reader::BitsReader, bytes_reader::BytesReader, bytes_writer::BytesWriter},
+    h264_decoder::utils::read_uev,
+};
+
+const HEVC_START_CODE: [u8; 4] = [0x00, 0x00, 0x00, 0x01];
 
+#[derive(Default)]
 pub struct Mpeg4Hevc {
-    configuration_version: u8, // 1-only
-    general_profile_space: u8, // 2bit,[0,3]
-    general_tier_flag: u8,     // 1bit,[0,1]
-    general_profile_idc: u8,   // 5bit,[0,31]
-    general_profile_compatibility_flags: u32,
-    general_constraint_indicator_flags: u64,
-    general_level_idc: u8,
-    min_spatial_segmentation_idc: u16,
-    parallelism_type: u8,        // 2bit,[0,3]
-    chroma_format: u8,           // 2bit,[0,3]
-    bit_depth_luma_minus8: u8,   // 3bit,[0,7]
-    bit_depth_chroma_minus8: u8, // 3bit,[0,7]
-    avg_frame_rate: u16,
-    constant_frame_rate: u8,   // 2bit,[0,3]
-    num_temporal_layers: u8,   // 3bit,[0,7]
-    temporal_id_nested: u8,    // 1bit,[0,1]
-    length_size_minus_one: u8, // 2bit,[0,3]
+    pub configuration_version: u8, // 1-only
+    pub general_profile_space: u8, // 2bit,[0,3]
+    pub general_tier_flag: u8,     // 1bit,[0,1]
+    pub general_profile_idc: u8,   // 5bit,[0,31]
+    pub general_profile_compatibility_flags: u32,
+    pub general_constraint_indicator_flags: u64,
+    pub general_level_idc: u8,
+    pub min_spatial_segmentation_idc: u16,
+    pub parallelism_type: u8,        // 2bit,[0,3]
+    pub chroma_format: u8,           // 2bit,[0,3]
+    pub bit_depth_luma_minus8: u8,   // 3bit,[0,7]
+    pub bit_depth_chroma_minus8: u8, // 3bit,[0,7]
+    pub avg_frame_rate: u16,
+    pub constant_frame_rate: u8,   // 2bit,[0,3]
+    pub num_temporal_layers: u8,   // 3bit,[0,7]
+    pub temporal_id_nested: u8,    // 1bit,[0,1]
+    pub length_size_minus_one: u8, // 2bit,[0,3]
+
+    pub vps: Vec<BytesMut>,
+    pub sps: Vec<BytesMut>,
+    pub pps: Vec<BytesMut>,
+
+    pub width: u32,
+    pub height: u32,
 }
 
 #[derive(Default)]
@@ -27,7 +44,33 @@ pub struct Mpeg4HevcProcessor {
     pub mpeg4_hevc: Mpeg4Hevc,
 }
 
+//remove the emulation prevention bytes(0x000003) of a nal unit
+fn nalu_to_rbsp(nalu: &[u8]) -> BytesMut {
+    let mut rbsp = BytesMut::with_capacity(nalu.len());
+    let mut zero_count = 0;
+
+    for byte in nalu {
+        if zero_count >= 2 && *byte == 0x03 {
+            zero_count = 0;
+            continue;
+        }
+        rbsp.extend_from_slice(&[*byte]);
+        zero_count = if *byte == 0 { zero_count + 1 } else { 0 };
+    }
+    rbsp
+}
+
 impl Mpeg4HevcProcessor {
+    pub fn new() -> Self {
+        Self {
+            mpeg4_hevc: Mpeg4Hevc {
+                configuration_version: 1,
+                length_size_minus_one: 3,
+                ..Default::default()
+            },
+        }
+    }
+
     pub fn decoder_configuration_record_load(
         &mut self,
         bytes_reader: &mut BytesReader,
@@ -47,7 +90,360 @@ impl Mpeg4HevcProcessor {
         self.mpeg4_hevc.parallelism_type = bytes_reader.read_u8()? & 0x03;
         self.mpeg4_hevc.chroma_format = bytes_reader.read_u8()? & 0x03;
         self.mpeg4_hevc.bit_depth_luma_minus8 = bytes_reader.read_u8()? & 0x07;
+        self.mpeg4_hevc.bit_depth_chroma_minus8 = bytes_reader.read_u8()? & 0x07;
+        self.mpeg4_hevc.avg_frame_rate = bytes_reader.read_u16::<BigEndian>()?;
+
+        let byte_21 = bytes_reader.read_u8()?;
+        self.mpeg4_hevc.constant_frame_rate = (byte_21 >> 6) & 0x03;
+        self.mpeg4_hevc.num_temporal_layers = (byte_21 >> 3) & 0x07;
+        self.mpeg4_hevc.temporal_id_nested = (byte_21 >> 2) & 0x01;
+        self.mpeg4_hevc.length_size_minus_one = byte_21 & 0x03;
+
+        self.mpeg4_hevc.vps.clear();
+        self.mpeg4_hevc.sps.clear();
+        self.mpeg4_hevc.pps.clear();
+
+        let num_of_arrays = bytes_reader.read_u8()?;
+        for _ in 0..num_of_arrays {
+            let nal_unit_type = bytes_reader.read_u8()? & 0x3F;
+            let num_nalus = bytes_reader.read_u16::<BigEndian>()?;
+
+            for _ in 0..num_nalus {
+                let nalu_length = bytes_reader.read_u16::<BigEndian>()?;
+                let nalu = bytes_reader.read_bytes(nalu_length as usize)?;
+
+                match nal_unit_type {
+                    hevc_nal_type::HEVC_NAL_VPS => self.mpeg4_hevc.vps.push(nalu),
+                    hevc_nal_type::HEVC_NAL_SPS => self.mpeg4_hevc.sps.push(nalu),
+                    hevc_nal_type::HEVC_NAL_PPS => self.mpeg4_hevc.pps.push(nalu),
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(sps) = self.mpeg4_hevc.sps.first().cloned() {
+            self.sps_parse(&sps)?;
+            log::info!(
+                "mpeg4 hevc profile: {} level: {} resolution: {}x{}",
+                self.mpeg4_hevc.general_profile_idc,
+                self.mpeg4_hevc.general_level_idc,
+                self.mpeg4_hevc.width,
+                self.mpeg4_hevc.height
+            );
+        }
 
         Ok(self)
     }
+
+    pub fn decoder_configuration_record_save(&mut self) -> Result<BytesMut, Mpeg4AvcHevcError> {
+        if self.mpeg4_hevc.vps.is_empty()
+            || self.mpeg4_hevc.sps.is_empty()
+            || self.mpeg4_hevc.pps.is_empty()
+        {
+            return Err(Mpeg4AvcHevcError {
+                value: MpegErrorValue::HevcParameterSetsNotFound,
+            });
+        }
+
+        let mut bytes_writer = BytesWriter::new();
+
+        bytes_writer.write_u8(1)?;
+        bytes_writer.write_u8(
+            self.mpeg4_hevc.general_profile_space << 6
+                | self.mpeg4_hevc.general_tier_flag << 5
+                | self.mpeg4_hevc.general_profile_idc,
+        )?;
+        bytes_writer.write_u32::<BigEndian>(self.mpeg4_hevc.general_profile_compatibility_flags)?;
+        //48 bits
+        bytes_writer.write_u16::<BigEndian>(
+            (self.mpeg4_hevc.general_constraint_indicator_flags >> 32) as u16,
+        )?;
+        bytes_writer
+            .write_u32::<BigEndian>(self.mpeg4_hevc.general_constraint_indicator_flags as u32)?;
+        bytes_writer.write_u8(self.mpeg4_hevc.general_level_idc)?;
+        bytes_writer
+            .write_u16::<BigEndian>(0xF000 | self.mpeg4_hevc.min_spatial_segmentation_idc)?;
+        bytes_writer.write_u8(0xFC | self.mpeg4_hevc.parallelism_type)?;
+        bytes_writer.write_u8(0xFC | self.mpeg4_hevc.chroma_format)?;
+        bytes_writer.write_u8(0xF8 | self.mpeg4_hevc.bit_depth_luma_minus8)?;
+        bytes_writer.write_u8(0xF8 | self.mpeg4_hevc.bit_depth_chroma_minus8)?;
+        bytes_writer.write_u16::<BigEndian>(self.mpeg4_hevc.avg_frame_rate)?;
+        bytes_writer.write_u8(
+            self.mpeg4_hevc.constant_frame_rate << 6
+                | self.mpeg4_hevc.num_temporal_layers << 3
+                | self.mpeg4_hevc.temporal_id_nested << 2
+                | self.mpeg4_hevc.length_size_minus_one,
+        )?;
+
+        //numOfArrays: vps/sps/pps
+        bytes_writer.write_u8(3)?;
+        for (nal_unit_type, nalus) in [
+            (hevc_nal_type::HEVC_NAL_VPS, &self.mpeg4_hevc.vps),
+            (hevc_nal_type::HEVC_NAL_SPS, &self.mpeg4_hevc.sps),
+            (hevc_nal_type::HEVC_NAL_PPS, &self.mpeg4_hevc.pps),
+        ] {
+            //array_completeness(1) reserved(1) NAL_unit_type(6)
+            bytes_writer.write_u8(0x80 | nal_unit_type)?;
+            bytes_writer.write_u16::<BigEndian>(nalus.len() as u16)?;
+            for nalu in nalus {
+                bytes_writer.write_u16::<BigEndian>(nalu.len() as u16)?;
+                bytes_writer.write(&nalu[..])?;
+            }
+        }
+
+        Ok(bytes_writer.extract_current_bytes())
+    }
+
+    //ITU-T H.265 7.3.2.2 Sequence parameter set RBSP syntax, only the fields
+    //needed by the HEVCDecoderConfigurationRecord and the resolution are parsed.
+    pub fn sps_parse(&mut self, sps: &[u8]) -> Result<(), Mpeg4AvcHevcError> {
+        let mut bits_reader = BitsReader::new(BytesReader::new(nalu_to_rbsp(sps)));
+
+        //nal unit header
+        bits_reader.read_n_bits(16)?;
+        //sps_video_parameter_set_id
+        bits_reader.read_n_bits(4)?;
+        let sps_max_sub_layers_minus1 = bits_reader.read_n_bits(3)? as usize;
+        self.mpeg4_hevc.num_temporal_layers = sps_max_sub_layers_minus1 as u8 + 1;
+        self.mpeg4_hevc.temporal_id_nested = bits_reader.read_bit()?;
+
+        //profile_tier_level
+        self.mpeg4_hevc.general_profile_space = bits_reader.read_n_bits(2)? as u8;
+        self.mpeg4_hevc.general_tier_flag = bits_reader.read_bit()?;
+        self.mpeg4_hevc.general_profile_idc = bits_reader.read_n_bits(5)? as u8;
+        self.mpeg4_hevc.general_profile_compatibility_flags = bits_reader.read_n_bits(32)? as u32;
+        self.mpeg4_hevc.general_constraint_indicator_flags = bits_reader.read_n_bits(48)?;
+        self.mpeg4_hevc.general_level_idc = bits_reader.read_n_bits(8)? as u8;
+
+        let mut sub_layer_profile_present_flags = Vec::new();
+        let mut sub_layer_level_present_flags = Vec::new();
+        for _ in 0..sps_max_sub_layers_minus1 {
+            sub_layer_profile_present_flags.push(bits_reader.read_bit()?);
+            sub_layer_level_present_flags.push(bits_reader.read_bit()?);
+        }
+        if sps_max_sub_layers_minus1 > 0 {
+            //reserved_zero_2bits
+            bits_reader.read_n_bits(2 * (8 - sps_max_sub_layers_minus1))?;
+        }
+        for i in 0..sps_max_sub_layers_minus1 {
+            if sub_layer_profile_present_flags[i] != 0 {
+                bits_reader.read_n_bits(88)?;
+            }
+            if sub_layer_level_present_flags[i] != 0 {
+                bits_reader.read_n_bits(8)?;
+            }
+        }
+
+        //sps_seq_parameter_set_id
+        read_uev(&mut bits_reader)?;
+        let chroma_format_idc = read_uev(&mut bits_reader)?;
+        self.mpeg4_hevc.chroma_format = chroma_format_idc as u8;
+        if chroma_format_idc == 3 {
+            //separate_colour_plane_flag
+            bits_reader.read_bit()?;
+        }
+
+        let mut width = read_uev(&mut bits_reader)?;
+        let mut height = read_uev(&mut bits_reader)?;
+
+        let conformance_window_flag = bits_reader.read_bit()?;
+        if conformance_window_flag != 0 {
+            let left_offset = read_uev(&mut bits_reader)?;
+            let right_offset = read_uev(&mut bits_reader)?;
+            let top_offset = read_uev(&mut bits_reader)?;
+            let bottom_offset = read_uev(&mut bits_reader)?;
+
+            let sub_width_c = if chroma_format_idc == 1 || chroma_format_idc == 2 {
+                2
+            } else {
+                1
+            };
+            let sub_height_c = if chroma_format_idc == 1 { 2 } else { 1 };
+
+            width = width.saturating_sub(sub_width_c * (left_offset + right_offset));
+            height = height.saturating_sub(sub_height_c * (top_offset + bottom_offset));
+        }
+        self.mpeg4_hevc.width = width;
+        self.mpeg4_hevc.height = height;
+
+        self.mpeg4_hevc.bit_depth_luma_minus8 = read_uev(&mut bits_reader)? as u8;
+        self.mpeg4_hevc.bit_depth_chroma_minus8 = read_uev(&mut bits_reader)? as u8;
+
+        Ok(())
+    }
+
+    pub fn read_nalu_size(
+        &mut self,
+        bytes_reader: &mut BytesReader,
+    ) -> Result<u32, Mpeg4AvcHevcError> {
+        let mut size: u32 = 0;
+
+        for _ in 0..self.mpeg4_hevc.length_size_minus_one + 1 {
+            size = bytes_reader.read_u8()? as u32 + (size << 8);
+        }
+        Ok(size)
+    }
+
+    //the vps/sps/pps are inserted before the first IRAP picture if they are not carried in-band
+    pub fn hevc_mp4toannexb(
+        &mut self,
+        bytes_reader: &mut BytesReader,
+    ) -> Result<BytesMut, Mpeg4AvcHevcError> {
+        let mut bytes_writer = BytesWriter::new();
+
+        let mut parameter_sets_flag = false;
+        while !bytes_reader.is_empty() {
+            let size = self.read_nalu_size(bytes_reader)?;
+            let nalu_type = (bytes_reader.advance_u8()? >> 1) & 0x3F;
+
+            match nalu_type {
+                hevc_nal_type::HEVC_NAL_VPS
+                | hevc_nal_type::HEVC_NAL_SPS
+                | hevc_nal_type::HEVC_NAL_PPS => {
+                    parameter_sets_flag = true;
+                }
+                hevc_nal_type::HEVC_NAL_BLA_W_LP..=hevc_nal_type::HEVC_NAL_RSV_IRAP_23
+                    if !parameter_sets_flag =>
+                {
+                    parameter_sets_flag = true;
+
+                    for nalu in self
+                        .mpeg4_hevc
+                        .vps
+                        .iter()
+                        .chain(self.mpeg4_hevc.sps.iter())
+                        .chain(self.mpeg4_hevc.pps.iter())
+                    {
+                        bytes_writer.write(&HEVC_START_CODE)?;
+                        bytes_writer.write(&nalu[..])?;
+                    }
+                }
+                _ => {}
+            }
+
+            bytes_writer.write(&HEVC_START_CODE)?;
+            let data = bytes_reader.read_bytes(size as usize)?;
+            bytes_writer.write(&data[..])?;
+        }
+
+        Ok(bytes_writer.extract_current_bytes())
+    }
+
+    pub fn write_nalu_size(
+        &mut self,
+        writer: &mut BytesWriter,
+        length: usize,
+    ) -> Result<(), Mpeg4AvcHevcError> {
+        let nalu_length = self.mpeg4_hevc.length_size_minus_one as usize + 1;
+        for i in 0..nalu_length {
+            let shift = (nalu_length - i - 1) * 8;
+            let num = ((length >> shift) & 0xFF) as u8;
+            writer.write_u8(num)?;
+        }
+        Ok(())
+    }
+
+    pub fn nalus_to_mpeg4hevc(
+        &mut self,
+        nalus: Vec<BytesMut>,
+    ) -> Result<BytesMut, Mpeg4AvcHevcError> {
+        let mut bytes_writer = BytesWriter::new();
+
+        for nalu in nalus {
+            let length = nalu.len();
+            self.write_nalu_size(&mut bytes_writer, length)?;
+            bytes_writer.write(&nalu)?;
+        }
+
+        Ok(bytes_writer.extract_current_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Mpeg4HevcProcessor;
+    use bytes::BytesMut;
+    use bytesio::bytes_reader::BytesReader;
+
+    //sprop-vps=QAEMAf//AWAAAAMAkAAAAwAAAwA/ugJA; sprop-sps=QgEBAWAAAAMAkAAAAwAAAwA/oAUCAXHy5bpKTC8BAQAAAwABAAADAA8I; sprop-pps=RAHAc8GJ
+    const VPS: &[u8] = &[
+        0x40, 0x01, 0x0c, 0x01, 0xff, 0xff, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00,
+        0x03, 0x00, 0x00, 0x03, 0x00, 0x3f, 0xba, 0x02, 0x40,
+    ];
+    const SPS: &[u8] = &[
+        0x42, 0x01, 0x01, 0x01, 0x60, 0x00, 0x00, 0x03, 0x00, 0x90, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x03, 0x00, 0x3f, 0xa0, 0x05, 0x02, 0x01, 0x71, 0xf2, 0xe5, 0xba, 0x4a, 0x4c, 0x2f, 0x01,
+        0x01, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0x03, 0x00, 0x0f, 0x08,
+    ];
+    const PPS: &[u8] = &[0x44, 0x01, 0xc0, 0x73, 0xc1, 0x89];
+
+    #[test]
+    fn test_sps_parse() {
+        let mut processor = Mpeg4HevcProcessor::new();
+        processor.sps_parse(SPS).unwrap();
+
+        assert_eq!(processor.mpeg4_hevc.general_profile_idc, 1);
+        assert_eq!(processor.mpeg4_hevc.general_level_idc, 63);
+        assert_eq!(processor.mpeg4_hevc.chroma_format, 1);
+        assert_eq!(processor.mpeg4_hevc.width, 640);
+        assert_eq!(processor.mpeg4_hevc.height, 360);
+    }
+
+    #[test]
+    fn test_decoder_configuration_record() {
+        let mut processor = Mpeg4HevcProcessor::new();
+        processor.sps_parse(SPS).unwrap();
+        processor.mpeg4_hevc.vps.push(BytesMut::from(VPS));
+        processor.mpeg4_hevc.sps.push(BytesMut::from(SPS));
+        processor.mpeg4_hevc.pps.push(BytesMut::from(PPS));
+
+        let record = processor.decoder_configuration_record_save().unwrap();
+        assert_eq!(record.len(), 23 + 3 * 5 + VPS.len() + SPS.len() + PPS.len());
+        assert_eq!(record[0], 1);
+        assert_eq!(record[21] & 0x03, 3);
+
+        let mut loaded = Mpeg4HevcProcessor::new();
+        loaded
+            .decoder_configuration_record_load(&mut BytesReader::new(record))
+            .unwrap();
+        assert_eq!(loaded.mpeg4_hevc.general_profile_idc, 1);
+        assert_eq!(loaded.mpeg4_hevc.general_level_idc, 63);
+        assert_eq!(
+            loaded.mpeg4_hevc.general_profile_compatibility_flags,
+            0x6000_0000
+        );
+        assert_eq!(
+            loaded.mpeg4_hevc.general_constraint_indicator_flags,
+            0x9000_0000_0000
+        );
+        assert_eq!(&loaded.mpeg4_hevc.vps[0][..], VPS);
+        assert_eq!(&loaded.mpeg4_hevc.sps[0][..], SPS);
+        assert_eq!(&loaded.mpeg4_hevc.pps[0][..], PPS);
+        assert_eq!(loaded.mpeg4_hevc.width, 640);
+        assert_eq!(loaded.mpeg4_hevc.height, 360);
+    }
+
+    #[test]
+    fn test_hevc_mp4toannexb() {
+        let mut processor = Mpeg4HevcProcessor::new();
+        processor.mpeg4_hevc.vps.push(BytesMut::from(VPS));
+        processor.mpeg4_hevc.sps.push(BytesMut::from(SPS));
+        processor.mpeg4_hevc.pps.push(BytesMut::from(PPS));
+
+        //an IDR_W_RADL picture without in-band parameter sets
+        let idr: &[u8] = &[0x26, 0x01, 0xaf, 0x1d];
+        let mpeg4hevc = processor
+            .nalus_to_mpeg4hevc(vec![BytesMut::from(idr)])
+            .unwrap();
+        let annexb = processor
+            .hevc_mp4toannexb(&mut BytesReader::new(mpeg4hevc))
+            .unwrap();
+
+        let mut expected = Vec::new();
+        for nalu in [VPS, SPS, PPS, idr] {
+            expected.extend_from_slice(&[0x00, 0x00, 0x00, 0x01]);
+            expected.extend_from_slice(nalu);
+        }
+        assert_eq!(&annexb[..], &expected[..]);
+    }
 }
@@ -0,0 +1,418 @@
+use {
+    super::{
+        define::{
+            box_type, brand, descriptor_tag, handler_type, sample_flags, tfhd_flags, trun_flags,
+            Mp4CodecType, MOVIE_TIMESCALE, OBJECT_TYPE_AAC, STREAM_TYPE_AUDIO,
+        },
+        errors::{Mp4Error, Mp4ErrorValue},
+        fmp4::Track,
+    },
+    byteorder::BigEndian,
+    bytes::BytesMut,
+    bytesio::bytes_writer::BytesWriter,
+};
+
+/*
+ ** ISO-BMFF Box **
+ +-----------------------------------------------------------+
+ | size(4 bytes) | type(4 bytes) | payload                   |
+ +-----------------------------------------------------------+
+
+ ** Full Box **
+ +-----------------------------------------------------------------------------+
+ | size(4 bytes) | type(4 bytes) | version(1 byte) | flags(3 bytes) | payload  |
+ +-----------------------------------------------------------------------------+
+
+ reference: ISO/IEC 14496-12
+*/
+
+const UNITY_MATRIX: [u32; 9] = [0x00010000, 0, 0, 0, 0x00010000, 0, 0, 0, 0x40000000];
+//ISO-639-2/T language code "und" packed into 15 bits
+const LANGUAGE_UND: u16 = 0x55c4;
+
+pub fn write_box(box_type: &[u8; 4], payload: &[u8]) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write_u32::<BigEndian>(8 + payload.len() as u32)?;
+    writer.write(box_type)?;
+    writer.write(payload)?;
+    Ok(writer.extract_current_bytes())
+}
+
+pub fn write_full_box(
+    box_type: &[u8; 4],
+    version: u8,
+    flags: u32,
+    payload: &[u8],
+) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write_u8(version)?;
+    writer.write_u24::<BigEndian>(flags)?;
+    writer.write(payload)?;
+    write_box(box_type, &writer.extract_current_bytes()[..])
+}
+
+fn write_matrix(writer: &mut BytesWriter) -> Result<(), Mp4Error> {
+    for value in UNITY_MATRIX {
+        writer.write_u32::<BigEndian>(value)?;
+    }
+    Ok(())
+}
+
+pub fn ftyp() -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    /*major brand*/
+    writer.write(brand::ISO6)?;
+    /*minor version*/
+    writer.write_u32::<BigEndian>(0)?;
+    /*compatible brands*/
+    for compatible_brand in [brand::ISO6, brand::CMFC, brand::ISOM, brand::MP41] {
+        writer.write(compatible_brand)?;
+    }
+    write_box(box_type::FTYP, &writer.extract_current_bytes()[..])
+}
+
+pub fn moov(tracks: &[Track]) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write(&mvhd(tracks.len() as u32 + 1)?[..])?;
+    for track in tracks {
+        writer.write(&trak(track)?[..])?;
+    }
+    writer.write(&mvex(tracks)?[..])?;
+    write_box(box_type::MOOV, &writer.extract_current_bytes()[..])
+}
+
+fn mvhd(next_track_id: u32) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    /*creation time and modification time*/
+    writer.write_u32::<BigEndian>(0)?;
+    writer.write_u32::<BigEndian>(0)?;
+    writer.write_u32::<BigEndian>(MOVIE_TIMESCALE)?;
+    /*duration, unknown for fragmented mp4*/
+    writer.write_u32::<BigEndian>(0)?;
+    /*rate 1.0*/
+    writer.write_u32::<BigEndian>(0x00010000)?;
+    /*volume 1.0*/
+    writer.write_u16::<BigEndian>(0x0100)?;
+    /*reserved*/
+    writer.write(&[0; 10])?;
+    write_matrix(&mut writer)?;
+    /*pre defined*/
+    writer.write(&[0; 24])?;
+    writer.write_u32::<BigEndian>(next_track_id)?;
+    write_full_box(box_type::MVHD, 0, 0, &writer.extract_current_bytes()[..])
+}
+
+fn trak(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write(&tkhd(track)?[..])?;
+    writer.write(&mdia(track)?[..])?;
+    write_box(box_type::TRAK, &writer.extract_current_bytes()[..])
+}
+
+fn tkhd(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    /*creation time and modification time*/
+    writer.write_u32::<BigEndian>(0)?;
+    writer.write_u32::<BigEndian>(0)?;
+    writer.write_u32::<BigEndian>(track.track_id)?;
+    /*reserved*/
+    writer.write_u32::<BigEndian>(0)?;
+    /*duration*/
+    writer.write_u32::<BigEndian>(0)?;
+    /*reserved*/
+    writer.write(&[0; 8])?;
+    /*layer and alternate group*/
+    writer.write_u16::<BigEndian>(0)?;
+    writer.write_u16::<BigEndian>(0)?;
+    /*volume*/
+    let volume = if track.is_audio() { 0x0100 } else { 0 };
+    writer.write_u16::<BigEndian>(volume)?;
+    /*reserved*/
+    writer.write_u16::<BigEndian>(0)?;
+    write_matrix(&mut writer)?;
+    /*width and height in 16.16 fixed point*/
+    writer.write_u32::<BigEndian>(track.width << 16)?;
+    writer.write_u32::<BigEndian>(track.height << 16)?;
+    /*track enabled | track in movie*/
+    write_full_box(
+        box_type::TKHD,
+        0,
+        0x000003,
+        &writer.extract_current_bytes()[..],
+    )
+}
+
+fn mdia(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write(&mdhd(track)?[..])?;
+    writer.write(&hdlr(track)?[..])?;
+    writer.write(&minf(track)?[..])?;
+    write_box(box_type::MDIA, &writer.extract_current_bytes()[..])
+}
+
+fn mdhd(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    /*creation time and modification time*/
+    writer.write_u32::<BigEndian>(0)?;
+    writer.write_u32::<BigEndian>(0)?;
+    writer.write_u32::<BigEndian>(track.timescale)?;
+    /*duration*/
+    writer.write_u32::<BigEndian>(0)?;
+    writer.write_u16::<BigEndian>(LANGUAGE_UND)?;
+    /*pre defined*/
+    writer.write_u16::<BigEndian>(0)?;
+    write_full_box(box_type::MDHD, 0, 0, &writer.extract_current_bytes()[..])
+}
+
+fn hdlr(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let (handler, name): (&[u8; 4], &[u8]) = if track.is_audio() {
+        (handler_type::SOUN, b"SoundHandler\0")
+    } else {
+        (handler_type::VIDE, b"VideoHandler\0")
+    };
+
+    let mut writer = BytesWriter::new();
+    /*pre defined*/
+    writer.write_u32::<BigEndian>(0)?;
+    writer.write(handler)?;
+    /*reserved*/
+    writer.write(&[0; 12])?;
+    writer.write(name)?;
+    write_full_box(box_type::HDLR, 0, 0, &writer.extract_current_bytes()[..])
+}
+
+fn minf(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    if track.is_audio() {
+        /*balance and reserved*/
+        writer.write(&write_full_box(box_type::SMHD, 0, 0, &[0; 4])?[..])?;
+    } else {
+        /*graphics mode and opcolor*/
+        writer.write(&write_full_box(box_type::VMHD, 0, 1, &[0; 8])?[..])?;
+    }
+    writer.write(&dinf()?[..])?;
+    writer.write(&stbl(track)?[..])?;
+    write_box(box_type::MINF, &writer.extract_current_bytes()[..])
+}
+
+fn dinf() -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    /*entry count*/
+    writer.write_u32::<BigEndian>(1)?;
+    /*the media data is in the same file*/
+    writer.write(&write_full_box(box_type::URL, 0, 1, &[])?[..])?;
+    let dref = write_full_box(box_type::DREF, 0, 0, &writer.extract_current_bytes()[..])?;
+    write_box(box_type::DINF, &dref[..])
+}
+
+fn stbl(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write(&stsd(track)?[..])?;
+    /*the sample tables are empty, the samples are described in the fragments*/
+    writer.write(&write_full_box(box_type::STTS, 0, 0, &[0; 4])?[..])?;
+    writer.write(&write_full_box(box_type::STSC, 0, 0, &[0; 4])?[..])?;
+    writer.write(&write_full_box(box_type::STSZ, 0, 0, &[0; 8])?[..])?;
+    writer.write(&write_full_box(box_type::STCO, 0, 0, &[0; 4])?[..])?;
+    write_box(box_type::STBL, &writer.extract_current_bytes()[..])
+}
+
+fn stsd(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    /*entry count*/
+    writer.write_u32::<BigEndian>(1)?;
+    let sample_entry = match track.codec {
+        Mp4CodecType::H264 => visual_sample_entry(track, box_type::AVC1, box_type::AVCC)?,
+        Mp4CodecType::H265 => visual_sample_entry(track, box_type::HVC1, box_type::HVCC)?,
+        Mp4CodecType::AAC => mp4a(track)?,
+    };
+    writer.write(&sample_entry[..])?;
+    write_full_box(box_type::STSD, 0, 0, &writer.extract_current_bytes()[..])
+}
+
+fn visual_sample_entry(
+    track: &Track,
+    entry_type: &[u8; 4],
+    config_type: &[u8; 4],
+) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    /*reserved*/
+    writer.write(&[0; 6])?;
+    /*data reference index*/
+    writer.write_u16::<BigEndian>(1)?;
+    /*pre defined and reserved*/
+    writer.write(&[0; 16])?;
+    writer.write_u16::<BigEndian>(track.width as u16)?;
+    writer.write_u16::<BigEndian>(track.height as u16)?;
+    /*horizontal and vertical resolution 72 dpi*/
+    writer.write_u32::<BigEndian>(0x00480000)?;
+    writer.write_u32::<BigEndian>(0x00480000)?;
+    /*reserved*/
+    writer.write_u32::<BigEndian>(0)?;
+    /*frame count*/
+    writer.write_u16::<BigEndian>(1)?;
+    /*compressor name*/
+    writer.write(&[0; 32])?;
+    /*depth*/
+    writer.write_u16::<BigEndian>(0x0018)?;
+    /*pre defined*/
+    writer.write_u16::<BigEndian>(0xffff)?;
+    /*the decoder configuration record(avcC/hvcC)*/
+    writer.write(&write_box(config_type, &track.codec_config[..])?[..])?;
+    write_box(entry_type, &writer.extract_current_bytes()[..])
+}
+
+fn mp4a(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    /*reserved*/
+    writer.write(&[0; 6])?;
+    /*data reference index*/
+    writer.write_u16::<BigEndian>(1)?;
+    /*reserved*/
+    writer.write(&[0; 8])?;
+    writer.write_u16::<BigEndian>(track.channels as u16)?;
+    /*sample size*/
+    writer.write_u16::<BigEndian>(16)?;
+    /*pre defined and reserved*/
+    writer.write_u32::<BigEndian>(0)?;
+    /*sample rate in 16.16 fixed point*/
+    writer.write_u32::<BigEndian>(track.timescale << 16)?;
+    writer.write(&esds(track)?[..])?;
+    write_box(box_type::MP4A, &writer.extract_current_bytes()[..])
+}
+
+fn write_descriptor(writer: &mut BytesWriter, tag: u8, payload: &[u8]) -> Result<(), Mp4Error> {
+    /*only the one byte size form is used*/
+    if payload.len() > 0x7f {
+        return Err(Mp4Error {
+            value: Mp4ErrorValue::DescriptorTooLarge,
+        });
+    }
+    writer.write_u8(tag)?;
+    writer.write_u8(payload.len() as u8)?;
+    writer.write(payload)?;
+    Ok(())
+}
+
+fn esds(track: &Track) -> Result<BytesMut, Mp4Error> {
+    let mut decoder_config = BytesWriter::new();
+    decoder_config.write_u8(OBJECT_TYPE_AAC)?;
+    /*stream type(6 bits) | up stream(1 bit) | reserved(1 bit)*/
+    decoder_config.write_u8((STREAM_TYPE_AUDIO << 2) | 0x01)?;
+    /*buffer size db, max bitrate and avg bitrate*/
+    decoder_config.write_u24::<BigEndian>(0)?;
+    decoder_config.write_u32::<BigEndian>(0)?;
+    decoder_config.write_u32::<BigEndian>(0)?;
+    write_descriptor(
+        &mut decoder_config,
+        descriptor_tag::DECODER_SPECIFIC_INFO,
+        &track.codec_config[..],
+    )?;
+
+    let mut es = BytesWriter::new();
+    /*ES_ID*/
+    es.write_u16::<BigEndian>(0)?;
+    /*flags*/
+    es.write_u8(0)?;
+    write_descriptor(
+        &mut es,
+        descriptor_tag::DECODER_CONFIG_DESCRIPTOR,
+        &decoder_config.extract_current_bytes()[..],
+    )?;
+    /*predefined SL config for mp4*/
+    write_descriptor(&mut es, descriptor_tag::SL_CONFIG_DESCRIPTOR, &[0x02])?;
+
+    let mut writer = BytesWriter::new();
+    write_descriptor(
+        &mut writer,
+        descriptor_tag::ES_DESCRIPTOR,
+        &es.extract_current_bytes()[..],
+    )?;
+    write_full_box(box_type::ESDS, 0, 0, &writer.extract_current_bytes()[..])
+}
+
+fn mvex(tracks: &[Track]) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    for track in tracks {
+        let mut trex = BytesWriter::new();
+        trex.write_u32::<BigEndian>(track.track_id)?;
+        /*default sample description index*/
+        trex.write_u32::<BigEndian>(1)?;
+        /*default sample duration, size and flags*/
+        trex.write_u32::<BigEndian>(0)?;
+        trex.write_u32::<BigEndian>(0)?;
+        trex.write_u32::<BigEndian>(0)?;
+        writer
+            .write(&write_full_box(box_type::TREX, 0, 0, &trex.extract_current_bytes()[..])?[..])?;
+    }
+    write_box(box_type::MVEX, &writer.extract_current_bytes()[..])
+}
+
+/*data_offsets are the offsets of the tracks' first samples from the start of the moof*/
+pub fn moof(
+    sequence_number: u32,
+    tracks: &[Track],
+    data_offsets: &[u32],
+) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write(&write_full_box(box_type::MFHD, 0, 0, &sequence_number.to_be_bytes())?[..])?;
+    for (track, data_offset) in tracks
+        .iter()
+        .filter(|track| !track.samples.is_empty())
+        .zip(data_offsets)
+    {
+        writer.write(&traf(track, *data_offset)?[..])?;
+    }
+    write_box(box_type::MOOF, &writer.extract_current_bytes()[..])
+}
+
+fn traf(track: &Track, data_offset: u32) -> Result<BytesMut, Mp4Error> {
+    let mut writer = BytesWriter::new();
+    writer.write(
+        &write_full_box(
+            box_type::TFHD,
+            0,
+            tfhd_flags::DEFAULT_BASE_IS_MOOF,
+            &track.track_id.to_be_bytes(),
+        )?[..],
+    )?;
+    writer.write(
+        &write_full_box(
+            box_type::TFDT,
+            1,
+            0,
+            &track.base_media_decode_time.to_be_bytes(),
+        )?[..],
+    )?;
+    writer.write(&trun(track, data_offset)?[..])?;
+    write_box(box_type::TRAF, &writer.extract_current_bytes()[..])
+}
+
+fn trun(track: &Track, data_offset: u32) -> Result<BytesMut, Mp4Error> {
+    let flags = trun_flags::DATA_OFFSET_PRESENT
+        | trun_flags::SAMPLE_DURATION_PRESENT
+        | trun_flags::SAMPLE_SIZE_PRESENT
+        | trun_flags::SAMPLE_FLAGS_PRESENT
+        | trun_flags::SAMPLE_COMPOSITION_TIME_OFFSET_PRESENT;
+
+    let mut writer = BytesWriter::new();
+    writer.write_u32::<BigEndian>(track.samples.len() as u32)?;
+    writer.write_u32::<BigEndian>(data_offset)?;
+    for sample in &track.samples {
+        writer.write_u32::<BigEndian>(sample.duration)?;
+        writer.write_u32::<BigEndian>(sample.data.len() as u32)?;
+        let flags = if sample.is_sync {
+            sample_flags::SYNC_SAMPLE
+        } else {
+            sample_flags::NON_SYNC_SAMPLE
+        };
+        writer.write_u32::<BigEndian>(flags)?;
+        /*signed composition time offset(version 1)*/
+        writer.write_u32::<BigEndian>(sample.composition_offset as u32)?;
+    }
+    write_full_box(
+        box_type::TRUN,
+        1,
+        flags,
+        &writer.extract_current_bytes()[..],
+    )
+}
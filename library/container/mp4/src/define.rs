@@ -0,0 +1,94 @@
+pub mod box_type {
+    pub const FTYP: &[u8; 4] = b"ftyp";
+    pub const MOOV: &[u8; 4] = b"moov";
+    pub const MVHD: &[u8; 4] = b"mvhd";
+    pub const TRAK: &[u8; 4] = b"trak";
+    pub const TKHD: &[u8; 4] = b"tkhd";
+    pub const MDIA: &[u8; 4] = b"mdia";
+    pub const MDHD: &[u8; 4] = b"mdhd";
+    pub const HDLR: &[u8; 4] = b"hdlr";
+    pub const MINF: &[u8; 4] = b"minf";
+    pub const VMHD: &[u8; 4] = b"vmhd";
+    pub const SMHD: &[u8; 4] = b"smhd";
+    pub const DINF: &[u8; 4] = b"dinf";
+    pub const DREF: &[u8; 4] = b"dref";
+    pub const URL: &[u8; 4] = b"url ";
+    pub const STBL: &[u8; 4] = b"stbl";
+    pub const STSD: &[u8; 4] = b"stsd";
+    pub const STTS: &[u8; 4] = b"stts";
+    pub const STSC: &[u8; 4] = b"stsc";
+    pub const STSZ: &[u8; 4] = b"stsz";
+    pub const STCO: &[u8; 4] = b"stco";
+    pub const AVC1: &[u8; 4] = b"avc1";
+    pub const AVCC: &[u8; 4] = b"avcC";
+    pub const HVC1: &[u8; 4] = b"hvc1";
+    pub const HVCC: &[u8; 4] = b"hvcC";
+    pub const MP4A: &[u8; 4] = b"mp4a";
+    pub const ESDS: &[u8; 4] = b"esds";
+    pub const MVEX: &[u8; 4] = b"mvex";
+    pub const TREX: &[u8; 4] = b"trex";
+    pub const MOOF: &[u8; 4] = b"moof";
+    pub const MFHD: &[u8; 4] = b"mfhd";
+    pub const TRAF: &[u8; 4] = b"traf";
+    pub const TFHD: &[u8; 4] = b"tfhd";
+    pub const TFDT: &[u8; 4] = b"tfdt";
+    pub const TRUN: &[u8; 4] = b"trun";
+    pub const MDAT: &[u8; 4] = b"mdat";
+}
+
+pub mod brand {
+    pub const ISO6: &[u8; 4] = b"iso6";
+    pub const CMFC: &[u8; 4] = b"cmfc";
+    pub const ISOM: &[u8; 4] = b"isom";
+    pub const MP41: &[u8; 4] = b"mp41";
+}
+
+pub mod handler_type {
+    pub const VIDE: &[u8; 4] = b"vide";
+    pub const SOUN: &[u8; 4] = b"soun";
+}
+
+pub mod tfhd_flags {
+    pub const DEFAULT_BASE_IS_MOOF: u32 = 0x020000;
+}
+
+pub mod trun_flags {
+    pub const DATA_OFFSET_PRESENT: u32 = 0x000001;
+    pub const SAMPLE_DURATION_PRESENT: u32 = 0x000100;
+    pub const SAMPLE_SIZE_PRESENT: u32 = 0x000200;
+    pub const SAMPLE_FLAGS_PRESENT: u32 = 0x000400;
+    pub const SAMPLE_COMPOSITION_TIME_OFFSET_PRESENT: u32 = 0x000800;
+}
+
+pub mod sample_flags {
+    //sample_depends_on = 2 (does not depend on others)
+    pub const SYNC_SAMPLE: u32 = 0x0200_0000;
+    //sample_depends_on = 1 and sample_is_non_sync_sample = 1
+    pub const NON_SYNC_SAMPLE: u32 = 0x0101_0000;
+}
+
+pub mod descriptor_tag {
+    pub const ES_DESCRIPTOR: u8 = 0x03;
+    pub const DECODER_CONFIG_DESCRIPTOR: u8 = 0x04;
+    pub const DECODER_SPECIFIC_INFO: u8 = 0x05;
+    pub const SL_CONFIG_DESCRIPTOR: u8 = 0x06;
+}
+
+//ISO/IEC 14496-1 objectTypeIndication of MPEG-4 audio(AAC)
+pub const OBJECT_TYPE_AAC: u8 = 0x40;
+//ISO/IEC 14496-1 streamType of audio stream
+pub const STREAM_TYPE_AUDIO: u8 = 0x05;
+
+pub const MOVIE_TIMESCALE: u32 = 1000;
+pub const VIDEO_TIMESCALE: u32 = 90000;
+//an AAC frame always contains 1024 samples
+pub const AAC_SAMPLES_PER_FRAME: u32 = 1024;
+
+pub const MAX_TRACK_COUNT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mp4CodecType {
+    H264,
+    H265,
+    AAC,
+}
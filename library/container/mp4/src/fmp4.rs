@@ -0,0 +1,458 @@
+use {
+    super::{
+        boxes,
+        define::{box_type, Mp4CodecType, AAC_SAMPLES_PER_FRAME, MAX_TRACK_COUNT, VIDEO_TIMESCALE},
+        errors::{Mp4Error, Mp4ErrorValue},
+    },
+    bytes::BytesMut,
+};
+
+/*
+ ** Fragmented MP4 **
+ +-----------------------------------------------------------------+
+ | init segment(ftyp | moov) | fragment(moof | mdat) | fragment...|
+ +-----------------------------------------------------------------+
+
+ The init segment describes the tracks(codec configurations, timescales),
+ every fragment carries the samples of all the tracks muxed since the
+ previous fragment.
+
+ reference: ISO/IEC 14496-12, ISO/IEC 23000-19(CMAF)
+*/
+
+pub struct Sample {
+    /*in the timescale of the track*/
+    pub dts: i64,
+    pub duration: u32,
+    pub composition_offset: i32,
+    pub is_sync: bool,
+    pub data: BytesMut,
+}
+
+pub struct Track {
+    pub track_id: u32,
+    pub codec: Mp4CodecType,
+    /*avcC/hvcC decoder configuration record or AAC audio specific config*/
+    pub codec_config: BytesMut,
+    pub timescale: u32,
+    pub width: u32,
+    pub height: u32,
+    pub channels: u8,
+    /*the decode time of the first sample in the current fragment*/
+    pub base_media_decode_time: u64,
+    pub samples: Vec<Sample>,
+    last_duration: u32,
+}
+
+impl Track {
+    pub fn is_audio(&self) -> bool {
+        self.codec == Mp4CodecType::AAC
+    }
+
+    /*The RFC 6381 codecs parameter, e.g. avc1.64001f, hvc1.1.6.L93.B0 and mp4a.40.2,
+    which is parsed from the decoder configuration.*/
+    pub fn codec_string(&self) -> String {
+        let config = &self.codec_config[..];
+        match self.codec {
+            Mp4CodecType::H264 if config.len() >= 4 => {
+                format!("avc1.{:02x}{:02x}{:02x}", config[1], config[2], config[3])
+            }
+            Mp4CodecType::H265 if config.len() >= 13 => {
+                let profile_space = ["", "A", "B", "C"][(config[1] >> 6) as usize];
+                let tier = if (config[1] >> 5) & 0x01 == 1 {
+                    'H'
+                } else {
+                    'L'
+                };
+                let profile_idc = config[1] & 0x1f;
+                let compatibility_flags =
+                    u32::from_be_bytes([config[2], config[3], config[4], config[5]]).reverse_bits();
+
+                let mut codec_string = format!(
+                    "hvc1.{profile_space}{profile_idc}.{compatibility_flags:X}.{tier}{}",
+                    config[12]
+                );
+                /*the constraint bytes without the trailing zero bytes*/
+                let constraints = &config[6..12];
+                let constraint_count = constraints
+                    .iter()
+                    .rposition(|b| *b != 0)
+                    .map_or(0, |i| i + 1);
+                for constraint in &constraints[..constraint_count] {
+                    codec_string += format!(".{constraint:02X}").as_str();
+                }
+                codec_string
+            }
+            Mp4CodecType::AAC if !config.is_empty() => {
+                format!("mp4a.40.{}", config[0] >> 3)
+            }
+            Mp4CodecType::H264 => String::from("avc1"),
+            Mp4CodecType::H265 => String::from("hvc1"),
+            Mp4CodecType::AAC => String::from("mp4a.40.2"),
+        }
+    }
+
+    fn to_timescale(&self, time_ms: i64) -> i64 {
+        time_ms * self.timescale as i64 / 1000
+    }
+
+    fn sample_duration(&self, dts: i64, next_dts: i64) -> u32 {
+        if self.is_audio() {
+            return AAC_SAMPLES_PER_FRAME;
+        }
+        if next_dts > dts {
+            (next_dts - dts) as u32
+        } else {
+            self.last_duration
+        }
+    }
+}
+
+pub struct Fmp4Muxer {
+    tracks: Vec<Track>,
+    sequence_number: u32,
+}
+
+impl Default for Fmp4Muxer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Fmp4Muxer {
+    pub fn new() -> Self {
+        Self {
+            tracks: Vec::new(),
+            sequence_number: 1,
+        }
+    }
+
+    fn add_track(
+        &mut self,
+        codec: Mp4CodecType,
+        codec_config: BytesMut,
+        timescale: u32,
+        width: u32,
+        height: u32,
+        channels: u8,
+    ) -> Result<u32, Mp4Error> {
+        if self.tracks.len() >= MAX_TRACK_COUNT {
+            return Err(Mp4Error {
+                value: Mp4ErrorValue::TrackCountExeceed,
+            });
+        }
+
+        let track_id = self.tracks.len() as u32 + 1;
+        self.tracks.push(Track {
+            track_id,
+            codec,
+            codec_config,
+            timescale,
+            width,
+            height,
+            channels,
+            base_media_decode_time: 0,
+            samples: Vec::new(),
+            //assume 25fps before the first duration is known
+            last_duration: timescale / 25,
+        });
+
+        Ok(track_id)
+    }
+
+    pub fn add_video_track(
+        &mut self,
+        codec: Mp4CodecType,
+        decoder_configuration_record: BytesMut,
+        width: u32,
+        height: u32,
+    ) -> Result<u32, Mp4Error> {
+        self.add_track(
+            codec,
+            decoder_configuration_record,
+            VIDEO_TIMESCALE,
+            width,
+            height,
+            0,
+        )
+    }
+
+    pub fn add_audio_track(
+        &mut self,
+        audio_specific_config: BytesMut,
+        sample_rate: u32,
+        channels: u8,
+    ) -> Result<u32, Mp4Error> {
+        self.add_track(
+            Mp4CodecType::AAC,
+            audio_specific_config,
+            sample_rate,
+            0,
+            0,
+            channels,
+        )
+    }
+
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    pub fn has_tracks(&self) -> bool {
+        !self.tracks.is_empty()
+    }
+
+    pub fn gen_init_segment(&self) -> Result<BytesMut, Mp4Error> {
+        let mut init_segment = boxes::ftyp()?;
+        init_segment.extend_from_slice(&boxes::moov(&self.tracks)?[..]);
+        Ok(init_segment)
+    }
+
+    /*pts and dts are in milliseconds, the video data is length prefixed(AVCC/HVCC) NALUs
+    and the audio data is a raw AAC frame.*/
+    pub fn write(
+        &mut self,
+        track_id: u32,
+        pts: i64,
+        dts: i64,
+        is_sync: bool,
+        data: BytesMut,
+    ) -> Result<(), Mp4Error> {
+        let track = self
+            .tracks
+            .iter_mut()
+            .find(|track| track.track_id == track_id)
+            .ok_or(Mp4Error {
+                value: Mp4ErrorValue::TrackNotFound,
+            })?;
+
+        let dts = track.to_timescale(dts);
+        let composition_offset = (track.to_timescale(pts) - dts) as i32;
+
+        let duration = match track.samples.last() {
+            Some(last_sample) => Some(track.sample_duration(last_sample.dts, dts)),
+            None => {
+                track.base_media_decode_time = dts as u64;
+                None
+            }
+        };
+        if let Some(duration) = duration {
+            track.last_duration = duration;
+            track.samples.last_mut().unwrap().duration = duration;
+        }
+
+        track.samples.push(Sample {
+            dts,
+            duration: track.last_duration,
+            composition_offset,
+            is_sync: is_sync || track.is_audio(),
+            data,
+        });
+
+        Ok(())
+    }
+
+    /*Generate a fragment(moof/mdat) from the samples written since the last fragment,
+    end_dts(milliseconds) is used to compute the duration of the last video sample.*/
+    pub fn get_data(&mut self, end_dts: i64) -> Result<BytesMut, Mp4Error> {
+        let mut data_size = 0;
+        for track in &mut self.tracks {
+            let end_dts = track.to_timescale(end_dts);
+            if let Some(last_sample) = track.samples.last() {
+                let duration = track.sample_duration(last_sample.dts, end_dts);
+                track.samples.last_mut().unwrap().duration = duration;
+            }
+            data_size += track
+                .samples
+                .iter()
+                .map(|sample| sample.data.len())
+                .sum::<usize>();
+        }
+
+        if data_size == 0 {
+            return Ok(BytesMut::new());
+        }
+
+        /*the moof size does not depend on the data offsets, so generate it
+        once to get the size and again with the real offsets*/
+        let track_count = self
+            .tracks
+            .iter()
+            .filter(|track| !track.samples.is_empty())
+            .count();
+        let moof_size =
+            boxes::moof(self.sequence_number, &self.tracks, &vec![0; track_count])?.len() as u32;
+
+        let mut data_offsets = Vec::new();
+        let mut mdat_payload = BytesMut::with_capacity(data_size);
+        for track in self.tracks.iter().filter(|track| !track.samples.is_empty()) {
+            /*8 bytes mdat box header*/
+            data_offsets.push(moof_size + 8 + mdat_payload.len() as u32);
+            for sample in &track.samples {
+                mdat_payload.extend_from_slice(&sample.data[..]);
+            }
+        }
+
+        let mut fragment = boxes::moof(self.sequence_number, &self.tracks, &data_offsets)?;
+        fragment.extend_from_slice(&boxes::write_box(box_type::MDAT, &mdat_payload[..])?[..]);
+
+        for track in &mut self.tracks {
+            track.samples.clear();
+        }
+        self.sequence_number += 1;
+
+        Ok(fragment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::Fmp4Muxer, crate::define::Mp4CodecType, bytes::BytesMut};
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    }
+
+    //returns the (type, offset, size) of the boxes in data
+    fn parse_boxes(data: &[u8]) -> Vec<(String, usize, usize)> {
+        let mut boxes = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= data.len() {
+            let size = read_u32(data, offset) as usize;
+            let box_type = String::from_utf8_lossy(&data[offset + 4..offset + 8]).to_string();
+            boxes.push((box_type, offset, size));
+            offset += size;
+        }
+        assert_eq!(offset, data.len());
+        boxes
+    }
+
+    fn find(data: &[u8], pattern: &[u8]) -> Option<usize> {
+        data.windows(pattern.len())
+            .position(|window| window == pattern)
+    }
+
+    #[test]
+    fn test_init_segment() {
+        let mut muxer = Fmp4Muxer::new();
+        let avcc = BytesMut::from(&[0x01, 0x64, 0x00, 0x1f, 0xff, 0xe0, 0x00][..]);
+        let asc = BytesMut::from(&[0x12, 0x10][..]);
+        assert_eq!(
+            muxer
+                .add_video_track(Mp4CodecType::H264, avcc, 1280, 720)
+                .unwrap(),
+            1
+        );
+        assert_eq!(muxer.add_audio_track(asc, 44100, 2).unwrap(), 2);
+
+        let init_segment = muxer.gen_init_segment().unwrap();
+        let boxes = parse_boxes(&init_segment[..]);
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(boxes[0].0, "ftyp");
+        assert_eq!(boxes[1].0, "moov");
+
+        let moov = &init_segment[boxes[1].1 + 8..boxes[1].1 + boxes[1].2];
+        let moov_boxes: Vec<String> = parse_boxes(moov).into_iter().map(|b| b.0).collect();
+        assert_eq!(moov_boxes, vec!["mvhd", "trak", "trak", "mvex"]);
+
+        for box_type in [
+            &b"avc1"[..],
+            b"avcC",
+            b"mp4a",
+            b"esds",
+            b"trex",
+            b"vmhd",
+            b"smhd",
+        ] {
+            assert!(find(&init_segment[..], box_type).is_some());
+        }
+        //the audio specific config is in the decoder specific info descriptor
+        assert!(find(&init_segment[..], &[0x05, 0x02, 0x12, 0x10]).is_some());
+    }
+
+    #[test]
+    fn test_codec_string() {
+        let mut muxer = Fmp4Muxer::new();
+        muxer
+            .add_video_track(
+                Mp4CodecType::H264,
+                BytesMut::from(&[0x01, 0x64, 0x00, 0x1f, 0xff][..]),
+                1280,
+                720,
+            )
+            .unwrap();
+        muxer
+            .add_audio_track(BytesMut::from(&[0x12, 0x10][..]), 44100, 2)
+            .unwrap();
+        assert_eq!(muxer.tracks()[0].codec_string(), "avc1.64001f");
+        assert_eq!(muxer.tracks()[1].codec_string(), "mp4a.40.2");
+
+        let mut muxer = Fmp4Muxer::new();
+        //main profile, main tier, level 3.1
+        let hvcc = [
+            0x01, 0x01, 0x60, 0x00, 0x00, 0x00, 0xb0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x5d,
+        ];
+        muxer
+            .add_video_track(Mp4CodecType::H265, BytesMut::from(&hvcc[..]), 640, 360)
+            .unwrap();
+        assert_eq!(muxer.tracks()[0].codec_string(), "hvc1.1.6.L93.B0");
+    }
+
+    #[test]
+    fn test_fragment() {
+        let mut muxer = Fmp4Muxer::new();
+        let video = muxer
+            .add_video_track(Mp4CodecType::H265, BytesMut::from(&[0x01][..]), 640, 360)
+            .unwrap();
+        let audio = muxer
+            .add_audio_track(BytesMut::from(&[0x12, 0x10][..]), 48000, 2)
+            .unwrap();
+
+        assert!(muxer.get_data(0).unwrap().is_empty());
+
+        muxer
+            .write(video, 1040, 1000, true, BytesMut::from(&[0xaa; 10][..]))
+            .unwrap();
+        muxer
+            .write(audio, 1000, 1000, false, BytesMut::from(&[0xbb; 4][..]))
+            .unwrap();
+        muxer
+            .write(video, 1080, 1040, false, BytesMut::from(&[0xcc; 6][..]))
+            .unwrap();
+
+        let fragment = muxer.get_data(1080).unwrap();
+        let boxes = parse_boxes(&fragment[..]);
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(boxes[0].0, "moof");
+        assert_eq!(boxes[1].0, "mdat");
+        assert_eq!(boxes[1].2, 8 + 10 + 6 + 4);
+
+        let moof = &fragment[..boxes[0].2];
+        //the first trun data offset points to the start of the mdat payload
+        let trun = find(moof, b"trun").unwrap();
+        let sample_count = read_u32(moof, trun + 8);
+        let data_offset = read_u32(moof, trun + 12) as usize;
+        assert_eq!(sample_count, 2);
+        assert_eq!(data_offset, boxes[1].1 + 8);
+        assert_eq!(fragment[data_offset], 0xaa);
+        //duration and composition offset of the first video sample in 90kHz
+        assert_eq!(read_u32(moof, trun + 16), 3600);
+        assert_eq!(read_u32(moof, trun + 28), 3600);
+        //the tfdt of the video track
+        let tfdt = find(moof, b"tfdt").unwrap();
+        assert_eq!(read_u32(moof, tfdt + 12), 90000);
+
+        //the second trun is for the audio track
+        let audio_trun = trun + 4 + find(&moof[trun + 4..], b"trun").unwrap();
+        let audio_data_offset = read_u32(moof, audio_trun + 12) as usize;
+        assert_eq!(fragment[audio_data_offset], 0xbb);
+        assert_eq!(read_u32(moof, audio_trun + 16), 1024);
+
+        //the samples are cleared and the sequence number increases
+        assert!(muxer.get_data(2000).unwrap().is_empty());
+    }
+}
@@ -162,6 +162,11 @@ impl PesMuxer {
         {
             let header: [u8; 6] = [0x00, 0x00, 0x00, 0x01, 0x09, 0xF0];
             self.bytes_writer.write(&header)?;
+        } else if define::epsi_stream_type::PSI_STREAM_H265 == stream_data.codec_id
+            && !h264_h265_with_aud
+        {
+            let header: [u8; 7] = [0x00, 0x00, 0x00, 0x01, 0x46, 0x01, 0x50];
+            self.bytes_writer.write(&header)?;
         }
 
         let pes_payload_length =
@@ -433,3 +433,40 @@ impl TsMuxer {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TsMuxer;
+    use crate::define::{epsi_stream_type, MPEG_FLAG_IDR_FRAME, TS_PACKET_SIZE};
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_h265_stream() {
+        let mut ts_muxer = TsMuxer::new();
+        let pid = ts_muxer
+            .add_stream(epsi_stream_type::PSI_STREAM_H265, BytesMut::new())
+            .unwrap();
+
+        let idr: &[u8] = &[0x00, 0x00, 0x00, 0x01, 0x26, 0x01, 0xaf, 0x1d];
+        ts_muxer
+            .write(
+                pid,
+                40 * 90,
+                40 * 90,
+                MPEG_FLAG_IDR_FRAME,
+                BytesMut::from(idr),
+            )
+            .unwrap();
+        let data = ts_muxer.get_data();
+        assert_eq!(data.len(), 3 * TS_PACKET_SIZE);
+
+        //PAT, PMT and one PES packet, the stream_type is the 13th byte of the PMT section
+        let pmt = &data[TS_PACKET_SIZE..2 * TS_PACKET_SIZE];
+        assert_eq!(pmt[5 + 12], epsi_stream_type::PSI_STREAM_H265);
+
+        //an AUD(nal type 35) is inserted before the payload
+        let aud: &[u8] = &[0x00, 0x00, 0x00, 0x01, 0x46, 0x01, 0x50];
+        let pes = &data[2 * TS_PACKET_SIZE..];
+        assert!(pes.windows(aud.len()).any(|w| w == aud));
+    }
+}
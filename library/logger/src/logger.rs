@@ -219,7 +219,7 @@ mod tests {
     fn test_write_file() {
         match OpenOptions::new().append(true).open("abc.txt") {
             Ok(mut file) => {
-                if let Err(err) = file.write_all(&[b'h', b'e', b'l', b'l', b'o', b'o']) {
+                if let Err(err) = file.write_all(b"helloo") {
                     println!("file write_all: {err}");
                 }
             }
@@ -0,0 +1,124 @@
+use {
+    super::errors::RecordError,
+    serde::Deserialize,
+    std::path::{Component, Path},
+    tokio::sync::{mpsc, oneshot},
+    xflv::define::FlvData,
+};
+
+/*{app}, {stream}, {timestamp}(the local time when the file is created) and
+{index}(the sequence number of the file in a recording) are replaced, the
+extension is appended according to the format.*/
+pub const DEFAULT_RECORD_PATH: &str = "./record/{app}/{stream}/{stream}_{timestamp}";
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+pub enum RecordFormat {
+    #[default]
+    #[serde(rename = "flv")]
+    Flv,
+    /* fragmented mp4 */
+    #[serde(rename = "mp4")]
+    Mp4,
+}
+
+impl RecordFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RecordFormat::Flv => "flv",
+            RecordFormat::Mp4 => "mp4",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordSetting {
+    pub format: RecordFormat,
+    pub path: String,
+    /* roll to a new file when the duration(seconds) is reached, 0 means never */
+    pub max_duration: u64,
+    /* roll to a new file when the size(bytes) is reached, 0 means never */
+    pub max_size: u64,
+}
+
+impl Default for RecordSetting {
+    fn default() -> Self {
+        Self {
+            format: RecordFormat::default(),
+            path: String::from(DEFAULT_RECORD_PATH),
+            max_duration: 0,
+            max_size: 0,
+        }
+    }
+}
+
+pub type RecordResultSender = oneshot::Sender<Result<(), RecordError>>;
+
+/* The commands from the http api. */
+pub enum RecordCommand {
+    Start {
+        app_name: String,
+        stream_name: String,
+        result_sender: RecordResultSender,
+    },
+    Stop {
+        app_name: String,
+        stream_name: String,
+        result_sender: RecordResultSender,
+    },
+}
+
+pub type RecordCommandSender = mpsc::UnboundedSender<RecordCommand>;
+pub type RecordCommandReceiver = mpsc::UnboundedReceiver<RecordCommand>;
+
+pub const DEFAULT_VOD_APP_NAME: &str = "vod";
+pub const DEFAULT_VOD_ROOT_DIR: &str = "./record";
+//the max count of tags queued for a vod player
+pub const VOD_DATA_QUEUE_SIZE: usize = 16;
+
+/* Plays the files under root_dir when the app_name is requested. */
+#[derive(Debug, Clone)]
+pub struct VodSetting {
+    pub app_name: String,
+    pub root_dir: String,
+}
+
+impl Default for VodSetting {
+    fn default() -> Self {
+        Self {
+            app_name: String::from(DEFAULT_VOD_APP_NAME),
+            root_dir: String::from(DEFAULT_VOD_ROOT_DIR),
+        }
+    }
+}
+
+impl VodSetting {
+    /*The stream name is the path of the file relative to the root directory(the
+    .flv extension can be omitted), none is returned if it is outside of the root.*/
+    pub fn get_file_path(&self, stream_name: &str) -> Option<String> {
+        let stream_name = stream_name.trim_start_matches('/');
+        if stream_name.is_empty()
+            || !Path::new(stream_name)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return None;
+        }
+
+        let file_path = format!("{}/{}", self.root_dir.trim_end_matches('/'), stream_name);
+        if file_path.ends_with(".flv") {
+            Some(file_path)
+        } else {
+            Some(format!("{file_path}.flv"))
+        }
+    }
+}
+
+pub enum VodCommand {
+    //the timestamp(ms) to seek to
+    Seek { timestamp: u32 },
+}
+
+pub type VodCommandSender = mpsc::UnboundedSender<VodCommand>;
+pub type VodCommandReceiver = mpsc::UnboundedReceiver<VodCommand>;
+pub type VodDataSender = mpsc::Sender<FlvData>;
+pub type VodDataReceiver = mpsc::Receiver<FlvData>;
@@ -0,0 +1,6 @@
+pub mod define;
+pub mod errors;
+pub mod manager;
+pub mod recorder;
+pub mod vod;
+pub mod writer;
@@ -0,0 +1,194 @@
+use {
+    super::{
+        define::{RecordCommand, RecordCommandReceiver, RecordCommandSender, RecordSetting},
+        errors::{RecordError, RecordErrorValue},
+        recorder::Recorder,
+    },
+    std::{collections::HashMap, sync::Arc},
+    streamhub::{
+        define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
+        notify::Notifier,
+        stream::StreamIdentifier,
+    },
+    tokio::sync::{mpsc, oneshot},
+};
+
+/*Starts a recorder for every published stream(if auto record is enabled)
+and starts/stops the recorders on the commands from the http api.*/
+pub struct RecordManager {
+    client_event_consumer: BroadcastEventReceiver,
+    event_producer: StreamHubEventSender,
+    command_sender: RecordCommandSender,
+    command_receiver: RecordCommandReceiver,
+    setting: RecordSetting,
+    auto_record: bool,
+    //the stop senders of the recorders, the key is {app_name}/{stream_name}
+    recorders: HashMap<String, oneshot::Sender<()>>,
+    notifier: Option<Arc<dyn Notifier>>,
+}
+
+impl RecordManager {
+    pub fn new(
+        consumer: BroadcastEventReceiver,
+        event_producer: StreamHubEventSender,
+        setting: RecordSetting,
+    ) -> Self {
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+
+        Self {
+            client_event_consumer: consumer,
+            event_producer,
+            command_sender,
+            command_receiver,
+            setting,
+            auto_record: true,
+            recorders: HashMap::new(),
+            notifier: None,
+        }
+    }
+
+    //record the streams only when requested by the http api
+    pub fn set_auto_record(&mut self, auto_record: bool) {
+        self.auto_record = auto_record;
+    }
+
+    //notify the finished record files
+    pub fn set_notifier(&mut self, notifier: Arc<dyn Notifier>) {
+        self.notifier = Some(notifier);
+    }
+
+    pub fn get_command_sender(&self) -> RecordCommandSender {
+        self.command_sender.clone()
+    }
+
+    pub async fn run(&mut self) -> Result<(), RecordError> {
+        loop {
+            tokio::select! {
+                event = self.client_event_consumer.recv() => {
+                    if let BroadcastEvent::Publish {
+                        identifier: StreamIdentifier::Rtmp {
+                            app_name,
+                            stream_name,
+                        },
+                    } = event?
+                    {
+                        if self.auto_record {
+                            if let Err(err) = self.start_record(app_name, stream_name) {
+                                log::warn!("start record error: {}", err);
+                            }
+                        }
+                    }
+                }
+                Some(command) = self.command_receiver.recv() => {
+                    match command {
+                        RecordCommand::Start {
+                            app_name,
+                            stream_name,
+                            result_sender,
+                        } => {
+                            let result = self.start_record(app_name, stream_name);
+                            if result_sender.send(result).is_err() {
+                                log::error!("send start record result error");
+                            }
+                        }
+                        RecordCommand::Stop {
+                            app_name,
+                            stream_name,
+                            result_sender,
+                        } => {
+                            let result = self.stop_record(app_name, stream_name);
+                            if result_sender.send(result).is_err() {
+                                log::error!("send stop record result error");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn start_record(&mut self, app_name: String, stream_name: String) -> Result<(), RecordError> {
+        let key = format!("{app_name}/{stream_name}");
+        //the receiver is dropped when the recorder exits
+        if let Some(stop_sender) = self.recorders.get(&key) {
+            if !stop_sender.is_closed() {
+                return Err(RecordError {
+                    value: RecordErrorValue::AlreadyRecording,
+                });
+            }
+        }
+
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        let mut recorder = Recorder::new(
+            app_name,
+            stream_name,
+            self.setting.clone(),
+            self.event_producer.clone(),
+            stop_receiver,
+        );
+        if let Some(notifier) = &self.notifier {
+            recorder.set_notifier(notifier.clone());
+        }
+        tokio::spawn(async move {
+            if let Err(err) = recorder.run().await {
+                log::error!("recorder run error: {}", err);
+            }
+        });
+        self.recorders.insert(key, stop_sender);
+
+        Ok(())
+    }
+
+    fn stop_record(&mut self, app_name: String, stream_name: String) -> Result<(), RecordError> {
+        if let Some(stop_sender) = self.recorders.remove(&format!("{app_name}/{stream_name}")) {
+            if stop_sender.send(()).is_ok() {
+                return Ok(());
+            }
+        }
+        Err(RecordError {
+            value: RecordErrorValue::NotRecording,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::RecordManager,
+        crate::{define::RecordSetting, errors::RecordErrorValue},
+        tokio::sync::{broadcast, mpsc},
+    };
+
+    #[tokio::test]
+    async fn test_start_stop_record() {
+        let (_, client_event_consumer) = broadcast::channel(1);
+        let (event_producer, _event_consumer) = mpsc::unbounded_channel();
+        let mut manager = RecordManager::new(
+            client_event_consumer,
+            event_producer,
+            RecordSetting::default(),
+        );
+
+        assert!(manager
+            .start_record(String::from("live"), String::from("test"))
+            .is_ok());
+        assert!(matches!(
+            manager
+                .start_record(String::from("live"), String::from("test"))
+                .unwrap_err()
+                .value,
+            RecordErrorValue::AlreadyRecording
+        ));
+
+        assert!(manager
+            .stop_record(String::from("live"), String::from("test"))
+            .is_ok());
+        assert!(matches!(
+            manager
+                .stop_record(String::from("live"), String::from("test"))
+                .unwrap_err()
+                .value,
+            RecordErrorValue::NotRecording
+        ));
+    }
+}
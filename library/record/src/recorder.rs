@@ -0,0 +1,416 @@
+use {
+    super::{
+        define::{RecordFormat, RecordSetting},
+        errors::{RecordError, RecordErrorValue},
+        writer::{create_writer, RecordWriter},
+    },
+    bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+    chrono::Local,
+    std::sync::Arc,
+    streamhub::{
+        define::{
+            FrameData, FrameDataReceiver, NotifyInfo, StreamHubEvent, StreamHubEventMessage,
+            StreamHubEventSender, SubDataType, SubscribeType, SubscriberInfo,
+        },
+        notify::Notifier,
+        queue,
+        stream::StreamIdentifier,
+        utils::{RandomDigitCount, Uuid},
+    },
+    tokio::sync::oneshot,
+    xflv::{
+        amf0::amf0_writer::Amf0Writer,
+        define::{aac_packet_type, avc_packet_type, frame_type, FlvData, SoundFormat},
+        flv_tag_header::{AudioTagHeader, VideoTagHeader},
+        Unmarshal,
+    },
+};
+
+//the names come from the clients, they must not point outside of the record path
+fn is_valid_path_component(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\', '\0'])
+}
+
+//replace the placeholders of the path template and append the extension
+pub fn generate_file_path(
+    template: &str,
+    app_name: &str,
+    stream_name: &str,
+    timestamp: &str,
+    index: u32,
+    format: RecordFormat,
+) -> Result<String, RecordError> {
+    for name in [app_name, stream_name] {
+        if !is_valid_path_component(name) {
+            return Err(RecordError {
+                value: RecordErrorValue::InvalidPathComponent(name.to_string()),
+            });
+        }
+    }
+
+    let path = template
+        .replace("{app}", app_name)
+        .replace("{stream}", stream_name)
+        .replace("{timestamp}", timestamp)
+        .replace("{index}", &index.to_string());
+    Ok(format!("{}.{}", path, format.extension()))
+}
+
+/*Records one stream, the sequence headers(and the metadata) are cached
+and written at the beginning of every file, and a new file always begins
+with a video key frame so that every file can be played independently.*/
+pub struct Recorder {
+    app_name: String,
+    stream_name: String,
+    setting: RecordSetting,
+
+    event_producer: StreamHubEventSender,
+    data_consumer: FrameDataReceiver,
+    subscriber_id: Uuid,
+    stop_receiver: oneshot::Receiver<()>,
+
+    metadata: Option<BytesMut>,
+    video_seq: Option<BytesMut>,
+    audio_seq: Option<BytesMut>,
+
+    writer: Option<Box<dyn RecordWriter>>,
+    file_path: String,
+    file_index: u32,
+    //the timestamp of the first frame of the current file
+    file_start_timestamp: u32,
+    //the timestamp of the last frame written to the current file
+    file_last_timestamp: u32,
+    //notify the finished files
+    notifier: Option<Arc<dyn Notifier>>,
+}
+
+impl Recorder {
+    pub fn new(
+        app_name: String,
+        stream_name: String,
+        setting: RecordSetting,
+        event_producer: StreamHubEventSender,
+        stop_receiver: oneshot::Receiver<()>,
+    ) -> Self {
+        let (_, data_consumer) = queue::unbounded();
+
+        Self {
+            app_name,
+            stream_name,
+            setting,
+            event_producer,
+            data_consumer,
+            subscriber_id: Uuid::new(RandomDigitCount::Four),
+            stop_receiver,
+            metadata: None,
+            video_seq: None,
+            audio_seq: None,
+            writer: None,
+            file_path: String::new(),
+            file_index: 0,
+            file_start_timestamp: 0,
+            file_last_timestamp: 0,
+            notifier: None,
+        }
+    }
+
+    pub fn set_notifier(&mut self, notifier: Arc<dyn Notifier>) {
+        self.notifier = Some(notifier);
+    }
+
+    pub async fn run(&mut self) -> Result<(), RecordError> {
+        self.subscribe_from_stream_hub().await?;
+
+        loop {
+            tokio::select! {
+                data = self.data_consumer.recv() => {
+                    match data {
+                        Some(data) => {
+                            if let Err(err) = self.process_frame_data(data) {
+                                log::error!("record {} error: {}", self.file_path, err);
+                                break;
+                            }
+                        }
+                        //the stream is unpublished
+                        None => break,
+                    }
+                }
+                _ = &mut self.stop_receiver => break,
+            }
+        }
+
+        self.close_file()?;
+        self.unsubscribe_from_stream_hub().await
+    }
+
+    fn process_frame_data(&mut self, data: FrameData) -> Result<(), RecordError> {
+        match data {
+            FrameData::MetaData { timestamp: _, data } => {
+                //remove @setDataFrame from RTMP's metadata
+                let mut amf_writer = Amf0Writer::new();
+                amf_writer.write_string(&String::from("@setDataFrame"))?;
+                let (_, right) = data.split_at(amf_writer.len());
+                self.metadata = Some(BytesMut::from(right));
+            }
+            FrameData::Video { timestamp, data } => {
+                let tag_header = VideoTagHeader::unmarshal(&mut BytesReader::new(data.clone()))?;
+                let is_key_frame = tag_header.frame_type == frame_type::KEY_FRAME;
+
+                if is_key_frame && tag_header.avc_packet_type == avc_packet_type::AVC_SEQHDR {
+                    self.video_seq = Some(data);
+                    return Ok(());
+                }
+
+                if is_key_frame && (self.writer.is_none() || self.need_new_file(timestamp)) {
+                    self.open_file(timestamp)?;
+                }
+                self.write(timestamp, data, true)?;
+            }
+            FrameData::Audio { timestamp, data } => {
+                let tag_header = AudioTagHeader::unmarshal(&mut BytesReader::new(data.clone()))?;
+
+                if tag_header.sound_format == SoundFormat::AAC as u8
+                    && tag_header.aac_packet_type == aac_packet_type::AAC_SEQHDR
+                {
+                    self.audio_seq = Some(data);
+                    return Ok(());
+                }
+
+                //the files are split by the video key frames if there is video
+                if self.video_seq.is_none()
+                    && (self.writer.is_none() || self.need_new_file(timestamp))
+                {
+                    self.open_file(timestamp)?;
+                }
+                self.write(timestamp, data, false)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn need_new_file(&self, timestamp: u32) -> bool {
+        let duration = timestamp.saturating_sub(self.file_start_timestamp) as u64;
+        let size = self.writer.as_ref().map_or(0, |writer| writer.size());
+
+        (self.setting.max_duration > 0 && duration >= self.setting.max_duration * 1000)
+            || (self.setting.max_size > 0 && size >= self.setting.max_size)
+    }
+
+    fn open_file(&mut self, timestamp: u32) -> Result<(), RecordError> {
+        self.close_file()?;
+
+        let file_path = generate_file_path(
+            &self.setting.path,
+            &self.app_name,
+            &self.stream_name,
+            &Local::now().format("%Y%m%d%H%M%S").to_string(),
+            self.file_index,
+            self.setting.format,
+        )?;
+        let mut writer = create_writer(
+            self.setting.format,
+            &file_path,
+            self.audio_seq.is_some(),
+            self.video_seq.is_some(),
+        )?;
+
+        if let Some(metadata) = &self.metadata {
+            writer.write(FlvData::MetaData {
+                timestamp: 0,
+                data: metadata.clone(),
+            })?;
+        }
+        if let Some(video_seq) = &self.video_seq {
+            writer.write(FlvData::Video {
+                timestamp: 0,
+                data: video_seq.clone(),
+            })?;
+        }
+        if let Some(audio_seq) = &self.audio_seq {
+            writer.write(FlvData::Audio {
+                timestamp: 0,
+                data: audio_seq.clone(),
+            })?;
+        }
+
+        log::info!("start recording to {}", file_path);
+        self.writer = Some(writer);
+        self.file_path = file_path;
+        self.file_index += 1;
+        self.file_start_timestamp = timestamp;
+        self.file_last_timestamp = timestamp;
+
+        Ok(())
+    }
+
+    fn close_file(&mut self) -> Result<(), RecordError> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.finish()?;
+            log::info!("stop recording to {}", self.file_path);
+
+            if let Some(notifier) = &self.notifier {
+                let notifier = notifier.clone();
+                let message = StreamHubEventMessage::Record {
+                    identifier: StreamIdentifier::Rtmp {
+                        app_name: self.app_name.clone(),
+                        stream_name: self.stream_name.clone(),
+                    },
+                    file_path: self.file_path.clone(),
+                    duration: (self.file_last_timestamp - self.file_start_timestamp) as u64,
+                };
+                tokio::spawn(async move { notifier.on_record_notify(&message).await });
+            }
+        }
+        Ok(())
+    }
+
+    //the timestamps in a file start from 0
+    fn write(&mut self, timestamp: u32, data: BytesMut, is_video: bool) -> Result<(), RecordError> {
+        //the frames before the first key frame of the file are dropped
+        if timestamp < self.file_start_timestamp {
+            return Ok(());
+        }
+
+        if let Some(writer) = &mut self.writer {
+            self.file_last_timestamp = self.file_last_timestamp.max(timestamp);
+            let timestamp = timestamp - self.file_start_timestamp;
+            writer.write(if is_video {
+                FlvData::Video { timestamp, data }
+            } else {
+                FlvData::Audio { timestamp, data }
+            })?;
+        }
+        Ok(())
+    }
+
+    fn subscriber_info(&self) -> SubscriberInfo {
+        SubscriberInfo {
+            id: self.subscriber_id,
+            sub_type: SubscribeType::RtmpRemux2Record,
+            sub_data_type: SubDataType::Frame,
+            notify_info: NotifyInfo {
+                request_url: String::from(""),
+                remote_addr: String::from(""),
+            },
+        }
+    }
+
+    async fn subscribe_from_stream_hub(&mut self) -> Result<(), RecordError> {
+        let identifier = StreamIdentifier::Rtmp {
+            app_name: self.app_name.clone(),
+            stream_name: self.stream_name.clone(),
+        };
+
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+
+        let subscribe_event = StreamHubEvent::Subscribe {
+            identifier,
+            info: self.subscriber_info(),
+            result_sender: event_result_sender,
+        };
+
+        if self.event_producer.send(subscribe_event).is_err() {
+            return Err(RecordError {
+                value: RecordErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        self.data_consumer = event_result_receiver.await??.0.frame_receiver.unwrap();
+
+        Ok(())
+    }
+
+    async fn unsubscribe_from_stream_hub(&mut self) -> Result<(), RecordError> {
+        let identifier = StreamIdentifier::Rtmp {
+            app_name: self.app_name.clone(),
+            stream_name: self.stream_name.clone(),
+        };
+
+        let subscribe_event = StreamHubEvent::UnSubscribe {
+            identifier,
+            info: self.subscriber_info(),
+        };
+        if let Err(err) = self.event_producer.send(subscribe_event) {
+            log::error!("unsubscribe_from_stream_hub err {}", err);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::generate_file_path,
+        crate::{define::RecordFormat, errors::RecordErrorValue},
+    };
+
+    #[test]
+    fn test_generate_file_path() {
+        assert_eq!(
+            generate_file_path(
+                "./record/{app}/{stream}/{stream}_{timestamp}",
+                "live",
+                "test",
+                "20240102030405",
+                0,
+                RecordFormat::Flv
+            )
+            .unwrap(),
+            "./record/live/test/test_20240102030405.flv"
+        );
+        assert_eq!(
+            generate_file_path(
+                "/data/{app}_{stream}_{index}",
+                "live",
+                "test",
+                "20240102030405",
+                3,
+                RecordFormat::Mp4
+            )
+            .unwrap(),
+            "/data/live_test_3.mp4"
+        );
+    }
+
+    #[test]
+    fn test_generate_file_path_traversal() {
+        for (app_name, stream_name) in [
+            ("live", ".."),
+            ("..", "test"),
+            ("live", "../../etc/cron.d/test"),
+            ("live", "a/b"),
+            ("live", "a\\b"),
+            ("", "test"),
+        ] {
+            let result = generate_file_path(
+                "./record/{app}/{stream}",
+                app_name,
+                stream_name,
+                "20240102030405",
+                0,
+                RecordFormat::Flv,
+            );
+            assert!(matches!(
+                result.err().map(|err| err.value),
+                Some(RecordErrorValue::InvalidPathComponent(_))
+            ));
+        }
+        //the dots inside of a name are allowed
+        assert_eq!(
+            generate_file_path(
+                "./record/{app}/{stream}",
+                "live",
+                "test..1",
+                "",
+                0,
+                RecordFormat::Flv
+            )
+            .unwrap(),
+            "./record/live/test..1.flv"
+        );
+    }
+}
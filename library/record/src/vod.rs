@@ -0,0 +1,523 @@
+use {
+    super::{
+        define::{
+            VodCommand, VodCommandReceiver, VodCommandSender, VodDataReceiver, VodDataSender,
+            VOD_DATA_QUEUE_SIZE,
+        },
+        errors::{RecordError, RecordErrorValue},
+    },
+    bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+    std::{io::SeekFrom, time::Duration},
+    tokio::{
+        fs::File,
+        io::{AsyncRead, AsyncReadExt, AsyncSeekExt, BufReader},
+        sync::mpsc,
+        time::{sleep_until, Instant},
+    },
+    xflv::{
+        define::{aac_packet_type, avc_packet_type, frame_type, FlvData, SoundFormat},
+        flv_tag_header::{AudioTagHeader, VideoTagHeader},
+        Unmarshal,
+    },
+};
+
+const FLV_HEADER_SIZE: usize = 9;
+const TAG_HEADER_SIZE: usize = 11;
+const PREVIOUS_TAG_SIZE: u64 = 4;
+//enough for the audio/video tag headers(including the enhanced rtmp fourcc)
+const TAG_PREFIX_SIZE: usize = 16;
+//the interval(ms) of the seek points of the files without video
+const AUDIO_SEEK_INTERVAL: u32 = 1000;
+
+const TAG_TYPE_AUDIO: u8 = 8;
+const TAG_TYPE_VIDEO: u8 = 9;
+const TAG_TYPE_SCRIPT: u8 = 18;
+
+fn get_timestamp(tag: &FlvData) -> u32 {
+    match tag {
+        FlvData::Video { timestamp, data: _ }
+        | FlvData::Audio { timestamp, data: _ }
+        | FlvData::MetaData { timestamp, data: _ } => *timestamp,
+    }
+}
+
+struct TagHeader {
+    tag_type: u8,
+    data_size: usize,
+    timestamp: u32,
+}
+
+//returns None at the end of the file(or a truncated tag)
+async fn read_tag_header<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Option<TagHeader>, RecordError> {
+    let mut header = [0u8; TAG_HEADER_SIZE];
+    match reader.read_exact(&mut header).await {
+        Ok(_) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+
+    Ok(Some(TagHeader {
+        tag_type: header[0] & 0x1F,
+        data_size: u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize,
+        //the extended byte is the upper 8 bits of the timestamp
+        timestamp: u32::from_be_bytes([header[7], header[4], header[5], header[6]]),
+    }))
+}
+
+//returns false at the end of the file
+async fn read_data<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    data: &mut [u8],
+) -> Result<bool, RecordError> {
+    match reader.read_exact(data).await {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err.into()),
+    }
+}
+
+//the position to start playing from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeekPoint {
+    pub timestamp: u32,
+    //the offset of the tag in the file
+    pub offset: u64,
+}
+
+/*Plays a flv file like a live stream: the tags are sent at the pace of their
+timestamps, and the metadata and the sequence headers are sent again before
+the first tag after a seek, which always starts from a video key frame.
+Only the headers and an index of the seek points are kept in memory, the tags
+are read from the file while playing.*/
+pub struct FlvVodReader {
+    file_path: String,
+    metadata: Option<BytesMut>,
+    video_seq: Option<BytesMut>,
+    audio_seq: Option<BytesMut>,
+    //the video key frames, or the audio tags every AUDIO_SEEK_INTERVAL if there is no video
+    seek_points: Vec<SeekPoint>,
+    has_audio: bool,
+    has_video: bool,
+    duration: u32,
+}
+
+impl FlvVodReader {
+    //scans the tag headers of the file to build the index of the seek points
+    pub async fn open(file_path: &str) -> Result<Self, RecordError> {
+        let mut reader = Self {
+            file_path: file_path.to_string(),
+            metadata: None,
+            video_seq: None,
+            audio_seq: None,
+            seek_points: Vec::new(),
+            has_audio: false,
+            has_video: false,
+            duration: 0,
+        };
+
+        let mut file = BufReader::new(File::open(file_path).await?);
+        let mut header = [0u8; FLV_HEADER_SIZE];
+        if !read_data(&mut file, &mut header).await? || &header[..3] != b"FLV" {
+            return Err(RecordError {
+                value: RecordErrorValue::InvalidVodFile,
+            });
+        }
+
+        let mut offset = u32::from_be_bytes([header[5], header[6], header[7], header[8]]) as u64
+            + PREVIOUS_TAG_SIZE;
+        file.seek(SeekFrom::Start(offset)).await?;
+        //the audio seek points are only used if there is no video
+        let mut audio_seek_points = Vec::new();
+
+        while let Some(tag_header) = read_tag_header(&mut file).await? {
+            let prefix_size = tag_header.data_size.min(TAG_PREFIX_SIZE);
+            let mut data = BytesMut::zeroed(prefix_size);
+            if !read_data(&mut file, &mut data).await? {
+                break;
+            }
+
+            let rest_size = (tag_header.data_size - prefix_size) as u64;
+            let keep = reader.index_tag(&tag_header, &data, offset, &mut audio_seek_points)?;
+            if keep {
+                let mut rest = BytesMut::zeroed(rest_size as usize);
+                if !read_data(&mut file, &mut rest).await? {
+                    break;
+                }
+                data.unsplit(rest);
+                reader.save_header(tag_header.tag_type, data);
+            } else {
+                let skipped =
+                    tokio::io::copy(&mut (&mut file).take(rest_size), &mut tokio::io::sink())
+                        .await?;
+                if skipped < rest_size {
+                    break;
+                }
+            }
+
+            let mut previous_tag_size = [0u8; PREVIOUS_TAG_SIZE as usize];
+            if !read_data(&mut file, &mut previous_tag_size).await? {
+                break;
+            }
+            offset += (TAG_HEADER_SIZE + tag_header.data_size) as u64 + PREVIOUS_TAG_SIZE;
+        }
+
+        if !reader.has_video {
+            reader.seek_points = audio_seek_points;
+        }
+        Ok(reader)
+    }
+
+    //returns true if the whole tag is needed as the metadata or a sequence header
+    fn index_tag(
+        &mut self,
+        tag_header: &TagHeader,
+        prefix: &BytesMut,
+        offset: u64,
+        audio_seek_points: &mut Vec<SeekPoint>,
+    ) -> Result<bool, RecordError> {
+        let timestamp = tag_header.timestamp;
+        let seek_point = SeekPoint { timestamp, offset };
+
+        match tag_header.tag_type {
+            TAG_TYPE_SCRIPT => return Ok(self.metadata.is_none()),
+            TAG_TYPE_VIDEO => {
+                let tag_header = VideoTagHeader::unmarshal(&mut BytesReader::new(prefix.clone()))?;
+                if tag_header.frame_type == frame_type::KEY_FRAME {
+                    if tag_header.avc_packet_type == avc_packet_type::AVC_SEQHDR {
+                        if self.video_seq.is_none() {
+                            return Ok(true);
+                        }
+                    } else {
+                        self.seek_points.push(seek_point);
+                    }
+                }
+                self.has_video = true;
+            }
+            TAG_TYPE_AUDIO => {
+                let tag_header = AudioTagHeader::unmarshal(&mut BytesReader::new(prefix.clone()))?;
+                if tag_header.sound_format == SoundFormat::AAC as u8
+                    && tag_header.aac_packet_type == aac_packet_type::AAC_SEQHDR
+                    && self.audio_seq.is_none()
+                {
+                    return Ok(true);
+                }
+                if audio_seek_points
+                    .last()
+                    .is_none_or(|last| timestamp >= last.timestamp + AUDIO_SEEK_INTERVAL)
+                {
+                    audio_seek_points.push(seek_point);
+                }
+                self.has_audio = true;
+            }
+            _ => return Ok(false),
+        }
+
+        self.duration = self.duration.max(timestamp);
+        Ok(false)
+    }
+
+    fn save_header(&mut self, tag_type: u8, data: BytesMut) {
+        match tag_type {
+            TAG_TYPE_SCRIPT => self.metadata = Some(data),
+            TAG_TYPE_VIDEO => self.video_seq = Some(data),
+            TAG_TYPE_AUDIO => self.audio_seq = Some(data),
+            _ => {}
+        }
+    }
+
+    //the duration(ms) of the file
+    pub fn duration(&self) -> u32 {
+        self.duration
+    }
+
+    pub fn has_audio(&self) -> bool {
+        self.audio_seq.is_some() || self.has_audio
+    }
+
+    pub fn has_video(&self) -> bool {
+        self.video_seq.is_some() || self.has_video
+    }
+
+    /*Returns the seek point to start from: the last key frame not after the
+    timestamp(ms), or the last audio seek point not after it if there is no video.*/
+    pub fn seek(&self, timestamp: u32) -> Option<SeekPoint> {
+        let count = self
+            .seek_points
+            .partition_point(|point| point.timestamp <= timestamp);
+        self.seek_points.get(count.saturating_sub(1)).copied()
+    }
+
+    //reads the next audio/video tag, None is returned at the end of the file
+    async fn read_tag(file: &mut BufReader<File>) -> Result<Option<FlvData>, RecordError> {
+        loop {
+            let Some(tag_header) = read_tag_header(file).await? else {
+                return Ok(None);
+            };
+            let mut data = BytesMut::zeroed(tag_header.data_size + PREVIOUS_TAG_SIZE as usize);
+            if !read_data(file, &mut data).await? {
+                return Ok(None);
+            }
+            data.truncate(tag_header.data_size);
+
+            let timestamp = tag_header.timestamp;
+            match tag_header.tag_type {
+                TAG_TYPE_AUDIO => return Ok(Some(FlvData::Audio { timestamp, data })),
+                TAG_TYPE_VIDEO => return Ok(Some(FlvData::Video { timestamp, data })),
+                //the metadata is only sent with the sequence headers
+                _ => continue,
+            }
+        }
+    }
+
+    //moves to the seek point and returns the first tag
+    async fn seek_file(
+        &self,
+        file: &mut BufReader<File>,
+        timestamp: u32,
+    ) -> Result<Option<FlvData>, RecordError> {
+        match self.seek(timestamp) {
+            Some(point) => {
+                file.seek(SeekFrom::Start(point.offset)).await?;
+                Self::read_tag(file).await
+            }
+            None => Ok(None),
+        }
+    }
+
+    //returns false if the player is gone
+    async fn send_headers(&self, timestamp: u32, data_sender: &VodDataSender) -> bool {
+        let mut headers = Vec::new();
+        if let Some(metadata) = &self.metadata {
+            headers.push(FlvData::MetaData {
+                timestamp,
+                data: metadata.clone(),
+            });
+        }
+        if let Some(video_seq) = &self.video_seq {
+            headers.push(FlvData::Video {
+                timestamp,
+                data: video_seq.clone(),
+            });
+        }
+        if let Some(audio_seq) = &self.audio_seq {
+            headers.push(FlvData::Audio {
+                timestamp,
+                data: audio_seq.clone(),
+            });
+        }
+
+        for header in headers {
+            if data_sender.send(header).await.is_err() {
+                return false;
+            }
+        }
+        true
+    }
+
+    /*Sends the tags from the start timestamp(ms), returns when the player is
+    gone, or when all the tags are sent and the player cannot seek any more.*/
+    pub async fn run(
+        &self,
+        start: u32,
+        data_sender: VodDataSender,
+        mut command_receiver: VodCommandReceiver,
+    ) -> Result<(), RecordError> {
+        let mut file = BufReader::new(File::open(&self.file_path).await?);
+        let mut tag = self.seek_file(&mut file, start).await?;
+        let mut base_timestamp = tag.as_ref().map_or(0, get_timestamp);
+        let mut base_instant = Instant::now();
+        if !self.send_headers(base_timestamp, &data_sender).await {
+            return Ok(());
+        }
+
+        //the player may not seek at all(e.g. http-flv)
+        let mut command_closed = false;
+        loop {
+            //wait for seeking at the end of the file if the player is able to
+            if tag.is_none() && command_closed {
+                return Ok(());
+            }
+
+            let offset = tag
+                .as_ref()
+                .map_or(0, get_timestamp)
+                .saturating_sub(base_timestamp);
+            tokio::select! {
+                _ = sleep_until(base_instant + Duration::from_millis(offset as u64)), if tag.is_some() => {
+                    if let Some(tag) = tag.take() {
+                        if data_sender.send(tag).await.is_err() {
+                            return Ok(());
+                        }
+                    }
+                    tag = Self::read_tag(&mut file).await?;
+                }
+                command = command_receiver.recv(), if !command_closed => {
+                    match command {
+                        Some(VodCommand::Seek { timestamp }) => {
+                            tag = self.seek_file(&mut file, timestamp).await?;
+                            base_timestamp = tag.as_ref().map_or(0, get_timestamp);
+                            base_instant = Instant::now();
+                            if !self.send_headers(base_timestamp, &data_sender).await {
+                                return Ok(());
+                            }
+                        }
+                        None => command_closed = true,
+                    }
+                }
+            }
+        }
+    }
+
+    //plays the file in a new task, drop the command sender if seeking is not needed
+    pub fn spawn(self, start: u32) -> (VodDataReceiver, VodCommandSender) {
+        let (data_sender, data_receiver) = mpsc::channel(VOD_DATA_QUEUE_SIZE);
+        let (command_sender, command_receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            if let Err(err) = self.run(start, data_sender, command_receiver).await {
+                log::error!("vod {} read error: {}", self.file_path, err);
+            }
+        });
+
+        (data_receiver, command_sender)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::FlvVodReader,
+        crate::{
+            define::{VodCommand, VodSetting},
+            writer::{flv::FlvRecordWriter, RecordWriter},
+        },
+        bytes::BytesMut,
+        xflv::define::FlvData,
+    };
+
+    //2 seconds of 25fps video with a key frame every second and 50fps audio
+    fn generate_flv(name: &str) -> String {
+        let mut file = Vec::new();
+        let mut writer = FlvRecordWriter::new(&mut file, true, true).unwrap();
+        writer
+            .write(FlvData::Video {
+                timestamp: 0,
+                data: BytesMut::from(&[0x17, 0x00, 0x00, 0x00, 0x00][..]),
+            })
+            .unwrap();
+        writer
+            .write(FlvData::Audio {
+                timestamp: 0,
+                data: BytesMut::from(&[0xaf, 0x00, 0x12, 0x10][..]),
+            })
+            .unwrap();
+        for i in 0..100 {
+            if i % 2 == 0 {
+                let frame_type = if i % 50 == 0 { 0x17 } else { 0x27 };
+                writer
+                    .write(FlvData::Video {
+                        timestamp: i * 20,
+                        data: BytesMut::from(&[frame_type, 0x01, 0x00, 0x00, 0x00][..]),
+                    })
+                    .unwrap();
+            }
+            writer
+                .write(FlvData::Audio {
+                    timestamp: i * 20,
+                    data: BytesMut::from(&[0xaf, 0x01, 0x21][..]),
+                })
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let file_path = std::env::temp_dir().join(format!("xiu_vod_{}.flv", name));
+        std::fs::write(&file_path, file).unwrap();
+        file_path.to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_vod_reader_seek() {
+        let file_path = generate_flv("seek");
+        let reader = FlvVodReader::open(&file_path).await.unwrap();
+
+        assert!(reader.has_audio() && reader.has_video());
+        assert_eq!(reader.duration(), 99 * 20);
+        //only the headers and the key frames are kept
+        assert!(reader.video_seq.is_some() && reader.audio_seq.is_some());
+        let timestamps: Vec<u32> = reader
+            .seek_points
+            .iter()
+            .map(|point| point.timestamp)
+            .collect();
+        assert_eq!(timestamps, vec![0, 1000]);
+        assert_eq!(reader.seek(0).unwrap().timestamp, 0);
+        assert_eq!(reader.seek(999).unwrap().timestamp, 0);
+        assert_eq!(reader.seek(1500).unwrap().timestamp, 1000);
+        assert_eq!(reader.seek(5000).unwrap().timestamp, 1000);
+
+        assert!(FlvVodReader::open("./not_exist.flv").await.is_err());
+        std::fs::remove_file(file_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_vod_reader_run() {
+        let file_path = generate_flv("run");
+        let reader = FlvVodReader::open(&file_path).await.unwrap();
+        let (mut data_receiver, command_sender) = reader.spawn(1000);
+
+        //the sequence headers are sent with the timestamp of the key frame
+        for _ in 0..2 {
+            match data_receiver.recv().await {
+                Some(FlvData::Video { timestamp, .. }) | Some(FlvData::Audio { timestamp, .. }) => {
+                    assert_eq!(timestamp, 1000)
+                }
+                _ => panic!("expect a sequence header"),
+            }
+        }
+        assert!(matches!(
+            data_receiver.recv().await,
+            Some(FlvData::Video {
+                timestamp: 1000,
+                ..
+            })
+        ));
+
+        //the headers are sent again after a seek
+        assert!(command_sender
+            .send(VodCommand::Seek { timestamp: 0 })
+            .is_ok());
+        let mut count = 0;
+        while let Some(tag) = data_receiver.recv().await {
+            if let FlvData::Video { timestamp: 0, data } = tag {
+                if data[1] == 0x00 {
+                    break;
+                }
+            }
+            count += 1;
+            assert!(count < 10);
+        }
+
+        drop(command_sender);
+        drop(data_receiver);
+        std::fs::remove_file(file_path).unwrap();
+    }
+
+    #[test]
+    fn test_vod_file_path() {
+        let setting = VodSetting::default();
+
+        assert_eq!(
+            setting.get_file_path("live/test/test_20240102030405"),
+            Some(String::from("./record/live/test/test_20240102030405.flv"))
+        );
+        assert_eq!(
+            setting.get_file_path("/test.flv"),
+            Some(String::from("./record/test.flv"))
+        );
+        assert_eq!(setting.get_file_path("../etc/passwd"), None);
+        assert_eq!(setting.get_file_path(""), None);
+    }
+}
@@ -0,0 +1,111 @@
+use {
+    super::RecordWriter,
+    crate::errors::RecordError,
+    std::io::Write,
+    xflv::{
+        define::{tag_type, FlvData},
+        muxer::{FlvMuxer, HEADER_LENGTH},
+    },
+};
+
+pub struct FlvRecordWriter<W: Write + Send> {
+    file: W,
+    muxer: FlvMuxer,
+    size: u64,
+}
+
+impl<W: Write + Send> FlvRecordWriter<W> {
+    pub fn new(file: W, has_audio: bool, has_video: bool) -> Result<Self, RecordError> {
+        let mut writer = Self {
+            file,
+            muxer: FlvMuxer::new(),
+            size: 0,
+        };
+
+        writer.muxer.write_flv_header(has_audio, has_video)?;
+        writer.muxer.write_previous_tag_size(0)?;
+        writer.flush_muxer_data()?;
+
+        Ok(writer)
+    }
+
+    fn flush_muxer_data(&mut self) -> Result<(), RecordError> {
+        let data = self.muxer.writer.extract_current_bytes();
+        self.file.write_all(&data[..])?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> RecordWriter for FlvRecordWriter<W> {
+    fn write(&mut self, data: FlvData) -> Result<(), RecordError> {
+        let (tag_type, timestamp, body) = match data {
+            FlvData::Audio { timestamp, data } => (tag_type::AUDIO, timestamp, data),
+            FlvData::Video { timestamp, data } => (tag_type::VIDEO, timestamp, data),
+            FlvData::MetaData { timestamp, data } => (tag_type::SCRIPT_DATA_AMF, timestamp, data),
+        };
+
+        let body_len = body.len() as u32;
+        self.muxer
+            .write_flv_tag_header(tag_type, body_len, timestamp)?;
+        self.muxer.write_flv_tag_body(body)?;
+        self.muxer
+            .write_previous_tag_size(body_len + HEADER_LENGTH)?;
+
+        self.flush_muxer_data()
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn finish(&mut self) -> Result<(), RecordError> {
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::FlvRecordWriter,
+        crate::writer::RecordWriter,
+        bytes::BytesMut,
+        xflv::{define::FlvData, demuxer::FlvDemuxer},
+    };
+
+    #[test]
+    fn test_flv_record_writer() {
+        let mut file = Vec::new();
+        let mut writer = FlvRecordWriter::new(&mut file, true, true).unwrap();
+        writer
+            .write(FlvData::Video {
+                timestamp: 0,
+                data: BytesMut::from(&[0x17, 0x01, 0x00, 0x00, 0x00][..]),
+            })
+            .unwrap();
+        writer
+            .write(FlvData::Audio {
+                timestamp: 23,
+                data: BytesMut::from(&[0xaf, 0x01, 0x21][..]),
+            })
+            .unwrap();
+        writer.finish().unwrap();
+        //9 bytes header, 4 bytes previous tag size and two tags
+        assert_eq!(writer.size(), 9 + 4 + (11 + 5 + 4) + (11 + 3 + 4));
+
+        let mut demuxer = FlvDemuxer::new(BytesMut::from(&file[..]));
+        demuxer.read_flv_header().unwrap();
+        assert!(matches!(
+            demuxer.read_flv_tag().unwrap(),
+            Some(FlvData::Video { timestamp: 0, .. })
+        ));
+        match demuxer.read_flv_tag().unwrap() {
+            Some(FlvData::Audio { timestamp, data }) => {
+                assert_eq!(timestamp, 23);
+                assert_eq!(&data[..], &[0xaf, 0x01, 0x21][..]);
+            }
+            _ => panic!("expect an audio tag"),
+        }
+    }
+}
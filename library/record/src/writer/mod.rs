@@ -0,0 +1,34 @@
+pub mod flv;
+pub mod mp4;
+
+use {
+    super::{define::RecordFormat, errors::RecordError},
+    std::{fs, fs::File, io::BufWriter, path::Path},
+    xflv::define::FlvData,
+};
+
+/*A writer of one record file, the timestamps of the written data are
+relative to the beginning of the file.*/
+pub trait RecordWriter: Send {
+    fn write(&mut self, data: FlvData) -> Result<(), RecordError>;
+    //the bytes written to the file
+    fn size(&self) -> u64;
+    fn finish(&mut self) -> Result<(), RecordError>;
+}
+
+pub fn create_writer(
+    format: RecordFormat,
+    file_path: &str,
+    has_audio: bool,
+    has_video: bool,
+) -> Result<Box<dyn RecordWriter>, RecordError> {
+    if let Some(parent) = Path::new(file_path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = BufWriter::new(File::create(file_path)?);
+
+    Ok(match format {
+        RecordFormat::Flv => Box::new(flv::FlvRecordWriter::new(file, has_audio, has_video)?),
+        RecordFormat::Mp4 => Box::new(mp4::Mp4RecordWriter::new(file)),
+    })
+}
@@ -0,0 +1,199 @@
+use {
+    super::RecordWriter,
+    crate::errors::RecordError,
+    bytes::BytesMut,
+    std::io::Write,
+    xflv::{
+        define::{frame_type, AvcCodecId, FlvData},
+        demuxer::{FlvMp4Demuxer, FlvMp4DemuxerData},
+    },
+    xmp4::{define::Mp4CodecType, fmp4::Fmp4Muxer},
+};
+
+//the max duration(ms) of a fragment if the stream has no video
+const AUDIO_FRAGMENT_DURATION: i64 = 1000;
+
+/*A fragmented mp4 file: the init segment(ftyp/moov) is written when the
+first media data arrives, then a fragment(moof/mdat) is written for every
+GOP, so the file is playable even if the recording is interrupted.*/
+pub struct Mp4RecordWriter<W: Write + Send> {
+    file: W,
+    size: u64,
+
+    demuxer: FlvMp4Demuxer,
+    muxer: Fmp4Muxer,
+    video_track_id: Option<u32>,
+    audio_track_id: Option<u32>,
+    init_segment_written: bool,
+
+    //the dts(ms) of the first sample of the current fragment
+    fragment_start_dts: Option<i64>,
+    last_dts: i64,
+}
+
+impl<W: Write + Send> Mp4RecordWriter<W> {
+    pub fn new(file: W) -> Self {
+        Self {
+            file,
+            size: 0,
+            demuxer: FlvMp4Demuxer::new(),
+            muxer: Fmp4Muxer::new(),
+            video_track_id: None,
+            audio_track_id: None,
+            init_segment_written: false,
+            fragment_start_dts: None,
+            last_dts: 0,
+        }
+    }
+
+    fn write_file(&mut self, data: &[u8]) -> Result<(), RecordError> {
+        self.file.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    fn write_sample(
+        &mut self,
+        track_id: u32,
+        pts: i64,
+        dts: i64,
+        is_sync: bool,
+        data: BytesMut,
+    ) -> Result<(), RecordError> {
+        //the tracks cannot be changed after the init segment is written
+        if !self.init_segment_written {
+            let init_segment = self.muxer.gen_init_segment()?;
+            self.write_file(&init_segment[..])?;
+            self.init_segment_written = true;
+        }
+
+        if self.fragment_start_dts.is_none() {
+            self.fragment_start_dts = Some(dts);
+        }
+        self.last_dts = dts;
+        self.muxer.write(track_id, pts, dts, is_sync, data)?;
+        Ok(())
+    }
+
+    fn flush_fragment(&mut self, end_dts: i64) -> Result<(), RecordError> {
+        if self.fragment_start_dts.take().is_some() {
+            let fragment = self.muxer.get_data(end_dts)?;
+            self.write_file(&fragment[..])?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write + Send> RecordWriter for Mp4RecordWriter<W> {
+    fn write(&mut self, data: FlvData) -> Result<(), RecordError> {
+        match self.demuxer.demux(data)? {
+            FlvMp4DemuxerData::VideoConfig {
+                codec_id,
+                decoder_configuration_record,
+                width,
+                height,
+            } => {
+                if !self.init_segment_written && self.video_track_id.is_none() {
+                    let codec = if codec_id == AvcCodecId::HEVC as u8 {
+                        Mp4CodecType::H265
+                    } else {
+                        Mp4CodecType::H264
+                    };
+                    self.video_track_id = Some(self.muxer.add_video_track(
+                        codec,
+                        decoder_configuration_record,
+                        width,
+                        height,
+                    )?);
+                }
+            }
+            FlvMp4DemuxerData::AudioConfig {
+                audio_specific_config,
+                sampling_frequency,
+                channels,
+            } => {
+                if !self.init_segment_written && self.audio_track_id.is_none() {
+                    self.audio_track_id = Some(self.muxer.add_audio_track(
+                        audio_specific_config,
+                        sampling_frequency,
+                        channels,
+                    )?);
+                }
+            }
+            FlvMp4DemuxerData::Video { data } => {
+                if let Some(track_id) = self.video_track_id {
+                    let is_key_frame = data.frame_type == frame_type::KEY_FRAME;
+                    if is_key_frame {
+                        self.flush_fragment(data.dts)?;
+                    }
+                    self.write_sample(track_id, data.pts, data.dts, is_key_frame, data.data)?;
+                }
+            }
+            FlvMp4DemuxerData::Audio { data } => {
+                if let (Some(track_id), true) = (self.audio_track_id, data.has_data) {
+                    if let (None, Some(fragment_start_dts)) =
+                        (self.video_track_id, self.fragment_start_dts)
+                    {
+                        if data.dts - fragment_start_dts >= AUDIO_FRAGMENT_DURATION {
+                            self.flush_fragment(data.dts)?;
+                        }
+                    }
+                    self.write_sample(track_id, data.pts, data.dts, true, data.data)?;
+                }
+            }
+            FlvMp4DemuxerData::None => {}
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn finish(&mut self) -> Result<(), RecordError> {
+        self.flush_fragment(self.last_dts)?;
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::Mp4RecordWriter, crate::writer::RecordWriter, bytes::BytesMut, xflv::define::FlvData,
+    };
+
+    fn find_box(data: &[u8], box_type: &[u8]) -> usize {
+        data.windows(4).filter(|window| *window == box_type).count()
+    }
+
+    #[test]
+    fn test_mp4_record_writer() {
+        let mut file = Vec::new();
+        let mut writer = Mp4RecordWriter::new(&mut file);
+        //AAC sequence header: AAC-LC, 44100Hz, stereo
+        writer
+            .write(FlvData::Audio {
+                timestamp: 0,
+                data: BytesMut::from(&[0xaf, 0x00, 0x12, 0x10][..]),
+            })
+            .unwrap();
+        for i in 0..50 {
+            writer
+                .write(FlvData::Audio {
+                    timestamp: i * 23,
+                    data: BytesMut::from(&[0xaf, 0x01, 0x21, 0x00][..]),
+                })
+                .unwrap();
+        }
+        writer.finish().unwrap();
+        let size = writer.size();
+
+        assert_eq!(size, file.len() as u64);
+        assert_eq!(&file[4..8], b"ftyp");
+        assert_eq!(find_box(&file, b"moov"), 1);
+        //a fragment is cut every second without video
+        assert_eq!(find_box(&file, b"moof"), 2);
+    }
+}
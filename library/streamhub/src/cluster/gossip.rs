@@ -0,0 +1,389 @@
+use {
+    super::{NodeInfo, StreamRegistry},
+    crate::errors::StreamHubError,
+    hmac::{Hmac, Mac, NewMac},
+    serde::{Deserialize, Serialize},
+    sha2::Sha256,
+    std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        sync::{Notify, Semaphore},
+        time::timeout,
+    },
+};
+
+pub const DEFAULT_ANNOUNCE_INTERVAL: Duration = Duration::from_secs(2);
+//the streams of a peer are removed if no announcement is received for
+//the count of intervals
+const PEER_EXPIRE_INTERVALS: u32 = 3;
+const MAX_ANNOUNCEMENT_SIZE: u64 = 1024 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+//the inbound connections which are reading announcements at the same time
+const MAX_INBOUND_CONNECTIONS: usize = 64;
+//the announcements whose timestamps differ more from the local clock are replays
+const MAX_CLOCK_SKEW_MILLIS: u64 = 30 * 1000;
+
+//The message sent to the peers, it carries all the streams published on the node.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Announcement {
+    pub node: NodeInfo,
+    pub streams: Vec<String>,
+    //the unix time in milliseconds when the announcement is sent
+    pub timestamp: u64,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+fn new_mac(secret: &str) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap()
+}
+
+/*An announcement is sent as "{hex(HMAC-SHA256(secret, json))}\n{json}", only
+the nodes sharing the secret can announce streams.*/
+fn encode(secret: &str, announcement: &Announcement) -> serde_json::Result<Vec<u8>> {
+    let json = serde_json::to_vec(announcement)?;
+    let mut mac = new_mac(secret);
+    mac.update(&json);
+
+    let mut data = hex::encode(mac.finalize().into_bytes()).into_bytes();
+    data.push(b'\n');
+    data.extend_from_slice(&json);
+    Ok(data)
+}
+
+fn decode(secret: &str, data: &[u8], now: u64) -> Result<Announcement, String> {
+    let pos = data
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .ok_or("no signature")?;
+    let (signature, json) = (&data[..pos], &data[pos + 1..]);
+
+    let signature = hex::decode(signature).map_err(|_| "invalid signature")?;
+    let mut mac = new_mac(secret);
+    mac.update(json);
+    mac.verify(&signature).map_err(|_| "signature mismatch")?;
+
+    let announcement: Announcement = serde_json::from_slice(json).map_err(|err| err.to_string())?;
+    if announcement.timestamp.abs_diff(now) > MAX_CLOCK_SKEW_MILLIS {
+        return Err(String::from("expired announcement"));
+    }
+    Ok(announcement)
+}
+
+struct Peer {
+    node: NodeInfo,
+    streams: HashSet<String>,
+    last_seen: Instant,
+    //the timestamp of the latest announcement
+    timestamp: u64,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    local_streams: HashSet<String>,
+    //the peers keyed by node id
+    peers: HashMap<String, Peer>,
+}
+
+/*An embedded stream registry shared by the nodes over tcp. Every node announces
+its published streams to the configured peers periodically(and immediately once
+a stream is published or unpublished), one signed json announcement per connection.*/
+pub struct GossipRegistry {
+    node: NodeInfo,
+    listen_port: usize,
+    peers: Vec<String>,
+    //the secret shared by the nodes to sign the announcements
+    secret: String,
+    interval: Duration,
+    state: Mutex<RegistryState>,
+    changed: Notify,
+}
+
+impl GossipRegistry {
+    pub fn new(node: NodeInfo, listen_port: usize, peers: Vec<String>, secret: String) -> Self {
+        Self {
+            node,
+            listen_port,
+            peers,
+            secret,
+            interval: DEFAULT_ANNOUNCE_INTERVAL,
+            state: Mutex::new(RegistryState::default()),
+            changed: Notify::new(),
+        }
+    }
+
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    pub async fn run(self: Arc<Self>) -> Result<(), StreamHubError> {
+        let listener = TcpListener::bind(format!("0.0.0.0:{}", self.listen_port)).await?;
+        log::info!(
+            "cluster registry listening on tcp://0.0.0.0:{}, node id: {}",
+            self.listen_port,
+            self.node.id
+        );
+
+        let registry = self.clone();
+        tokio::spawn(async move { registry.announce_loop().await });
+
+        let connections = Arc::new(Semaphore::new(MAX_INBOUND_CONNECTIONS));
+        //the announcement must be received in a few intervals, then the connections
+        //cannot be held open before the signature is checked
+        let read_timeout = self.interval * PEER_EXPIRE_INTERVALS;
+
+        loop {
+            let (mut stream, remote_addr) = listener.accept().await?;
+            let Ok(permit) = connections.clone().try_acquire_owned() else {
+                log::warn!(
+                    "cluster registry too many connections, reject {}",
+                    remote_addr
+                );
+                continue;
+            };
+            let registry = self.clone();
+
+            tokio::spawn(async move {
+                let _permit = permit;
+                let mut data = Vec::new();
+                let mut reader = (&mut stream).take(MAX_ANNOUNCEMENT_SIZE);
+                match timeout(read_timeout, reader.read_to_end(&mut data)).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(err)) => {
+                        log::error!("cluster registry read from {} err: {}", remote_addr, err);
+                        return;
+                    }
+                    Err(_) => {
+                        log::error!("cluster registry read from {} timeout", remote_addr);
+                        return;
+                    }
+                }
+
+                match decode(&registry.secret, &data, unix_now()) {
+                    Ok(announcement) => registry.on_announcement(announcement, Instant::now()),
+                    Err(err) => {
+                        log::error!("cluster registry reject from {} err: {}", remote_addr, err)
+                    }
+                }
+            });
+        }
+    }
+
+    async fn announce_loop(&self) {
+        loop {
+            let data = match encode(&self.secret, &self.announcement()) {
+                Ok(data) => data,
+                Err(err) => {
+                    log::error!("cluster registry serialize err: {}", err);
+                    return;
+                }
+            };
+
+            for peer in &self.peers {
+                if let Err(err) = Self::send(peer, &data).await {
+                    log::debug!("cluster registry announce to {} err: {}", peer, err);
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(self.interval) => {}
+                _ = self.changed.notified() => {}
+            }
+        }
+    }
+
+    async fn send(peer: &str, data: &[u8]) -> std::io::Result<()> {
+        let mut stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(peer)).await??;
+        stream.write_all(data).await?;
+        stream.shutdown().await
+    }
+
+    fn announcement(&self) -> Announcement {
+        let state = self.state.lock().unwrap();
+        Announcement {
+            node: self.node.clone(),
+            streams: state.local_streams.iter().cloned().collect(),
+            timestamp: unix_now(),
+        }
+    }
+
+    fn on_announcement(&self, announcement: Announcement, now: Instant) {
+        if announcement.node.id == self.node.id {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        //a delayed announcement cannot roll back the streams of the peer
+        if let Some(peer) = state.peers.get(&announcement.node.id) {
+            if announcement.timestamp < peer.timestamp {
+                return;
+            }
+        }
+        state.peers.insert(
+            announcement.node.id.clone(),
+            Peer {
+                node: announcement.node,
+                streams: announcement.streams.into_iter().collect(),
+                last_seen: now,
+                timestamp: announcement.timestamp,
+            },
+        );
+    }
+
+    fn locate_at(&self, key: &str, now: Instant) -> Option<NodeInfo> {
+        let expire = self.interval * PEER_EXPIRE_INTERVALS;
+        let mut state = self.state.lock().unwrap();
+        state
+            .peers
+            .retain(|_, peer| now.duration_since(peer.last_seen) < expire);
+
+        if state.local_streams.contains(key) {
+            return None;
+        }
+
+        //the node with the smallest id wins if the stream is published on several nodes
+        state
+            .peers
+            .values()
+            .filter(|peer| peer.streams.contains(key))
+            .min_by(|a, b| a.node.id.cmp(&b.node.id))
+            .map(|peer| peer.node.clone())
+    }
+}
+
+impl StreamRegistry for GossipRegistry {
+    fn register(&self, key: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .local_streams
+            .insert(key.to_string());
+        self.changed.notify_one();
+    }
+
+    fn unregister(&self, key: &str) {
+        self.state.lock().unwrap().local_streams.remove(key);
+        self.changed.notify_one();
+    }
+
+    fn locate(&self, key: &str) -> Option<NodeInfo> {
+        self.locate_at(key, Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            decode, encode, unix_now, Announcement, GossipRegistry, DEFAULT_ANNOUNCE_INTERVAL,
+        },
+        crate::cluster::{NodeInfo, StreamRegistry},
+        std::time::Instant,
+    };
+
+    fn node(id: &str) -> NodeInfo {
+        NodeInfo {
+            id: String::from(id),
+            rtmp_address: Some(format!("{id}:1935")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_locate() {
+        let registry = GossipRegistry::new(node("a"), 7946, Vec::new(), String::from("secret"));
+        let now = Instant::now();
+
+        registry.on_announcement(
+            Announcement {
+                node: node("c"),
+                streams: vec![String::from("live/test")],
+                timestamp: 0,
+            },
+            now,
+        );
+        registry.on_announcement(
+            Announcement {
+                node: node("b"),
+                streams: vec![String::from("live/test"), String::from("live/b")],
+                timestamp: 0,
+            },
+            now,
+        );
+        assert_eq!(registry.locate_at("live/test", now), Some(node("b")));
+        assert_eq!(registry.locate_at("live/none", now), None);
+
+        //the streams published locally are not located on other nodes
+        registry.register("live/b");
+        assert_eq!(registry.locate_at("live/b", now), None);
+
+        //the delayed announcements are ignored
+        registry.on_announcement(
+            Announcement {
+                node: node("b"),
+                streams: vec![String::from("live/b")],
+                timestamp: 2000,
+            },
+            now,
+        );
+        registry.on_announcement(
+            Announcement {
+                node: node("b"),
+                streams: vec![String::from("live/test"), String::from("live/b")],
+                timestamp: 1000,
+            },
+            now,
+        );
+        assert_eq!(registry.locate_at("live/test", now), Some(node("c")));
+
+        //the peers expire without announcements
+        let later = now + DEFAULT_ANNOUNCE_INTERVAL * 3;
+        assert_eq!(registry.locate_at("live/test", later), None);
+    }
+
+    #[test]
+    fn test_announcement() {
+        let registry = GossipRegistry::new(node("a"), 7946, Vec::new(), String::from("secret"));
+        registry.register("live/test");
+
+        let data = encode("secret", &registry.announcement()).unwrap();
+        let announcement = decode("secret", &data, unix_now()).unwrap();
+        assert_eq!(announcement.node, node("a"));
+        assert_eq!(announcement.streams, vec![String::from("live/test")]);
+
+        registry.unregister("live/test");
+        assert!(registry.announcement().streams.is_empty());
+    }
+
+    #[test]
+    fn test_signed_announcement() {
+        let announcement = Announcement {
+            node: node("a"),
+            streams: vec![String::from("live/test")],
+            timestamp: 1700000000000,
+        };
+        let data = encode("secret", &announcement).unwrap();
+        assert!(decode("secret", &data, 1700000000000).is_ok());
+
+        //the nodes without the secret cannot announce streams
+        assert!(decode("other", &data, 1700000000000).is_err());
+        let json = serde_json::to_vec(&announcement).unwrap();
+        assert!(decode("secret", &json, 1700000000000).is_err());
+        let mut tampered = data.clone();
+        let last = tampered.len() - 2;
+        tampered[last] ^= 1;
+        assert!(decode("secret", &tampered, 1700000000000).is_err());
+
+        //the old announcements cannot be replayed
+        assert!(decode("secret", &data, 1700000000000 + 60 * 1000).is_err());
+    }
+}
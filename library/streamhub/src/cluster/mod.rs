@@ -0,0 +1,39 @@
+pub mod gossip;
+
+use serde::{Deserialize, Serialize};
+
+//The addresses of a node in the cluster, they are announced to the other nodes
+//to relay the streams from or redirect the players to.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    pub id: String,
+    //host:port of the rtmp server
+    pub rtmp_address: Option<String>,
+    //host:port of the httpflv server
+    pub httpflv_address: Option<String>,
+    //host:port of the hls server
+    pub hls_address: Option<String>,
+}
+
+//what to do with the players requesting a stream published on another node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ClusterMode {
+    //pull the stream from the owning node and serve it locally
+    #[default]
+    #[serde(rename = "relay")]
+    Relay,
+    //redirect the players to the owning node
+    #[serde(rename = "redirect")]
+    Redirect,
+}
+
+/*The registry of the streams published in the cluster, the streams are keyed
+by "app_name/stream_name". The stream hub registers the rtmp streams published
+locally and looks up the owning nodes of the missing streams.*/
+pub trait StreamRegistry: Sync + Send {
+    fn register(&self, key: &str);
+    fn unregister(&self, key: &str);
+    //returns the node which publishes the stream, None if the stream is
+    //published locally or not found in the cluster
+    fn locate(&self, key: &str) -> Option<NodeInfo>;
+}
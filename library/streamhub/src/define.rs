@@ -7,6 +7,7 @@ use crate::utils;
 
 use {
     super::errors::StreamHubError,
+    crate::queue::{DataQueueReceiver, DataQueueSender},
     crate::statistics::StatisticsStream,
     crate::stream::StreamIdentifier,
     async_trait::async_trait,
@@ -29,6 +30,14 @@ pub enum SubscribeType {
     RtmpRemux2HttpFlv,
     /* The publishing of RTMP stream triggers remuxing from RTMP to HLS protocol.(NOTICE:It is not triggerred by players.)*/
     RtmpRemux2Hls,
+    /* The publishing of RTMP stream triggers remuxing from RTMP to MPEG-DASH protocol.(NOTICE:It is not triggerred by players.)*/
+    RtmpRemux2Dash,
+    /* The publishing of RTMP stream(or the http api) triggers recording the stream to files.*/
+    RtmpRemux2Record,
+    /* The publishing of RTMP stream triggers remuxing from RTMP to RTSP protocol.*/
+    RtmpRemux2Rtsp,
+    /* The publishing of RTMP stream triggers remuxing from RTMP to WebRTC(whep) protocol.*/
+    RtmpRemux2WebRTC,
     /* Relay(Push) local RTMP stream from stream hub to other RTMP nodes.*/
     RtmpRelay,
     /* Remote client request pulling(play) a rtsp stream.*/
@@ -64,6 +73,12 @@ pub enum PublishType {
     WhepRelay,
     /* It used for publishing raw rtp data of rtsp/whbrtc(whip) */
     RtpPush,
+    /* Republish the rtsp/whip stream of the local stream hub as a rtmp stream. */
+    RtmpRemux,
+    /* Republish the rtmp stream of the local stream hub as a rtsp stream. */
+    RtspRemux,
+    /* Republish the rtmp stream of the local stream hub as a webrtc stream. */
+    WebRtcRemux,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -124,11 +139,22 @@ pub enum VideoCodecType {
     H265,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AudioCodecType {
+    AAC,
+    Opus,
+    //G.711 A-law
+    G711A,
+    //G.711 mu-law
+    G711U,
+}
+
 #[derive(Clone)]
 pub struct MediaInfo {
     pub audio_clock_rate: u32,
     pub video_clock_rate: u32,
     pub vcodec: VideoCodecType,
+    pub acodec: AudioCodecType,
 }
 
 #[derive(Clone)]
@@ -154,14 +180,15 @@ pub enum Information {
 
 //used to transfer a/v frame between different protocols(rtmp/rtsp/webrtc/http-flv/hls)
 //or send a/v frame data from publisher to subscribers.
-pub type FrameDataSender = mpsc::UnboundedSender<FrameData>;
-pub type FrameDataReceiver = mpsc::UnboundedReceiver<FrameData>;
+//The queues from stream hub to subscribers are bounded, see queue::BackpressurePolicy.
+pub type FrameDataSender = DataQueueSender<FrameData>;
+pub type FrameDataReceiver = DataQueueReceiver<FrameData>;
 
 //used to transfer rtp packet data,it includles the following directions:
 // rtsp(publisher)->stream hub->rtsp(subscriber)
 // webrtc(publisher whip)->stream hub->webrtc(subscriber whep)
-pub type PacketDataSender = mpsc::UnboundedSender<PacketData>;
-pub type PacketDataReceiver = mpsc::UnboundedReceiver<PacketData>;
+pub type PacketDataSender = DataQueueSender<PacketData>;
+pub type PacketDataReceiver = DataQueueReceiver<PacketData>;
 
 pub type InformationSender = mpsc::UnboundedSender<Information>;
 pub type InformationReceiver = mpsc::UnboundedReceiver<Information>;
@@ -182,6 +209,7 @@ pub type StatisticStreamSender = mpsc::UnboundedSender<StatisticsStream>;
 pub type StatisticStreamReceiver = mpsc::UnboundedReceiver<StatisticsStream>;
 
 pub type StatisticApiResultSender = oneshot::Sender<Value>;
+pub type MetricsApiResultSender = oneshot::Sender<String>;
 pub type StatisticApiResultReceiver = oneshot::Receiver<Value>;
 
 pub type SubEventExecuteResultSender =
@@ -256,6 +284,19 @@ pub enum StreamHubEventMessage {
         identifier: StreamIdentifier,
         info: PublisherInfo,
     },
+    //a record file is finished, the duration is in milliseconds
+    Record {
+        identifier: StreamIdentifier,
+        file_path: String,
+        duration: u64,
+    },
+    //a hls segment is written, the duration is in milliseconds
+    HlsSegment {
+        identifier: StreamIdentifier,
+        segment_path: String,
+        sequence: u64,
+        duration: i64,
+    },
     NotSupport {},
 }
 
@@ -298,6 +339,10 @@ pub enum StreamHubEvent {
         result_sender: StatisticApiResultSender,
     },
     #[serde(skip_serializing)]
+    ApiMetrics {
+        result_sender: MetricsApiResultSender,
+    },
+    #[serde(skip_serializing)]
     ApiKickClient { id: Uuid },
     #[serde(skip_serializing)]
     ApiStartRelayStream {
@@ -444,4 +489,9 @@ pub enum StatisticData {
         sub_type: SubscribeType,
         start_time: DateTime<Local>,
     },
+    /* frames dropped by the backpressure policy of a lagging subscriber */
+    SubscriberDroppedFrames {
+        id: Uuid,
+        count: usize,
+    },
 }
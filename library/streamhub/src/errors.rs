@@ -5,6 +5,8 @@ use failure::Backtrace;
 use serde_json::error::Error;
 use tokio::sync::oneshot::error::RecvError;
 
+use crate::cluster::NodeInfo;
+
 use {failure::Fail, std::fmt};
 #[derive(Debug, Fail)]
 pub enum StreamHubErrorValue {
@@ -34,6 +36,16 @@ pub enum StreamHubErrorValue {
     SerdeError(Error),
     #[fail(display = "the client session error: {}", _0)]
     RtspClientSessionError(String),
+    #[fail(display = "io error: {}", _0)]
+    IoError(std::io::Error),
+    #[fail(display = "the stream is published on another node")]
+    Redirect(NodeInfo),
+    #[fail(display = "denied by the hook")]
+    HookDenied,
+    #[fail(display = "wait for the stream pulled from the origin timeout")]
+    OriginPullTimeout,
+    #[fail(display = "the stream pulled from the origin is unpublished")]
+    OriginPullFailed,
 }
 #[derive(Debug)]
 pub struct StreamHubError {
@@ -88,6 +100,14 @@ impl From<Error> for StreamHubError {
     }
 }
 
+impl From<std::io::Error> for StreamHubError {
+    fn from(error: std::io::Error) -> Self {
+        StreamHubError {
+            value: StreamHubErrorValue::IoError(error),
+        }
+    }
+}
+
 impl From<String> for StreamHubError {
     fn from(error: String) -> Self {
         StreamHubError {
@@ -3,30 +3,38 @@ use define::{
     StatisticDataReceiver, StatisticDataSender,
 };
 use serde_json::{json, Value};
-use statistics::{StatisticSubscriber, StatisticsStream};
+use statistics::{metrics, metrics::SessionMetrics, StatisticSubscriber, StatisticsStream};
 use tokio::sync::oneshot;
 use xflv::define::aac_packet_type;
 
 use crate::define::PacketData;
 
+pub mod cluster;
 pub mod define;
 pub mod errors;
 pub mod notify;
+pub mod origin_pull;
+pub mod queue;
 pub mod statistics;
 pub mod stream;
 pub mod utils;
 
 use {
     crate::notify::Notifier,
+    cluster::{ClusterMode, StreamRegistry},
     define::{
         BroadcastEvent, BroadcastEventReceiver, BroadcastEventSender, DataReceiver, DataSender,
-        FrameData, FrameDataSender, Information, StreamHubEvent, StreamHubEventReceiver,
-        StreamHubEventSender, SubscribeType, SubscriberInfo, TStreamHandler, TransceiverEvent,
-        TransceiverEventReceiver, TransceiverEventSender,
+        FrameData, FrameDataSender, Information, PublishType, StreamHubEvent,
+        StreamHubEventMessage, StreamHubEventReceiver, StreamHubEventSender,
+        SubEventExecuteResultSender, SubscribeType, SubscriberInfo, TStreamHandler,
+        TransceiverEvent, TransceiverEventReceiver, TransceiverEventSender,
     },
     errors::{StreamHubError, StreamHubErrorValue},
+    origin_pull::{OriginPull, PendingRequest, PendingSubscriber},
+    queue::{BackpressurePolicy, SendError},
     std::collections::HashMap,
     std::sync::Arc,
+    std::time::{Duration, Instant},
     stream::StreamIdentifier,
     tokio::sync::{broadcast, mpsc, mpsc::UnboundedReceiver, Mutex},
     utils::Uuid,
@@ -73,57 +81,57 @@ impl StreamDataTransceiver {
         }
     }
 
+    //Send the data to one subscriber and submit the frames dropped by the backpressure
+    //policy, returns false if the subscriber's queue is closed and should be removed.
+    fn send_to_subscriber<T: queue::QueueItem>(
+        id: &Uuid,
+        sender: &queue::DataQueueSender<T>,
+        data: T,
+        statistic_sender: &StatisticDataSender,
+    ) -> bool {
+        let dropped = match sender.send(data) {
+            Ok(dropped) => dropped,
+            Err(SendError::Overflow(_)) => {
+                log::warn!(
+                    "subscriber: {} cannot keep up with the stream, disconnect it",
+                    id
+                );
+                1
+            }
+            Err(err) => {
+                log::error!("Transmiter send error: {}", err);
+                0
+            }
+        };
+
+        if dropped > 0 {
+            if let Err(err) = statistic_sender.send(StatisticData::SubscriberDroppedFrames {
+                id: *id,
+                count: dropped,
+            }) {
+                log::error!("send statistic_data err: {}", err);
+            }
+        }
+
+        !sender.is_closed()
+    }
+
     async fn receive_frame_data(
         data: Option<FrameData>,
         frame_senders: &Arc<Mutex<HashMap<Uuid, FrameDataSender>>>,
+        statistic_sender: &StatisticDataSender,
     ) {
-        if let Some(val) = data {
-            match val {
-                FrameData::MetaData {
-                    timestamp: _,
-                    data: _,
-                } => {}
-                FrameData::Audio { timestamp, data } => {
-                    let data = FrameData::Audio {
-                        timestamp,
-                        data: data.clone(),
-                    };
-
-                    for (_, v) in frame_senders.lock().await.iter() {
-                        if let Err(audio_err) = v.send(data.clone()).map_err(|_| StreamHubError {
-                            value: StreamHubErrorValue::SendAudioError,
-                        }) {
-                            log::error!("Transmiter send error: {}", audio_err);
-                        }
-                    }
-                }
-                FrameData::Video { timestamp, data } => {
-                    let data = FrameData::Video {
-                        timestamp,
-                        data: data.clone(),
-                    };
-                    for (_, v) in frame_senders.lock().await.iter() {
-                        if let Err(video_err) = v.send(data.clone()).map_err(|_| StreamHubError {
-                            value: StreamHubErrorValue::SendVideoError,
-                        }) {
-                            log::error!("Transmiter send error: {}", video_err);
-                        }
-                    }
-                }
-                FrameData::MediaInfo {
-                    media_info: info_value,
-                } => {
-                    let data = FrameData::MediaInfo {
-                        media_info: info_value,
-                    };
-                    for (_, v) in frame_senders.lock().await.iter() {
-                        if let Err(media_err) = v.send(data.clone()).map_err(|_| StreamHubError {
-                            value: StreamHubErrorValue::SendVideoError,
-                        }) {
-                            log::error!("Transmiter send error: {}", media_err);
-                        }
-                    }
-                }
+        match data {
+            Some(FrameData::MetaData {
+                timestamp: _,
+                data: _,
+            })
+            | None => {}
+            Some(val) => {
+                frame_senders
+                    .lock()
+                    .await
+                    .retain(|id, v| Self::send_to_subscriber(id, v, val.clone(), statistic_sender));
             }
         }
     }
@@ -132,12 +140,13 @@ impl StreamDataTransceiver {
         mut exit: broadcast::Receiver<()>,
         mut receiver: FrameDataReceiver,
         frame_senders: Arc<Mutex<HashMap<Uuid, FrameDataSender>>>,
+        statistic_sender: StatisticDataSender,
     ) {
         tokio::spawn(async move {
             loop {
                 tokio::select! {
                     data = receiver.recv() => {
-                       Self::receive_frame_data(data, &frame_senders).await;
+                       Self::receive_frame_data(data, &frame_senders, &statistic_sender).await;
                     }
                     _ = exit.recv()=>{
                         break;
@@ -150,37 +159,13 @@ impl StreamDataTransceiver {
     async fn receive_packet_data(
         data: Option<PacketData>,
         packet_senders: &Arc<Mutex<HashMap<Uuid, PacketDataSender>>>,
+        statistic_sender: &StatisticDataSender,
     ) {
         if let Some(val) = data {
-            match val {
-                PacketData::Audio { timestamp, data } => {
-                    let data = PacketData::Audio {
-                        timestamp,
-                        data: data.clone(),
-                    };
-
-                    for (_, v) in packet_senders.lock().await.iter() {
-                        if let Err(audio_err) = v.send(data.clone()).map_err(|_| StreamHubError {
-                            value: StreamHubErrorValue::SendAudioError,
-                        }) {
-                            log::error!("Transmiter send error: {}", audio_err);
-                        }
-                    }
-                }
-                PacketData::Video { timestamp, data } => {
-                    let data = PacketData::Video {
-                        timestamp,
-                        data: data.clone(),
-                    };
-                    for (_, v) in packet_senders.lock().await.iter() {
-                        if let Err(video_err) = v.send(data.clone()).map_err(|_| StreamHubError {
-                            value: StreamHubErrorValue::SendVideoError,
-                        }) {
-                            log::error!("Transmiter send error: {}", video_err);
-                        }
-                    }
-                }
-            }
+            packet_senders
+                .lock()
+                .await
+                .retain(|id, v| Self::send_to_subscriber(id, v, val.clone(), statistic_sender));
         }
     }
 
@@ -188,12 +173,13 @@ impl StreamDataTransceiver {
         mut exit: broadcast::Receiver<()>,
         mut receiver: PacketDataReceiver,
         packet_senders: Arc<Mutex<HashMap<Uuid, PacketDataSender>>>,
+        statistic_sender: StatisticDataSender,
     ) {
         tokio::spawn(async move {
             loop {
                 tokio::select! {
                     data = receiver.recv() => {
-                       Self::receive_packet_data(data, &packet_senders).await;
+                       Self::receive_packet_data(data, &packet_senders, &statistic_sender).await;
                     }
                     _ = exit.recv()=>{
                         break;
@@ -325,9 +311,16 @@ impl StreamDataTransceiver {
                         send_bitrate: 0,
                         send_bytes: 0,
                         total_send_bytes: 0,
+                        dropped_frames: 0,
                     };
                     subscriber.insert(id, sub);
                 }
+                StatisticData::SubscriberDroppedFrames { id, count } => {
+                    let subscriber = &mut statistics_data.lock().await.subscribers;
+                    if let Some(sub) = subscriber.get_mut(&id) {
+                        sub.dropped_frames += count;
+                    }
+                }
             }
         }
     }
@@ -456,6 +449,7 @@ impl StreamDataTransceiver {
                 tx.subscribe(),
                 receiver,
                 self.id_to_frame_sender.clone(),
+                self.statistic_data_sender.clone(),
             )
             .await;
         }
@@ -465,6 +459,7 @@ impl StreamDataTransceiver {
                 tx.subscribe(),
                 receiver,
                 self.id_to_packet_sender.clone(),
+                self.statistic_data_sender.clone(),
             )
             .await;
         }
@@ -511,12 +506,38 @@ pub struct StreamsHub {
     //add a control switches separately.
     rtmp_push_enabled: bool,
     rtmp_remuxer_enabled: bool,
+    rtsp_remuxer_enabled: bool,
+    webrtc_remuxer_enabled: bool,
     //enable rtmp pull
     rtmp_pull_enabled: bool,
+    //the streams pulled from the origin on demand, keyed by app_name/stream_name
+    origin_pulls: HashMap<String, OriginPull>,
+    //how long the subscribers wait for the streams pulled from the origin
+    pull_wait_timeout: Duration,
+    //how long the pulled streams are kept after the last player leaves
+    pull_idle_timeout: Duration,
+    //the registry of the streams published in the cluster
+    registry: Option<Arc<dyn StreamRegistry>>,
+    cluster_mode: ClusterMode,
     //enable hls
     hls_enabled: bool,
+    //enable dash
+    dash_enabled: bool,
+    //enable record
+    record_enabled: bool,
     //http notifier on sub/pub event
     notifier: Option<Arc<dyn Notifier>>,
+    //the publishers/players being checked by the hooks in sync mode, the value is
+    //the original identifier
+    checking: HashMap<Uuid, StreamIdentifier>,
+    //the identifiers rewritten by the hooks, keyed by the publisher/player id
+    rewritten: HashMap<Uuid, StreamIdentifier>,
+    //the max count of frames/packets queued for one subscriber
+    subscriber_queue_size: usize,
+    //what to do when a subscriber's queue is full
+    backpressure_policy: BackpressurePolicy,
+    //the counts of the publish/subscribe sessions exposed by the metrics api
+    session_metrics: SessionMetrics,
 }
 
 impl StreamsHub {
@@ -532,9 +553,23 @@ impl StreamsHub {
             client_event_sender: client_producer,
             rtmp_push_enabled: false,
             rtmp_pull_enabled: false,
+            origin_pulls: HashMap::new(),
+            pull_wait_timeout: origin_pull::DEFAULT_PULL_WAIT_TIMEOUT,
+            pull_idle_timeout: origin_pull::DEFAULT_PULL_IDLE_TIMEOUT,
+            registry: None,
+            cluster_mode: ClusterMode::default(),
             rtmp_remuxer_enabled: false,
+            rtsp_remuxer_enabled: false,
+            webrtc_remuxer_enabled: false,
             hls_enabled: false,
+            dash_enabled: false,
+            record_enabled: false,
             notifier,
+            checking: HashMap::new(),
+            rewritten: HashMap::new(),
+            subscriber_queue_size: queue::DEFAULT_SUBSCRIBER_QUEUE_SIZE,
+            backpressure_policy: BackpressurePolicy::default(),
+            session_metrics: SessionMetrics::default(),
         }
     }
     pub async fn run(&mut self) {
@@ -549,14 +584,45 @@ impl StreamsHub {
         self.rtmp_pull_enabled = enabled;
     }
 
+    pub fn set_rtmp_pull_timeout(&mut self, wait_timeout: Duration, idle_timeout: Duration) {
+        self.pull_wait_timeout = wait_timeout;
+        self.pull_idle_timeout = idle_timeout;
+    }
+
+    pub fn set_registry(&mut self, registry: Arc<dyn StreamRegistry>, mode: ClusterMode) {
+        self.registry = Some(registry);
+        self.cluster_mode = mode;
+    }
+
     pub fn set_rtmp_remuxer_enabled(&mut self, enabled: bool) {
         self.rtmp_remuxer_enabled = enabled;
     }
 
+    pub fn set_rtsp_remuxer_enabled(&mut self, enabled: bool) {
+        self.rtsp_remuxer_enabled = enabled;
+    }
+
+    pub fn set_webrtc_remuxer_enabled(&mut self, enabled: bool) {
+        self.webrtc_remuxer_enabled = enabled;
+    }
+
     pub fn set_hls_enabled(&mut self, enabled: bool) {
         self.hls_enabled = enabled;
     }
 
+    pub fn set_dash_enabled(&mut self, enabled: bool) {
+        self.dash_enabled = enabled;
+    }
+
+    pub fn set_record_enabled(&mut self, enabled: bool) {
+        self.record_enabled = enabled;
+    }
+
+    pub fn set_subscriber_queue(&mut self, size: usize, policy: BackpressurePolicy) {
+        self.subscriber_queue_size = size;
+        self.backpressure_policy = policy;
+    }
+
     pub fn get_hub_event_sender(&mut self) -> StreamHubEventSender {
         self.hub_event_sender.clone()
     }
@@ -566,7 +632,23 @@ impl StreamsHub {
     }
 
     pub async fn event_loop(&mut self) {
-        while let Some(event) = self.hub_event_receiver.recv().await {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+
+        loop {
+            let event = tokio::select! {
+                event = self.hub_event_receiver.recv() => match event {
+                    Some(event) => event,
+                    None => break,
+                },
+                _ = interval.tick() => {
+                    self.check_origin_pulls();
+                    continue;
+                }
+            };
+            let Some(event) = self.check_hooks(event) else {
+                continue;
+            };
+            let event = self.apply_rewrite(event);
             let message = event.to_message();
 
             match event {
@@ -578,7 +660,7 @@ impl StreamsHub {
                 } => {
                     let (frame_sender, packet_sender, receiver) = match info.pub_data_type {
                         define::PubDataType::Frame => {
-                            let (sender_chan, receiver_chan) = mpsc::unbounded_channel();
+                            let (sender_chan, receiver_chan) = queue::unbounded();
                             (
                                 Some(sender_chan),
                                 None,
@@ -589,7 +671,7 @@ impl StreamsHub {
                             )
                         }
                         define::PubDataType::Packet => {
-                            let (sender_chan, receiver_chan) = mpsc::unbounded_channel();
+                            let (sender_chan, receiver_chan) = queue::unbounded();
                             (
                                 None,
                                 Some(sender_chan),
@@ -600,10 +682,8 @@ impl StreamsHub {
                             )
                         }
                         define::PubDataType::Both => {
-                            let (sender_frame_chan, receiver_frame_chan) =
-                                mpsc::unbounded_channel();
-                            let (sender_packet_chan, receiver_packet_chan) =
-                                mpsc::unbounded_channel();
+                            let (sender_frame_chan, receiver_frame_chan) = queue::unbounded();
+                            let (sender_packet_chan, receiver_packet_chan) = queue::unbounded();
 
                             (
                                 Some(sender_frame_chan),
@@ -621,11 +701,18 @@ impl StreamsHub {
                         .await
                     {
                         Ok(statistic_data_sender) => {
-                            if let Some(notifier) = &self.notifier {
+                            if let Some(notifier) = self.async_notifier() {
                                 notifier.on_publish_notify(&message).await;
                             }
-                            self.un_pub_sub_events
-                                .insert(info.id, StreamHubEvent::UnPublish { identifier, info });
+                            self.session_metrics
+                                .on_publish(identifier.clone(), &info.pub_type);
+                            self.un_pub_sub_events.insert(
+                                info.id,
+                                StreamHubEvent::UnPublish {
+                                    identifier: identifier.clone(),
+                                    info,
+                                },
+                            );
 
                             Ok((frame_sender, packet_sender, Some(statistic_data_sender)))
                         }
@@ -635,9 +722,13 @@ impl StreamsHub {
                         }
                     };
 
+                    let published = result.is_ok();
                     if result_sender.send(result).is_err() {
                         log::error!("event_loop Subscribe error: The receiver dropped.")
                     }
+                    if published {
+                        self.resolve_pending(&identifier).await;
+                    }
                 }
 
                 StreamHubEvent::UnPublish {
@@ -661,13 +752,13 @@ impl StreamsHub {
                     info,
                     result_sender,
                 } => {
-                    let sub_id = info.id;
-                    let info_clone = info.clone();
-
                     //new chan for Frame/Packet sender and receiver
                     let (sender, receiver) = match info.sub_data_type {
                         define::SubDataType::Frame => {
-                            let (sender_chan, receiver_chan) = mpsc::unbounded_channel();
+                            let (sender_chan, receiver_chan) = queue::bounded(
+                                self.subscriber_queue_size,
+                                self.backpressure_policy,
+                            );
                             (
                                 DataSender::Frame {
                                     sender: sender_chan,
@@ -679,7 +770,10 @@ impl StreamsHub {
                             )
                         }
                         define::SubDataType::Packet => {
-                            let (sender_chan, receiver_chan) = mpsc::unbounded_channel();
+                            let (sender_chan, receiver_chan) = queue::bounded(
+                                self.subscriber_queue_size,
+                                self.backpressure_policy,
+                            );
                             (
                                 DataSender::Packet {
                                     sender: sender_chan,
@@ -692,27 +786,51 @@ impl StreamsHub {
                         }
                     };
 
-                    let rv = match self.subscribe(&identifier, info_clone, sender).await {
-                        Ok(statistic_data_sender) => {
-                            if let Some(notifier) = &self.notifier {
-                                notifier.on_play_notify(&message).await;
-                            }
-
-                            self.un_pub_sub_events
-                                .insert(sub_id, StreamHubEvent::UnSubscribe { identifier, info });
-                            Ok((receiver, Some(statistic_data_sender)))
-                        }
-                        Err(err) => {
-                            log::error!("event_loop Subscribe error: {}", err);
-                            Err(err)
+                    //redirect the player to the node which publishes the stream
+                    if let Some(node) = self.locate_redirect(&identifier, &info.sub_type) {
+                        log::info!(
+                            "subscribe: redirect to node: {}, identifier: {}",
+                            node.id,
+                            identifier
+                        );
+                        let err = Err(StreamHubError {
+                            value: StreamHubErrorValue::Redirect(node),
+                        });
+                        if result_sender.send(err).is_err() {
+                            log::error!("event_loop Subscribe error: The receiver dropped.")
                         }
-                    };
+                        continue;
+                    }
 
-                    if result_sender.send(rv).is_err() {
-                        log::error!("event_loop Subscribe error: The receiver dropped.")
+                    //hold the subscriber until the stream is pulled from the origin
+                    let deadline = Instant::now() + self.pull_wait_timeout;
+                    if let Some(origin_pull) = self.get_origin_pull(&identifier) {
+                        log::info!(
+                            "subscribe: wait for the stream pulled from origin, identifier: {}",
+                            identifier
+                        );
+                        origin_pull.subscribers.push(PendingSubscriber {
+                            identifier,
+                            info,
+                            sender,
+                            receiver,
+                            result_sender,
+                            deadline,
+                        });
+                        continue;
                     }
+
+                    self.subscribe_and_respond(identifier, info, sender, receiver, result_sender)
+                        .await;
                 }
                 StreamHubEvent::UnSubscribe { identifier, info } => {
+                    self.session_metrics.on_unsubscribe(&info.id);
+                    if let Some(origin_pull) = origin_pull::stream_key(&identifier)
+                        .and_then(|key| self.origin_pulls.get_mut(&key))
+                    {
+                        origin_pull.remove_player(&info.id);
+                    }
+
                     if self.unsubscribe(&identifier, info).is_ok() {
                         if let Some(notifier) = &self.notifier {
                             notifier.on_stop_notify(&message).await;
@@ -738,6 +856,12 @@ impl StreamsHub {
                         log::error!("event_loop api error: {}", err);
                     }
                 }
+                StreamHubEvent::ApiMetrics { result_sender } => {
+                    let metrics = self.api_metrics().await;
+                    if result_sender.send(metrics).is_err() {
+                        log::error!("event_loop api metrics error: The receiver dropped.");
+                    }
+                }
                 StreamHubEvent::ApiKickClient { id } => {
                     if let Err(err) = self.api_kick_off_client(id) {
                         log::error!("api_kick_off_client api error: {}", err);
@@ -770,6 +894,16 @@ impl StreamsHub {
                     }
                 }
                 StreamHubEvent::Request { identifier, sender } => {
+                    let deadline = Instant::now() + self.pull_wait_timeout;
+                    if let Some(origin_pull) = self.get_origin_pull(&identifier) {
+                        origin_pull.requests.push(PendingRequest {
+                            identifier,
+                            sender,
+                            deadline,
+                        });
+                        continue;
+                    }
+
                     if let Err(err) = self.request(&identifier, sender) {
                         log::error!("event_loop request error: {}", err);
                     }
@@ -778,6 +912,283 @@ impl StreamsHub {
         }
     }
 
+    async fn subscribe_and_respond(
+        &mut self,
+        identifier: StreamIdentifier,
+        info: SubscriberInfo,
+        sender: DataSender,
+        receiver: DataReceiver,
+        result_sender: SubEventExecuteResultSender,
+    ) {
+        let message = StreamHubEventMessage::Subscribe {
+            identifier: identifier.clone(),
+            info: info.clone(),
+        };
+
+        let rv = match self.subscribe(&identifier, info.clone(), sender).await {
+            Ok(statistic_data_sender) => {
+                if let Some(notifier) = self.async_notifier() {
+                    notifier.on_play_notify(&message).await;
+                }
+
+                self.session_metrics.on_subscribe(info.id, &info.sub_type);
+                if origin_pull::is_player(&info.sub_type) {
+                    if let Some(origin_pull) = origin_pull::stream_key(&identifier)
+                        .and_then(|key| self.origin_pulls.get_mut(&key))
+                    {
+                        origin_pull.add_player(info.id);
+                    }
+                }
+
+                self.un_pub_sub_events
+                    .insert(info.id, StreamHubEvent::UnSubscribe { identifier, info });
+                Ok((receiver, Some(statistic_data_sender)))
+            }
+            Err(err) => {
+                log::error!("event_loop Subscribe error: {}", err);
+                Err(err)
+            }
+        };
+
+        if result_sender.send(rv).is_err() {
+            log::error!("event_loop Subscribe error: The receiver dropped.")
+        }
+    }
+
+    //the on_publish/on_play notifications are replaced by the hook checks in sync mode
+    fn async_notifier(&self) -> Option<&Arc<dyn Notifier>> {
+        self.notifier
+            .as_ref()
+            .filter(|notifier| !notifier.is_sync())
+    }
+
+    /*Hands the publish/play events to the hooks in sync mode, they are sent back
+    to the stream hub once allowed. Returns None if the event is being checked.*/
+    fn check_hooks(&mut self, event: StreamHubEvent) -> Option<StreamHubEvent> {
+        let notifier = match &self.notifier {
+            Some(notifier) if notifier.is_sync() => notifier.clone(),
+            _ => return Some(event),
+        };
+
+        let (id, identifier, need_check, receiver_closed) = match &event {
+            StreamHubEvent::Publish {
+                identifier,
+                info,
+                result_sender,
+                ..
+            } => (
+                info.id,
+                identifier,
+                !Self::is_relayed_or_remuxed(&info.pub_type),
+                result_sender.is_closed(),
+            ),
+            StreamHubEvent::Subscribe {
+                identifier,
+                info,
+                result_sender,
+            } => (
+                info.id,
+                identifier,
+                origin_pull::is_player(&info.sub_type),
+                result_sender.is_closed(),
+            ),
+            _ => return Some(event),
+        };
+
+        //the event is sent back after it is allowed
+        if let Some(original) = self.checking.remove(&id) {
+            if receiver_closed {
+                log::warn!("the session is closed while being checked, id: {}", id);
+                return None;
+            }
+            if *identifier != original {
+                log::info!("the hook rewrites {} to {}", original, identifier);
+                self.rewritten.insert(id, identifier.clone());
+            }
+            return Some(event);
+        }
+
+        if !need_check {
+            return Some(event);
+        }
+        self.checking.insert(id, identifier.clone());
+        tokio::spawn(notify::check_event(
+            notifier,
+            event,
+            self.hub_event_sender.clone(),
+        ));
+        None
+    }
+
+    //the publishers/players unpublish/unsubscribe with their original identifiers
+    fn apply_rewrite(&mut self, event: StreamHubEvent) -> StreamHubEvent {
+        match event {
+            StreamHubEvent::UnPublish { identifier, info } => StreamHubEvent::UnPublish {
+                identifier: self.rewritten.remove(&info.id).unwrap_or(identifier),
+                info,
+            },
+            StreamHubEvent::UnSubscribe { identifier, info } => StreamHubEvent::UnSubscribe {
+                identifier: self.rewritten.remove(&info.id).unwrap_or(identifier),
+                info,
+            },
+            event => event,
+        }
+    }
+
+    /*The relayed streams are started by the api or pulled from the origin, and the
+    remuxers republish the checked streams in the other protocols.*/
+    fn is_relayed_or_remuxed(pub_type: &PublishType) -> bool {
+        match pub_type {
+            PublishType::RtmpRelay
+            | PublishType::RtspRelay
+            | PublishType::WhepRelay
+            | PublishType::RtmpRemux
+            | PublishType::RtspRemux
+            | PublishType::WebRtcRemux => true,
+            PublishType::RtmpPush
+            | PublishType::RtspPush
+            | PublishType::WhipPush
+            | PublishType::RtpPush => false,
+        }
+    }
+
+    //returns the node to redirect the player to if the stream is published on another node
+    fn locate_redirect(
+        &self,
+        identifier: &StreamIdentifier,
+        sub_type: &SubscribeType,
+    ) -> Option<cluster::NodeInfo> {
+        if self.cluster_mode != ClusterMode::Redirect
+            || !origin_pull::is_player(sub_type)
+            || self.streams.contains_key(identifier)
+        {
+            return None;
+        }
+        let key = origin_pull::stream_key(identifier)?;
+        if self.origin_pulls.contains_key(&key)
+            || self
+                .streams
+                .contains_key(&origin_pull::rtmp_identifier(&key)?)
+        {
+            return None;
+        }
+
+        self.registry.as_ref()?.locate(&key)
+    }
+
+    /*Returns the origin pull which the subscribers/requests of the missing stream
+    should wait for, the pulling is started if the rtmp stream of the same name
+    does not exist. The stream is pulled from the node which publishes it if it
+    is found in the cluster registry, otherwise from the configured origins.
+    Returns None if the stream exists or is published locally.*/
+    fn get_origin_pull(&mut self, identifier: &StreamIdentifier) -> Option<&mut OriginPull> {
+        if (!self.rtmp_pull_enabled && self.registry.is_none())
+            || self.streams.contains_key(identifier)
+        {
+            return None;
+        }
+        let key = origin_pull::stream_key(identifier)?;
+        let rtmp_identifier = origin_pull::rtmp_identifier(&key)?;
+        let is_pulling = self.origin_pulls.contains_key(&key);
+
+        //the subscribe info is sent again while the rtmp stream is not published,
+        //then a failed pulling is retried by the next subscriber, the pull clients
+        //ignore it if the pulling session is still running
+        if !self.streams.contains_key(&rtmp_identifier) {
+            let server_address = self
+                .registry
+                .as_ref()
+                .and_then(|registry| registry.locate(&key))
+                .and_then(|node| node.rtmp_address);
+            if server_address.is_none() && !self.rtmp_pull_enabled {
+                return self.origin_pulls.get_mut(&key);
+            }
+
+            log::info!(
+                "start pulling stream from origin, identifier: {}",
+                rtmp_identifier
+            );
+            let client_event = BroadcastEvent::Subscribe {
+                id: key.clone(),
+                identifier: rtmp_identifier,
+                server_address,
+                result_sender: None,
+            };
+            //send subscribe info to pull clients
+            if let Err(err) = self.client_event_sender.send(client_event) {
+                log::error!("send subscribe info to pull clients err: {}", err);
+                if !is_pulling {
+                    return None;
+                }
+            }
+        } else if !is_pulling {
+            return None;
+        }
+
+        Some(self.origin_pulls.entry(key).or_default())
+    }
+
+    //respond the subscribers/requests waiting for the published stream
+    async fn resolve_pending(&mut self, identifier: &StreamIdentifier) {
+        let Some(origin_pull) =
+            origin_pull::stream_key(identifier).and_then(|key| self.origin_pulls.get_mut(&key))
+        else {
+            return;
+        };
+
+        let (subscribers, requests) = origin_pull.take_pending(identifier);
+        for subscriber in subscribers {
+            self.subscribe_and_respond(
+                subscriber.identifier,
+                subscriber.info,
+                subscriber.sender,
+                subscriber.receiver,
+                subscriber.result_sender,
+            )
+            .await;
+        }
+        for request in requests {
+            if let Err(err) = self.request(&request.identifier, request.sender) {
+                log::error!("resolve_pending request error: {}", err);
+            }
+        }
+    }
+
+    //expire the waiting subscribers and stop pulling the idle streams
+    fn check_origin_pulls(&mut self) {
+        let now = Instant::now();
+        let mut idle_keys = Vec::new();
+
+        for (key, origin_pull) in self.origin_pulls.iter_mut() {
+            origin_pull.expire_pending(now);
+            if origin_pull.is_idle(now, self.pull_idle_timeout) {
+                idle_keys.push(key.clone());
+            }
+        }
+
+        for key in idle_keys {
+            log::info!("stop pulling the idle stream from origin: {}", key);
+            self.origin_pulls.remove(&key);
+
+            if let Some(identifier) = origin_pull::rtmp_identifier(&key) {
+                if self.streams.contains_key(&identifier) {
+                    if let Err(err) = self.unpublish(&identifier) {
+                        log::error!("unpublish the idle stream err: {}", err);
+                    }
+                }
+            }
+
+            //send unsubscribe info to pull clients to stop the pulling sessions
+            let client_event = BroadcastEvent::UnSubscribe {
+                id: key,
+                result_sender: None,
+            };
+            if let Err(err) = self.client_event_sender.send(client_event) {
+                log::error!("send unsubscribe info to pull clients err: {}", err);
+            }
+        }
+    }
+
     fn request(
         &mut self,
         identifier: &StreamIdentifier,
@@ -846,7 +1257,7 @@ impl StreamsHub {
         }
 
         if let Some(topn) = top_n {
-            data.sort_by(|a, b| b.subscriber_count.cmp(&a.subscriber_count));
+            data.sort_by_key(|b| std::cmp::Reverse(b.subscriber_count));
             let top_streams: Vec<StatisticsStream> = data.into_iter().take(topn).collect();
             return Ok(serde_json::to_value(top_streams)?);
         }
@@ -854,36 +1265,50 @@ impl StreamsHub {
         Ok(serde_json::to_value(data)?)
     }
 
+    //renders the statistics of all the streams and the session counts as metrics
+    async fn api_metrics(&mut self) -> String {
+        let (stream_sender, mut stream_receiver) = mpsc::unbounded_channel();
+        for event_sender in self.streams.values() {
+            if let Err(err) = event_sender.send(TransceiverEvent::Api {
+                sender: stream_sender.clone(),
+                uuid: None,
+            }) {
+                log::error!("api_metrics send event err: {}", err);
+            }
+        }
+        //the receiving ends after all the transceivers responded
+        drop(stream_sender);
+
+        let mut streams = Vec::new();
+        while let Some(stream_statistics) = stream_receiver.recv().await {
+            streams.push(stream_statistics);
+        }
+
+        metrics::render(&streams, &self.session_metrics)
+    }
+
     fn api_kick_off_client(&mut self, uid: Uuid) -> Result<(), StreamHubError> {
         if let Some(event) = self.un_pub_sub_events.get(&uid) {
             match event {
                 StreamHubEvent::UnPublish { identifier, info } => {
-                    if self
-                        .hub_event_sender
+                    self.hub_event_sender
                         .send(StreamHubEvent::UnPublish {
                             identifier: identifier.clone(),
                             info: info.clone(),
                         })
-                        .is_err()
-                    {
-                        return Err(StreamHubError {
+                        .map_err(|_| StreamHubError {
                             value: StreamHubErrorValue::SendError,
-                        });
-                    }
+                        })?;
                 }
                 StreamHubEvent::UnSubscribe { identifier, info } => {
-                    if self
-                        .hub_event_sender
+                    self.hub_event_sender
                         .send(StreamHubEvent::UnSubscribe {
                             identifier: identifier.clone(),
                             info: info.clone(),
                         })
-                        .is_err()
-                    {
-                        return Err(StreamHubError {
+                        .map_err(|_| StreamHubError {
                             value: StreamHubErrorValue::SendError,
-                        });
-                    }
+                        })?;
                 }
                 _ => {}
             }
@@ -979,24 +1404,6 @@ impl StreamsHub {
             return Ok(result_receiver.await?);
         }
 
-        if self.rtmp_pull_enabled {
-            log::info!("subscribe: try to pull stream, identifier: {}", identifer);
-
-            let client_event = BroadcastEvent::Subscribe {
-                id: String::from("rtmp_relay"),
-                identifier: identifer.clone(),
-                server_address: None,
-                result_sender: None,
-            };
-
-            //send subscribe info to pull clients
-            self.client_event_sender
-                .send(client_event)
-                .map_err(|_| StreamHubError {
-                    value: StreamHubErrorValue::SendError,
-                })?;
-        }
-
         Err(StreamHubError {
             value: StreamHubErrorValue::NoAppOrStreamName,
         })
@@ -1033,7 +1440,7 @@ impl StreamsHub {
         receiver: DataReceiver,
         handler: Arc<dyn TStreamHandler>,
     ) -> Result<StatisticDataSender, StreamHubError> {
-        if self.streams.get(&identifier).is_some() {
+        if self.streams.contains_key(&identifier) {
             return Err(StreamHubError {
                 value: StreamHubErrorValue::Exists,
             });
@@ -1058,7 +1465,26 @@ impl StreamsHub {
 
         self.streams.insert(identifier.clone(), event_sender);
 
-        if self.rtmp_push_enabled || self.hls_enabled || self.rtmp_remuxer_enabled {
+        //announce the rtmp streams published locally to the cluster, the streams
+        //pulled from other nodes are not announced to avoid pulling loops
+        if let Some(registry) = &self.registry {
+            if let StreamIdentifier::Rtmp { .. } = identifier {
+                if let Some(key) = origin_pull::stream_key(&identifier)
+                    .filter(|key| !self.origin_pulls.contains_key(key))
+                {
+                    registry.register(&key);
+                }
+            }
+        }
+
+        if self.rtmp_push_enabled
+            || self.hls_enabled
+            || self.dash_enabled
+            || self.record_enabled
+            || self.rtmp_remuxer_enabled
+            || self.rtsp_remuxer_enabled
+            || self.webrtc_remuxer_enabled
+        {
             let client_event = BroadcastEvent::Publish { identifier };
 
             //send publish info to push clients
@@ -1080,7 +1506,32 @@ impl StreamsHub {
                     value: StreamHubErrorValue::SendError,
                 })?;
                 self.streams.remove(identifier);
+                self.session_metrics.on_unpublish(identifier);
                 log::info!("unpublish remove stream, stream identifier: {}", identifier);
+
+                //the stream pulled from the origin ends
+                if let StreamIdentifier::Rtmp { .. } = identifier {
+                    if let Some(key) = origin_pull::stream_key(identifier) {
+                        match self.origin_pulls.remove(&key) {
+                            Some(mut origin_pull) => origin_pull.fail_pending(),
+                            None => {
+                                if let Some(registry) = &self.registry {
+                                    registry.unregister(&key);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                //send unpublish info to push clients to stop relaying
+                if self.rtmp_push_enabled {
+                    let client_event = BroadcastEvent::UnPublish {
+                        identifier: identifier.clone(),
+                    };
+                    if let Err(err) = self.client_event_sender.send(client_event) {
+                        log::error!("send unpublish info to push clients err: {}", err);
+                    }
+                }
             }
             None => {
                 return Err(StreamHubError {
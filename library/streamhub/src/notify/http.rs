@@ -1,7 +1,13 @@
-use crate::notify::Notifier;
-use reqwest::Client;
+use crate::define::StreamHubEventMessage;
+use crate::notify::{HookDecision, Notifier};
 use async_trait::async_trait;
-use crate::define::{StreamHubEventMessage};
+use reqwest::{Client, Response};
+use {
+    hmac::{Hmac, Mac, NewMac},
+    serde::Deserialize,
+    sha2::Sha256,
+    std::time::Duration,
+};
 
 macro_rules! serialize_event {
     ($message:expr) => {{
@@ -16,6 +22,81 @@ macro_rules! serialize_event {
     }};
 }
 
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_RETRIES: u32 = 2;
+pub const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
+pub const TIMESTAMP_HEADER: &str = "X-Xiu-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Xiu-Signature";
+
+fn hmac_sha256(key: &str, data: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).unwrap();
+    mac.update(data.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+//hex(HMAC-SHA256(secret, "{timestamp}.{body}"))
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    hmac_sha256(secret, &format!("{timestamp}.{body}"))
+}
+
+//the optional json body of a 2xx response of the publish/play hook
+#[derive(Deserialize, Default)]
+struct HookResponse {
+    stream_name: Option<String>,
+}
+
+struct HookRequest {
+    client: Client,
+    url: String,
+    body: String,
+    secret: Option<String>,
+    retries: u32,
+    retry_interval: Duration,
+}
+
+impl HookRequest {
+    /*Posts the event, the connection errors and the 5xx responses are retried
+    with exponential backoff. None is returned if all the attempts fail.*/
+    async fn send(&self, name: &str) -> Option<Response> {
+        let mut interval = self.retry_interval;
+
+        for attempt in 0..=self.retries {
+            if attempt > 0 {
+                tokio::time::sleep(interval).await;
+                interval *= 2;
+            }
+
+            let mut request = self
+                .client
+                .post(&self.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(self.body.clone());
+            if let Some(secret) = &self.secret {
+                let timestamp = chrono::Utc::now().timestamp();
+                request = request.header(TIMESTAMP_HEADER, timestamp).header(
+                    SIGNATURE_HEADER,
+                    format!("sha256={}", sign(secret, timestamp, &self.body)),
+                );
+            }
+
+            match request.send().await {
+                Ok(response) if !response.status().is_server_error() => {
+                    log::info!("{} response status: {}", name, response.status());
+                    return Some(response);
+                }
+                Ok(response) => {
+                    log::error!("{} attempt {} status: {}", name, attempt, response.status());
+                }
+                Err(err) => {
+                    log::error!("{} attempt {} error: {}", name, attempt, err);
+                }
+            }
+        }
+
+        None
+    }
+}
 
 pub struct HttpNotifier {
     request_client: Client,
@@ -23,6 +104,14 @@ pub struct HttpNotifier {
     on_unpublish_url: Option<String>,
     on_play_url: Option<String>,
     on_stop_url: Option<String>,
+    on_record_url: Option<String>,
+    on_hls_segment_url: Option<String>,
+    //a non-2xx response of on_publish/on_play denies the session in sync mode
+    sync: bool,
+    //sign the bodies with HMAC-SHA256 if set
+    secret: Option<String>,
+    retries: u32,
+    retry_interval: Duration,
 }
 
 impl HttpNotifier {
@@ -32,12 +121,102 @@ impl HttpNotifier {
         on_play_url: Option<String>,
         on_stop_url: Option<String>,
     ) -> Self {
-        Self {
-            request_client: reqwest::Client::new(),
+        let mut notifier = Self {
+            request_client: Client::new(),
             on_publish_url,
             on_unpublish_url,
             on_play_url,
             on_stop_url,
+            on_record_url: None,
+            on_hls_segment_url: None,
+            sync: false,
+            secret: None,
+            retries: DEFAULT_RETRIES,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+        };
+        notifier.set_timeout(DEFAULT_TIMEOUT);
+        notifier
+    }
+
+    pub fn set_on_record_url(&mut self, on_record_url: Option<String>) {
+        self.on_record_url = on_record_url;
+    }
+
+    pub fn set_on_hls_segment_url(&mut self, on_hls_segment_url: Option<String>) {
+        self.on_hls_segment_url = on_hls_segment_url;
+    }
+
+    pub fn set_sync(&mut self, sync: bool) {
+        self.sync = sync;
+    }
+
+    pub fn set_secret(&mut self, secret: Option<String>) {
+        self.secret = secret;
+    }
+
+    //the timeout of every attempt
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.request_client = match Client::builder().timeout(timeout).build() {
+            Ok(client) => client,
+            Err(err) => {
+                log::error!("build http notifier client error: {}", err);
+                Client::new()
+            }
+        };
+    }
+
+    //the retry interval is doubled after every attempt
+    pub fn set_retry(&mut self, retries: u32, retry_interval: Duration) {
+        self.retries = retries;
+        self.retry_interval = retry_interval;
+    }
+
+    fn request(&self, url: &str, event: &StreamHubEventMessage) -> HookRequest {
+        HookRequest {
+            client: self.request_client.clone(),
+            url: url.to_string(),
+            body: serialize_event!(event),
+            secret: self.secret.clone(),
+            retries: self.retries,
+            retry_interval: self.retry_interval,
+        }
+    }
+
+    //the notifications are sent in the background so that the stream hub is not blocked
+    fn notify(&self, name: &'static str, url: &Option<String>, event: &StreamHubEventMessage) {
+        if let Some(url) = url {
+            let request = self.request(url, event);
+            tokio::spawn(async move {
+                request.send(name).await;
+            });
+        }
+    }
+
+    async fn check(
+        &self,
+        name: &'static str,
+        url: &Option<String>,
+        event: &StreamHubEventMessage,
+    ) -> HookDecision {
+        let Some(url) = url else {
+            return HookDecision::Allow { stream_name: None };
+        };
+
+        match self.request(url, event).send(name).await {
+            Some(response) if response.status().is_success() => {
+                //an empty or non-json body allows the session without rewriting
+                let hook_response = match response.text().await {
+                    Ok(body) => serde_json::from_str::<HookResponse>(&body).unwrap_or_default(),
+                    Err(_) => HookResponse::default(),
+                };
+                HookDecision::Allow {
+                    stream_name: hook_response
+                        .stream_name
+                        .filter(|stream_name| !stream_name.is_empty()),
+                }
+            }
+            //fail closed if the hook server is unreachable
+            _ => HookDecision::Deny,
         }
     }
 }
@@ -45,78 +224,56 @@ impl HttpNotifier {
 #[async_trait]
 impl Notifier for HttpNotifier {
     async fn on_publish_notify(&self, event: &StreamHubEventMessage) {
-        if let Some(on_publish_url) = &self.on_publish_url {
-            match self
-                .request_client
-                .post(on_publish_url)
-                .body(serialize_event!(event))
-                .send()
-                .await
-            {
-                Err(err) => {
-                    log::error!("on_publish error: {}", err);
-                }
-                Ok(response) => {
-                    log::info!("on_publish success: {:?}", response);
-                }
-            }
-        }
+        self.notify("on_publish", &self.on_publish_url, event);
     }
 
     async fn on_unpublish_notify(&self, event: &StreamHubEventMessage) {
-        if let Some(on_unpublish_url) = &self.on_unpublish_url {
-            match self
-                .request_client
-                .post(on_unpublish_url)
-                .body(serialize_event!(event))
-                .send()
-                .await
-            {
-                Err(err) => {
-                    log::error!("on_unpublish error: {}", err);
-                }
-                Ok(response) => {
-                    log::info!("on_unpublish success: {:?}", response);
-                }
-            }
-        }
+        self.notify("on_unpublish", &self.on_unpublish_url, event);
     }
 
     async fn on_play_notify(&self, event: &StreamHubEventMessage) {
-        if let Some(on_play_url) = &self.on_play_url {
-            match self
-                .request_client
-                .post(on_play_url)
-                .body(serialize_event!(event))
-                .send()
-                .await
-            {
-                Err(err) => {
-                    log::error!("on_play error: {}", err);
-                }
-                Ok(response) => {
-                    log::info!("on_play success: {:?}", response);
-                }
-            }
-        }
+        self.notify("on_play", &self.on_play_url, event);
     }
 
     async fn on_stop_notify(&self, event: &StreamHubEventMessage) {
-        if let Some(on_stop_url) = &self.on_stop_url {
-            match self
-                .request_client
-                .post(on_stop_url)
-                .body(serialize_event!(event))
-                .send()
-                .await
-            {
-                Err(err) => {
-                    log::error!("on_stop error: {}", err);
-                }
-                Ok(response) => {
-                    log::info!("on_stop success: {:?}", response);
-                }
-            }
-        }
+        self.notify("on_stop", &self.on_stop_url, event);
+    }
+
+    async fn on_record_notify(&self, event: &StreamHubEventMessage) {
+        self.notify("on_record", &self.on_record_url, event);
+    }
+
+    async fn on_hls_segment_notify(&self, event: &StreamHubEventMessage) {
+        self.notify("on_hls_segment", &self.on_hls_segment_url, event);
+    }
+
+    fn is_sync(&self) -> bool {
+        self.sync
+    }
+
+    async fn on_publish_check(&self, event: &StreamHubEventMessage) -> HookDecision {
+        self.check("on_publish", &self.on_publish_url, event).await
+    }
+
+    async fn on_play_check(&self, event: &StreamHubEventMessage) -> HookDecision {
+        self.check("on_play", &self.on_play_url, event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hmac_sha256, sign};
+
+    #[test]
+    fn test_sign() {
+        //RFC 4231 test case 2
+        assert_eq!(
+            hmac_sha256("Jefe", "what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_eq!(
+            sign("secret", 1700000000, "{}"),
+            hmac_sha256("secret", "1700000000.{}")
+        );
     }
 }
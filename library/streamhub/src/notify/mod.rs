@@ -1,7 +1,18 @@
 pub mod http;
 
+use crate::define::{StreamHubEvent, StreamHubEventMessage, StreamHubEventSender};
+use crate::errors::{StreamHubError, StreamHubErrorValue};
+use crate::stream::StreamIdentifier;
 use async_trait::async_trait;
-use crate::define::{StreamHubEventMessage};
+use std::sync::Arc;
+
+//the result of a publish/play hook in sync mode
+#[derive(Debug, PartialEq)]
+pub enum HookDecision {
+    //the stream name can be rewritten by the hook
+    Allow { stream_name: Option<String> },
+    Deny,
+}
 
 #[async_trait]
 pub trait Notifier: Sync + Send {
@@ -9,4 +20,223 @@ pub trait Notifier: Sync + Send {
     async fn on_unpublish_notify(&self, event: &StreamHubEventMessage);
     async fn on_play_notify(&self, event: &StreamHubEventMessage);
     async fn on_stop_notify(&self, event: &StreamHubEventMessage);
-}
\ No newline at end of file
+    async fn on_record_notify(&self, _event: &StreamHubEventMessage) {}
+    async fn on_hls_segment_notify(&self, _event: &StreamHubEventMessage) {}
+
+    /*In sync mode the publishers and players are accepted only after they are
+    allowed by on_publish_check/on_play_check, which replace the on_publish
+    and on_play notifications.*/
+    fn is_sync(&self) -> bool {
+        false
+    }
+    async fn on_publish_check(&self, _event: &StreamHubEventMessage) -> HookDecision {
+        HookDecision::Allow { stream_name: None }
+    }
+    async fn on_play_check(&self, _event: &StreamHubEventMessage) -> HookDecision {
+        HookDecision::Allow { stream_name: None }
+    }
+}
+
+//replaces the stream name of the identifier with the one returned by the hook
+pub fn rewrite_stream_name(identifier: &StreamIdentifier, stream_name: &str) -> StreamIdentifier {
+    match identifier {
+        StreamIdentifier::Rtmp { app_name, .. } => StreamIdentifier::Rtmp {
+            app_name: app_name.clone(),
+            stream_name: stream_name.to_string(),
+        },
+        StreamIdentifier::WebRTC { app_name, .. } => StreamIdentifier::WebRTC {
+            app_name: app_name.clone(),
+            stream_name: stream_name.to_string(),
+        },
+        StreamIdentifier::Rtsp { stream_path } => {
+            let stream_path = match stream_path.rsplit_once('/') {
+                Some((prefix, _)) => format!("{prefix}/{stream_name}"),
+                None => stream_name.to_string(),
+            };
+            StreamIdentifier::Rtsp { stream_path }
+        }
+        StreamIdentifier::Unkonwn => StreamIdentifier::Unkonwn,
+    }
+}
+
+/*Runs the publish/play hook of a publish/subscribe event in sync mode. The allowed
+event is sent back to the stream hub(with the stream name rewritten if returned by
+the hook), and the denied one is rejected with a HookDenied error.*/
+pub async fn check_event(
+    notifier: Arc<dyn Notifier>,
+    event: StreamHubEvent,
+    hub_event_sender: StreamHubEventSender,
+) {
+    let message = event.to_message();
+    let denied = || StreamHubError {
+        value: StreamHubErrorValue::HookDenied,
+    };
+
+    let event = match event {
+        StreamHubEvent::Publish {
+            identifier,
+            info,
+            result_sender,
+            stream_handler,
+        } => match notifier.on_publish_check(&message).await {
+            HookDecision::Allow { stream_name } => StreamHubEvent::Publish {
+                identifier: match stream_name {
+                    Some(stream_name) => rewrite_stream_name(&identifier, &stream_name),
+                    None => identifier,
+                },
+                info,
+                result_sender,
+                stream_handler,
+            },
+            HookDecision::Deny => {
+                log::warn!("publish is denied by the hook, identifier: {}", identifier);
+                if result_sender.send(Err(denied())).is_err() {
+                    log::error!("check_event publish error: The receiver dropped.");
+                }
+                return;
+            }
+        },
+        StreamHubEvent::Subscribe {
+            identifier,
+            info,
+            result_sender,
+        } => match notifier.on_play_check(&message).await {
+            HookDecision::Allow { stream_name } => StreamHubEvent::Subscribe {
+                identifier: match stream_name {
+                    Some(stream_name) => rewrite_stream_name(&identifier, &stream_name),
+                    None => identifier,
+                },
+                info,
+                result_sender,
+            },
+            HookDecision::Deny => {
+                log::warn!("play is denied by the hook, identifier: {}", identifier);
+                if result_sender.send(Err(denied())).is_err() {
+                    log::error!("check_event subscribe error: The receiver dropped.");
+                }
+                return;
+            }
+        },
+        event => event,
+    };
+
+    if hub_event_sender.send(event).is_err() {
+        log::error!("check_event error: The stream hub is closed.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_event, rewrite_stream_name, HookDecision, Notifier};
+    use crate::define::{
+        NotifyInfo, StreamHubEvent, StreamHubEventMessage, SubDataType,
+        SubEventExecuteResultSender, SubscribeType, SubscriberInfo,
+    };
+    use crate::errors::StreamHubErrorValue;
+    use crate::stream::StreamIdentifier;
+    use crate::utils::{RandomDigitCount, Uuid};
+    use async_trait::async_trait;
+    use std::sync::Arc;
+    use tokio::sync::{mpsc, oneshot};
+
+    //allows the players of "live/test" as "live/user_1" and denies the others
+    struct TestNotifier;
+
+    #[async_trait]
+    impl Notifier for TestNotifier {
+        async fn on_publish_notify(&self, _event: &StreamHubEventMessage) {}
+        async fn on_unpublish_notify(&self, _event: &StreamHubEventMessage) {}
+        async fn on_play_notify(&self, _event: &StreamHubEventMessage) {}
+        async fn on_stop_notify(&self, _event: &StreamHubEventMessage) {}
+
+        fn is_sync(&self) -> bool {
+            true
+        }
+        async fn on_play_check(&self, event: &StreamHubEventMessage) -> HookDecision {
+            match event {
+                StreamHubEventMessage::Subscribe {
+                    identifier: StreamIdentifier::Rtmp { stream_name, .. },
+                    ..
+                } if stream_name == "test" => HookDecision::Allow {
+                    stream_name: Some(String::from("user_1")),
+                },
+                _ => HookDecision::Deny,
+            }
+        }
+    }
+
+    fn subscribe_event(
+        stream_name: &str,
+        result_sender: SubEventExecuteResultSender,
+    ) -> StreamHubEvent {
+        StreamHubEvent::Subscribe {
+            identifier: StreamIdentifier::Rtmp {
+                app_name: String::from("live"),
+                stream_name: String::from(stream_name),
+            },
+            info: SubscriberInfo {
+                id: Uuid::new(RandomDigitCount::Four),
+                sub_type: SubscribeType::RtmpPull,
+                sub_data_type: SubDataType::Frame,
+                notify_info: NotifyInfo {
+                    request_url: String::from(""),
+                    remote_addr: String::from(""),
+                },
+            },
+            result_sender,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_event() {
+        let (hub_event_sender, mut hub_event_receiver) = mpsc::unbounded_channel();
+
+        let (result_sender, _result_receiver) = oneshot::channel();
+        let event = subscribe_event("test", result_sender);
+        check_event(Arc::new(TestNotifier), event, hub_event_sender.clone()).await;
+        match hub_event_receiver.try_recv() {
+            Ok(StreamHubEvent::Subscribe { identifier, .. }) => assert_eq!(
+                identifier,
+                StreamIdentifier::Rtmp {
+                    app_name: String::from("live"),
+                    stream_name: String::from("user_1"),
+                }
+            ),
+            _ => panic!("the allowed event is not sent back"),
+        }
+
+        let (result_sender, result_receiver) = oneshot::channel();
+        let event = subscribe_event("other", result_sender);
+        check_event(Arc::new(TestNotifier), event, hub_event_sender).await;
+        assert!(hub_event_receiver.try_recv().is_err());
+        match result_receiver.await {
+            Ok(Err(err)) => assert!(matches!(err.value, StreamHubErrorValue::HookDenied)),
+            _ => panic!("the denied event is not rejected"),
+        }
+    }
+
+    #[test]
+    fn test_rewrite_stream_name() {
+        let rtmp = StreamIdentifier::Rtmp {
+            app_name: String::from("live"),
+            stream_name: String::from("test"),
+        };
+        assert_eq!(
+            rewrite_stream_name(&rtmp, "user_1"),
+            StreamIdentifier::Rtmp {
+                app_name: String::from("live"),
+                stream_name: String::from("user_1"),
+            }
+        );
+
+        let rtsp = StreamIdentifier::Rtsp {
+            stream_path: String::from("/live/test"),
+        };
+        assert_eq!(
+            rewrite_stream_name(&rtsp, "user_1"),
+            StreamIdentifier::Rtsp {
+                stream_path: String::from("/live/user_1"),
+            }
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use {
+    crate::{
+        define::{
+            DataReceiver, DataSender, InformationSender, SubEventExecuteResultSender,
+            SubscribeType, SubscriberInfo,
+        },
+        errors::{StreamHubError, StreamHubErrorValue},
+        stream::StreamIdentifier,
+        utils::Uuid,
+    },
+    std::{
+        collections::HashSet,
+        time::{Duration, Instant},
+    },
+};
+
+pub const DEFAULT_PULL_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_PULL_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+//the streams of different protocols with the same app/stream name share one key,
+//they are all served by the rtmp stream pulled from the origin
+pub fn stream_key(identifier: &StreamIdentifier) -> Option<String> {
+    match identifier {
+        StreamIdentifier::Rtmp {
+            app_name,
+            stream_name,
+        }
+        | StreamIdentifier::WebRTC {
+            app_name,
+            stream_name,
+        } => Some(format!("{app_name}/{stream_name}")),
+        StreamIdentifier::Rtsp { stream_path } => {
+            let (app_name, stream_name) = stream_path.trim_start_matches('/').split_once('/')?;
+            Some(format!("{app_name}/{stream_name}"))
+        }
+        StreamIdentifier::Unkonwn => None,
+    }
+}
+
+//the rtmp stream pulled from the origin for the key
+pub fn rtmp_identifier(key: &str) -> Option<StreamIdentifier> {
+    let (app_name, stream_name) = key.split_once('/')?;
+    Some(StreamIdentifier::Rtmp {
+        app_name: app_name.to_string(),
+        stream_name: stream_name.to_string(),
+    })
+}
+
+//the subscribers requested by remote players, the remuxers are not counted
+//when deciding whether a pulled stream is idle
+pub fn is_player(sub_type: &SubscribeType) -> bool {
+    matches!(
+        sub_type,
+        SubscribeType::RtmpPull
+            | SubscribeType::RtmpRemux2HttpFlv
+            | SubscribeType::RtspPull
+            | SubscribeType::WhepPull
+    )
+}
+
+pub struct PendingSubscriber {
+    pub identifier: StreamIdentifier,
+    pub info: SubscriberInfo,
+    pub sender: DataSender,
+    pub receiver: DataReceiver,
+    pub result_sender: SubEventExecuteResultSender,
+    pub deadline: Instant,
+}
+
+//the information(e.g. the sdp of rtsp) requested before the stream is published
+pub struct PendingRequest {
+    pub identifier: StreamIdentifier,
+    pub sender: InformationSender,
+    pub deadline: Instant,
+}
+
+//A stream which is pulled from the origin on demand, the subscribers are held
+//until the stream is published, and the pulling is stopped after it has no
+//players for the idle timeout.
+#[derive(Default)]
+pub struct OriginPull {
+    pub subscribers: Vec<PendingSubscriber>,
+    pub requests: Vec<PendingRequest>,
+    pub players: HashSet<Uuid>,
+    idle_since: Option<Instant>,
+}
+
+impl OriginPull {
+    pub fn add_player(&mut self, id: Uuid) {
+        self.players.insert(id);
+        self.idle_since = None;
+    }
+
+    pub fn remove_player(&mut self, id: &Uuid) {
+        self.players.remove(id);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.subscribers.is_empty() || !self.requests.is_empty()
+    }
+
+    //takes out the subscribers and requests waiting for the stream
+    pub fn take_pending(
+        &mut self,
+        identifier: &StreamIdentifier,
+    ) -> (Vec<PendingSubscriber>, Vec<PendingRequest>) {
+        let (subscribers, left): (Vec<_>, Vec<_>) = self
+            .subscribers
+            .drain(..)
+            .partition(|subscriber| &subscriber.identifier == identifier);
+        self.subscribers = left;
+
+        let (requests, left): (Vec<_>, Vec<_>) = self
+            .requests
+            .drain(..)
+            .partition(|request| &request.identifier == identifier);
+        self.requests = left;
+
+        (subscribers, requests)
+    }
+
+    //the subscribers waiting longer than the timeout are responded with errors and
+    //the requests are dropped, then the senders of the requests receive nothing.
+    //The subscribers/requests whose sessions have disconnected are dropped at once.
+    pub fn expire_pending(&mut self, now: Instant) {
+        self.subscribers
+            .retain(|subscriber| !subscriber.result_sender.is_closed());
+        let (expired, left): (Vec<_>, Vec<_>) = self
+            .subscribers
+            .drain(..)
+            .partition(|subscriber| subscriber.deadline <= now);
+        self.subscribers = left;
+        self.requests
+            .retain(|request| request.deadline > now && !request.sender.is_closed());
+
+        for subscriber in expired {
+            log::warn!(
+                "origin pull: wait for stream timeout, identifier: {}",
+                subscriber.identifier
+            );
+            Self::respond_error(subscriber, StreamHubErrorValue::OriginPullTimeout);
+        }
+    }
+
+    pub fn fail_pending(&mut self) {
+        for subscriber in self.subscribers.drain(..) {
+            Self::respond_error(subscriber, StreamHubErrorValue::OriginPullFailed);
+        }
+        self.requests.clear();
+    }
+
+    //returns true if the stream has no players and no pending subscribers
+    //for the idle timeout
+    pub fn is_idle(&mut self, now: Instant, idle_timeout: Duration) -> bool {
+        if !self.players.is_empty() || self.is_pending() {
+            self.idle_since = None;
+            return false;
+        }
+
+        let idle_since = *self.idle_since.get_or_insert(now);
+        now.duration_since(idle_since) >= idle_timeout
+    }
+
+    fn respond_error(subscriber: PendingSubscriber, value: StreamHubErrorValue) {
+        let err = Err(StreamHubError { value });
+        if subscriber.result_sender.send(err).is_err() {
+            log::error!("origin pull: the subscriber dropped.");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{rtmp_identifier, stream_key, OriginPull, PendingRequest, PendingSubscriber},
+        crate::{
+            define::{
+                DataReceiver, DataSender, NotifyInfo, SubDataType, SubscribeType, SubscriberInfo,
+            },
+            errors::{StreamHubError, StreamHubErrorValue},
+            queue,
+            stream::StreamIdentifier,
+            utils::{RandomDigitCount, Uuid},
+        },
+        std::time::{Duration, Instant},
+        tokio::sync::{mpsc, oneshot},
+    };
+
+    #[test]
+    fn test_stream_key() {
+        let rtmp = StreamIdentifier::Rtmp {
+            app_name: String::from("live"),
+            stream_name: String::from("test"),
+        };
+        let rtsp = StreamIdentifier::Rtsp {
+            stream_path: String::from("/live/test"),
+        };
+        assert_eq!(stream_key(&rtmp), Some(String::from("live/test")));
+        assert_eq!(stream_key(&rtsp), stream_key(&rtmp));
+        assert_eq!(rtmp_identifier("live/test"), Some(rtmp));
+
+        let rtsp = StreamIdentifier::Rtsp {
+            stream_path: String::from("test"),
+        };
+        assert_eq!(stream_key(&rtsp), None);
+    }
+
+    #[test]
+    fn test_is_idle() {
+        let idle_timeout = Duration::from_secs(30);
+        let now = Instant::now();
+        let mut origin_pull = OriginPull::default();
+
+        let id = Uuid::new(RandomDigitCount::Four);
+        origin_pull.add_player(id);
+        assert!(!origin_pull.is_idle(now, idle_timeout));
+
+        origin_pull.remove_player(&id);
+        assert!(!origin_pull.is_idle(now, idle_timeout));
+        assert!(!origin_pull.is_idle(now + Duration::from_secs(29), idle_timeout));
+        assert!(origin_pull.is_idle(now + Duration::from_secs(30), idle_timeout));
+
+        //a new player resets the idle time
+        origin_pull.add_player(id);
+        origin_pull.remove_player(&id);
+        assert!(!origin_pull.is_idle(now + Duration::from_secs(31), idle_timeout));
+    }
+
+    #[test]
+    fn test_expire_pending() {
+        let now = Instant::now();
+        let deadline = now + Duration::from_secs(10);
+        let identifier = StreamIdentifier::Rtsp {
+            stream_path: String::from("/live/test"),
+        };
+        let mut origin_pull = OriginPull::default();
+
+        let mut result_receivers = Vec::new();
+        let mut request_receivers = Vec::new();
+        for _ in 0..2 {
+            let (sender, frame_receiver) = queue::unbounded();
+            let (result_sender, result_receiver) = oneshot::channel();
+            origin_pull.subscribers.push(PendingSubscriber {
+                identifier: identifier.clone(),
+                info: SubscriberInfo {
+                    id: Uuid::new(RandomDigitCount::Four),
+                    sub_type: SubscribeType::RtspPull,
+                    notify_info: NotifyInfo {
+                        request_url: String::from(""),
+                        remote_addr: String::from(""),
+                    },
+                    sub_data_type: SubDataType::Frame,
+                },
+                sender: DataSender::Frame { sender },
+                receiver: DataReceiver {
+                    frame_receiver: Some(frame_receiver),
+                    packet_receiver: None,
+                },
+                result_sender,
+                deadline,
+            });
+            result_receivers.push(result_receiver);
+
+            let (sender, request_receiver) = mpsc::unbounded_channel();
+            origin_pull.requests.push(PendingRequest {
+                identifier: identifier.clone(),
+                sender,
+                deadline,
+            });
+            request_receivers.push(request_receiver);
+        }
+
+        //the disconnected subscriber and request are dropped before the deadline
+        result_receivers.pop();
+        request_receivers.pop();
+        origin_pull.expire_pending(now);
+        assert_eq!(origin_pull.subscribers.len(), 1);
+        assert_eq!(origin_pull.requests.len(), 1);
+
+        origin_pull.expire_pending(deadline);
+        assert!(!origin_pull.is_pending());
+        assert!(matches!(
+            result_receivers[0].try_recv(),
+            Ok(Err(StreamHubError {
+                value: StreamHubErrorValue::OriginPullTimeout
+            }))
+        ));
+    }
+}
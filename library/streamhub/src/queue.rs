@@ -0,0 +1,589 @@
+use {
+    crate::define::{FrameData, PacketData, VideoCodecType},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::VecDeque,
+        fmt,
+        sync::{Arc, Mutex},
+    },
+    tokio::sync::Notify,
+};
+
+/* What to do when the queue of a subscriber is full. */
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /* Drop the incoming frames until the next key frame can be queued. */
+    #[default]
+    #[serde(rename = "drop_until_key_frame")]
+    DropUntilKeyFrame,
+    /* Drop the oldest GOP which is still waiting in the queue. */
+    #[serde(rename = "drop_oldest_gop")]
+    DropOldestGop,
+    /* Close the queue, the subscriber will receive None and exit. */
+    #[serde(rename = "disconnect")]
+    Disconnect,
+}
+
+pub const DEFAULT_SUBSCRIBER_QUEUE_SIZE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    /* sequence headers, meta data and media info, they are never dropped */
+    Config,
+    Audio,
+    Video { gop_start: bool },
+}
+
+#[derive(Default)]
+pub struct ClassifyState {
+    vcodec: Option<VideoCodecType>,
+    /* Annex-B and RTP payloads carry one NALU per item, SPS/PPS/IDR are
+    sent one by one, only the first of them starts a new GOP. */
+    last_nalu_is_key: bool,
+    /* a video frame whose key frames can be recognized was received, the
+    queue only waits for a GOP start after an overflow if it is set */
+    has_gop_video: bool,
+}
+
+pub trait QueueItem {
+    fn classify(&self, state: &mut ClassifyState) -> ItemKind;
+}
+
+const NALU_TYPE_H264_IDR: u8 = 5;
+const NALU_TYPE_H264_SPS: u8 = 7;
+const NALU_TYPE_H264_PPS: u8 = 8;
+
+fn is_h264_key_nalu(nalu_type: u8) -> bool {
+    matches!(
+        nalu_type,
+        NALU_TYPE_H264_IDR | NALU_TYPE_H264_SPS | NALU_TYPE_H264_PPS
+    )
+}
+
+fn is_h265_key_nalu(nalu_type: u8) -> bool {
+    /* IRAP pictures(16-21) and VPS/SPS/PPS(32-34) */
+    (16..=21).contains(&nalu_type) || (32..=34).contains(&nalu_type)
+}
+
+fn annexb_nalu_header(data: &[u8]) -> Option<u8> {
+    if data.starts_with(&[0x00, 0x00, 0x00, 0x01]) {
+        data.get(4).copied()
+    } else if data.starts_with(&[0x00, 0x00, 0x01]) {
+        data.get(3).copied()
+    } else {
+        None
+    }
+}
+
+impl ClassifyState {
+    fn nalu_kind(&mut self, is_key: bool) -> ItemKind {
+        self.has_gop_video = true;
+        let gop_start = is_key && !self.last_nalu_is_key;
+        self.last_nalu_is_key = is_key;
+        ItemKind::Video { gop_start }
+    }
+}
+
+fn classify_flv_video(data: &[u8]) -> ItemKind {
+    let Some(first) = data.first() else {
+        return ItemKind::Config;
+    };
+
+    /* Enhanced RTMP: IsExHeader(1 bit) | FrameType(3 bits) | PacketType(4 bits) */
+    if first & 0x80 != 0 {
+        let frame_type = (first >> 4) & 0x07;
+        let packet_type = first & 0x0F;
+        //PacketTypeSequenceStart
+        if packet_type == 0 {
+            return ItemKind::Config;
+        }
+        return ItemKind::Video {
+            gop_start: frame_type == 1,
+        };
+    }
+
+    let frame_type = first >> 4;
+    let codec_id = first & 0x0F;
+    /* AVC/HEVC sequence header */
+    if (codec_id == 7 || codec_id == 12) && data.get(1) == Some(&0) {
+        return ItemKind::Config;
+    }
+    ItemKind::Video {
+        gop_start: frame_type == 1,
+    }
+}
+
+impl QueueItem for FrameData {
+    fn classify(&self, state: &mut ClassifyState) -> ItemKind {
+        match self {
+            FrameData::MediaInfo { media_info } => {
+                state.vcodec = Some(media_info.vcodec.clone());
+                ItemKind::Config
+            }
+            FrameData::MetaData { .. } => ItemKind::Config,
+            FrameData::Audio { data, .. } => {
+                /* AAC sequence header of a FLV audio tag */
+                if data.len() > 1 && data[0] >> 4 == 10 && data[1] == 0 {
+                    ItemKind::Config
+                } else {
+                    ItemKind::Audio
+                }
+            }
+            FrameData::Video { data, .. } => match annexb_nalu_header(data) {
+                Some(header) => {
+                    let is_key = match state.vcodec {
+                        Some(VideoCodecType::H265) => is_h265_key_nalu((header >> 1) & 0x3F),
+                        _ => is_h264_key_nalu(header & 0x1F),
+                    };
+                    state.nalu_kind(is_key)
+                }
+                None => {
+                    let kind = classify_flv_video(data);
+                    if kind != ItemKind::Config {
+                        state.has_gop_video = true;
+                    }
+                    kind
+                }
+            },
+        }
+    }
+}
+
+impl QueueItem for PacketData {
+    fn classify(&self, _state: &mut ClassifyState) -> ItemKind {
+        match self {
+            PacketData::Audio { .. } => ItemKind::Audio,
+            /* the codec of the RTP packets is unknown here, so the key frames cannot
+            be recognized and the packets never start a GOP */
+            PacketData::Video { .. } => ItemKind::Video { gop_start: false },
+        }
+    }
+}
+
+pub enum SendError<T> {
+    /* the receiver is dropped or the queue is closed */
+    Closed(T),
+    /* the queue is full and the policy is Disconnect */
+    Overflow(T),
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SendError::Closed(_) => write!(f, "queue closed"),
+            SendError::Overflow(_) => write!(f, "queue overflow, the subscriber is disconnected"),
+        }
+    }
+}
+
+struct Entry<T> {
+    item: T,
+    kind: ItemKind,
+}
+
+struct QueueState<T> {
+    entries: VecDeque<Entry<T>>,
+    classify_state: ClassifyState,
+    /* a frame was dropped, wait for the next GOP to keep the stream decodable */
+    waiting_for_gop: bool,
+    closed: bool,
+    sender_count: usize,
+}
+
+struct Shared<T> {
+    state: Mutex<QueueState<T>>,
+    notify: Notify,
+    capacity: usize,
+    policy: BackpressurePolicy,
+}
+
+impl<T> QueueState<T> {
+    /* after an overflow, only a GOP start keeps the video decodable. Audio only
+    streams and the video which cannot be classified resume with the next item. */
+    fn can_resume(&self, kind: ItemKind) -> bool {
+        !self.classify_state.has_gop_video || kind == (ItemKind::Video { gop_start: true })
+    }
+}
+
+impl<T> Shared<T> {
+    /* remove the oldest droppable GOP, it starts from the first non-config entry
+    and ends before the next GOP start, the config entries are kept. Returns the
+    dropped count. */
+    fn drop_oldest_gop(state: &mut QueueState<T>) -> usize {
+        let Some(start) = state
+            .entries
+            .iter()
+            .position(|entry| entry.kind != ItemKind::Config)
+        else {
+            return 0;
+        };
+        let next_gop_start = state
+            .entries
+            .iter()
+            .skip(start + 1)
+            .position(|entry| entry.kind == ItemKind::Video { gop_start: true })
+            .map(|idx| idx + start + 1);
+
+        let end = next_gop_start.unwrap_or(state.entries.len());
+        let before = state.entries.len();
+        let mut idx = 0;
+        state.entries.retain(|entry| {
+            let keep = idx < start || idx >= end || entry.kind == ItemKind::Config;
+            idx += 1;
+            keep
+        });
+        /* no complete GOP left in the queue, the next frame must be a key frame */
+        if next_gop_start.is_none() {
+            state.waiting_for_gop = true;
+        }
+        before - state.entries.len()
+    }
+}
+
+/* A FIFO queue between the stream hub and one subscriber. When it is full, the
+configured BackpressurePolicy is applied instead of buffering without limit. */
+pub struct DataQueueSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub struct DataQueueReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+pub fn bounded<T: QueueItem>(
+    capacity: usize,
+    policy: BackpressurePolicy,
+) -> (DataQueueSender<T>, DataQueueReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(QueueState {
+            entries: VecDeque::new(),
+            classify_state: ClassifyState::default(),
+            waiting_for_gop: false,
+            closed: false,
+            sender_count: 1,
+        }),
+        notify: Notify::new(),
+        capacity: capacity.max(1),
+        policy,
+    });
+
+    (
+        DataQueueSender {
+            shared: shared.clone(),
+        },
+        DataQueueReceiver { shared },
+    )
+}
+
+/* Used between publishers and the stream hub, the hub consumes the data immediately. */
+pub fn unbounded<T: QueueItem>() -> (DataQueueSender<T>, DataQueueReceiver<T>) {
+    bounded(usize::MAX, BackpressurePolicy::DropUntilKeyFrame)
+}
+
+impl<T: QueueItem> DataQueueSender<T> {
+    /* Returns the number of frames dropped by the backpressure policy. */
+    pub fn send(&self, item: T) -> Result<usize, SendError<T>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closed {
+            return Err(SendError::Closed(item));
+        }
+
+        let kind = item.classify(&mut state.classify_state);
+        let mut dropped = 0;
+
+        if kind != ItemKind::Config {
+            if state.waiting_for_gop {
+                if state.can_resume(kind) && state.entries.len() < self.shared.capacity {
+                    state.waiting_for_gop = false;
+                } else {
+                    return Ok(1);
+                }
+            }
+
+            if state.entries.len() >= self.shared.capacity {
+                match self.shared.policy {
+                    BackpressurePolicy::DropUntilKeyFrame => {
+                        state.waiting_for_gop = true;
+                        return Ok(1);
+                    }
+                    BackpressurePolicy::DropOldestGop => {
+                        dropped = Shared::drop_oldest_gop(&mut state);
+                        /* the queue is filled with the config entries, drop the
+                        incoming frame instead */
+                        if state.entries.len() >= self.shared.capacity {
+                            state.waiting_for_gop = true;
+                            return Ok(dropped + 1);
+                        }
+                        if state.waiting_for_gop {
+                            if state.can_resume(kind) {
+                                state.waiting_for_gop = false;
+                            } else {
+                                return Ok(dropped + 1);
+                            }
+                        }
+                    }
+                    BackpressurePolicy::Disconnect => {
+                        state.closed = true;
+                        drop(state);
+                        self.shared.notify.notify_one();
+                        return Err(SendError::Overflow(item));
+                    }
+                }
+            }
+        }
+
+        state.entries.push_back(Entry { item, kind });
+        drop(state);
+        self.shared.notify.notify_one();
+
+        Ok(dropped)
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.state.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().unwrap().closed
+    }
+}
+
+impl<T> Clone for DataQueueSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().sender_count += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for DataQueueSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.sender_count -= 1;
+        if state.sender_count == 0 {
+            drop(state);
+            self.shared.notify.notify_one();
+        }
+    }
+}
+
+impl<T> fmt::Debug for DataQueueSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DataQueueSender")
+            .field("capacity", &self.shared.capacity)
+            .field("policy", &self.shared.policy)
+            .finish()
+    }
+}
+
+impl<T> DataQueueReceiver<T> {
+    /* Returns None if the queue is closed or all the senders are dropped. */
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if state.closed {
+                    return None;
+                }
+                if let Some(entry) = state.entries.pop_front() {
+                    return Some(entry.item);
+                }
+                if state.sender_count == 0 {
+                    return None;
+                }
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+    }
+}
+
+impl<T> Drop for DataQueueReceiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        state.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bounded, BackpressurePolicy, SendError};
+    use crate::define::{FrameData, PacketData};
+    use bytes::BytesMut;
+
+    fn video(key: bool, timestamp: u32) -> FrameData {
+        let frame_type = if key { 0x17 } else { 0x27 };
+        FrameData::Video {
+            timestamp,
+            data: BytesMut::from(&[frame_type, 0x01, 0x00, 0x00, 0x00][..]),
+        }
+    }
+
+    fn audio(timestamp: u32) -> FrameData {
+        FrameData::Audio {
+            timestamp,
+            data: BytesMut::from(&[0xAF, 0x01, 0x00][..]),
+        }
+    }
+
+    fn timestamp_of(data: FrameData) -> u32 {
+        match data {
+            FrameData::Video { timestamp, .. } | FrameData::Audio { timestamp, .. } => timestamp,
+            _ => 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_until_key_frame() {
+        let (sender, mut receiver) = bounded(2, BackpressurePolicy::DropUntilKeyFrame);
+        assert_eq!(sender.send(video(true, 0)).unwrap(), 0);
+        assert_eq!(sender.send(video(false, 1)).unwrap(), 0);
+        //full
+        assert_eq!(sender.send(video(false, 2)).unwrap(), 1);
+
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 0);
+        //there is room now, but the GOP is broken
+        assert_eq!(sender.send(video(false, 3)).unwrap(), 1);
+        assert_eq!(sender.send(video(true, 4)).unwrap(), 0);
+
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 1);
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 4);
+    }
+
+    #[tokio::test]
+    async fn test_audio_only_resume() {
+        let (sender, mut receiver) = bounded(1, BackpressurePolicy::DropUntilKeyFrame);
+        sender.send(audio(0)).unwrap();
+        assert_eq!(sender.send(audio(1)).unwrap(), 1);
+
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 0);
+        //there is no video track to wait for
+        assert_eq!(sender.send(audio(2)).unwrap(), 0);
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 2);
+    }
+
+    #[tokio::test]
+    async fn test_packet_resume() {
+        let packet = |timestamp| PacketData::Video {
+            timestamp,
+            //a H.265 RTP packet, its key frames are not recognized
+            data: BytesMut::from(&[0x80, 0x60, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x26, 0x01][..]),
+        };
+        let (sender, mut receiver) = bounded(1, BackpressurePolicy::DropUntilKeyFrame);
+        sender.send(packet(0)).unwrap();
+        assert_eq!(sender.send(packet(1)).unwrap(), 1);
+
+        receiver.recv().await.unwrap();
+        assert_eq!(sender.send(packet(2)).unwrap(), 0);
+        assert!(matches!(
+            receiver.recv().await,
+            Some(PacketData::Video { timestamp: 2, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_gop() {
+        let (sender, mut receiver) = bounded(4, BackpressurePolicy::DropOldestGop);
+        sender.send(video(true, 0)).unwrap();
+        sender.send(video(false, 1)).unwrap();
+        sender.send(video(true, 2)).unwrap();
+        sender.send(video(false, 3)).unwrap();
+        //drop frames 0 and 1
+        assert_eq!(sender.send(video(false, 4)).unwrap(), 2);
+
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 2);
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 3);
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 4);
+    }
+
+    fn video_seq_header() -> FrameData {
+        FrameData::Video {
+            timestamp: 0,
+            data: BytesMut::from(&[0x17, 0x00, 0x00, 0x00, 0x00][..]),
+        }
+    }
+
+    fn audio_seq_header() -> FrameData {
+        FrameData::Audio {
+            timestamp: 0,
+            data: BytesMut::from(&[0xAF, 0x00, 0x12, 0x10][..]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_gop_with_seq_headers() {
+        let (sender, mut receiver) = bounded(5, BackpressurePolicy::DropOldestGop);
+        sender.send(audio_seq_header()).unwrap();
+        sender.send(video_seq_header()).unwrap();
+        sender.send(video(true, 0)).unwrap();
+        sender.send(video(false, 1)).unwrap();
+        sender.send(video(true, 2)).unwrap();
+        //drop frames 0 and 1, the sequence headers are kept
+        assert_eq!(sender.send(video(false, 3)).unwrap(), 2);
+        assert_eq!(sender.send(video(false, 4)).unwrap(), 0);
+        assert_eq!(sender.len(), 5);
+        //no complete GOP is left, drop frames 2, 3 and 4 and the incoming frame
+        assert_eq!(sender.send(video(false, 5)).unwrap(), 4);
+        assert_eq!(sender.len(), 2);
+        assert_eq!(sender.send(video(true, 6)).unwrap(), 0);
+
+        assert!(matches!(
+            receiver.recv().await,
+            Some(FrameData::Audio { .. })
+        ));
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 0);
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 6);
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_gop_only_config() {
+        let (sender, mut receiver) = bounded(2, BackpressurePolicy::DropOldestGop);
+        sender.send(audio_seq_header()).unwrap();
+        sender.send(video_seq_header()).unwrap();
+        //nothing can be dropped, the incoming frame is dropped
+        assert_eq!(sender.send(video(true, 0)).unwrap(), 1);
+        assert_eq!(sender.len(), 2);
+
+        receiver.recv().await.unwrap();
+        assert_eq!(sender.send(video(true, 1)).unwrap(), 0);
+        assert_eq!(sender.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_disconnect() {
+        let (sender, mut receiver) = bounded(1, BackpressurePolicy::Disconnect);
+        sender.send(video(true, 0)).unwrap();
+        assert!(matches!(
+            sender.send(video(false, 1)),
+            Err(SendError::Overflow(_))
+        ));
+        assert!(sender.is_closed());
+        assert!(receiver.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_senders_dropped() {
+        let (sender, mut receiver) = bounded(8, BackpressurePolicy::DropUntilKeyFrame);
+        let sender_clone = sender.clone();
+        sender.send(video(true, 0)).unwrap();
+        drop(sender);
+        drop(sender_clone);
+        assert_eq!(timestamp_of(receiver.recv().await.unwrap()), 0);
+        assert!(receiver.recv().await.is_none());
+    }
+}
@@ -0,0 +1,357 @@
+use {
+    super::StatisticsStream,
+    crate::{
+        define::{PublishType, SubscribeType},
+        stream::StreamIdentifier,
+        utils::Uuid,
+    },
+    std::{
+        collections::{BTreeMap, HashMap},
+        fmt::Write,
+        sync::Mutex,
+    },
+};
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureKind {
+    Handshake,
+    Auth,
+    AccessDenied,
+}
+
+//the failures of all the sessions in the process, keyed by kind and protocol
+static FAILURES: Mutex<BTreeMap<(FailureKind, &'static str), u64>> = Mutex::new(BTreeMap::new());
+
+pub fn inc_failure(kind: FailureKind, protocol: &'static str) {
+    *FAILURES
+        .lock()
+        .unwrap()
+        .entry((kind, protocol))
+        .or_default() += 1;
+}
+
+pub fn inc_handshake_failure(protocol: &'static str) {
+    inc_failure(FailureKind::Handshake, protocol);
+}
+
+pub fn inc_auth_failure(protocol: &'static str) {
+    inc_failure(FailureKind::Auth, protocol);
+}
+
+//the connections or sessions rejected by the ip access control
+pub fn inc_acl_rejection(protocol: &'static str) {
+    inc_failure(FailureKind::AccessDenied, protocol);
+}
+
+//the current and total counts of the publish/subscribe sessions, labelled by type
+#[derive(Default)]
+pub struct SessionMetrics {
+    publishers: HashMap<StreamIdentifier, String>,
+    subscribers: HashMap<Uuid, String>,
+    publish_totals: BTreeMap<String, u64>,
+    subscribe_totals: BTreeMap<String, u64>,
+}
+
+impl SessionMetrics {
+    pub fn on_publish(&mut self, identifier: StreamIdentifier, pub_type: &PublishType) {
+        let label = format!("{pub_type:?}");
+        *self.publish_totals.entry(label.clone()).or_default() += 1;
+        self.publishers.insert(identifier, label);
+    }
+
+    pub fn on_unpublish(&mut self, identifier: &StreamIdentifier) {
+        self.publishers.remove(identifier);
+    }
+
+    pub fn on_subscribe(&mut self, id: Uuid, sub_type: &SubscribeType) {
+        let label = format!("{sub_type:?}");
+        *self.subscribe_totals.entry(label.clone()).or_default() += 1;
+        self.subscribers.insert(id, label);
+    }
+
+    pub fn on_unsubscribe(&mut self, id: &Uuid) {
+        self.subscribers.remove(id);
+    }
+}
+
+//the protocol, app name and stream name labels of a stream
+fn stream_labels(identifier: &StreamIdentifier) -> String {
+    let (protocol, app_name, stream_name) = match identifier {
+        StreamIdentifier::Rtmp {
+            app_name,
+            stream_name,
+        } => ("rtmp", app_name.as_str(), stream_name.as_str()),
+        StreamIdentifier::WebRTC {
+            app_name,
+            stream_name,
+        } => ("webrtc", app_name.as_str(), stream_name.as_str()),
+        StreamIdentifier::Rtsp { stream_path } => {
+            let path = stream_path.trim_start_matches('/');
+            let (app_name, stream_name) = path.split_once('/').unwrap_or(("", path));
+            ("rtsp", app_name, stream_name)
+        }
+        StreamIdentifier::Unkonwn => ("unknown", "", ""),
+    };
+
+    format!(
+        "protocol=\"{}\",app=\"{}\",stream=\"{}\"",
+        protocol,
+        escape(app_name),
+        escape(stream_name)
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn counts_by_type<'a>(values: impl Iterator<Item = &'a String>) -> BTreeMap<&'a str, u64> {
+    let mut counts = BTreeMap::new();
+    for value in values {
+        *counts.entry(value.as_str()).or_default() += 1;
+    }
+    counts
+}
+
+//writes a metric family, each sample is (labels, value)
+fn write_family(
+    out: &mut String,
+    name: &str,
+    metric_type: &str,
+    help: &str,
+    samples: impl IntoIterator<Item = (String, u64)>,
+) {
+    let suffix = if metric_type == "counter" {
+        "_total"
+    } else {
+        ""
+    };
+
+    let _ = writeln!(out, "# TYPE {name} {metric_type}");
+    let _ = writeln!(out, "# HELP {name} {help}");
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{name}{suffix}{{{labels}}} {value}");
+    }
+}
+
+/*Renders the metrics in the OpenMetrics text format, the per-stream metrics are
+labelled by protocol, app and stream.*/
+pub fn render(streams: &[StatisticsStream], sessions: &SessionMetrics) -> String {
+    let mut out = String::new();
+
+    type StreamValue = fn(&StatisticsStream) -> u64;
+    let stream_families: [(&str, &str, &str, StreamValue); 9] = [
+        (
+            "xiu_stream_subscribers",
+            "gauge",
+            "The count of the subscribers of the stream.",
+            |s| s.subscriber_count as u64,
+        ),
+        (
+            "xiu_stream_received_bytes",
+            "counter",
+            "The audio and video bytes received from the publisher.",
+            |s| s.total_recv_bytes as u64,
+        ),
+        (
+            "xiu_stream_sent_bytes",
+            "counter",
+            "The audio and video bytes sent to all the subscribers.",
+            |s| s.total_send_bytes as u64,
+        ),
+        (
+            "xiu_stream_recv_bitrate_kbps",
+            "gauge",
+            "The bitrate at which the stream is received.",
+            |s| s.publisher.recv_bitrate as u64,
+        ),
+        (
+            "xiu_stream_video_bitrate_kbps",
+            "gauge",
+            "The video bitrate of the stream.",
+            |s| s.publisher.video.bitrate as u64,
+        ),
+        (
+            "xiu_stream_video_frame_rate",
+            "gauge",
+            "The video frame rate of the stream.",
+            |s| s.publisher.video.frame_rate as u64,
+        ),
+        (
+            "xiu_stream_video_gop",
+            "gauge",
+            "The count of the video frames in a GOP.",
+            |s| s.publisher.video.gop as u64,
+        ),
+        (
+            "xiu_stream_audio_bitrate_kbps",
+            "gauge",
+            "The audio bitrate of the stream.",
+            |s| s.publisher.audio.bitrate as u64,
+        ),
+        (
+            "xiu_stream_start_time_seconds",
+            "gauge",
+            "The unix time when the stream was published.",
+            |s| s.publisher.start_time.timestamp().max(0) as u64,
+        ),
+    ];
+
+    for (name, metric_type, help, value) in stream_families {
+        write_family(
+            &mut out,
+            name,
+            metric_type,
+            help,
+            streams
+                .iter()
+                .map(|stream| (stream_labels(stream.publisher.identifier()), value(stream))),
+        );
+    }
+
+    let type_samples = |counts: BTreeMap<&str, u64>| {
+        counts
+            .into_iter()
+            .map(|(label, count)| (format!("type=\"{label}\""), count))
+            .collect::<Vec<_>>()
+    };
+    write_family(
+        &mut out,
+        "xiu_publish_sessions",
+        "gauge",
+        "The count of the current publish sessions.",
+        type_samples(counts_by_type(sessions.publishers.values())),
+    );
+    write_family(
+        &mut out,
+        "xiu_publish_sessions_started",
+        "counter",
+        "The count of the publish sessions started.",
+        type_samples(
+            sessions
+                .publish_totals
+                .iter()
+                .map(|(label, count)| (label.as_str(), *count))
+                .collect(),
+        ),
+    );
+    write_family(
+        &mut out,
+        "xiu_subscribe_sessions",
+        "gauge",
+        "The count of the current subscribe sessions.",
+        type_samples(counts_by_type(sessions.subscribers.values())),
+    );
+    write_family(
+        &mut out,
+        "xiu_subscribe_sessions_started",
+        "counter",
+        "The count of the subscribe sessions started.",
+        type_samples(
+            sessions
+                .subscribe_totals
+                .iter()
+                .map(|(label, count)| (label.as_str(), *count))
+                .collect(),
+        ),
+    );
+
+    let failures = FAILURES.lock().unwrap().clone();
+    for (kind, name, help) in [
+        (
+            FailureKind::Handshake,
+            "xiu_handshake_failures",
+            "The count of the failed handshakes.",
+        ),
+        (
+            FailureKind::Auth,
+            "xiu_auth_failures",
+            "The count of the failed authentications.",
+        ),
+        (
+            FailureKind::AccessDenied,
+            "xiu_acl_rejections",
+            "The count of the connections rejected by the access control.",
+        ),
+    ] {
+        write_family(
+            &mut out,
+            name,
+            "counter",
+            help,
+            failures
+                .iter()
+                .filter(|((failure_kind, _), _)| *failure_kind == kind)
+                .map(|((_, protocol), count)| (format!("protocol=\"{protocol}\""), *count)),
+        );
+    }
+
+    out.push_str("# EOF\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{render, stream_labels, SessionMetrics},
+        crate::{
+            define::{PublishType, SubscribeType},
+            statistics::StatisticsStream,
+            stream::StreamIdentifier,
+            utils::{RandomDigitCount, Uuid},
+        },
+    };
+
+    #[test]
+    fn test_stream_labels() {
+        let rtsp = StreamIdentifier::Rtsp {
+            stream_path: String::from("/live/test"),
+        };
+        assert_eq!(
+            stream_labels(&rtsp),
+            "protocol=\"rtsp\",app=\"live\",stream=\"test\""
+        );
+
+        let rtmp = StreamIdentifier::Rtmp {
+            app_name: String::from("live"),
+            stream_name: String::from("a\"b"),
+        };
+        assert_eq!(
+            stream_labels(&rtmp),
+            "protocol=\"rtmp\",app=\"live\",stream=\"a\\\"b\""
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let identifier = StreamIdentifier::Rtmp {
+            app_name: String::from("live"),
+            stream_name: String::from("test"),
+        };
+        let mut stream = StatisticsStream::new(identifier.clone());
+        stream.subscriber_count = 2;
+        stream.total_recv_bytes = 1024;
+
+        let mut sessions = SessionMetrics::default();
+        sessions.on_publish(identifier.clone(), &PublishType::RtmpPush);
+        let id = Uuid::new(RandomDigitCount::Four);
+        sessions.on_subscribe(id, &SubscribeType::RtmpPull);
+        sessions.on_unsubscribe(&id);
+        sessions.on_unpublish(&identifier);
+
+        let text = render(&[stream], &sessions);
+        let labels = "{protocol=\"rtmp\",app=\"live\",stream=\"test\"}";
+        assert!(text.contains(&format!("xiu_stream_subscribers{labels} 2\n")));
+        assert!(text.contains(&format!("xiu_stream_received_bytes_total{labels} 1024\n")));
+        assert!(text.contains("# TYPE xiu_stream_received_bytes counter\n"));
+        assert!(!text.contains("xiu_publish_sessions{"));
+        assert!(text.contains("xiu_publish_sessions_started_total{type=\"RtmpPush\"} 1\n"));
+        assert!(text.contains("xiu_subscribe_sessions_started_total{type=\"RtmpPull\"} 1\n"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
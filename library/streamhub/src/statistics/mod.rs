@@ -1,3 +1,5 @@
+pub mod metrics;
+
 use {
     super::stream::StreamIdentifier,
     crate::{define::SubscribeType, utils::Uuid},
@@ -80,6 +82,10 @@ impl StatisticPublisher {
             ..Default::default()
         }
     }
+
+    pub fn identifier(&self) -> &StreamIdentifier {
+        &self.identifier
+    }
 }
 #[derive(Debug, Clone, Serialize)]
 pub struct StatisticSubscriber {
@@ -95,6 +101,8 @@ pub struct StatisticSubscriber {
     pub send_bitrate: usize,
     #[serde(rename = "total_send_bytes(kbits/s)")]
     pub total_send_bytes: usize,
+    /*frames dropped because the subscriber cannot keep up with the stream*/
+    pub dropped_frames: usize,
 }
 
 impl StatisticsStream {
@@ -0,0 +1,4 @@
+//the target duration(seconds) of a segment
+pub const DASH_SEGMENT_DURATION: i64 = 4;
+//how many segments are listed in the dynamic mpd
+pub const DASH_LIVE_SEGMENT_COUNT: usize = 6;
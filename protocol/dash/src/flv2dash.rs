@@ -0,0 +1,234 @@
+use {
+    super::{
+        define::DASH_LIVE_SEGMENT_COUNT,
+        errors::MediaError,
+        mpd::{ContentType, Mpd, Representation},
+    },
+    bytes::BytesMut,
+    hls::storage::SharedSegmentStorage,
+    xflv::{
+        define::{frame_type, AvcCodecId, FlvData},
+        demuxer::{FlvMp4Demuxer, FlvMp4DemuxerData},
+    },
+    xmp4::{define::Mp4CodecType, fmp4::Fmp4Muxer},
+};
+
+/*Every representation has its own fmp4 muxer, the audio and the video
+segments are cut at the same time(on a video key frame if there is video).*/
+struct DashTrack {
+    muxer: Fmp4Muxer,
+    track_id: u32,
+    //the dts(ms) of the first sample of the current segment
+    segment_start_dts: Option<i64>,
+}
+
+impl DashTrack {
+    fn write(
+        &mut self,
+        pts: i64,
+        dts: i64,
+        is_sync: bool,
+        data: BytesMut,
+    ) -> Result<(), MediaError> {
+        if self.segment_start_dts.is_none() {
+            self.segment_start_dts = Some(dts);
+        }
+        self.muxer.write(self.track_id, pts, dts, is_sync, data)?;
+        Ok(())
+    }
+}
+
+pub struct Flv2DashRemuxer {
+    mp4_demuxer: FlvMp4Demuxer,
+
+    video_track: Option<DashTrack>,
+    audio_track: Option<DashTrack>,
+
+    //the target segment duration(ms)
+    duration: i64,
+    last_segment_dts: Option<i64>,
+    last_dts: i64,
+
+    mpd_handler: Mpd,
+}
+
+impl Flv2DashRemuxer {
+    //duration is in seconds
+    pub fn new(
+        duration: i64,
+        app_name: String,
+        stream_name: String,
+        storage: SharedSegmentStorage,
+    ) -> Self {
+        Self {
+            mp4_demuxer: FlvMp4Demuxer::new(),
+
+            video_track: None,
+            audio_track: None,
+
+            duration: duration * 1000,
+            last_segment_dts: None,
+            last_dts: 0,
+
+            mpd_handler: Mpd::new(
+                duration * 1000,
+                DASH_LIVE_SEGMENT_COUNT,
+                app_name,
+                stream_name,
+                storage,
+            ),
+        }
+    }
+
+    fn add_track(
+        &mut self,
+        content_type: ContentType,
+        muxer: Fmp4Muxer,
+        track_id: u32,
+    ) -> Result<(), MediaError> {
+        let representation = Representation::new(content_type, &muxer.tracks()[0]);
+        self.mpd_handler
+            .add_representation(representation, muxer.gen_init_segment()?)?;
+
+        let track = Some(DashTrack {
+            muxer,
+            track_id,
+            segment_start_dts: None,
+        });
+        match content_type {
+            ContentType::Video => self.video_track = track,
+            ContentType::Audio => self.audio_track = track,
+        }
+        Ok(())
+    }
+
+    pub fn process_flv_data(&mut self, data: FlvData) -> Result<(), MediaError> {
+        match self.mp4_demuxer.demux(data)? {
+            FlvMp4DemuxerData::VideoConfig {
+                codec_id,
+                decoder_configuration_record,
+                width,
+                height,
+            } => {
+                //the codec configuration cannot be changed after the init segment is written
+                if self.video_track.is_none() {
+                    let codec = if codec_id == AvcCodecId::HEVC as u8 {
+                        Mp4CodecType::H265
+                    } else {
+                        Mp4CodecType::H264
+                    };
+                    let mut muxer = Fmp4Muxer::new();
+                    let track_id = muxer.add_video_track(
+                        codec,
+                        decoder_configuration_record,
+                        width,
+                        height,
+                    )?;
+                    self.add_track(ContentType::Video, muxer, track_id)?;
+                }
+            }
+            FlvMp4DemuxerData::AudioConfig {
+                audio_specific_config,
+                sampling_frequency,
+                channels,
+            } => {
+                if self.audio_track.is_none() {
+                    let mut muxer = Fmp4Muxer::new();
+                    let track_id = muxer.add_audio_track(
+                        audio_specific_config,
+                        sampling_frequency,
+                        channels,
+                    )?;
+                    self.add_track(ContentType::Audio, muxer, track_id)?;
+                }
+            }
+            FlvMp4DemuxerData::Video { data } => {
+                if self.video_track.is_none() {
+                    return Ok(());
+                }
+                let is_key_frame = data.frame_type == frame_type::KEY_FRAME;
+                if is_key_frame {
+                    self.try_cut_segment(data.dts)?;
+                }
+                if self.last_segment_dts.is_none() {
+                    //a segment should begin with a key frame
+                    if !is_key_frame {
+                        return Ok(());
+                    }
+                    self.start(data.dts);
+                }
+
+                self.last_dts = data.dts;
+                if let Some(video_track) = &mut self.video_track {
+                    video_track.write(data.pts, data.dts, is_key_frame, data.data)?;
+                }
+            }
+            FlvMp4DemuxerData::Audio { data } => {
+                if !data.has_data || self.audio_track.is_none() {
+                    return Ok(());
+                }
+                //the segments are cut by the video key frames if there is video
+                if self.video_track.is_none() {
+                    self.try_cut_segment(data.dts)?;
+                    if self.last_segment_dts.is_none() {
+                        self.start(data.dts);
+                    }
+                } else if self.last_segment_dts.is_none() {
+                    return Ok(());
+                }
+
+                self.last_dts = data.dts;
+                if let Some(audio_track) = &mut self.audio_track {
+                    audio_track.write(data.pts, data.dts, true, data.data)?;
+                }
+            }
+            FlvMp4DemuxerData::None => {}
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self, dts: i64) {
+        self.last_segment_dts = Some(dts);
+        self.mpd_handler.set_availability_start_time(dts);
+    }
+
+    fn try_cut_segment(&mut self, dts: i64) -> Result<(), MediaError> {
+        if let Some(last_segment_dts) = self.last_segment_dts {
+            if dts - last_segment_dts >= self.duration {
+                self.cut_segment(dts)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cut_segment(&mut self, end_dts: i64) -> Result<(), MediaError> {
+        for (content_type, track) in [
+            (ContentType::Video, &mut self.video_track),
+            (ContentType::Audio, &mut self.audio_track),
+        ] {
+            if let Some(track) = track {
+                if let Some(start_dts) = track.segment_start_dts.take() {
+                    let data = track.muxer.get_data(end_dts)?;
+                    self.mpd_handler
+                        .add_segment(content_type, start_dts, end_dts, data)?;
+                }
+            }
+        }
+        self.mpd_handler.refresh_mpd()?;
+        self.last_segment_dts = Some(end_dts);
+
+        Ok(())
+    }
+
+    pub fn flush_remaining_data(&mut self) -> Result<(), MediaError> {
+        if self.last_segment_dts.is_some() {
+            self.cut_segment(self.last_dts)?;
+        }
+        Ok(())
+    }
+
+    pub fn clear_files(&mut self) -> Result<(), MediaError> {
+        self.mpd_handler.clear()
+    }
+}
@@ -0,0 +1,7 @@
+pub mod define;
+pub mod errors;
+pub mod flv2dash;
+pub mod flv_data_receiver;
+pub mod mpd;
+pub mod remuxer;
+pub mod server;
@@ -0,0 +1,372 @@
+use {
+    super::errors::MediaError,
+    bytes::BytesMut,
+    chrono::{DateTime, Duration, SecondsFormat, Utc},
+    hls::storage::SharedSegmentStorage,
+    std::collections::VecDeque,
+    xmp4::fmp4::Track,
+};
+
+pub struct Segment {
+    /*the start time and the duration in the timescale of the representation*/
+    pub time: i64,
+    pub duration: i64,
+    pub size: usize,
+    path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    Video,
+    Audio,
+}
+
+impl ContentType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ContentType::Video => "video",
+            ContentType::Audio => "audio",
+        }
+    }
+}
+
+/*A representation holds one track, the segment files are named
+{content type}_{segment start time}.m4s(SegmentTemplate with $Time$).*/
+pub struct Representation {
+    pub content_type: ContentType,
+    codecs: String,
+    timescale: u32,
+    width: u32,
+    height: u32,
+    channels: u8,
+    segments: VecDeque<Segment>,
+}
+
+impl Representation {
+    pub fn new(content_type: ContentType, track: &Track) -> Self {
+        Self {
+            content_type,
+            codecs: track.codec_string(),
+            timescale: track.timescale,
+            width: track.width,
+            height: track.height,
+            channels: track.channels,
+            segments: VecDeque::new(),
+        }
+    }
+
+    fn to_timescale(&self, time_ms: i64) -> i64 {
+        time_ms * self.timescale as i64 / 1000
+    }
+
+    fn init_segment_name(&self) -> String {
+        format!("init_{}.mp4", self.content_type.as_str())
+    }
+
+    fn segment_name(&self, time: i64) -> String {
+        format!("{}_{}.m4s", self.content_type.as_str(), time)
+    }
+
+    //bits per second of the listed segments
+    fn bandwidth(&self) -> u64 {
+        let size: usize = self.segments.iter().map(|segment| segment.size).sum();
+        let duration: i64 = self.segments.iter().map(|segment| segment.duration).sum();
+        if duration <= 0 {
+            return 0;
+        }
+        size as u64 * 8 * self.timescale as u64 / duration as u64
+    }
+
+    fn generate_adaptation_set(&self, id: usize) -> String {
+        let mut content = format!(
+            "    <AdaptationSet id=\"{id}\" contentType=\"{content_type}\" mimeType=\"{content_type}/mp4\" segmentAlignment=\"true\" startWithSAP=\"1\">\n",
+            content_type = self.content_type.as_str()
+        );
+
+        match self.content_type {
+            ContentType::Video => {
+                content += format!(
+                    "      <Representation id=\"{}\" codecs=\"{}\" bandwidth=\"{}\" width=\"{}\" height=\"{}\">\n",
+                    self.content_type.as_str(),
+                    self.codecs,
+                    self.bandwidth(),
+                    self.width,
+                    self.height
+                )
+                .as_str();
+            }
+            ContentType::Audio => {
+                content += format!(
+                    "      <Representation id=\"{}\" codecs=\"{}\" bandwidth=\"{}\" audioSamplingRate=\"{}\">\n",
+                    self.content_type.as_str(),
+                    self.codecs,
+                    self.bandwidth(),
+                    self.timescale
+                )
+                .as_str();
+                content += format!(
+                    "        <AudioChannelConfiguration schemeIdUri=\"urn:mpeg:dash:23003:3:audio_channel_configuration:2011\" value=\"{}\"/>\n",
+                    self.channels
+                )
+                .as_str();
+            }
+        }
+
+        content += format!(
+            "        <SegmentTemplate timescale=\"{}\" initialization=\"{}\" media=\"{}_$Time$.m4s\">\n",
+            self.timescale,
+            self.init_segment_name(),
+            self.content_type.as_str()
+        )
+        .as_str();
+        content += "          <SegmentTimeline>\n";
+        for segment in &self.segments {
+            content += format!(
+                "            <S t=\"{}\" d=\"{}\"/>\n",
+                segment.time, segment.duration
+            )
+            .as_str();
+        }
+        content += "          </SegmentTimeline>\n";
+        content += "        </SegmentTemplate>\n";
+        content += "      </Representation>\n";
+        content += "    </AdaptationSet>\n";
+
+        content
+    }
+}
+
+pub struct Mpd {
+    /*the target segment duration(ms)*/
+    duration: i64,
+    /*how many segments are listed for each representation*/
+    live_segment_count: usize,
+
+    //the files are written to the storage with the path: {app_name}/{stream_name}/{file_name}
+    folder: String,
+    mpd_name: String,
+    storage: SharedSegmentStorage,
+
+    /*the wall clock time of the media time 0*/
+    availability_start_time: Option<DateTime<Utc>>,
+    representations: Vec<Representation>,
+}
+
+impl Mpd {
+    pub fn new(
+        duration: i64,
+        live_segment_count: usize,
+        app_name: String,
+        stream_name: String,
+        storage: SharedSegmentStorage,
+    ) -> Self {
+        Self {
+            duration,
+            live_segment_count,
+            folder: format!("{app_name}/{stream_name}"),
+            mpd_name: format!("{stream_name}.mpd"),
+            storage,
+            availability_start_time: None,
+            representations: Vec::new(),
+        }
+    }
+
+    /*The media timeline starts from the flv timestamp 0, so the availability
+    start time is the wall clock time when the timestamp 0 would be received.*/
+    pub fn set_availability_start_time(&mut self, first_dts: i64) {
+        self.availability_start_time = Some(Utc::now() - Duration::milliseconds(first_dts));
+    }
+
+    pub fn add_representation(
+        &mut self,
+        representation: Representation,
+        init_segment: BytesMut,
+    ) -> Result<(), MediaError> {
+        let init_segment_path = format!("{}/{}", self.folder, representation.init_segment_name());
+        self.storage.write(&init_segment_path, init_segment)?;
+
+        self.representations.push(representation);
+        Ok(())
+    }
+
+    /*start and end are in milliseconds*/
+    pub fn add_segment(
+        &mut self,
+        content_type: ContentType,
+        start: i64,
+        end: i64,
+        data: BytesMut,
+    ) -> Result<(), MediaError> {
+        let live_segment_count = self.live_segment_count;
+        let representation = match self
+            .representations
+            .iter_mut()
+            .find(|representation| representation.content_type == content_type)
+        {
+            Some(representation) => representation,
+            None => return Ok(()),
+        };
+
+        if representation.segments.len() >= live_segment_count {
+            let segment = representation.segments.pop_front().unwrap();
+            self.storage.delete(&segment.path)?;
+        }
+
+        let time = representation.to_timescale(start);
+        let duration = representation.to_timescale(end) - time;
+        let path = format!("{}/{}", self.folder, representation.segment_name(time));
+
+        let size = data.len();
+        self.storage.write(&path, data)?;
+
+        representation.segments.push_back(Segment {
+            time,
+            duration,
+            size,
+            path,
+        });
+
+        Ok(())
+    }
+
+    fn format_duration(duration_ms: i64) -> String {
+        format!("PT{:.3}S", duration_ms as f64 / 1000.0)
+    }
+
+    pub fn generate_mpd(&self) -> String {
+        let availability_start_time = self.availability_start_time.unwrap_or_else(Utc::now);
+
+        let mut content = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        content += format!(
+            "<MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" profiles=\"urn:mpeg:dash:profile:isoff-live:2011\" type=\"dynamic\" availabilityStartTime=\"{}\" publishTime=\"{}\" minimumUpdatePeriod=\"{}\" minBufferTime=\"{}\" timeShiftBufferDepth=\"{}\" suggestedPresentationDelay=\"{}\">\n",
+            availability_start_time.to_rfc3339_opts(SecondsFormat::Millis, true),
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            Self::format_duration(self.duration),
+            Self::format_duration(self.duration),
+            Self::format_duration(self.duration * self.live_segment_count as i64),
+            Self::format_duration(self.duration * 2),
+        )
+        .as_str();
+        content += "  <Period id=\"0\" start=\"PT0S\">\n";
+        for (id, representation) in self.representations.iter().enumerate() {
+            content += representation.generate_adaptation_set(id).as_str();
+        }
+        content += "  </Period>\n";
+        content += "</MPD>\n";
+
+        content
+    }
+
+    pub fn refresh_mpd(&mut self) -> Result<String, MediaError> {
+        let mpd_content = self.generate_mpd();
+
+        let mpd_path = format!("{}/{}", self.folder, self.mpd_name);
+        self.storage
+            .write(&mpd_path, BytesMut::from(mpd_content.as_bytes()))?;
+
+        Ok(mpd_content)
+    }
+
+    pub fn clear(&mut self) -> Result<(), MediaError> {
+        for representation in &self.representations {
+            for segment in &representation.segments {
+                self.storage.delete(&segment.path)?;
+            }
+            let init_segment_path =
+                format!("{}/{}", self.folder, representation.init_segment_name());
+            self.storage.delete(&init_segment_path)?;
+        }
+
+        let mpd_path = format!("{}/{}", self.folder, self.mpd_name);
+        self.storage.delete(&mpd_path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{ContentType, Mpd, Representation},
+        bytes::BytesMut,
+        hls::storage::{MemoryStorage, SegmentStorage},
+        std::sync::Arc,
+        xmp4::{define::Mp4CodecType, fmp4::Fmp4Muxer},
+    };
+
+    #[test]
+    fn test_mpd() {
+        let mut muxer = Fmp4Muxer::new();
+        muxer
+            .add_video_track(
+                Mp4CodecType::H264,
+                BytesMut::from(&[0x01, 0x64, 0x00, 0x1f, 0xff][..]),
+                1280,
+                720,
+            )
+            .unwrap();
+        muxer
+            .add_audio_track(BytesMut::from(&[0x12, 0x10][..]), 48000, 2)
+            .unwrap();
+
+        let storage = Arc::new(MemoryStorage::new(1024 * 1024));
+        let mut mpd = Mpd::new(
+            4000,
+            2,
+            String::from("live"),
+            String::from("test"),
+            storage.clone(),
+        );
+        mpd.set_availability_start_time(0);
+        mpd.add_representation(
+            Representation::new(ContentType::Video, &muxer.tracks()[0]),
+            BytesMut::from(&b"init"[..]),
+        )
+        .unwrap();
+        mpd.add_representation(
+            Representation::new(ContentType::Audio, &muxer.tracks()[1]),
+            BytesMut::from(&b"init"[..]),
+        )
+        .unwrap();
+
+        for (start, end) in [(0, 4000), (4000, 8000), (8000, 12000)] {
+            mpd.add_segment(
+                ContentType::Video,
+                start,
+                end,
+                BytesMut::from(&[0; 500][..]),
+            )
+            .unwrap();
+            mpd.add_segment(ContentType::Audio, start, end, BytesMut::from(&[0; 50][..]))
+                .unwrap();
+        }
+
+        let content = mpd.refresh_mpd().unwrap();
+        assert!(content.contains("type=\"dynamic\""));
+        assert!(content.contains(
+            "<Representation id=\"video\" codecs=\"avc1.64001f\" bandwidth=\"1000\" width=\"1280\" height=\"720\">"
+        ));
+        assert!(content.contains(
+            "<SegmentTemplate timescale=\"90000\" initialization=\"init_video.mp4\" media=\"video_$Time$.m4s\">"
+        ));
+        //the oldest segment is removed from the timeline and deleted
+        assert!(!content.contains("<S t=\"0\" d=\"360000\"/>"));
+        assert!(content.contains("<S t=\"360000\" d=\"360000\"/>"));
+        assert!(content.contains("<S t=\"720000\" d=\"360000\"/>"));
+        assert!(!storage.exists("live/test/video_0.m4s"));
+        assert!(storage.exists("live/test/video_720000.m4s"));
+        assert!(storage.exists("live/test/init_video.mp4"));
+        assert_eq!(
+            &storage.read("live/test/test.mpd").unwrap()[..],
+            content.as_bytes()
+        );
+        assert!(content.contains("<S t=\"384000\" d=\"192000\"/>"));
+        assert!(content.contains("audioSamplingRate=\"48000\""));
+        assert!(content.contains("value=\"2\""));
+
+        mpd.clear().unwrap();
+        assert!(!storage.exists("live/test/test.mpd"));
+        assert!(!storage.exists("live/test/init_video.mp4"));
+        assert!(!storage.exists("live/test/video_720000.m4s"));
+    }
+}
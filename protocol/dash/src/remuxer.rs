@@ -0,0 +1,61 @@
+use {
+    super::{define::DASH_SEGMENT_DURATION, errors::DashError, flv_data_receiver::FlvDataReceiver},
+    hls::storage::SharedSegmentStorage,
+    streamhub::{
+        define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
+        stream::StreamIdentifier,
+    },
+};
+
+pub struct DashRemuxer {
+    client_event_consumer: BroadcastEventReceiver,
+    event_producer: StreamHubEventSender,
+    //where the mpds and the segments are written, shared with the dash server
+    storage: SharedSegmentStorage,
+}
+
+impl DashRemuxer {
+    pub fn new(
+        consumer: BroadcastEventReceiver,
+        event_producer: StreamHubEventSender,
+        storage: SharedSegmentStorage,
+    ) -> Self {
+        Self {
+            client_event_consumer: consumer,
+            event_producer,
+            storage,
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), DashError> {
+        loop {
+            let val = self.client_event_consumer.recv().await?;
+            match val {
+                BroadcastEvent::Publish { identifier } => {
+                    if let StreamIdentifier::Rtmp {
+                        app_name,
+                        stream_name,
+                    } = identifier
+                    {
+                        let mut rtmp_subscriber = FlvDataReceiver::new(
+                            app_name,
+                            stream_name,
+                            self.event_producer.clone(),
+                            DASH_SEGMENT_DURATION,
+                            self.storage.clone(),
+                        );
+
+                        tokio::spawn(async move {
+                            if let Err(err) = rtmp_subscriber.run().await {
+                                log::error!("dash handler run error {err}");
+                            }
+                        });
+                    }
+                }
+                _ => {
+                    log::trace!("other infos...");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use {
+    axum::{
+        body::Body,
+        extract::{Request, State},
+        handler::Handler,
+        http::StatusCode,
+        response::Response,
+        serve::IncomingStream,
+        Extension,
+    },
+    commonlib::{
+        acl::{AccessControl, ConnectionAccess},
+        auth::{Auth, SecretCarrier},
+        errors::{AclError, AclErrorValue},
+    },
+    hls::storage::SharedSegmentStorage,
+    std::{convert::Infallible, future::ready, net::SocketAddr, sync::Arc},
+    streamhub::statistics::metrics,
+    tokio::net::TcpListener,
+    tower::{service_fn, Layer},
+};
+
+type GenericError = Box<dyn std::error::Error + Send + Sync>;
+type Result<T> = std::result::Result<T, GenericError>;
+
+static NOTFOUND: &[u8] = b"Not Found";
+static UNAUTHORIZED: &[u8] = b"Unauthorized";
+
+#[derive(Debug)]
+enum DashFileType {
+    Manifest,
+    Segment,
+    InitSegment,
+}
+
+impl DashFileType {
+    const CONTENT_TYPE_MANIFEST: &'static str = "application/dash+xml";
+    const CONTENT_TYPE_SEGMENT: &'static str = "video/iso.segment";
+    const CONTENT_TYPE_INIT_SEGMENT: &'static str = "video/mp4";
+
+    fn content_type(&self) -> &str {
+        match self {
+            Self::Manifest => Self::CONTENT_TYPE_MANIFEST,
+            Self::Segment => Self::CONTENT_TYPE_SEGMENT,
+            Self::InitSegment => Self::CONTENT_TYPE_INIT_SEGMENT,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct DashPath {
+    app_name: String,
+    stream_name: String,
+    file_name: String,
+    file_type: DashFileType,
+}
+
+impl DashPath {
+    const MPD_EXT: &'static str = "mpd";
+    const M4S_EXT: &'static str = "m4s";
+    const MP4_EXT: &'static str = "mp4";
+
+    fn parse(path: &str) -> Option<Self> {
+        if path.is_empty() || path.contains("..") {
+            return None;
+        }
+
+        let mut parts = path[1..].split('/');
+        let app_name = parts.next()?;
+        let stream_name = parts.next()?;
+        let file_part = parts.next()?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        let (file_name, ext) = file_part.rsplit_once('.')?;
+        if file_name.is_empty() {
+            return None;
+        }
+
+        let file_type = match ext {
+            Self::MPD_EXT => DashFileType::Manifest,
+            Self::M4S_EXT => DashFileType::Segment,
+            Self::MP4_EXT => DashFileType::InitSegment,
+            _ => return None,
+        };
+
+        Some(Self {
+            app_name: app_name.into(),
+            stream_name: stream_name.into(),
+            file_name: file_name.into(),
+            file_type,
+        })
+    }
+
+    fn to_file_path(&self) -> String {
+        let ext = match self.file_type {
+            DashFileType::Manifest => Self::MPD_EXT,
+            DashFileType::Segment => Self::M4S_EXT,
+            DashFileType::InitSegment => Self::MP4_EXT,
+        };
+        format!(
+            "{}/{}/{}.{}",
+            self.app_name, self.stream_name, self.file_name, ext
+        )
+    }
+}
+
+fn response_unauthorized() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .body(UNAUTHORIZED.into())
+        .unwrap()
+}
+
+fn response_acl_rejected(err: &AclError) -> Response<Body> {
+    let status = match err.value {
+        AclErrorValue::IpNotAllowed => StatusCode::FORBIDDEN,
+        _ => StatusCode::TOO_MANY_REQUESTS,
+    };
+    Response::builder()
+        .status(status)
+        .body(status.canonical_reason().unwrap_or_default().into())
+        .unwrap()
+}
+
+fn response_not_found() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NOT_FOUND)
+        .body(NOTFOUND.into())
+        .unwrap()
+}
+
+fn response_file(storage: &SharedSegmentStorage, dash_path: &DashPath) -> Response<Body> {
+    match storage.read(&dash_path.to_file_path()) {
+        Some(data) => Response::builder()
+            .header("Content-Type", dash_path.file_type.content_type())
+            .body(Body::from(data))
+            .unwrap(),
+        None => response_not_found(),
+    }
+}
+
+async fn handle_connection(
+    State((auth, storage)): State<(Option<Auth>, SharedSegmentStorage)>,
+    Extension(access): Extension<ConnectionAccess>,
+    req: Request<Body>,
+) -> Response<Body> {
+    //the connection is checked at accept time, all its requests are rejected
+    if let Err(err) = access.result() {
+        return response_acl_rejected(err);
+    }
+
+    let path = req.uri().path();
+    let query_string = req.uri().query().map(|s| s.to_string());
+
+    let dash_path = match DashPath::parse(path) {
+        Some(p) => p,
+        None => return response_not_found(),
+    };
+
+    if let (Some(auth_val), DashFileType::Manifest) = (auth.as_ref(), &dash_path.file_type) {
+        if auth_val
+            .authenticate(
+                &dash_path.app_name,
+                &dash_path.stream_name,
+                &query_string.map(SecretCarrier::Query),
+                true,
+            )
+            .is_err()
+        {
+            metrics::inc_auth_failure("dash");
+            return response_unauthorized();
+        }
+    }
+
+    response_file(&storage, &dash_path)
+}
+
+pub async fn run(
+    port: usize,
+    auth: Option<Auth>,
+    access_control: Option<Arc<AccessControl>>,
+    storage: SharedSegmentStorage,
+) -> Result<()> {
+    let listen_address = format!("0.0.0.0:{port}");
+    let sock_addr: SocketAddr = listen_address.parse().unwrap();
+
+    let listener = TcpListener::bind(sock_addr).await?;
+
+    log::info!("Dash server listening on http://{}", sock_addr);
+
+    let handle_connection = handle_connection.with_state((auth, storage));
+
+    //the connections of the players are checked by the access control when they are accepted
+    let make_service = service_fn(move |stream: IncomingStream<'_>| {
+        let remote_addr = stream.remote_addr();
+        let access = ConnectionAccess::check(&access_control, remote_addr.ip(), true);
+        if let Err(err) = access.result() {
+            log::warn!("dash connection from {} is rejected: {}", remote_addr, err);
+            metrics::inc_acl_rejection("dash");
+        }
+        let service = Layer::layer(&Extension(access), handle_connection.clone());
+        ready(Ok::<_, Infallible>(service))
+    });
+
+    axum::serve(listener, make_service).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DashFileType, DashPath};
+
+    #[test]
+    fn test_dash_path_parse() {
+        let manifest = DashPath::parse("/live/stream/stream.mpd").unwrap();
+        assert_eq!(manifest.app_name, "live");
+        assert_eq!(manifest.stream_name, "stream");
+        assert_eq!(manifest.file_name, "stream");
+        assert!(matches!(manifest.file_type, DashFileType::Manifest));
+        assert_eq!(manifest.to_file_path(), "live/stream/stream.mpd");
+        assert_eq!(manifest.file_type.content_type(), "application/dash+xml");
+
+        let segment = DashPath::parse("/live/stream/video_360000.m4s").unwrap();
+        assert_eq!(segment.file_name, "video_360000");
+        assert!(matches!(segment.file_type, DashFileType::Segment));
+        assert_eq!(segment.to_file_path(), "live/stream/video_360000.m4s");
+        assert_eq!(segment.file_type.content_type(), "video/iso.segment");
+
+        let init_segment = DashPath::parse("/live/stream/init_audio.mp4").unwrap();
+        assert!(matches!(init_segment.file_type, DashFileType::InitSegment));
+        assert_eq!(init_segment.to_file_path(), "live/stream/init_audio.mp4");
+        assert_eq!(init_segment.file_type.content_type(), "video/mp4");
+
+        assert!(DashPath::parse("").is_none());
+        assert!(DashPath::parse("/live/stream.mpd").is_none());
+        assert!(DashPath::parse("/too/many/parts/of/path.mpd").is_none());
+        assert!(DashPath::parse("/live/stream/stream.m3u8").is_none());
+        assert!(DashPath::parse("/live/stream/../../etc/passwd").is_none());
+        assert!(DashPath::parse("/live/stream/.mpd").is_none());
+    }
+}
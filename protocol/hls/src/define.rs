@@ -1,6 +1,10 @@
 use xflv::demuxer::{FlvDemuxerAudioData, FlvDemuxerVideoData};
 
 pub const HLS_DURATION: u8 = 10;
+//the default partial segment duration(ms) of low-latency hls
+pub const LL_HLS_PART_DURATION: i64 = 500;
+//the default capacity(bytes) of the segments of a stream in the in-memory storage
+pub const HLS_MEMORY_STORAGE_CAPACITY: usize = 256 * 1024 * 1024;
 
 pub enum FlvDemuxerData {
     Video { data: FlvDemuxerVideoData },
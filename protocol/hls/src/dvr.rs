@@ -0,0 +1,271 @@
+use {
+    chrono::{DateTime, SecondsFormat, TimeZone, Utc},
+    std::collections::VecDeque,
+};
+
+const PROGRAM_DATE_TIME_TAG: &str = "#EXT-X-PROGRAM-DATE-TIME:";
+const MEDIA_SEQUENCE_TAG: &str = "#EXT-X-MEDIA-SEQUENCE:";
+const DISCONTINUITY_TAG: &str = "#EXT-X-DISCONTINUITY";
+const EXTINF_TAG: &str = "#EXTINF:";
+
+pub struct DvrSegment {
+    pub sequence_no: u64,
+    /*the wall clock time(unix ms) when the segment begins*/
+    pub start_time: i64,
+    /*segment duration(ms)*/
+    pub duration: i64,
+    pub discontinuity: bool,
+    pub name: String,
+    pub path: String,
+}
+
+/*Retains the segments of the last window(ms) after they leave the live
+playlist, they are listed with their program date time in the dvr playlist,
+from which the hls server builds the playlist of a time range.*/
+pub struct DvrWindow {
+    window: i64,
+    segments: VecDeque<DvrSegment>,
+    /*the total duration(ms) of the segments*/
+    duration: i64,
+}
+
+impl DvrWindow {
+    pub fn new(window: i64) -> Self {
+        Self {
+            window,
+            segments: VecDeque::new(),
+            duration: 0,
+        }
+    }
+
+    /*Returns the segments out of the window, except the ones whose sequence
+    number is not less than live_sequence_no(still in the live playlist).*/
+    pub fn add_segment(&mut self, segment: DvrSegment, live_sequence_no: u64) -> Vec<DvrSegment> {
+        self.duration += segment.duration;
+        self.segments.push_back(segment);
+
+        let mut expired_segments = Vec::new();
+        while let Some(front) = self.segments.front() {
+            if self.duration - front.duration < self.window || front.sequence_no >= live_sequence_no
+            {
+                break;
+            }
+            self.duration -= front.duration;
+            expired_segments.extend(self.segments.pop_front());
+        }
+        expired_segments
+    }
+
+    /*Drops the segments which are no longer in the storage, e.g. the ones evicted
+    by the memory storage when the window does not fit into its capacity.*/
+    pub fn remove_missing(&mut self, exists: impl Fn(&str) -> bool) {
+        let duration = &mut self.duration;
+        self.segments.retain(|segment| {
+            let keep = exists(&segment.path);
+            if !keep {
+                *duration -= segment.duration;
+            }
+            keep
+        });
+    }
+
+    pub fn segments(&self) -> &VecDeque<DvrSegment> {
+        &self.segments
+    }
+
+    //the header contains the tags before the media sequence number
+    pub fn generate_playlist(&self, header: &str, map: Option<&str>) -> String {
+        let mut content = String::from(header);
+        let sequence_no = self
+            .segments
+            .front()
+            .map_or(0, |segment| segment.sequence_no);
+        content += format!("{MEDIA_SEQUENCE_TAG}{sequence_no}\n").as_str();
+        if let Some(map) = map {
+            content += map;
+        }
+
+        for segment in &self.segments {
+            if segment.discontinuity {
+                content += format!("{DISCONTINUITY_TAG}\n").as_str();
+            }
+            if let Some(date_time) = Utc.timestamp_millis_opt(segment.start_time).single() {
+                content += format!(
+                    "{PROGRAM_DATE_TIME_TAG}{}\n",
+                    date_time.to_rfc3339_opts(SecondsFormat::Millis, true)
+                )
+                .as_str();
+            }
+            content += format!(
+                "{EXTINF_TAG}{:.3}\n{}\n",
+                segment.duration as f64 / 1000.0,
+                segment.name
+            )
+            .as_str();
+        }
+
+        content
+    }
+}
+
+/*Builds the playlist of the segments overlapping [start, end)(unix seconds)
+from a dvr playlist, it is a vod playlist if all the segments of the range are
+produced, or an event playlist which grows with the live stream.*/
+pub fn generate_time_range_playlist(
+    dvr_playlist: &str,
+    start: Option<i64>,
+    end: Option<i64>,
+) -> Option<String> {
+    let start = start.map_or(i64::MIN, |start| start.saturating_mul(1000));
+    let end = end.map(|end| end.saturating_mul(1000));
+
+    let mut header = String::new();
+    let mut sequence_no: u64 = 0;
+    let mut first_sequence_no = None;
+    let mut segments = String::new();
+    let mut last_end_time = i64::MIN;
+
+    //the tags of the current segment
+    let mut segment_tags = String::new();
+    let mut start_time = None;
+    let mut duration = 0;
+
+    for line in dvr_playlist.lines() {
+        if let Some(value) = line.strip_prefix(MEDIA_SEQUENCE_TAG) {
+            sequence_no = value.parse().ok()?;
+        } else if let Some(value) = line.strip_prefix(PROGRAM_DATE_TIME_TAG) {
+            start_time = DateTime::parse_from_rfc3339(value)
+                .ok()
+                .map(|date_time| date_time.timestamp_millis());
+            segment_tags += line;
+            segment_tags += "\n";
+        } else if let Some(value) = line.strip_prefix(EXTINF_TAG) {
+            let value = value.trim_end_matches(',');
+            duration = (value.parse::<f64>().ok()? * 1000.0) as i64;
+            segment_tags += line;
+            segment_tags += "\n";
+        } else if line.starts_with(DISCONTINUITY_TAG) {
+            segment_tags += line;
+            segment_tags += "\n";
+        } else if line.starts_with('#') {
+            if segment_tags.is_empty() && start_time.is_none() {
+                header += line;
+                header += "\n";
+            }
+        } else if !line.is_empty() {
+            //the uri ends a segment
+            if let Some(segment_start_time) = start_time {
+                let segment_end_time = segment_start_time + duration;
+                last_end_time = segment_end_time;
+                if segment_end_time > start && end.is_none_or(|end| segment_start_time < end) {
+                    first_sequence_no.get_or_insert(sequence_no);
+                    segments += segment_tags.as_str();
+                    segments += line;
+                    segments += "\n";
+                }
+            }
+            sequence_no += 1;
+            segment_tags.clear();
+            start_time = None;
+        }
+    }
+
+    let first_sequence_no = first_sequence_no?;
+    let is_vod = end.is_some_and(|end| end <= last_end_time);
+
+    //the map tag is after the media sequence number in the dvr playlist
+    let (header, map) = match header.find("#EXT-X-MAP") {
+        Some(index) => header.split_at(index),
+        None => (header.as_str(), ""),
+    };
+
+    let mut content = String::from(header);
+    content += format!("{MEDIA_SEQUENCE_TAG}{first_sequence_no}\n").as_str();
+    content += if is_vod {
+        "#EXT-X-PLAYLIST-TYPE:VOD\n"
+    } else {
+        "#EXT-X-PLAYLIST-TYPE:EVENT\n"
+    };
+    content += map;
+    content += segments.as_str();
+    if is_vod {
+        content += "#EXT-X-ENDLIST\n";
+    }
+
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_time_range_playlist, DvrSegment, DvrWindow};
+
+    fn new_segment(sequence_no: u64) -> DvrSegment {
+        DvrSegment {
+            sequence_no,
+            //2024-01-02T03:04:00Z
+            start_time: 1_704_164_640_000 + sequence_no as i64 * 10_000,
+            duration: 10_000,
+            discontinuity: false,
+            name: format!("{sequence_no}.ts"),
+            path: format!("live/test/{sequence_no}.ts"),
+        }
+    }
+
+    #[test]
+    fn test_dvr_window() {
+        let mut dvr_window = DvrWindow::new(30_000);
+        for sequence_no in 0..3 {
+            assert!(dvr_window
+                .add_segment(new_segment(sequence_no), 0)
+                .is_empty());
+        }
+        //the segments still in the live playlist are retained
+        assert!(dvr_window.add_segment(new_segment(3), 0).is_empty());
+        let expired_segments = dvr_window.add_segment(new_segment(4), 2);
+        assert_eq!(expired_segments.len(), 2);
+        assert_eq!(expired_segments[1].path, "live/test/1.ts");
+        assert_eq!(dvr_window.segments().len(), 3);
+
+        let playlist = dvr_window.generate_playlist(
+            "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:10\n",
+            None,
+        );
+        assert!(playlist.contains("#EXT-X-MEDIA-SEQUENCE:2\n"));
+        assert!(playlist.contains("#EXT-X-PROGRAM-DATE-TIME:2024-01-02T03:04:20.000Z\n"));
+        assert!(playlist.ends_with("#EXTINF:10.000\n4.ts\n"));
+    }
+
+    #[test]
+    fn test_time_range_playlist() {
+        let mut dvr_window = DvrWindow::new(3_600_000);
+        for sequence_no in 0..6 {
+            dvr_window.add_segment(new_segment(sequence_no), 0);
+        }
+        let dvr_playlist = dvr_window.generate_playlist(
+            "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-TARGETDURATION:10\n",
+            Some("#EXT-X-MAP:URI=\"init.mp4\"\n"),
+        );
+
+        //a finished range is a vod playlist
+        let playlist =
+            generate_time_range_playlist(&dvr_playlist, Some(1_704_164_655), Some(1_704_164_680))
+                .unwrap();
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-TARGETDURATION:10\n\
+             #EXT-X-MEDIA-SEQUENCE:1\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-MAP:URI=\"init.mp4\"\n\
+             #EXT-X-PROGRAM-DATE-TIME:2024-01-02T03:04:10.000Z\n#EXTINF:10.000\n1.ts\n\
+             #EXT-X-PROGRAM-DATE-TIME:2024-01-02T03:04:20.000Z\n#EXTINF:10.000\n2.ts\n\
+             #EXT-X-PROGRAM-DATE-TIME:2024-01-02T03:04:30.000Z\n#EXTINF:10.000\n3.ts\n\
+             #EXT-X-ENDLIST\n"
+        );
+
+        //an unfinished range is an event playlist
+        let playlist =
+            generate_time_range_playlist(&dvr_playlist, Some(1_704_164_680), None).unwrap();
+        assert!(playlist.contains("#EXT-X-MEDIA-SEQUENCE:4\n#EXT-X-PLAYLIST-TYPE:EVENT\n"));
+        assert!(!playlist.contains("#EXT-X-ENDLIST"));
+
+        assert!(generate_time_range_playlist(&dvr_playlist, Some(1_704_164_700), None).is_none());
+    }
+}
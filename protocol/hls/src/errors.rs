@@ -1,11 +1,14 @@
 #![allow(non_local_definitions)]
 use {
     failure::{Backtrace, Fail},
+    fdk_aac::enc::EncoderError as AacEncoderError,
     std::fmt,
     streamhub::errors::StreamHubError,
     tokio::sync::broadcast::error::RecvError,
     tokio::sync::oneshot::error::RecvError as OneshotRecvError,
     xflv::errors::FlvDemuxerError,
+    xflv::errors::FlvMuxerError,
+    xmp4::errors::Mp4Error,
     xmpegts::errors::MpegTsError,
 };
 
@@ -34,8 +37,14 @@ pub enum MediaErrorValue {
     FlvDemuxerError(#[cause] FlvDemuxerError),
     #[fail(display = "mpegts error:{}", _0)]
     MpegTsError(#[cause] MpegTsError),
+    #[fail(display = "mp4 error:{}", _0)]
+    Mp4Error(#[cause] Mp4Error),
     #[fail(display = "write file error:{}", _0)]
     IOError(#[cause] std::io::Error),
+    #[fail(display = "flv muxer error:{}", _0)]
+    FlvMuxerError(#[cause] FlvMuxerError),
+    #[fail(display = "aac encoder error:{}", _0)]
+    AacEncoderError(AacEncoderError),
 }
 
 impl From<FlvDemuxerError> for MediaError {
@@ -46,6 +55,22 @@ impl From<FlvDemuxerError> for MediaError {
     }
 }
 
+impl From<FlvMuxerError> for MediaError {
+    fn from(error: FlvMuxerError) -> Self {
+        MediaError {
+            value: MediaErrorValue::FlvMuxerError(error),
+        }
+    }
+}
+
+impl From<AacEncoderError> for MediaError {
+    fn from(error: AacEncoderError) -> Self {
+        MediaError {
+            value: MediaErrorValue::AacEncoderError(error),
+        }
+    }
+}
+
 impl From<MpegTsError> for MediaError {
     fn from(error: MpegTsError) -> Self {
         MediaError {
@@ -54,6 +79,14 @@ impl From<MpegTsError> for MediaError {
     }
 }
 
+impl From<Mp4Error> for MediaError {
+    fn from(error: Mp4Error) -> Self {
+        MediaError {
+            value: MediaErrorValue::Mp4Error(error),
+        }
+    }
+}
+
 impl From<std::io::Error> for MediaError {
     fn from(error: std::io::Error) -> Self {
         MediaError {
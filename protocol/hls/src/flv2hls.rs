@@ -1,10 +1,16 @@
 use {
-    super::{define::FlvDemuxerData, errors::MediaError, m3u8::M3u8},
+    super::{
+        define::FlvDemuxerData, errors::MediaError, g711toaac::G711ToAacTranscoder, m3u8::M3u8,
+        playlist_notifier::PlaylistNotifier, storage::SharedSegmentStorage,
+    },
     bytes::BytesMut,
+    std::sync::Arc,
+    streamhub::notify::Notifier,
     xflv::{
-        define::{frame_type, FlvData},
-        demuxer::{FlvAudioTagDemuxer, FlvVideoTagDemuxer},
+        define::{frame_type, AvcCodecId, FlvData},
+        demuxer::{FlvAudioTagDemuxer, FlvMp4Demuxer, FlvMp4DemuxerData, FlvVideoTagDemuxer},
     },
+    xmp4::{define::Mp4CodecType, fmp4::Fmp4Muxer},
     xmpegts::{
         define::{epsi_stream_type, MPEG_FLAG_IDR_FRAME},
         ts::TsMuxer,
@@ -26,21 +32,50 @@ pub struct Flv2HlsRemuxer {
     duration: i64,
     need_new_segment: bool,
 
-    video_pid: u16,
+    /*low-latency hls: the segment is also cut into partial segments*/
+    part_duration: i64,
+    last_part_dts: i64,
+    //the dts intervals of the last two frames of each track, used to predict the
+    //next frame, they are tracked separately because the tracks are interleaved
+    video_frame_interval: i64,
+    audio_frame_interval: i64,
+    last_video_dts: Option<i64>,
+    last_audio_dts: Option<i64>,
+    part_independent: bool,
+    segment_data: BytesMut,
+
+    //the video stream is added when the first video frame arrives,
+    //because the codec(H.264/H.265) is unknown before that.
+    video_pid: Option<u16>,
     audio_pid: u16,
 
+    /*fMP4 segments: the samples are muxed as they are in the flv tags(length
+    prefixed NALUs and raw AAC frames), and the tracks are added when the
+    sequence headers arrive.*/
+    mp4_demuxer: FlvMp4Demuxer,
+    fmp4_muxer: Option<Fmp4Muxer>,
+    video_track_id: Option<u16>,
+    audio_track_id: Option<u16>,
+    init_segment_written: bool,
+
+    //the G.711 audio is transcoded to aac which can be played by the hls players
+    g711_transcoder: Option<G711ToAacTranscoder>,
+
     m3u8_handler: M3u8,
 }
 
 impl Flv2HlsRemuxer {
-    pub fn new(duration: i64, app_name: String, stream_name: String, need_record: bool) -> Self {
+    pub fn new(
+        duration: i64,
+        app_name: String,
+        stream_name: String,
+        need_record: bool,
+        storage: SharedSegmentStorage,
+    ) -> Self {
         let mut ts_muxer = TsMuxer::new();
         let audio_pid = ts_muxer
             .add_stream(epsi_stream_type::PSI_STREAM_AAC, BytesMut::new())
             .unwrap();
-        let video_pid = ts_muxer
-            .add_stream(epsi_stream_type::PSI_STREAM_H264, BytesMut::new())
-            .unwrap();
 
         Self {
             video_demuxer: FlvVideoTagDemuxer::new(),
@@ -57,14 +92,176 @@ impl Flv2HlsRemuxer {
             duration,
             need_new_segment: false,
 
-            video_pid,
+            part_duration: 0,
+            last_part_dts: 0,
+            video_frame_interval: 0,
+            audio_frame_interval: 0,
+            last_video_dts: None,
+            last_audio_dts: None,
+            part_independent: false,
+            segment_data: BytesMut::new(),
+
+            video_pid: None,
             audio_pid,
 
-            m3u8_handler: M3u8::new(duration, 6, app_name, stream_name, need_record),
+            mp4_demuxer: FlvMp4Demuxer::new(),
+            fmp4_muxer: None,
+            video_track_id: None,
+            audio_track_id: None,
+            init_segment_written: false,
+
+            g711_transcoder: None,
+
+            m3u8_handler: M3u8::new(duration, 6, app_name, stream_name, need_record, storage),
+        }
+    }
+
+    //part_duration is in milliseconds
+    pub fn set_low_latency(&mut self, part_duration: i64, playlist_notifier: PlaylistNotifier) {
+        self.part_duration = part_duration;
+        self.m3u8_handler
+            .set_low_latency(part_duration, playlist_notifier);
+    }
+
+    //emit fMP4(.m4s) segments with an init segment instead of MPEG-TS segments
+    pub fn set_fmp4(&mut self) {
+        self.fmp4_muxer = Some(Fmp4Muxer::new());
+        self.m3u8_handler.set_fmp4();
+    }
+
+    //notify the written segments
+    pub fn set_notifier(&mut self, notifier: Arc<dyn Notifier>) {
+        self.m3u8_handler.set_notifier(notifier);
+    }
+
+    //retain the segments of the last dvr window(ms) for timeshift
+    pub fn set_dvr_window(&mut self, dvr_window: i64) {
+        self.m3u8_handler.set_dvr_window(dvr_window);
+    }
+
+    fn has_video(&self) -> bool {
+        match self.fmp4_muxer {
+            Some(_) => self.video_track_id.is_some(),
+            None => self.video_pid.is_some(),
+        }
+    }
+
+    fn get_muxer_data(&mut self, dts: i64) -> Result<BytesMut, MediaError> {
+        match &mut self.fmp4_muxer {
+            Some(fmp4_muxer) => {
+                //the tracks are known once the first media data is flushed
+                if !self.init_segment_written && fmp4_muxer.has_tracks() {
+                    self.m3u8_handler
+                        .write_init_segment(fmp4_muxer.gen_init_segment()?)?;
+                    self.init_segment_written = true;
+                }
+                Ok(fmp4_muxer.get_data(dts)?)
+            }
+            None => Ok(self.ts_muxer.get_data()),
+        }
+    }
+
+    /*Cut the data muxed since the last cut into a partial segment, in low-latency
+    mode a segment is the concatenation of its partial segments.*/
+    fn cut_part(&mut self, dts: i64) -> Result<(), MediaError> {
+        let data = self.get_muxer_data(dts)?;
+        if data.is_empty() {
+            return Ok(());
         }
+        self.segment_data.extend_from_slice(&data[..]);
+
+        //an audio only stream has no dependencies between frames
+        let independent = self.part_independent || !self.has_video();
+        self.m3u8_handler
+            .add_part(dts - self.last_part_dts, independent, data)?;
+
+        //start every part with PAT/PMT
+        self.ts_muxer.reset();
+        self.last_part_dts = dts;
+        self.part_independent = false;
+
+        Ok(())
+    }
+
+    fn get_segment_data(&mut self, dts: i64) -> Result<BytesMut, MediaError> {
+        if self.m3u8_handler.is_low_latency() {
+            self.cut_part(dts)?;
+            Ok(self.segment_data.split())
+        } else {
+            self.get_muxer_data(dts)
+        }
+    }
+
+    fn process_fmp4_flv_data(&mut self, data: FlvData) -> Result<(), MediaError> {
+        let flv_demux_data: FlvDemuxerData = match self.mp4_demuxer.demux(data)? {
+            FlvMp4DemuxerData::VideoConfig {
+                codec_id,
+                decoder_configuration_record,
+                width,
+                height,
+            } => {
+                //the codec configuration cannot be changed after the init segment is written
+                if let (Some(fmp4_muxer), None) = (&mut self.fmp4_muxer, self.video_track_id) {
+                    let codec = if codec_id == AvcCodecId::HEVC as u8 {
+                        Mp4CodecType::H265
+                    } else {
+                        Mp4CodecType::H264
+                    };
+                    let track_id = fmp4_muxer.add_video_track(
+                        codec,
+                        decoder_configuration_record,
+                        width,
+                        height,
+                    )?;
+                    self.video_track_id = Some(track_id as u16);
+                }
+                return Ok(());
+            }
+            FlvMp4DemuxerData::AudioConfig {
+                audio_specific_config,
+                sampling_frequency,
+                channels,
+            } => {
+                if let (Some(fmp4_muxer), None) = (&mut self.fmp4_muxer, self.audio_track_id) {
+                    let track_id = fmp4_muxer.add_audio_track(
+                        audio_specific_config,
+                        sampling_frequency,
+                        channels,
+                    )?;
+                    self.audio_track_id = Some(track_id as u16);
+                }
+                return Ok(());
+            }
+            FlvMp4DemuxerData::Video { data } => FlvDemuxerData::Video { data },
+            FlvMp4DemuxerData::Audio { data } => FlvDemuxerData::Audio { data },
+            FlvMp4DemuxerData::None => return Ok(()),
+        };
+
+        self.process_demux_data(&flv_demux_data)
     }
 
     pub fn process_flv_data(&mut self, data: FlvData) -> Result<(), MediaError> {
+        if let FlvData::Audio { timestamp, data } = &data {
+            if let Some(law) = G711ToAacTranscoder::g711_law(data) {
+                let transcoder = match &mut self.g711_transcoder {
+                    Some(transcoder) => transcoder,
+                    None => self.g711_transcoder.insert(G711ToAacTranscoder::new(law)?),
+                };
+                for aac_data in transcoder.transcode(*timestamp, data)? {
+                    self.remux_flv_data(aac_data)?;
+                }
+                return Ok(());
+            }
+        }
+
+        self.remux_flv_data(data)
+    }
+
+    fn remux_flv_data(&mut self, data: FlvData) -> Result<(), MediaError> {
+        if self.fmp4_muxer.is_some() {
+            return self.process_fmp4_flv_data(data);
+        }
+
         let flv_demux_data: FlvDemuxerData = match data {
             FlvData::Audio { timestamp, data } => {
                 let audio_data = self.audio_demuxer.demux(timestamp, data)?;
@@ -86,7 +283,7 @@ impl Flv2HlsRemuxer {
     }
 
     pub fn flush_remaining_data(&mut self) -> Result<(), MediaError> {
-        let data = self.ts_muxer.get_data();
+        let data = self.get_segment_data(self.last_dts)?;
         let mut discontinuity: bool = false;
         if self.last_dts > self.last_ts_dts + 15 * 1000 {
             discontinuity = true;
@@ -118,8 +315,30 @@ impl Flv2HlsRemuxer {
             FlvDemuxerData::Video { data } => {
                 pts = data.pts;
                 dts = data.dts;
-                pid = self.video_pid;
+                pid = match (&self.fmp4_muxer, self.video_pid) {
+                    (Some(_), _) => match self.video_track_id {
+                        Some(video_track_id) => video_track_id,
+                        //no sequence header yet
+                        None => return Ok(()),
+                    },
+                    (None, Some(video_pid)) => video_pid,
+                    (None, None) => {
+                        let stream_type = if data.codec_id == AvcCodecId::HEVC as u8 {
+                            epsi_stream_type::PSI_STREAM_H265
+                        } else {
+                            epsi_stream_type::PSI_STREAM_H264
+                        };
+                        let video_pid = self.ts_muxer.add_stream(stream_type, BytesMut::new())?;
+                        self.video_pid = Some(video_pid);
+                        video_pid
+                    }
+                };
                 payload.extend_from_slice(&data.data[..]);
+                update_frame_interval(
+                    &mut self.last_video_dts,
+                    &mut self.video_frame_interval,
+                    dts,
+                );
 
                 if data.frame_type == frame_type::KEY_FRAME {
                     flags = MPEG_FLAG_IDR_FRAME;
@@ -135,8 +354,17 @@ impl Flv2HlsRemuxer {
 
                 pts = data.pts;
                 dts = data.dts;
-                pid = self.audio_pid;
+                pid = match (&self.fmp4_muxer, self.audio_track_id) {
+                    (Some(_), Some(audio_track_id)) => audio_track_id,
+                    (Some(_), None) => return Ok(()),
+                    (None, _) => self.audio_pid,
+                };
                 payload.extend_from_slice(&data.data[..]);
+                update_frame_interval(
+                    &mut self.last_audio_dts,
+                    &mut self.audio_frame_interval,
+                    dts,
+                );
 
                 if dts - self.last_ts_dts >= self.duration * 1000 {
                     self.need_new_segment = true;
@@ -150,7 +378,7 @@ impl Flv2HlsRemuxer {
             if dts > self.last_ts_dts + 15 * 1000 {
                 discontinuity = true;
             }
-            let data = self.ts_muxer.get_data();
+            let data = self.get_segment_data(dts)?;
 
             self.m3u8_handler
                 .add_segment(dts - self.last_ts_dts, discontinuity, false, data)?;
@@ -160,13 +388,37 @@ impl Flv2HlsRemuxer {
             self.last_ts_dts = dts;
             self.last_ts_pts = pts;
             self.need_new_segment = false;
+        } else if self.m3u8_handler.is_low_latency()
+            && is_part_full(
+                dts - self.last_part_dts,
+                //the parts are cut by the video frames if there is a video track
+                if self.video_frame_interval > 0 {
+                    self.video_frame_interval
+                } else {
+                    self.audio_frame_interval
+                },
+                self.part_duration,
+            )
+        {
+            self.cut_part(dts)?;
+            self.m3u8_handler.refresh_playlist()?;
+        }
+
+        if flags == MPEG_FLAG_IDR_FRAME {
+            self.part_independent = true;
         }
 
         self.last_dts = dts;
         self.last_pts = pts;
 
-        self.ts_muxer
-            .write(pid, pts * 90, dts * 90, flags, payload)?;
+        match &mut self.fmp4_muxer {
+            Some(fmp4_muxer) => {
+                fmp4_muxer.write(pid as u32, pts, dts, flags == MPEG_FLAG_IDR_FRAME, payload)?
+            }
+            None => self
+                .ts_muxer
+                .write(pid, pts * 90, dts * 90, flags, payload)?,
+        }
 
         Ok(())
     }
@@ -175,8 +427,51 @@ impl Flv2HlsRemuxer {
         self.m3u8_handler.clear()
     }
 }
+fn update_frame_interval(last_dts: &mut Option<i64>, frame_interval: &mut i64, dts: i64) {
+    if let Some(last_dts) = *last_dts {
+        if dts > last_dts {
+            *frame_interval = dts - last_dts;
+        }
+    }
+    *last_dts = Some(dts);
+}
+
+/*The part is cut before the frame which would make it longer than the part target,
+the next frame is predicted by the interval of the last frames.*/
+fn is_part_full(part_duration: i64, frame_interval: i64, part_target: i64) -> bool {
+    part_duration >= part_target || part_duration + frame_interval > part_target
+}
+
 #[cfg(test)]
 mod tests {
+    use super::{is_part_full, update_frame_interval};
+
+    #[test]
+    fn test_is_part_full() {
+        //25fps video with a 500ms part target: the parts are cut at 480ms
+        assert!(!is_part_full(440, 40, 500));
+        assert!(is_part_full(480, 40, 500));
+        assert!(is_part_full(500, 0, 500));
+        //a long gap between the frames
+        assert!(is_part_full(600, 40, 500));
+    }
+
+    #[test]
+    fn test_update_frame_interval() {
+        let (mut last_video_dts, mut video_frame_interval) = (None, 0);
+        let (mut last_audio_dts, mut audio_frame_interval) = (None, 0);
+        //25fps video interleaved with 48kHz AAC audio
+        for (is_video, dts) in [(true, 0), (false, 0), (false, 21), (true, 40), (false, 42)] {
+            if is_video {
+                update_frame_interval(&mut last_video_dts, &mut video_frame_interval, dts);
+            } else {
+                update_frame_interval(&mut last_audio_dts, &mut audio_frame_interval, dts);
+            }
+        }
+        assert_eq!(video_frame_interval, 40);
+        assert_eq!(audio_frame_interval, 21);
+    }
+
     // use std::{
     //     env,
     //     fs::{self},
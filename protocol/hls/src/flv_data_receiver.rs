@@ -4,22 +4,25 @@ use {
     super::{
         errors::{HlsError, HlsErrorValue},
         flv2hls::Flv2HlsRemuxer,
+        playlist_notifier::PlaylistNotifier,
+        storage::SharedSegmentStorage,
     },
-    std::time::Duration,
+    std::{sync::Arc, time::Duration},
     streamhub::{
         define::{
             FrameData, FrameDataReceiver, NotifyInfo, StreamHubEvent, StreamHubEventSender,
             SubscribeType, SubscriberInfo,
         },
+        notify::Notifier,
+        queue,
         stream::StreamIdentifier,
         utils::{RandomDigitCount, Uuid},
     },
-    tokio::{sync::mpsc, time::sleep},
+    tokio::time::sleep,
     xflv::define::FlvData,
 };
 
 ///https://www.jianshu.com/p/d6311f03b81f
-
 pub struct FlvDataReceiver {
     app_name: String,
     stream_name: String,
@@ -36,8 +39,9 @@ impl FlvDataReceiver {
         event_producer: StreamHubEventSender,
         duration: i64,
         need_record: bool,
+        storage: SharedSegmentStorage,
     ) -> Self {
-        let (_, data_consumer) = mpsc::unbounded_channel();
+        let (_, data_consumer) = queue::unbounded();
         let subscriber_id = Uuid::new(RandomDigitCount::Four);
 
         Self {
@@ -45,11 +49,34 @@ impl FlvDataReceiver {
             stream_name: stream_name.clone(),
             data_consumer,
             event_producer,
-            media_processor: Flv2HlsRemuxer::new(duration, app_name, stream_name, need_record),
+            media_processor: Flv2HlsRemuxer::new(
+                duration,
+                app_name,
+                stream_name,
+                need_record,
+                storage,
+            ),
             subscriber_id,
         }
     }
 
+    pub fn set_low_latency(&mut self, part_duration: i64, playlist_notifier: PlaylistNotifier) {
+        self.media_processor
+            .set_low_latency(part_duration, playlist_notifier);
+    }
+
+    pub fn set_fmp4(&mut self) {
+        self.media_processor.set_fmp4();
+    }
+
+    pub fn set_dvr_window(&mut self, dvr_window: i64) {
+        self.media_processor.set_dvr_window(dvr_window);
+    }
+
+    pub fn set_notifier(&mut self, notifier: Arc<dyn Notifier>) {
+        self.media_processor.set_notifier(notifier);
+    }
+
     pub async fn run(&mut self) -> Result<(), HlsError> {
         self.subscribe_from_stream_hub(self.app_name.clone(), self.stream_name.clone())
             .await?;
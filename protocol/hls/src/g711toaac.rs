@@ -0,0 +1,189 @@
+use {
+    super::errors::MediaError,
+    bytes::BytesMut,
+    fdk_aac::enc::{BitRate, ChannelMode, Encoder as AacEncoder, EncoderParams, Transport},
+    g711::{G711Law, G711_SAMPLE_RATE},
+    xflv::{
+        define::{aac_packet_type, FlvData, SoundFormat},
+        flv_tag_header::AudioTagHeader,
+        Marshal,
+    },
+};
+
+//the samples of one aac frame
+const AAC_FRAME_SIZE: usize = 1024;
+const AAC_BIT_RATE: u32 = 32000;
+
+/*Transcodes the G.711 flv audio tags to aac flv tags, the aac sequence header
+is generated before the first frame, so the tags can be muxed to the MPEG-TS or
+fMP4 segments in the same way as the aac tags.*/
+pub struct G711ToAacTranscoder {
+    law: G711Law,
+    encoder: AacEncoder,
+    pcm_data: Vec<i16>,
+    //the timestamp(ms) of the first G.711 sample
+    base_timestamp: Option<u32>,
+    encoded_frames: u64,
+}
+
+impl G711ToAacTranscoder {
+    pub fn new(law: G711Law) -> Result<Self, MediaError> {
+        let encoder = AacEncoder::new(EncoderParams {
+            bit_rate: BitRate::Cbr(AAC_BIT_RATE),
+            transport: Transport::Raw,
+            channels: ChannelMode::Mono,
+            sample_rate: G711_SAMPLE_RATE,
+        })?;
+
+        Ok(Self {
+            law,
+            encoder,
+            pcm_data: Vec::new(),
+            base_timestamp: None,
+            encoded_frames: 0,
+        })
+    }
+
+    //returns the G.711 law of the flv audio tag
+    pub fn g711_law(data: &BytesMut) -> Option<G711Law> {
+        match data.first().map(|flags| flags >> 4) {
+            Some(sound_format) if sound_format == SoundFormat::G711A as u8 => Some(G711Law::ALaw),
+            Some(sound_format) if sound_format == SoundFormat::G711U as u8 => Some(G711Law::MuLaw),
+            _ => None,
+        }
+    }
+
+    fn gen_aac_tag(
+        timestamp: u32,
+        aac_packet_type: u8,
+        data: &[u8],
+    ) -> Result<FlvData, MediaError> {
+        let audio_tag_header = AudioTagHeader {
+            sound_format: SoundFormat::AAC as u8,
+            sound_rate: 3,
+            sound_size: 1,
+            sound_type: 0,
+            aac_packet_type,
+        };
+
+        let mut tag = audio_tag_header.marshal()?;
+        tag.extend_from_slice(data);
+        Ok(FlvData::Audio {
+            timestamp,
+            data: tag,
+        })
+    }
+
+    //the input is a G.711 flv audio tag
+    pub fn transcode(
+        &mut self,
+        timestamp: u32,
+        data: &BytesMut,
+    ) -> Result<Vec<FlvData>, MediaError> {
+        let mut tags = Vec::new();
+
+        let base_timestamp = match self.base_timestamp {
+            Some(base_timestamp) => base_timestamp,
+            None => {
+                let info = self.encoder.info()?;
+                let audio_specific_config = &info.confBuf[..info.confSize as usize];
+                tags.push(Self::gen_aac_tag(
+                    timestamp,
+                    aac_packet_type::AAC_SEQHDR,
+                    audio_specific_config,
+                )?);
+                *self.base_timestamp.insert(timestamp)
+            }
+        };
+
+        //skip the flags of the tag header
+        self.pcm_data
+            .extend(self.law.decode(data.get(1..).unwrap_or_default()));
+
+        let mut aac_output: Vec<u8> = vec![0; 2048];
+        while self.pcm_data.len() >= AAC_FRAME_SIZE {
+            let pcm = self.pcm_data.split_off(AAC_FRAME_SIZE);
+            let encoder_info = self.encoder.encode(&self.pcm_data, &mut aac_output)?;
+            self.pcm_data = pcm;
+
+            if encoder_info.output_size > 0 {
+                let frame_timestamp = base_timestamp as u64
+                    + self.encoded_frames * AAC_FRAME_SIZE as u64 * 1000 / G711_SAMPLE_RATE as u64;
+                self.encoded_frames += 1;
+                tags.push(Self::gen_aac_tag(
+                    frame_timestamp as u32,
+                    aac_packet_type::AAC_RAW,
+                    &aac_output[..encoder_info.output_size],
+                )?);
+            }
+        }
+
+        Ok(tags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::G711ToAacTranscoder,
+        bytes::BytesMut,
+        g711::G711Law,
+        xflv::{
+            define::{aac_packet_type, FlvData, SoundFormat},
+            demuxer::FlvMp4Demuxer,
+            demuxer::FlvMp4DemuxerData,
+        },
+    };
+
+    #[test]
+    fn test_transcode() {
+        let mut transcoder = G711ToAacTranscoder::new(G711Law::ALaw).unwrap();
+
+        //a 440Hz tone, 20ms(160 samples) in one tag
+        let pcm: Vec<i16> = (0..8000)
+            .map(|i| ((i as f64 * 440. * 2. * std::f64::consts::PI / 8000.).sin() * 8000.) as i16)
+            .collect();
+        let mut tags = Vec::new();
+        for (index, samples) in pcm.chunks(160).enumerate() {
+            let mut tag = BytesMut::from(&[(SoundFormat::G711A as u8) << 4 | 0x02][..]);
+            tag.extend_from_slice(&G711Law::ALaw.encode(samples));
+            assert_eq!(G711ToAacTranscoder::g711_law(&tag), Some(G711Law::ALaw));
+
+            tags.extend(
+                transcoder
+                    .transcode(1000 + index as u32 * 20, &tag)
+                    .unwrap(),
+            );
+        }
+
+        let mut demuxer = FlvMp4Demuxer::new();
+        let mut frames = 0;
+        for (index, tag) in tags.into_iter().enumerate() {
+            let FlvData::Audio { timestamp, data } = tag else {
+                panic!("expect audio tag");
+            };
+            if index == 0 {
+                assert_eq!(data[1], aac_packet_type::AAC_SEQHDR);
+            }
+
+            match demuxer.demux(FlvData::Audio { timestamp, data }).unwrap() {
+                FlvMp4DemuxerData::AudioConfig {
+                    sampling_frequency,
+                    channels,
+                    ..
+                } => {
+                    assert_eq!(index, 0);
+                    assert_eq!(sampling_frequency, 8000);
+                    assert_eq!(channels, 1);
+                }
+                FlvMp4DemuxerData::Audio { data } => {
+                    assert_eq!(data.dts, 1000 + frames * 128);
+                    frames += 1;
+                }
+                _ => panic!("expect aac data"),
+            }
+        }
+        //the encoder delays some frames
+        assert!((5..=7).contains(&frames));
+    }
+}
@@ -1,9 +1,14 @@
 pub mod define;
+pub mod dvr;
 pub mod errors;
 pub mod flv2hls;
 pub mod flv_data_receiver;
+pub mod g711toaac;
 pub mod m3u8;
+pub mod playlist_notifier;
 pub mod remuxer;
 pub mod server;
+pub mod storage;
 mod test_flv2hls;
 pub mod ts;
+pub mod url_signer;
@@ -1,9 +1,43 @@
 use {
-    super::{errors::MediaError, ts::Ts},
+    super::{
+        dvr::{DvrSegment, DvrWindow},
+        errors::MediaError,
+        playlist_notifier::{PlaylistNotifier, PlaylistPosition},
+        storage::SharedSegmentStorage,
+        ts::{Ts, FMP4_EXTENSION},
+    },
     bytes::BytesMut,
-    std::{collections::VecDeque, fs, fs::File, io::Write},
+    chrono::Utc,
+    std::{collections::VecDeque, sync::Arc},
+    streamhub::{define::StreamHubEventMessage, notify::Notifier, stream::StreamIdentifier},
+    tokio::sync::watch,
 };
 
+/*Only the partial segments of the latest segments are listed in the
+low-latency playlist, the older ones are deleted.*/
+const LL_HLS_PART_SEGMENT_COUNT: usize = 2;
+const INIT_SEGMENT_NAME: &str = "init.mp4";
+
+pub struct Part {
+    /*part duration*/
+    pub duration: i64,
+    /*part name*/
+    pub name: String,
+    path: String,
+    pub independent: bool,
+}
+
+impl Part {
+    pub fn new(duration: i64, name: String, path: String, independent: bool) -> Self {
+        Self {
+            duration,
+            name,
+            path,
+            independent,
+        }
+    }
+}
+
 pub struct Segment {
     /*ts duration*/
     pub duration: i64,
@@ -12,6 +46,8 @@ pub struct Segment {
     pub name: String,
     path: String,
     pub is_eof: bool,
+    /*the partial segments of this segment(low-latency mode)*/
+    pub parts: Vec<Part>,
 }
 
 impl Segment {
@@ -28,6 +64,7 @@ impl Segment {
             name,
             path,
             is_eof,
+            parts: Vec::new(),
         }
     }
 }
@@ -49,10 +86,29 @@ pub struct M3u8 {
     live_m3u8_name: String,
 
     ts_handler: Ts,
+    storage: SharedSegmentStorage,
 
     need_record: bool,
     vod_m3u8_content: String,
     vod_m3u8_name: String,
+
+    app_name: String,
+    stream_name: String,
+    /*low-latency hls*/
+    low_latency: bool,
+    /*the part target duration(ms)*/
+    part_duration: i64,
+    /*the partial segments of the segment being produced*/
+    parts: Vec<Part>,
+    playlist_notifier: Option<PlaylistNotifier>,
+    position_sender: Option<watch::Sender<PlaylistPosition>>,
+    /*fMP4 segments: the init segment is referenced by #EXT-X-MAP*/
+    is_fmp4: bool,
+    /*timeshift: the segments of the dvr window are retained*/
+    dvr_window: Option<DvrWindow>,
+    dvr_m3u8_name: String,
+    /*notify the written segments*/
+    notifier: Option<Arc<dyn Notifier>>,
 }
 
 impl M3u8 {
@@ -62,9 +118,9 @@ impl M3u8 {
         app_name: String,
         stream_name: String,
         need_record: bool,
+        storage: SharedSegmentStorage,
     ) -> Self {
-        let m3u8_folder = format!("./{app_name}/{stream_name}");
-        fs::create_dir_all(m3u8_folder.clone()).unwrap();
+        let m3u8_folder = format!("{app_name}/{stream_name}");
 
         let live_m3u8_name = format!("{stream_name}.m3u8");
         let vod_m3u8_name = if need_record {
@@ -81,11 +137,23 @@ impl M3u8 {
             segments: VecDeque::new(),
             m3u8_folder,
             live_m3u8_name,
-            ts_handler: Ts::new(app_name, stream_name),
+            ts_handler: Ts::new(app_name.clone(), stream_name.clone(), storage.clone()),
+            storage,
             // record,
             need_record,
             vod_m3u8_content: String::default(),
             vod_m3u8_name,
+            app_name,
+            stream_name,
+            low_latency: false,
+            part_duration: 0,
+            parts: Vec::new(),
+            playlist_notifier: None,
+            position_sender: None,
+            is_fmp4: false,
+            dvr_window: None,
+            dvr_m3u8_name: String::default(),
+            notifier: None,
         };
 
         if need_record {
@@ -94,6 +162,64 @@ impl M3u8 {
         m3u8
     }
 
+    /*Enable low-latency hls: the playlist lists partial segments, a preload hint
+    and the server control tags, and the playlist position is published to the
+    notifier so that the hls server can hold blocking playlist reload requests.*/
+    pub fn set_low_latency(&mut self, part_duration: i64, playlist_notifier: PlaylistNotifier) {
+        self.version = 9;
+        self.low_latency = true;
+        self.part_duration = part_duration;
+        self.position_sender = Some(playlist_notifier.register(&self.app_name, &self.stream_name));
+        self.playlist_notifier = Some(playlist_notifier);
+    }
+
+    /*Retain the segments of the last window(ms) for timeshift, they are listed
+    in the dvr playlist(dvr_{stream}.m3u8).*/
+    pub fn set_dvr_window(&mut self, window: i64) {
+        self.dvr_window = Some(DvrWindow::new(window));
+        self.dvr_m3u8_name = format!("dvr_{}.m3u8", self.stream_name);
+    }
+
+    pub fn set_notifier(&mut self, notifier: Arc<dyn Notifier>) {
+        self.notifier = Some(notifier);
+    }
+
+    //must be called before any segment is added
+    pub fn set_fmp4(&mut self) {
+        self.version = std::cmp::max(self.version, 7);
+        self.is_fmp4 = true;
+        self.ts_handler.set_extension(FMP4_EXTENSION);
+
+        if self.need_record {
+            self.vod_m3u8_content = self.generate_m3u8_header(true);
+        }
+    }
+
+    pub fn write_init_segment(&mut self, data: BytesMut) -> Result<(), MediaError> {
+        let init_segment_path = format!("{}/{}", self.m3u8_folder, INIT_SEGMENT_NAME);
+        self.storage.write(&init_segment_path, data)
+    }
+
+    pub fn is_low_latency(&self) -> bool {
+        self.low_latency
+    }
+
+    pub fn add_part(
+        &mut self,
+        duration: i64,
+        independent: bool,
+        ts_data: BytesMut,
+    ) -> Result<(), MediaError> {
+        //the part target is the configured part duration, the remuxer cuts the parts at or below it
+        let (part_name, part_path) = self
+            .ts_handler
+            .write_part(self.parts.len() as u32, ts_data)?;
+        self.parts
+            .push(Part::new(duration, part_name, part_path, independent));
+
+        Ok(())
+    }
+
     pub fn add_segment(
         &mut self,
         duration: i64,
@@ -105,40 +231,103 @@ impl M3u8 {
 
         if segment_count >= self.live_ts_count {
             let segment = self.segments.pop_front().unwrap();
-            if !self.need_record {
-                self.ts_handler.delete(segment.path);
+            for part in segment.parts {
+                self.ts_handler.delete(&part.path)?;
+            }
+            //the segments of the dvr window are deleted when they are out of the window
+            if !self.need_record && self.dvr_window.is_none() {
+                self.ts_handler.delete(&segment.path)?;
             }
 
             self.sequence_no += 1;
         }
         self.duration = std::cmp::max(duration, self.duration);
         let (ts_name, ts_path) = self.ts_handler.write(ts_data)?;
-        let segment = Segment::new(duration, discontinuity, ts_name, ts_path, is_eof);
+        let mut segment = Segment::new(duration, discontinuity, ts_name, ts_path, is_eof);
+        segment.parts = std::mem::take(&mut self.parts);
 
         if self.need_record {
             self.update_vod_m3u8(&segment);
         }
 
+        if self.dvr_window.is_some() {
+            self.update_dvr_m3u8(&segment)?;
+        }
+
+        if let Some(notifier) = &self.notifier {
+            let notifier = notifier.clone();
+            let message = StreamHubEventMessage::HlsSegment {
+                identifier: StreamIdentifier::Rtmp {
+                    app_name: self.app_name.clone(),
+                    stream_name: self.stream_name.clone(),
+                },
+                segment_path: segment.path.clone(),
+                sequence: self.current_msn(),
+                duration,
+            };
+            tokio::spawn(async move { notifier.on_hls_segment_notify(&message).await });
+        }
+
         self.segments.push_back(segment);
 
+        if self.segments.len() > LL_HLS_PART_SEGMENT_COUNT {
+            let index = self.segments.len() - LL_HLS_PART_SEGMENT_COUNT - 1;
+            for part in std::mem::take(&mut self.segments[index].parts) {
+                self.ts_handler.delete(&part.path)?;
+            }
+        }
+
         Ok(())
     }
 
     pub fn clear(&mut self) -> Result<(), MediaError> {
         if self.need_record {
             let vod_m3u8_path = format!("{}/{}", self.m3u8_folder, self.vod_m3u8_name);
-            let mut file_handler = File::create(vod_m3u8_path).unwrap();
             self.vod_m3u8_content += "#EXT-X-ENDLIST\n";
-            file_handler.write_all(self.vod_m3u8_content.as_bytes())?;
+            self.storage.write(
+                &vod_m3u8_path,
+                BytesMut::from(self.vod_m3u8_content.as_bytes()),
+            )?;
         } else {
             for segment in &self.segments {
-                self.ts_handler.delete(segment.path.clone());
+                self.ts_handler.delete(&segment.path)?;
+            }
+            //the segments in the live playlist are deleted above
+            if let Some(dvr_window) = &self.dvr_window {
+                for segment in dvr_window.segments() {
+                    if segment.sequence_no < self.sequence_no {
+                        self.ts_handler.delete(&segment.path)?;
+                    }
+                }
+            }
+        }
+
+        if self.dvr_window.is_some() {
+            let dvr_m3u8_path = format!("{}/{}", self.m3u8_folder, self.dvr_m3u8_name);
+            self.storage.delete(&dvr_m3u8_path)?;
+        }
+
+        for segment in &self.segments {
+            for part in &segment.parts {
+                self.ts_handler.delete(&part.path)?;
             }
         }
+        for part in &self.parts {
+            self.ts_handler.delete(&part.path)?;
+        }
+
+        if let Some(playlist_notifier) = &self.playlist_notifier {
+            playlist_notifier.unregister(&self.app_name, &self.stream_name);
+        }
+
+        if self.is_fmp4 && !self.need_record {
+            let init_segment_path = format!("{}/{}", self.m3u8_folder, INIT_SEGMENT_NAME);
+            self.storage.delete(&init_segment_path)?;
+        }
 
         //clear live m3u8
         let live_m3u8_path = format!("{}/{}", self.m3u8_folder, self.live_m3u8_name);
-        fs::remove_file(live_m3u8_path)?;
+        self.storage.delete(&live_m3u8_path)?;
 
         Ok(())
     }
@@ -153,19 +342,51 @@ impl M3u8 {
             m3u8_header += "#EXT-X-PLAYLIST-TYPE:VOD\n";
             m3u8_header += "#EXT-X-ALLOW-CACHE:YES\n";
         } else {
+            if self.low_latency {
+                let part_target = self.part_duration as f64 / 1000.0;
+                m3u8_header += format!(
+                    "#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK={:.3}\n",
+                    part_target * 3.0
+                )
+                .as_str();
+                m3u8_header += format!("#EXT-X-PART-INF:PART-TARGET={part_target:.3}\n").as_str();
+            }
             m3u8_header += format!("#EXT-X-MEDIA-SEQUENCE:{}\n", self.sequence_no).as_str();
         }
 
+        if self.is_fmp4 {
+            m3u8_header += format!("#EXT-X-MAP:URI=\"{INIT_SEGMENT_NAME}\"\n").as_str();
+        }
+
         m3u8_header
     }
 
+    fn generate_parts(parts: &[Part]) -> String {
+        let mut parts_content = String::new();
+        for part in parts {
+            parts_content += format!(
+                "#EXT-X-PART:DURATION={:.3},URI=\"{}\"",
+                part.duration as f64 / 1000.0,
+                part.name
+            )
+            .as_str();
+            if part.independent {
+                parts_content += ",INDEPENDENT=YES";
+            }
+            parts_content += "\n";
+        }
+        parts_content
+    }
+
     pub fn refresh_playlist(&mut self) -> Result<String, MediaError> {
         let mut m3u8_content = self.generate_m3u8_header(false);
+        let mut is_eof = false;
 
         for segment in &self.segments {
             if segment.discontinuity {
                 m3u8_content += "#EXT-X-DISCONTINUITY\n";
             }
+            m3u8_content += Self::generate_parts(&segment.parts).as_str();
             m3u8_content += format!(
                 "#EXTINF:{:.3}\n{}\n",
                 segment.duration as f64 / 1000.0,
@@ -175,18 +396,77 @@ impl M3u8 {
 
             if segment.is_eof {
                 m3u8_content += "#EXT-X-ENDLIST\n";
+                is_eof = true;
                 break;
             }
         }
 
+        if self.low_latency && !is_eof {
+            m3u8_content += Self::generate_parts(&self.parts).as_str();
+            m3u8_content += format!(
+                "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"{}.{}.{}\"\n",
+                self.current_msn(),
+                self.parts.len(),
+                self.ts_handler.extension()
+            )
+            .as_str();
+        }
+
         let m3u8_path = format!("{}/{}", self.m3u8_folder, self.live_m3u8_name);
 
-        let mut file_handler = File::create(m3u8_path).unwrap();
-        file_handler.write_all(m3u8_content.as_bytes())?;
+        self.storage
+            .write(&m3u8_path, BytesMut::from(m3u8_content.as_bytes()))?;
+
+        if let Some(position_sender) = &self.position_sender {
+            position_sender.send_replace(PlaylistPosition {
+                msn: self.current_msn(),
+                part_count: self.parts.len() as u64,
+            });
+        }
 
         Ok(m3u8_content)
     }
 
+    //the media sequence number of the segment being produced
+    fn current_msn(&self) -> u64 {
+        self.sequence_no + self.segments.len() as u64
+    }
+
+    fn update_dvr_m3u8(&mut self, segment: &Segment) -> Result<(), MediaError> {
+        let mut header = "#EXTM3U\n".to_string();
+        header += format!("#EXT-X-VERSION:{}\n", self.version).as_str();
+        header += format!("#EXT-X-TARGETDURATION:{}\n", (self.duration + 999) / 1000).as_str();
+        let map = format!("#EXT-X-MAP:URI=\"{INIT_SEGMENT_NAME}\"\n");
+        let sequence_no = self.current_msn();
+
+        if let Some(dvr_window) = &mut self.dvr_window {
+            let dvr_segment = DvrSegment {
+                sequence_no,
+                //the segment is finished now
+                start_time: Utc::now().timestamp_millis() - segment.duration,
+                duration: segment.duration,
+                discontinuity: segment.discontinuity,
+                name: segment.name.clone(),
+                path: segment.path.clone(),
+            };
+            for expired_segment in dvr_window.add_segment(dvr_segment, self.sequence_no) {
+                if !self.need_record {
+                    self.ts_handler.delete(&expired_segment.path)?;
+                }
+            }
+            let storage = &self.storage;
+            dvr_window.remove_missing(|path| storage.exists(path));
+
+            let content =
+                dvr_window.generate_playlist(&header, self.is_fmp4.then_some(map.as_str()));
+            let dvr_m3u8_path = format!("{}/{}", self.m3u8_folder, self.dvr_m3u8_name);
+            self.storage
+                .write(&dvr_m3u8_path, BytesMut::from(content.as_bytes()))?;
+        }
+
+        Ok(())
+    }
+
     pub fn update_vod_m3u8(&mut self, segment: &Segment) {
         if segment.discontinuity {
             self.vod_m3u8_content += "#EXT-X-DISCONTINUITY\n";
@@ -199,3 +479,206 @@ impl M3u8 {
         .as_str();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::M3u8,
+        crate::{
+            playlist_notifier::{PlaylistNotifier, PlaylistPosition},
+            storage::{FileStorage, MemoryStorage, SharedSegmentStorage},
+        },
+        bytes::BytesMut,
+        std::{fs, sync::Arc},
+    };
+
+    #[test]
+    fn test_low_latency_playlist() {
+        let notifier = PlaylistNotifier::new();
+        let mut m3u8 = M3u8::new(
+            1000,
+            6,
+            String::from("test_ll_hls"),
+            String::from("test"),
+            false,
+            Arc::new(FileStorage::default()),
+        );
+        m3u8.set_low_latency(500, notifier.clone());
+
+        m3u8.add_part(500, true, BytesMut::from(&[0x47][..]))
+            .unwrap();
+        m3u8.add_part(480, false, BytesMut::from(&[0x47][..]))
+            .unwrap();
+        m3u8.add_segment(980, false, false, BytesMut::from(&[0x47][..]))
+            .unwrap();
+        m3u8.add_part(400, true, BytesMut::from(&[0x47][..]))
+            .unwrap();
+
+        let playlist = m3u8.refresh_playlist().unwrap();
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n\
+             #EXT-X-VERSION:9\n\
+             #EXT-X-TARGETDURATION:1\n\
+             #EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=1.500\n\
+             #EXT-X-PART-INF:PART-TARGET=0.500\n\
+             #EXT-X-MEDIA-SEQUENCE:0\n\
+             #EXT-X-PART:DURATION=0.500,URI=\"0.0.ts\",INDEPENDENT=YES\n\
+             #EXT-X-PART:DURATION=0.480,URI=\"0.1.ts\"\n\
+             #EXTINF:0.980\n\
+             0.ts\n\
+             #EXT-X-PART:DURATION=0.400,URI=\"1.0.ts\",INDEPENDENT=YES\n\
+             #EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"1.1.ts\"\n"
+        );
+
+        let receiver = notifier.subscribe("test_ll_hls", "test").unwrap();
+        assert_eq!(
+            *receiver.borrow(),
+            PlaylistPosition {
+                msn: 1,
+                part_count: 1
+            }
+        );
+
+        m3u8.clear().unwrap();
+        assert!(notifier.subscribe("test_ll_hls", "test").is_none());
+        fs::remove_dir_all("./test_ll_hls").unwrap();
+    }
+
+    #[test]
+    fn test_fmp4_playlist() {
+        let mut m3u8 = M3u8::new(
+            1000,
+            6,
+            String::from("test_fmp4_hls"),
+            String::from("test"),
+            false,
+            Arc::new(FileStorage::default()),
+        );
+        m3u8.set_fmp4();
+        m3u8.write_init_segment(BytesMut::from(&b"ftyp"[..]))
+            .unwrap();
+        m3u8.add_segment(1000, false, true, BytesMut::from(&b"moof"[..]))
+            .unwrap();
+
+        let playlist = m3u8.refresh_playlist().unwrap();
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n\
+             #EXT-X-VERSION:7\n\
+             #EXT-X-TARGETDURATION:1\n\
+             #EXT-X-MEDIA-SEQUENCE:0\n\
+             #EXT-X-MAP:URI=\"init.mp4\"\n\
+             #EXTINF:1.000\n\
+             0.m4s\n\
+             #EXT-X-ENDLIST\n"
+        );
+        assert!(fs::metadata("./test_fmp4_hls/test/init.mp4").is_ok());
+        assert!(fs::metadata("./test_fmp4_hls/test/0.m4s").is_ok());
+
+        m3u8.clear().unwrap();
+        assert!(fs::metadata("./test_fmp4_hls/test/init.mp4").is_err());
+        fs::remove_dir_all("./test_fmp4_hls").unwrap();
+    }
+
+    #[test]
+    fn test_memory_storage_playlist() {
+        let storage: SharedSegmentStorage = Arc::new(MemoryStorage::new(1024));
+        let mut m3u8 = M3u8::new(
+            1000,
+            2,
+            String::from("live"),
+            String::from("test"),
+            false,
+            storage.clone(),
+        );
+
+        for _ in 0..3 {
+            m3u8.add_segment(1000, false, false, BytesMut::from(&[0x47][..]))
+                .unwrap();
+        }
+        m3u8.refresh_playlist().unwrap();
+
+        //the segments out of the live window are removed from the storage
+        assert!(!storage.exists("live/test/0.ts"));
+        assert!(storage.exists("live/test/1.ts"));
+        assert!(storage.exists("live/test/2.ts"));
+        let playlist = storage.read("live/test/test.m3u8").unwrap();
+        assert!(std::str::from_utf8(&playlist[..])
+            .unwrap()
+            .contains("#EXT-X-MEDIA-SEQUENCE:1\n"));
+        assert!(fs::metadata("./live/test").is_err());
+
+        m3u8.clear().unwrap();
+        assert!(!storage.exists("live/test/2.ts"));
+        assert!(!storage.exists("live/test/test.m3u8"));
+    }
+
+    #[test]
+    fn test_dvr_window_playlist() {
+        let storage: SharedSegmentStorage = Arc::new(MemoryStorage::new(1024));
+        let mut m3u8 = M3u8::new(
+            1000,
+            2,
+            String::from("live"),
+            String::from("test"),
+            false,
+            storage.clone(),
+        );
+        m3u8.set_dvr_window(3000);
+
+        for _ in 0..5 {
+            m3u8.add_segment(1000, false, false, BytesMut::from(&[0x47][..]))
+                .unwrap();
+        }
+        m3u8.refresh_playlist().unwrap();
+
+        //the segments out of the live playlist are retained in the dvr window
+        assert!(!storage.exists("live/test/1.ts"));
+        assert!(storage.exists("live/test/2.ts"));
+        let playlist = storage.read("live/test/test.m3u8").unwrap();
+        assert!(std::str::from_utf8(&playlist[..])
+            .unwrap()
+            .contains("#EXT-X-MEDIA-SEQUENCE:3\n"));
+        let dvr_playlist = storage.read("live/test/dvr_test.m3u8").unwrap();
+        let dvr_playlist = std::str::from_utf8(&dvr_playlist[..]).unwrap();
+        assert!(dvr_playlist.contains("#EXT-X-MEDIA-SEQUENCE:2\n"));
+        assert_eq!(dvr_playlist.matches("#EXT-X-PROGRAM-DATE-TIME:").count(), 3);
+
+        m3u8.clear().unwrap();
+        assert!(!storage.exists("live/test/2.ts"));
+        assert!(!storage.exists("live/test/4.ts"));
+        assert!(!storage.exists("live/test/dvr_test.m3u8"));
+    }
+
+    #[test]
+    fn test_dvr_window_evicted_segments() {
+        //the memory storage holds only two segments of the dvr window
+        let storage: SharedSegmentStorage = Arc::new(MemoryStorage::new(250));
+        let mut m3u8 = M3u8::new(
+            1000,
+            2,
+            String::from("live"),
+            String::from("test"),
+            false,
+            storage.clone(),
+        );
+        m3u8.set_dvr_window(10000);
+
+        for _ in 0..5 {
+            m3u8.add_segment(1000, false, false, BytesMut::from(&[0x47; 100][..]))
+                .unwrap();
+        }
+
+        //the evicted segments are not referenced by the dvr playlist
+        assert!(!storage.exists("live/test/2.ts"));
+        assert!(storage.exists("live/test/3.ts"));
+        let dvr_playlist = storage.read("live/test/dvr_test.m3u8").unwrap();
+        let dvr_playlist = std::str::from_utf8(&dvr_playlist[..]).unwrap();
+        assert!(dvr_playlist.contains("#EXT-X-MEDIA-SEQUENCE:3\n"));
+        assert!(!dvr_playlist.contains("\n2.ts\n"));
+        assert_eq!(dvr_playlist.matches("#EXT-X-PROGRAM-DATE-TIME:").count(), 2);
+
+        m3u8.clear().unwrap();
+    }
+}
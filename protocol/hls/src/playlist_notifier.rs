@@ -0,0 +1,113 @@
+use {
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    },
+    tokio::sync::watch,
+};
+
+/*The position of the live edge of a low-latency playlist.
+msn is the media sequence number of the segment being produced,
+part_count is how many partial segments of it have been published.*/
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PlaylistPosition {
+    pub msn: u64,
+    pub part_count: u64,
+}
+
+impl PlaylistPosition {
+    //whether the playlist already contains segment `msn`(or partial segment `part` of it)
+    pub fn contains(&self, msn: u64, part: Option<u64>) -> bool {
+        match part {
+            Some(part) => self.msn > msn || (self.msn == msn && self.part_count > part),
+            None => self.msn > msn,
+        }
+    }
+}
+
+/*Shared between the remuxers and the hls server, the remuxers publish
+the playlist position after the playlist file is refreshed and the
+server uses it to hold blocking playlist reload requests.*/
+#[derive(Default, Clone)]
+pub struct PlaylistNotifier {
+    streams: Arc<Mutex<HashMap<String, watch::Receiver<PlaylistPosition>>>>,
+}
+
+impl PlaylistNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn key(app_name: &str, stream_name: &str) -> String {
+        format!("{app_name}/{stream_name}")
+    }
+
+    pub fn register(&self, app_name: &str, stream_name: &str) -> watch::Sender<PlaylistPosition> {
+        let (sender, receiver) = watch::channel(PlaylistPosition::default());
+        self.streams
+            .lock()
+            .unwrap()
+            .insert(Self::key(app_name, stream_name), receiver);
+        sender
+    }
+
+    pub fn unregister(&self, app_name: &str, stream_name: &str) {
+        self.streams
+            .lock()
+            .unwrap()
+            .remove(&Self::key(app_name, stream_name));
+    }
+
+    pub fn subscribe(
+        &self,
+        app_name: &str,
+        stream_name: &str,
+    ) -> Option<watch::Receiver<PlaylistPosition>> {
+        self.streams
+            .lock()
+            .unwrap()
+            .get(&Self::key(app_name, stream_name))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlaylistNotifier, PlaylistPosition};
+
+    #[test]
+    fn test_playlist_position_contains() {
+        let position = PlaylistPosition {
+            msn: 5,
+            part_count: 2,
+        };
+
+        assert!(position.contains(4, None));
+        assert!(!position.contains(5, None));
+        assert!(position.contains(5, Some(1)));
+        assert!(!position.contains(5, Some(2)));
+        assert!(position.contains(4, Some(10)));
+        assert!(!position.contains(6, Some(0)));
+    }
+
+    #[tokio::test]
+    async fn test_playlist_notifier() {
+        let notifier = PlaylistNotifier::new();
+        assert!(notifier.subscribe("live", "test").is_none());
+
+        let sender = notifier.register("live", "test");
+        let mut receiver = notifier.subscribe("live", "test").unwrap();
+
+        sender
+            .send(PlaylistPosition {
+                msn: 1,
+                part_count: 0,
+            })
+            .unwrap();
+        receiver.changed().await.unwrap();
+        assert!(receiver.borrow().contains(0, None));
+
+        notifier.unregister("live", "test");
+        assert!(notifier.subscribe("live", "test").is_none());
+    }
+}
@@ -1,7 +1,12 @@
 use {
-    super::{errors::HlsError, flv_data_receiver::FlvDataReceiver},
+    super::{
+        errors::HlsError, flv_data_receiver::FlvDataReceiver, playlist_notifier::PlaylistNotifier,
+        storage::SharedSegmentStorage,
+    },
+    std::sync::Arc,
     streamhub::{
         define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
+        notify::Notifier,
         stream::StreamIdentifier,
     },
 };
@@ -10,6 +15,16 @@ pub struct HlsRemuxer {
     client_event_consumer: BroadcastEventReceiver,
     event_producer: StreamHubEventSender,
     need_record: bool,
+    //shared with the hls server
+    storage: SharedSegmentStorage,
+    //the part duration(ms) and the notifier shared with the hls server if low-latency hls is enabled
+    low_latency: Option<(i64, PlaylistNotifier)>,
+    //fMP4 segments instead of MPEG-TS segments
+    fmp4: bool,
+    //the dvr window(ms) of timeshift, 0 means disabled
+    dvr_window: i64,
+    //notify the written segments
+    notifier: Option<Arc<dyn Notifier>>,
 }
 
 impl HlsRemuxer {
@@ -17,14 +32,36 @@ impl HlsRemuxer {
         consumer: BroadcastEventReceiver,
         event_producer: StreamHubEventSender,
         need_record: bool,
+        storage: SharedSegmentStorage,
     ) -> Self {
         Self {
             client_event_consumer: consumer,
             event_producer,
             need_record,
+            storage,
+            low_latency: None,
+            fmp4: false,
+            dvr_window: 0,
+            notifier: None,
         }
     }
 
+    pub fn set_low_latency(&mut self, part_duration: i64, playlist_notifier: PlaylistNotifier) {
+        self.low_latency = Some((part_duration, playlist_notifier));
+    }
+
+    pub fn set_fmp4(&mut self, fmp4: bool) {
+        self.fmp4 = fmp4;
+    }
+
+    pub fn set_dvr_window(&mut self, dvr_window: i64) {
+        self.dvr_window = dvr_window;
+    }
+
+    pub fn set_notifier(&mut self, notifier: Arc<dyn Notifier>) {
+        self.notifier = Some(notifier);
+    }
+
     pub async fn run(&mut self) -> Result<(), HlsError> {
         loop {
             let val = self.client_event_consumer.recv().await?;
@@ -41,7 +78,21 @@ impl HlsRemuxer {
                             self.event_producer.clone(),
                             5,
                             self.need_record,
+                            self.storage.clone(),
                         );
+                        if let Some((part_duration, playlist_notifier)) = &self.low_latency {
+                            rtmp_subscriber
+                                .set_low_latency(*part_duration, playlist_notifier.clone());
+                        }
+                        if self.fmp4 {
+                            rtmp_subscriber.set_fmp4();
+                        }
+                        if self.dvr_window > 0 {
+                            rtmp_subscriber.set_dvr_window(self.dvr_window);
+                        }
+                        if let Some(notifier) = &self.notifier {
+                            rtmp_subscriber.set_notifier(notifier.clone());
+                        }
 
                         tokio::spawn(async move {
                             if let Err(err) = rtmp_subscriber.run().await {
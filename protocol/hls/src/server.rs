@@ -1,15 +1,35 @@
 use {
+    super::{
+        dvr::generate_time_range_playlist,
+        playlist_notifier::{PlaylistNotifier, PlaylistPosition},
+        storage::SharedSegmentStorage,
+        url_signer::UrlSigner,
+    },
     axum::{
         body::Body,
-        extract::{Request, State},
+        extract::{ConnectInfo, Request, State},
         handler::Handler,
         http::StatusCode,
         response::Response,
+        serve::IncomingStream,
+        Extension,
+    },
+    chrono::Utc,
+    commonlib::{
+        acl::{AccessControl, ConnectionAccess},
+        auth::{Auth, SecretCarrier},
+        errors::{AclError, AclErrorValue},
     },
-    commonlib::auth::{Auth, SecretCarrier},
-    std::net::SocketAddr,
-    tokio::{fs::File, net::TcpListener},
-    tokio_util::codec::{BytesCodec, FramedRead},
+    std::{
+        convert::Infallible,
+        future::ready,
+        net::{IpAddr, SocketAddr},
+        sync::Arc,
+        time::Duration,
+    },
+    streamhub::{cluster::StreamRegistry, statistics::metrics},
+    tokio::{net::TcpListener, sync::watch, time::timeout},
+    tower::{service_fn, Layer},
 };
 
 type GenericError = Box<dyn std::error::Error + Send + Sync>;
@@ -17,21 +37,43 @@ type Result<T> = std::result::Result<T, GenericError>;
 
 static NOTFOUND: &[u8] = b"Not Found";
 static UNAUTHORIZED: &[u8] = b"Unauthorized";
+static FORBIDDEN: &[u8] = b"Forbidden";
+static BADREQUEST: &[u8] = b"Bad Request";
+
+//the max time a blocking playlist reload(or a preload hint) request is held
+const BLOCKING_RELOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+struct HlsServerState {
+    auth: Option<Auth>,
+    playlist_notifier: PlaylistNotifier,
+    storage: SharedSegmentStorage,
+    //the players of the streams published on other nodes are redirected
+    registry: Option<Arc<dyn StreamRegistry>>,
+    //every request must carry a valid signature if set
+    url_signer: Option<UrlSigner>,
+}
 
 #[derive(Debug)]
 enum HlsFileType {
     Playlist,
     Segment,
+    Fmp4Segment,
+    InitSegment,
 }
 
 impl HlsFileType {
     const CONTENT_TYPE_PLAYLIST: &'static str = "application/vnd.apple.mpegurl";
     const CONTENT_TYPE_SEGMENT: &'static str = "video/mp2t";
+    const CONTENT_TYPE_FMP4_SEGMENT: &'static str = "video/iso.segment";
+    const CONTENT_TYPE_INIT_SEGMENT: &'static str = "video/mp4";
 
     fn content_type(&self) -> &str {
         match self {
             Self::Playlist => Self::CONTENT_TYPE_PLAYLIST,
             Self::Segment => Self::CONTENT_TYPE_SEGMENT,
+            Self::Fmp4Segment => Self::CONTENT_TYPE_FMP4_SEGMENT,
+            Self::InitSegment => Self::CONTENT_TYPE_INIT_SEGMENT,
         }
     }
 }
@@ -47,6 +89,8 @@ struct HlsPath {
 impl HlsPath {
     const M3U8_EXT: &'static str = "m3u8";
     const TS_EXT: &'static str = "ts";
+    const M4S_EXT: &'static str = "m4s";
+    const MP4_EXT: &'static str = "mp4";
 
     fn parse(path: &str) -> Option<Self> {
         if path.is_empty() || path.contains("..") {
@@ -69,6 +113,8 @@ impl HlsPath {
         let file_type = match ext {
             Self::M3U8_EXT => HlsFileType::Playlist,
             Self::TS_EXT => HlsFileType::Segment,
+            Self::M4S_EXT => HlsFileType::Fmp4Segment,
+            Self::MP4_EXT => HlsFileType::InitSegment,
             _ => return None,
         };
 
@@ -80,16 +126,168 @@ impl HlsPath {
         })
     }
 
-    fn to_file_path(&self) -> String {
+    //the path of the file in the segment storage
+    fn to_storage_path(&self) -> String {
         let ext = match self.file_type {
             HlsFileType::Playlist => Self::M3U8_EXT,
             HlsFileType::Segment => Self::TS_EXT,
+            HlsFileType::Fmp4Segment => Self::M4S_EXT,
+            HlsFileType::InitSegment => Self::MP4_EXT,
         };
         format!(
-            "./{}/{}/{}.{}",
+            "{}/{}/{}.{}",
             self.app_name, self.stream_name, self.file_name, ext
         )
     }
+
+    //whether it is the partial segment in the preload hint of the playlist, which
+    //is named "{msn}.{part}" and is the next one to be written
+    fn is_preload_hint(&self, position: &PlaylistPosition) -> bool {
+        let Some((msn, part)) = self.file_name.split_once('.') else {
+            return false;
+        };
+        msn.parse::<u64>() == Ok(position.msn) && part.parse::<u64>() == Ok(position.part_count)
+    }
+}
+
+//the _HLS_msn/_HLS_part delivery directives of a low-latency playlist request
+#[derive(Debug, PartialEq)]
+struct BlockingReload {
+    msn: u64,
+    part: Option<u64>,
+}
+
+impl BlockingReload {
+    const MSN_DIRECTIVE: &'static str = "_HLS_msn";
+    const PART_DIRECTIVE: &'static str = "_HLS_part";
+
+    //Err if a directive is malformed or _HLS_part is given without _HLS_msn
+    fn parse(query: &str) -> std::result::Result<Option<Self>, ()> {
+        let mut msn = None;
+        let mut part = None;
+
+        for pair in query.split('&') {
+            if let Some((key, value)) = pair.split_once('=') {
+                match key {
+                    Self::MSN_DIRECTIVE => msn = Some(value.parse::<u64>().map_err(|_| ())?),
+                    Self::PART_DIRECTIVE => part = Some(value.parse::<u64>().map_err(|_| ())?),
+                    _ => {}
+                }
+            }
+        }
+
+        match (msn, part) {
+            (Some(msn), part) => Ok(Some(Self { msn, part })),
+            (None, Some(_)) => Err(()),
+            (None, None) => Ok(None),
+        }
+    }
+
+    //false if the requested segment is too far beyond the live edge
+    async fn wait(&self, mut receiver: watch::Receiver<PlaylistPosition>) -> bool {
+        //the segment being produced is listed by its parts, and a segment up to
+        //two beyond it may be requested
+        if self.msn > receiver.borrow().msn + 2 {
+            return false;
+        }
+
+        let _ = timeout(
+            BLOCKING_RELOAD_TIMEOUT,
+            receiver.wait_for(|position| position.contains(self.msn, self.part)),
+        )
+        .await;
+
+        true
+    }
+}
+
+//the start=<unix-ts>&end=<unix-ts> parameters of a timeshift playlist request
+#[derive(Debug, PartialEq)]
+struct TimeRange {
+    start: Option<i64>,
+    end: Option<i64>,
+}
+
+impl TimeRange {
+    const START_PARAM: &'static str = "start";
+    const END_PARAM: &'static str = "end";
+
+    //Err if a parameter is malformed
+    fn parse(query: &str) -> std::result::Result<Option<Self>, ()> {
+        let mut start = None;
+        let mut end = None;
+
+        for pair in query.split('&') {
+            if let Some((key, value)) = pair.split_once('=') {
+                match key {
+                    Self::START_PARAM => start = Some(value.parse::<i64>().map_err(|_| ())?),
+                    Self::END_PARAM => end = Some(value.parse::<i64>().map_err(|_| ())?),
+                    _ => {}
+                }
+            }
+        }
+
+        if start.is_none() && end.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self { start, end }))
+    }
+
+    //the playlist of the time range is built from the dvr playlist
+    async fn response_playlist(
+        &self,
+        storage: SharedSegmentStorage,
+        hls_path: &HlsPath,
+    ) -> Response<Body> {
+        let dvr_playlist_path = format!(
+            "{}/{}/dvr_{}.m3u8",
+            hls_path.app_name, hls_path.stream_name, hls_path.stream_name
+        );
+        let data = tokio::task::spawn_blocking(move || storage.read(&dvr_playlist_path))
+            .await
+            .unwrap_or_default();
+
+        let playlist = data.and_then(|data| {
+            generate_time_range_playlist(&String::from_utf8_lossy(&data), self.start, self.end)
+        });
+
+        match playlist {
+            Some(playlist) => Response::builder()
+                .header("Content-Type", hls_path.file_type.content_type())
+                .body(Body::from(playlist))
+                .unwrap(),
+            None => response_not_found(),
+        }
+    }
+}
+
+//hold the request of the hinted partial segment until it is written, the other
+//missing files(e.g. evicted or mistyped ones) are responded with 404 at once
+async fn wait_for_file(
+    storage: &SharedSegmentStorage,
+    hls_path: &HlsPath,
+    mut receiver: watch::Receiver<PlaylistPosition>,
+) {
+    let file_path = hls_path.to_storage_path();
+    if storage.exists(&file_path) || !hls_path.is_preload_hint(&receiver.borrow()) {
+        return;
+    }
+
+    let _ = timeout(BLOCKING_RELOAD_TIMEOUT, async {
+        while !storage.exists(&file_path) {
+            if receiver.changed().await.is_err() {
+                break;
+            }
+        }
+    })
+    .await;
+}
+
+fn response_bad_request() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .body(BADREQUEST.into())
+        .unwrap()
 }
 
 fn response_unauthorized() -> Response<Body> {
@@ -99,6 +297,32 @@ fn response_unauthorized() -> Response<Body> {
         .unwrap()
 }
 
+fn response_forbidden() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(FORBIDDEN.into())
+        .unwrap()
+}
+
+fn response_acl_rejected(err: &AclError) -> Response<Body> {
+    let status = match err.value {
+        AclErrorValue::IpNotAllowed => StatusCode::FORBIDDEN,
+        _ => StatusCode::TOO_MANY_REQUESTS,
+    };
+    Response::builder()
+        .status(status)
+        .body(status.canonical_reason().unwrap_or_default().into())
+        .unwrap()
+}
+
+fn response_redirect(location: String) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::FOUND)
+        .header("Location", location)
+        .body(Body::empty())
+        .unwrap()
+}
+
 fn response_not_found() -> Response<Body> {
     Response::builder()
         .status(StatusCode::NOT_FOUND)
@@ -106,21 +330,64 @@ fn response_not_found() -> Response<Body> {
         .unwrap()
 }
 
-async fn response_file(hls_path: &HlsPath) -> Response<Body> {
-    let file_path = hls_path.to_file_path();
+async fn response_file(storage: SharedSegmentStorage, hls_path: &HlsPath) -> Response<Body> {
+    let file_path = hls_path.to_storage_path();
 
-    if let Ok(file) = File::open(&file_path).await {
-        let builder = Response::builder().header("Content-Type", hls_path.file_type.content_type());
+    //the file storage reads from the disk, so do not block the runtime
+    let data = tokio::task::spawn_blocking(move || storage.read(&file_path))
+        .await
+        .unwrap_or_default();
 
-        // Serve a file by asynchronously reading it by chunks using tokio-util crate.
-        let stream = FramedRead::new(file, BytesCodec::new());
-        return builder.body(Body::from_stream(stream)).unwrap();
+    if let Some(data) = data {
+        return Response::builder()
+            .header("Content-Type", hls_path.file_type.content_type())
+            .body(Body::from(data))
+            .unwrap();
     }
 
     response_not_found()
 }
 
-async fn handle_connection(State(auth): State<Option<Auth>>, req: Request<Body>) -> Response<Body> {
+//rewrite the uris of the served playlist with fresh signatures
+async fn sign_playlist(
+    state: &HlsServerState,
+    hls_path: &HlsPath,
+    path: &str,
+    ip: IpAddr,
+    response: Response<Body>,
+) -> Response<Body> {
+    let Some(url_signer) = &state.url_signer else {
+        return response;
+    };
+    if !matches!(hls_path.file_type, HlsFileType::Playlist) || response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let data = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(data) => data,
+        Err(_) => return response_not_found(),
+    };
+    let playlist = url_signer.rewrite_playlist(
+        &String::from_utf8_lossy(&data),
+        path,
+        ip,
+        Utc::now().timestamp(),
+    );
+    Response::from_parts(parts, Body::from(playlist))
+}
+
+async fn handle_connection(
+    State(state): State<HlsServerState>,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
+    Extension(access): Extension<ConnectionAccess>,
+    req: Request<Body>,
+) -> Response<Body> {
+    //the connection is checked at accept time, all its requests are rejected
+    if let Err(err) = access.result() {
+        return response_acl_rejected(err);
+    }
+
     let path = req.uri().path();
     let query_string = req.uri().query().map(|s| s.to_string());
 
@@ -129,23 +396,97 @@ async fn handle_connection(State(auth): State<Option<Auth>>, req: Request<Body>)
         None => return response_not_found(),
     };
 
-    if let (Some(auth_val), HlsFileType::Playlist) = (auth.as_ref(), &hls_path.file_type) {
+    if let Some(url_signer) = &state.url_signer {
+        if !url_signer.verify(
+            path,
+            query_string.as_deref().unwrap_or_default(),
+            remote_addr.ip(),
+            Utc::now().timestamp(),
+        ) {
+            metrics::inc_auth_failure("hls");
+            return response_forbidden();
+        }
+    }
+
+    if let (Some(auth_val), HlsFileType::Playlist) = (state.auth.as_ref(), &hls_path.file_type) {
         if auth_val
             .authenticate(
+                &hls_path.app_name,
                 &hls_path.stream_name,
-                &query_string.map(SecretCarrier::Query),
+                &query_string.clone().map(SecretCarrier::Query),
                 true,
             )
             .is_err()
         {
+            metrics::inc_auth_failure("hls");
             return response_unauthorized();
         }
     }
 
-    response_file(&hls_path).await
+    if let Some(registry) = &state.registry {
+        if !state.storage.exists(&hls_path.to_storage_path()) {
+            if let Some(address) = registry
+                .locate(&format!("{}/{}", hls_path.app_name, hls_path.stream_name))
+                .and_then(|node| node.hls_address)
+            {
+                return response_redirect(format!("http://{}{}", address, req.uri()));
+            }
+        }
+    }
+
+    if let (HlsFileType::Playlist, true) = (
+        &hls_path.file_type,
+        hls_path.file_name == hls_path.stream_name,
+    ) {
+        match TimeRange::parse(query_string.as_deref().unwrap_or_default()) {
+            Ok(Some(time_range)) => {
+                let response = time_range
+                    .response_playlist(state.storage.clone(), &hls_path)
+                    .await;
+                return sign_playlist(&state, &hls_path, path, remote_addr.ip(), response).await;
+            }
+            Ok(None) => {}
+            Err(_) => return response_bad_request(),
+        }
+    }
+
+    //only the streams remuxed in low-latency mode are registered
+    if let Some(receiver) = state
+        .playlist_notifier
+        .subscribe(&hls_path.app_name, &hls_path.stream_name)
+    {
+        match hls_path.file_type {
+            HlsFileType::Playlist => {
+                match BlockingReload::parse(query_string.as_deref().unwrap_or_default()) {
+                    Ok(Some(blocking_reload)) => {
+                        if !blocking_reload.wait(receiver).await {
+                            return response_bad_request();
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(_) => return response_bad_request(),
+                }
+            }
+            HlsFileType::Segment | HlsFileType::Fmp4Segment => {
+                wait_for_file(&state.storage, &hls_path, receiver).await;
+            }
+            HlsFileType::InitSegment => {}
+        }
+    }
+
+    let response = response_file(state.storage.clone(), &hls_path).await;
+    sign_playlist(&state, &hls_path, path, remote_addr.ip(), response).await
 }
 
-pub async fn run(port: usize, auth: Option<Auth>) -> Result<()> {
+pub async fn run(
+    port: usize,
+    auth: Option<Auth>,
+    playlist_notifier: PlaylistNotifier,
+    storage: SharedSegmentStorage,
+    registry: Option<Arc<dyn StreamRegistry>>,
+    url_signer: Option<UrlSigner>,
+    access_control: Option<Arc<AccessControl>>,
+) -> Result<()> {
     let listen_address = format!("0.0.0.0:{port}");
     let sock_addr: SocketAddr = listen_address.parse().unwrap();
 
@@ -153,16 +494,39 @@ pub async fn run(port: usize, auth: Option<Auth>) -> Result<()> {
 
     log::info!("Hls server listening on http://{}", sock_addr);
 
-    let handle_connection = handle_connection.with_state(auth);
+    let handle_connection = handle_connection.with_state(HlsServerState {
+        auth,
+        playlist_notifier,
+        storage,
+        registry,
+        url_signer,
+    });
+
+    //the connections of the players are checked by the access control when they are accepted
+    let make_service = service_fn(move |stream: IncomingStream<'_>| {
+        let remote_addr = stream.remote_addr();
+        let access = ConnectionAccess::check(&access_control, remote_addr.ip(), true);
+        if let Err(err) = access.result() {
+            log::warn!("hls connection from {} is rejected: {}", remote_addr, err);
+            metrics::inc_acl_rejection("hls");
+        }
+        let service = Layer::layer(
+            &(Extension(access), Extension(ConnectInfo(remote_addr))),
+            handle_connection.clone(),
+        );
+        ready(Ok::<_, Infallible>(service))
+    });
 
-    axum::serve(listener, handle_connection.into_make_service()).await?;
+    axum::serve(listener, make_service).await?;
 
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{HlsFileType, HlsPath};
+    use super::{BlockingReload, HlsFileType, HlsPath, TimeRange};
+    use crate::playlist_notifier::PlaylistPosition;
+    use tokio::sync::watch;
 
     #[test]
     fn test_hls_path_parse() {
@@ -172,7 +536,7 @@ mod tests {
         assert_eq!(playlist.stream_name, "stream");
         assert_eq!(playlist.file_name, "stream");
         assert!(matches!(playlist.file_type, HlsFileType::Playlist));
-        assert_eq!(playlist.to_file_path(), "./live/stream/stream.m3u8");
+        assert_eq!(playlist.to_storage_path(), "live/stream/stream.m3u8");
         assert_eq!(
             playlist.file_type.content_type(),
             "application/vnd.apple.mpegurl"
@@ -184,14 +548,30 @@ mod tests {
         assert_eq!(segment.stream_name, "stream");
         assert_eq!(segment.file_name, "123");
         assert!(matches!(segment.file_type, HlsFileType::Segment));
-        assert_eq!(segment.to_file_path(), "./live/stream/123.ts");
+        assert_eq!(segment.to_storage_path(), "live/stream/123.ts");
         assert_eq!(segment.file_type.content_type(), "video/mp2t");
 
+        // Partial segment
+        let part = HlsPath::parse("/live/stream/123.4.ts").unwrap();
+        assert_eq!(part.file_name, "123.4");
+        assert!(matches!(part.file_type, HlsFileType::Segment));
+        assert_eq!(part.to_storage_path(), "live/stream/123.4.ts");
+
+        // fMP4 segment and init segment
+        let fmp4_segment = HlsPath::parse("/live/stream/123.m4s").unwrap();
+        assert!(matches!(fmp4_segment.file_type, HlsFileType::Fmp4Segment));
+        assert_eq!(fmp4_segment.to_storage_path(), "live/stream/123.m4s");
+        assert_eq!(fmp4_segment.file_type.content_type(), "video/iso.segment");
+        let init_segment = HlsPath::parse("/live/stream/init.mp4").unwrap();
+        assert!(matches!(init_segment.file_type, HlsFileType::InitSegment));
+        assert_eq!(init_segment.to_storage_path(), "live/stream/init.mp4");
+        assert_eq!(init_segment.file_type.content_type(), "video/mp4");
+
         // Negative
         assert!(HlsPath::parse("").is_none());
         assert!(HlsPath::parse("/invalid").is_none());
         assert!(HlsPath::parse("/too/many/parts/of/path.m3u8").is_none());
-        assert!(HlsPath::parse("/live/stream/invalid.mp4").is_none());
+        assert!(HlsPath::parse("/live/stream/invalid.flv").is_none());
         assert!(HlsPath::parse("/live/stream/../../etc/passwd").is_none());
         assert!(HlsPath::parse("/live/stream/...").is_none());
         assert!(HlsPath::parse("/live/stream.m3u8").is_none());
@@ -204,4 +584,86 @@ mod tests {
         assert!(HlsPath::parse("/live/stream/file.M3U8").is_none());
         assert!(HlsPath::parse("/live/stream/file.TS").is_none());
     }
+
+    #[test]
+    fn test_is_preload_hint() {
+        let position = PlaylistPosition {
+            msn: 123,
+            part_count: 4,
+        };
+        let is_preload_hint = |path: &str| HlsPath::parse(path).unwrap().is_preload_hint(&position);
+        assert!(is_preload_hint("/live/stream/123.4.ts"));
+        assert!(is_preload_hint("/live/stream/123.4.m4s"));
+        //written, evicted or mistyped files
+        assert!(!is_preload_hint("/live/stream/123.3.ts"));
+        assert!(!is_preload_hint("/live/stream/122.4.ts"));
+        assert!(!is_preload_hint("/live/stream/123.5.ts"));
+        assert!(!is_preload_hint("/live/stream/123.ts"));
+        assert!(!is_preload_hint("/live/stream/abc.4.ts"));
+    }
+
+    #[test]
+    fn test_blocking_reload_parse() {
+        assert_eq!(BlockingReload::parse(""), Ok(None));
+        assert_eq!(BlockingReload::parse("token=abc"), Ok(None));
+        assert_eq!(
+            BlockingReload::parse("_HLS_msn=10"),
+            Ok(Some(BlockingReload {
+                msn: 10,
+                part: None
+            }))
+        );
+        assert_eq!(
+            BlockingReload::parse("token=abc&_HLS_msn=10&_HLS_part=2"),
+            Ok(Some(BlockingReload {
+                msn: 10,
+                part: Some(2)
+            }))
+        );
+        assert!(BlockingReload::parse("_HLS_part=2").is_err());
+        assert!(BlockingReload::parse("_HLS_msn=abc").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_reload_wait() {
+        let (sender, receiver) = watch::channel(PlaylistPosition {
+            msn: 5,
+            part_count: 0,
+        });
+
+        let too_far = BlockingReload { msn: 8, part: None };
+        assert!(!too_far.wait(receiver.clone()).await);
+
+        tokio::spawn(async move {
+            sender
+                .send(PlaylistPosition {
+                    msn: 8,
+                    part_count: 0,
+                })
+                .unwrap();
+            sender.closed().await;
+        });
+        let reload = BlockingReload { msn: 7, part: None };
+        assert!(reload.wait(receiver).await);
+    }
+
+    #[test]
+    fn test_time_range_parse() {
+        assert_eq!(TimeRange::parse("token=abc"), Ok(None));
+        assert_eq!(
+            TimeRange::parse("start=1704164640&token=abc"),
+            Ok(Some(TimeRange {
+                start: Some(1704164640),
+                end: None
+            }))
+        );
+        assert_eq!(
+            TimeRange::parse("start=1704164640&end=1704168240"),
+            Ok(Some(TimeRange {
+                start: Some(1704164640),
+                end: Some(1704168240)
+            }))
+        );
+        assert!(TimeRange::parse("end=abc").is_err());
+    }
 }
@@ -0,0 +1,311 @@
+use {
+    super::errors::MediaError,
+    bytes::{Bytes, BytesMut},
+    std::{
+        collections::{HashMap, VecDeque},
+        fs,
+        io::ErrorKind,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    },
+};
+
+/*Where the playlists and the segments are stored. It is shared between
+the remuxers(writers) and the hls server(reader), the files are identified
+by their relative path: {app_name}/{stream_name}/{file_name}.*/
+pub trait SegmentStorage: Send + Sync {
+    fn write(&self, path: &str, data: BytesMut) -> Result<(), MediaError>;
+    fn read(&self, path: &str) -> Option<Bytes>;
+    fn exists(&self, path: &str) -> bool;
+    //deleting a file which does not exist is not an error
+    fn delete(&self, path: &str) -> Result<(), MediaError>;
+}
+
+pub type SharedSegmentStorage = Arc<dyn SegmentStorage>;
+
+pub struct FileStorage {
+    root_dir: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(root_dir: &str) -> Self {
+        Self {
+            root_dir: PathBuf::from(root_dir),
+        }
+    }
+}
+
+impl Default for FileStorage {
+    fn default() -> Self {
+        Self::new(".")
+    }
+}
+
+impl SegmentStorage for FileStorage {
+    fn write(&self, path: &str, data: BytesMut) -> Result<(), MediaError> {
+        let file_path = self.root_dir.join(path);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(file_path, &data[..])?;
+        Ok(())
+    }
+
+    fn read(&self, path: &str) -> Option<Bytes> {
+        fs::read(self.root_dir.join(path)).ok().map(Bytes::from)
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.root_dir.join(path).is_file()
+    }
+
+    fn delete(&self, path: &str) -> Result<(), MediaError> {
+        match fs::remove_file(self.root_dir.join(path)) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+//the playlists and the init segments are rewritten in place, they are never evicted
+fn is_evictable(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    !(file_name.ends_with(".m3u8") || file_name.ends_with(".mpd") || file_name.starts_with("init"))
+}
+
+//the stream of a file is the directory of its path: {app_name}/{stream_name}
+fn stream_of(path: &str) -> &str {
+    path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+struct MemoryFile {
+    data: Bytes,
+    //identifies the write of the file in the segment window
+    seq: u64,
+}
+
+//the segments of a stream in the write order
+#[derive(Default)]
+struct SegmentWindow {
+    //the entries of the deleted or rewritten files are skipped by the seq
+    order: VecDeque<(String, u64)>,
+    size: usize,
+}
+
+#[derive(Default)]
+struct MemoryStorageState {
+    files: HashMap<String, MemoryFile>,
+    windows: HashMap<String, SegmentWindow>,
+    next_seq: u64,
+}
+
+impl MemoryStorageState {
+    fn remove(&mut self, path: &str) {
+        if let Some(file) = self.files.remove(path) {
+            if let Some(window) = self.windows.get_mut(stream_of(path)) {
+                if is_evictable(path) {
+                    window.size -= file.data.len();
+                }
+            }
+        }
+    }
+
+    //pops the entries of the removed files at the front of the window
+    fn pop_stale(&mut self, stream: &str) {
+        let Some(window) = self.windows.get_mut(stream) else {
+            return;
+        };
+        while let Some((path, seq)) = window.order.front() {
+            if self.files.get(path).is_some_and(|file| file.seq == *seq) {
+                break;
+            }
+            window.order.pop_front();
+        }
+        if window.order.is_empty() {
+            self.windows.remove(stream);
+        }
+    }
+}
+
+/*Keeps the files in memory. The segments of every stream are kept in a window:
+when the total size of the segments of a stream exceeds the capacity, its oldest
+segments are evicted, the other streams are not affected. The playlists and the
+init segments are never evicted, they are deleted by the remuxers.*/
+pub struct MemoryStorage {
+    //the max total size(bytes) of the segments of one stream
+    capacity: usize,
+    state: Mutex<MemoryStorageState>,
+}
+
+impl MemoryStorage {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(MemoryStorageState::default()),
+        }
+    }
+}
+
+impl SegmentStorage for MemoryStorage {
+    fn write(&self, path: &str, data: BytesMut) -> Result<(), MediaError> {
+        let mut state = self.state.lock().unwrap();
+        state.remove(path);
+
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        let size = data.len();
+        state.files.insert(
+            path.to_string(),
+            MemoryFile {
+                data: data.freeze(),
+                seq,
+            },
+        );
+
+        let stream = stream_of(path);
+        if is_evictable(path) {
+            let window = state.windows.entry(stream.to_string()).or_default();
+            window.order.push_back((path.to_string(), seq));
+            window.size += size;
+        }
+
+        loop {
+            state.pop_stale(stream);
+            let MemoryStorageState { files, windows, .. } = &mut *state;
+            let Some(window) = windows.get_mut(stream) else {
+                break;
+            };
+            //the segment being written is kept
+            if window.size <= self.capacity || window.order.len() <= 1 {
+                break;
+            }
+            if let Some((oldest, _)) = window.order.pop_front() {
+                if let Some(file) = files.remove(&oldest) {
+                    window.size -= file.data.len();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(&self, path: &str) -> Option<Bytes> {
+        self.state
+            .lock()
+            .unwrap()
+            .files
+            .get(path)
+            .map(|file| file.data.clone())
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.state.lock().unwrap().files.contains_key(path)
+    }
+
+    fn delete(&self, path: &str) -> Result<(), MediaError> {
+        let mut state = self.state.lock().unwrap();
+        state.remove(path);
+        state.pop_stale(stream_of(path));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{FileStorage, MemoryStorage, SegmentStorage},
+        bytes::BytesMut,
+        std::fs,
+    };
+
+    #[test]
+    fn test_memory_storage() {
+        let storage = MemoryStorage::new(10);
+
+        storage
+            .write("live/test/0.ts", BytesMut::from(&[0; 4][..]))
+            .unwrap();
+        storage
+            .write("live/test/1.ts", BytesMut::from(&[1; 4][..]))
+            .unwrap();
+        assert_eq!(storage.read("live/test/0.ts").unwrap().len(), 4);
+
+        //the oldest segment is evicted when the capacity is exceeded
+        storage
+            .write("live/test/2.ts", BytesMut::from(&[2; 4][..]))
+            .unwrap();
+        assert!(!storage.exists("live/test/0.ts"));
+        assert!(storage.exists("live/test/1.ts"));
+        assert!(storage.exists("live/test/2.ts"));
+
+        //a rewritten segment becomes the newest one
+        storage
+            .write("live/test/1.ts", BytesMut::from(&[1; 4][..]))
+            .unwrap();
+        storage
+            .write("live/test/3.ts", BytesMut::from(&[3; 3][..]))
+            .unwrap();
+        assert!(!storage.exists("live/test/2.ts"));
+        assert_eq!(&storage.read("live/test/1.ts").unwrap()[..], &[1; 4][..]);
+
+        storage.delete("live/test/1.ts").unwrap();
+        storage.delete("live/test/1.ts").unwrap();
+        assert!(storage.read("live/test/1.ts").is_none());
+    }
+
+    #[test]
+    fn test_memory_storage_window() {
+        let storage = MemoryStorage::new(10);
+
+        storage
+            .write("live/a/init.mp4", BytesMut::from(&[0; 8][..]))
+            .unwrap();
+        storage
+            .write("live/a/a.m3u8", BytesMut::from(&[0; 8][..]))
+            .unwrap();
+        storage
+            .write("live/a/dvr_a.m3u8", BytesMut::from(&[0; 8][..]))
+            .unwrap();
+        storage
+            .write("live/b/0.ts", BytesMut::from(&[0; 8][..]))
+            .unwrap();
+        for i in 0..4 {
+            storage
+                .write(&format!("live/a/{i}.m4s"), BytesMut::from(&[0; 4][..]))
+                .unwrap();
+        }
+
+        //the playlists, the init segment and the segments of other streams are kept
+        assert!(storage.exists("live/a/init.mp4"));
+        assert!(storage.exists("live/a/a.m3u8"));
+        assert!(storage.exists("live/a/dvr_a.m3u8"));
+        assert!(storage.exists("live/b/0.ts"));
+        assert!(!storage.exists("live/a/1.m4s"));
+        assert!(storage.exists("live/a/2.m4s"));
+        assert!(storage.exists("live/a/3.m4s"));
+
+        //the entries of the deleted segments are released
+        for i in 2..4 {
+            storage.delete(&format!("live/a/{i}.m4s")).unwrap();
+        }
+        storage.delete("live/b/0.ts").unwrap();
+        assert!(storage.state.lock().unwrap().windows.is_empty());
+    }
+
+    #[test]
+    fn test_file_storage() {
+        let storage = FileStorage::new("./test_hls_storage");
+
+        storage
+            .write("live/test/0.ts", BytesMut::from(&b"data"[..]))
+            .unwrap();
+        assert!(fs::metadata("./test_hls_storage/live/test/0.ts").is_ok());
+        assert_eq!(&storage.read("live/test/0.ts").unwrap()[..], b"data");
+
+        storage.delete("live/test/0.ts").unwrap();
+        storage.delete("live/test/0.ts").unwrap();
+        assert!(!storage.exists("live/test/0.ts"));
+
+        fs::remove_dir_all("./test_hls_storage").unwrap();
+    }
+}
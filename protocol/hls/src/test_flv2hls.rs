@@ -2,6 +2,7 @@
 mod tests {
     use crate::errors::MediaError;
     use crate::flv2hls::Flv2HlsRemuxer;
+    use crate::storage::FileStorage;
     use bytes::BytesMut;
     use xflv::define::FlvData;
 
@@ -9,6 +10,7 @@ mod tests {
 
     use std::fs::File;
     use std::io::prelude::*;
+    use std::sync::Arc;
     use std::time::Instant;
 
     #[allow(dead_code)]
@@ -64,8 +66,13 @@ mod tests {
         demuxer.read_flv_header()?;
 
         let start = Instant::now();
-        let mut media_demuxer =
-            Flv2HlsRemuxer::new(5, String::from("live"), String::from("test"), false);
+        let mut media_demuxer = Flv2HlsRemuxer::new(
+            5,
+            String::from("live"),
+            String::from("test"),
+            false,
+            Arc::new(FileStorage::default()),
+        );
 
         loop {
             let data_ = demuxer.read_flv_tag();
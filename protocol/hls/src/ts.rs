@@ -1,35 +1,57 @@
 use {
-    super::errors::MediaError,
+    super::{errors::MediaError, storage::SharedSegmentStorage},
     bytes::BytesMut,
-    std::{fs, fs::File, io::Write},
 };
 
+pub const TS_EXTENSION: &str = "ts";
+pub const FMP4_EXTENSION: &str = "m4s";
+
 pub struct Ts {
     ts_number: u32,
     live_path: String,
+    //ts for MPEG-TS segments and m4s for fMP4 segments
+    extension: &'static str,
+    storage: SharedSegmentStorage,
 }
 
 impl Ts {
-    pub fn new(app_name: String, stream_name: String) -> Self {
-        let live_path = format!("./{app_name}/{stream_name}");
-        fs::create_dir_all(live_path.clone()).unwrap();
-
+    pub fn new(app_name: String, stream_name: String, storage: SharedSegmentStorage) -> Self {
         Self {
             ts_number: 0,
-            live_path,
+            live_path: format!("{app_name}/{stream_name}"),
+            extension: TS_EXTENSION,
+            storage,
         }
     }
+    pub fn set_extension(&mut self, extension: &'static str) {
+        self.extension = extension;
+    }
+    pub fn extension(&self) -> &'static str {
+        self.extension
+    }
     pub fn write(&mut self, data: BytesMut) -> Result<(String, String), MediaError> {
-        let ts_file_name = format!("{}.ts", self.ts_number);
+        let ts_file_name = format!("{}.{}", self.ts_number, self.extension);
         let ts_file_path = format!("{}/{}", self.live_path, ts_file_name);
         self.ts_number += 1;
 
-        let mut ts_file_handler = File::create(ts_file_path.clone())?;
-        ts_file_handler.write_all(&data[..])?;
+        self.storage.write(&ts_file_path, data)?;
 
         Ok((ts_file_name, ts_file_path))
     }
-    pub fn delete(&mut self, ts_file_name: String) {
-        fs::remove_file(ts_file_name).unwrap();
+    //partial segments of the segment being produced are named {ts_number}.{part_number}.{extension}
+    pub fn write_part(
+        &mut self,
+        part_number: u32,
+        data: BytesMut,
+    ) -> Result<(String, String), MediaError> {
+        let part_file_name = format!("{}.{}.{}", self.ts_number, part_number, self.extension);
+        let part_file_path = format!("{}/{}", self.live_path, part_file_name);
+
+        self.storage.write(&part_file_path, data)?;
+
+        Ok((part_file_name, part_file_path))
+    }
+    pub fn delete(&mut self, ts_file_path: &str) -> Result<(), MediaError> {
+        self.storage.delete(ts_file_path)
     }
 }
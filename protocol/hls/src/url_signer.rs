@@ -0,0 +1,218 @@
+use {
+    hmac::{Hmac, Mac, NewMac},
+    sha2::Sha256,
+    std::net::IpAddr,
+};
+
+const EXPIRES_PARAM: &str = "expires";
+const SIGN_PARAM: &str = "sign";
+
+pub const DEFAULT_SIGN_TTL: i64 = 300;
+
+/*Signs the hls urls with expiry: ?expires={unix time}&sign={hex(HMAC-SHA256(secret,
+path + "\n" + expires + "\n" + client ip))}. The playlist urls are signed by the
+business servers, and the uris in the served playlists are rewritten with fresh
+signatures, so every playlist/segment request must carry a valid signature.*/
+#[derive(Clone)]
+pub struct UrlSigner {
+    secret: String,
+    //the seconds the rewritten uris are valid for
+    ttl: i64,
+    //the ip is left out of the signature if disabled(e.g. behind a proxy)
+    bind_ip: bool,
+}
+
+impl UrlSigner {
+    pub fn new(secret: String) -> Self {
+        Self {
+            secret,
+            ttl: DEFAULT_SIGN_TTL,
+            bind_ip: true,
+        }
+    }
+
+    pub fn set_ttl(&mut self, ttl: i64) {
+        self.ttl = ttl;
+    }
+
+    pub fn set_bind_ip(&mut self, bind_ip: bool) {
+        self.bind_ip = bind_ip;
+    }
+
+    pub fn sign(&self, path: &str, expires: i64, ip: IpAddr) -> String {
+        let ip = if self.bind_ip {
+            ip.to_string()
+        } else {
+            String::new()
+        };
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).unwrap();
+        //the fields are delimited, so the digits cannot be moved between them
+        mac.update(format!("{path}\n{expires}\n{ip}").as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    pub fn verify(&self, path: &str, query: &str, ip: IpAddr, now: i64) -> bool {
+        let mut expires = None;
+        let mut sign = None;
+        for pair in query.split('&') {
+            match pair.split_once('=') {
+                Some((EXPIRES_PARAM, value)) => expires = value.parse::<i64>().ok(),
+                Some((SIGN_PARAM, value)) => sign = Some(value),
+                _ => {}
+            }
+        }
+
+        let (Some(expires), Some(sign)) = (expires, sign) else {
+            return false;
+        };
+        if expires <= now {
+            return false;
+        }
+
+        let expected = self.sign(path, expires, ip);
+        //compare in constant time
+        expected.len() == sign.len()
+            && expected
+                .bytes()
+                .zip(sign.bytes())
+                .fold(0, |acc, (a, b)| acc | (a ^ b))
+                == 0
+    }
+
+    fn sign_uri(&self, uri: &str, playlist_dir: &str, ip: IpAddr, now: i64) -> String {
+        //the absolute urls of other servers are not signed
+        if uri.contains("://") {
+            return uri.to_string();
+        }
+
+        let path = uri.split('?').next().unwrap_or(uri);
+        let absolute_path = if path.starts_with('/') {
+            path.to_string()
+        } else {
+            format!("{playlist_dir}/{path}")
+        };
+
+        let expires = now + self.ttl;
+        let separator = if uri.contains('?') { '&' } else { '?' };
+        format!(
+            "{uri}{separator}{EXPIRES_PARAM}={expires}&{SIGN_PARAM}={}",
+            self.sign(&absolute_path, expires, ip)
+        )
+    }
+
+    //the uri lines and the URI attributes(EXT-X-MAP, EXT-X-PART etc.) are signed
+    pub fn rewrite_playlist(
+        &self,
+        playlist: &str,
+        playlist_path: &str,
+        ip: IpAddr,
+        now: i64,
+    ) -> String {
+        let playlist_dir = playlist_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+        let mut rewritten = String::with_capacity(playlist.len() * 2);
+        for line in playlist.lines() {
+            if !line.is_empty() && !line.starts_with('#') {
+                rewritten += &self.sign_uri(line, playlist_dir, ip, now);
+            } else if let Some(start) = line.find("URI=\"").map(|index| index + 5) {
+                match line[start..].find('"') {
+                    Some(len) => {
+                        rewritten += &line[..start];
+                        rewritten +=
+                            &self.sign_uri(&line[start..start + len], playlist_dir, ip, now);
+                        rewritten += &line[start + len..];
+                    }
+                    None => rewritten += line,
+                }
+            } else {
+                rewritten += line;
+            }
+            rewritten.push('\n');
+        }
+        rewritten
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UrlSigner;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 0, 10));
+
+    #[test]
+    fn test_verify() {
+        let signer = UrlSigner::new(String::from("secret"));
+        let path = "/live/test/test.m3u8";
+        let sign = signer.sign(path, 2000, IP);
+
+        let query = format!("expires=2000&sign={sign}&_HLS_msn=3");
+        assert!(signer.verify(path, &query, IP, 1000));
+        //expired
+        assert!(!signer.verify(path, &query, IP, 2000));
+        //another path or ip
+        assert!(!signer.verify("/live/test/other.m3u8", &query, IP, 1000));
+        assert!(!signer.verify(path, &query, IpAddr::V4(Ipv4Addr::LOCALHOST), 1000));
+        //the expiry cannot be extended
+        let query = format!("expires=3000&sign={sign}");
+        assert!(!signer.verify(path, &query, IP, 1000));
+        assert!(!signer.verify(path, "expires=2000", IP, 1000));
+
+        let mut signer = signer;
+        signer.set_bind_ip(false);
+        let query = format!("expires=2000&sign={}", signer.sign(path, 2000, IP));
+        assert!(signer.verify(path, &query, IpAddr::V4(Ipv4Addr::LOCALHOST), 1000));
+    }
+
+    #[test]
+    fn test_sign_fields_delimited() {
+        let signer = UrlSigner::new(String::from("secret"));
+        //"2000" + "11.2.3.4" and "20001" + "1.2.3.4"
+        assert_ne!(
+            signer.sign("/live/test.m3u8", 2000, "11.2.3.4".parse().unwrap()),
+            signer.sign("/live/test.m3u8", 20001, "1.2.3.4".parse().unwrap())
+        );
+        //"/live/test-1" + "2000" and "/live/test-" + "12000"
+        assert_ne!(
+            signer.sign("/live/test-1", 2000, IP),
+            signer.sign("/live/test-", 12000, IP)
+        );
+    }
+
+    #[test]
+    fn test_rewrite_playlist() {
+        let mut signer = UrlSigner::new(String::from("secret"));
+        signer.set_ttl(60);
+
+        let playlist = "#EXTM3U\n\
+            #EXT-X-MAP:URI=\"init.mp4\"\n\
+            #EXTINF:2.000,\n\
+            test-1.m4s\n\
+            #EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"test-2.0.m4s\"\n";
+        let rewritten = signer.rewrite_playlist(playlist, "/live/test/test.m3u8", IP, 1000);
+        let lines: Vec<&str> = rewritten.lines().collect();
+
+        let signed = |path: &str| format!("expires=1060&sign={}", signer.sign(path, 1060, IP));
+        assert_eq!(lines[0], "#EXTM3U");
+        assert_eq!(
+            lines[1],
+            format!(
+                "#EXT-X-MAP:URI=\"init.mp4?{}\"",
+                signed("/live/test/init.mp4")
+            )
+        );
+        assert_eq!(lines[2], "#EXTINF:2.000,");
+        assert_eq!(
+            lines[3],
+            format!("test-1.m4s?{}", signed("/live/test/test-1.m4s"))
+        );
+        assert_eq!(
+            lines[4],
+            format!(
+                "#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"test-2.0.m4s?{}\"",
+                signed("/live/test/test-2.0.m4s")
+            )
+        );
+    }
+}
@@ -12,10 +12,10 @@ use {
         SubDataType, SubscribeType, SubscriberInfo,
     },
     streamhub::{
+        queue,
         stream::StreamIdentifier,
         utils::{RandomDigitCount, Uuid},
     },
-    tokio::sync::mpsc,
     xflv::amf0::amf0_writer::Amf0Writer,
     xflv::muxer::{FlvMuxer, HEADER_LENGTH},
 };
@@ -49,7 +49,7 @@ impl HttpFlv {
         request_url: String,
         remote_addr: SocketAddr,
     ) -> Self {
-        let (_, data_receiver) = mpsc::unbounded_channel();
+        let (_, data_receiver) = queue::unbounded();
         let subscriber_id = Uuid::new(RandomDigitCount::Four);
 
         Self {
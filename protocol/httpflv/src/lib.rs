@@ -3,3 +3,4 @@ pub mod errors;
 pub mod httpflv;
 pub mod server;
 pub mod server_test;
+pub mod vod;
@@ -1,5 +1,5 @@
 use {
-    super::httpflv::HttpFlv,
+    super::{errors::HttpFLvErrorValue, httpflv::HttpFlv, vod::HttpFlvVod},
     axum::{
         body::Body,
         extract::{ConnectInfo, Request, State},
@@ -7,10 +7,19 @@ use {
         http::StatusCode,
         response::Response,
     },
-    commonlib::auth::{Auth, SecretCarrier},
+    commonlib::{
+        acl::{AccessControl, ConnectionGuard},
+        auth::{Auth, SecretCarrier},
+        errors::{AclError, AclErrorValue},
+    },
     futures::channel::mpsc::unbounded,
-    std::net::SocketAddr,
-    streamhub::define::StreamHubEventSender,
+    record::{define::VodSetting, vod::FlvVodReader},
+    std::{net::SocketAddr, sync::Arc},
+    streamhub::{
+        define::StreamHubEventSender,
+        errors::{StreamHubError, StreamHubErrorValue},
+        statistics::metrics,
+    },
     tokio::net::TcpListener,
 };
 
@@ -18,15 +27,147 @@ type GenericError = Box<dyn std::error::Error + Send + Sync>;
 type Result<T> = std::result::Result<T, GenericError>;
 static NOTFOUND: &[u8] = b"Not Found";
 static UNAUTHORIZED: &[u8] = b"Unauthorized";
+static FORBIDDEN: &[u8] = b"Forbidden";
+
+//the start position(seconds) of a vod file, e.g. ?start=10.5
+fn get_vod_start(query_string: &Option<String>) -> u32 {
+    query_string
+        .as_ref()
+        .and_then(|query| {
+            query
+                .split('&')
+                .find_map(|param| param.strip_prefix("start="))
+                .and_then(|start| start.parse::<f64>().ok())
+        })
+        .map_or(0, |start| (start.max(0.) * 1000.) as u32)
+}
+
+#[derive(Clone)]
+struct HttpFlvServerState {
+    event_producer: StreamHubEventSender,
+    auth: Option<Auth>,
+    vod_setting: Option<VodSetting>,
+    //the players are rejected by the ip access control if set
+    access_control: Option<Arc<AccessControl>>,
+}
+
+fn response_acl_rejected(err: &AclError) -> Response<Body> {
+    let status = match err.value {
+        AclErrorValue::IpNotAllowed => StatusCode::FORBIDDEN,
+        _ => StatusCode::TOO_MANY_REQUESTS,
+    };
+    Response::builder()
+        .status(status)
+        .body(status.canonical_reason().unwrap_or_default().into())
+        .unwrap()
+}
+
+async fn handle_vod(
+    vod_setting: &VodSetting,
+    file_name: &str,
+    query_string: &Option<String>,
+    connection_guard: Option<ConnectionGuard>,
+) -> Response<Body> {
+    let file_path = match vod_setting.get_file_path(file_name) {
+        Some(file_path) => file_path,
+        None => {
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(NOTFOUND.into())
+                .unwrap();
+        }
+    };
+
+    let reader = match FlvVodReader::open(&file_path).await {
+        Ok(reader) => reader,
+        Err(err) => {
+            log::error!("open vod file {} error: {}", file_path, err);
+            return Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(NOTFOUND.into())
+                .unwrap();
+        }
+    };
+
+    let (http_response_data_producer, http_response_data_consumer) = unbounded();
+    let mut vod_handler = HttpFlvVod::new(
+        reader,
+        get_vod_start(query_string),
+        http_response_data_producer,
+    );
+
+    tokio::spawn(async move {
+        let _connection_guard = connection_guard;
+        if let Err(err) = vod_handler.run().await {
+            log::error!("flv vod handler run error {}", err);
+        }
+    });
+
+    let mut resp = Response::new(Body::from_stream(http_response_data_consumer));
+    resp.headers_mut()
+        .insert("Access-Control-Allow-Origin", "*".parse().unwrap());
+
+    resp
+}
 
 async fn handle_connection(
-    State((event_producer, auth)): State<(StreamHubEventSender, Option<Auth>)>, // event_producer: ChannelEventProducer
+    State(HttpFlvServerState {
+        event_producer,
+        auth,
+        vod_setting,
+        access_control,
+    }): State<HttpFlvServerState>,
     ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     req: Request<Body>,
 ) -> Response<Body> {
+    //the requests are checked as the connections of the players
+    let connection_guard = match &access_control {
+        Some(access_control) => match access_control
+            .check(remote_addr.ip(), true)
+            .and_then(|_| access_control.check_connection(remote_addr.ip()))
+        {
+            Ok(guard) => Some(guard),
+            Err(err) => {
+                log::warn!("httpflv request from {} is rejected: {}", remote_addr, err);
+                metrics::inc_acl_rejection("httpflv");
+                return response_acl_rejected(&err);
+            }
+        },
+        None => None,
+    };
+
     let path = req.uri().path();
     let query_string: Option<String> = req.uri().query().map(|s| s.to_string());
 
+    /*the vod files are requested by /{vod app}/{the file path relative to the root directory}*/
+    if let Some(vod_setting) = &vod_setting {
+        if let Some(file_name) = path
+            .strip_prefix('/')
+            .and_then(|path| path.strip_prefix(vod_setting.app_name.as_str()))
+            .and_then(|path| path.strip_prefix('/'))
+        {
+            if let Some(auth_val) = &auth {
+                if auth_val
+                    .authenticate(
+                        &vod_setting.app_name,
+                        &String::from(file_name.trim_end_matches(".flv")),
+                        &query_string.clone().map(SecretCarrier::Query),
+                        true,
+                    )
+                    .is_err()
+                {
+                    metrics::inc_auth_failure("httpflv");
+                    return Response::builder()
+                        .status(StatusCode::UNAUTHORIZED)
+                        .body(UNAUTHORIZED.into())
+                        .unwrap();
+                }
+            }
+
+            return handle_vod(vod_setting, file_name, &query_string, connection_guard).await;
+        }
+    }
+
     match path.find(".flv") {
         Some(index) if index > 0 => {
             let (left, _) = path.split_at(index);
@@ -38,12 +179,14 @@ async fn handle_connection(
             if let Some(auth_val) = auth {
                 if auth_val
                     .authenticate(
+                        &app_name,
                         &stream_name,
                         &query_string.map(SecretCarrier::Query),
                         true,
                     )
                     .is_err()
                 {
+                    metrics::inc_auth_failure("httpflv");
                     return Response::builder()
                         .status(StatusCode::UNAUTHORIZED)
                         .body(UNAUTHORIZED.into())
@@ -62,8 +205,44 @@ async fn handle_connection(
                 remote_addr,
             );
 
+            /*subscribe before responding, then the player can be redirected to the
+            node which publishes the stream*/
+            if let Err(err) = flv_hanlder.subscribe_from_stream_hub().await {
+                match &err.value {
+                    HttpFLvErrorValue::ChannelError(StreamHubError {
+                        value: StreamHubErrorValue::Redirect(node),
+                    }) => {
+                        if let Some(address) = &node.httpflv_address {
+                            return Response::builder()
+                                .status(StatusCode::FOUND)
+                                .header("Location", format!("http://{}{}", address, req.uri()))
+                                .body(Body::empty())
+                                .unwrap();
+                        }
+                    }
+                    //rejected by the on_play hook
+                    HttpFLvErrorValue::ChannelError(StreamHubError {
+                        value: StreamHubErrorValue::HookDenied,
+                    }) => {
+                        return Response::builder()
+                            .status(StatusCode::FORBIDDEN)
+                            .body(FORBIDDEN.into())
+                            .unwrap();
+                    }
+                    _ => {}
+                }
+
+                log::error!("flv handler subscribe error {}", err);
+                return Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(NOTFOUND.into())
+                    .unwrap();
+            }
+
             tokio::spawn(async move {
-                if let Err(err) = flv_hanlder.run().await {
+                //the connection is counted until the player stops
+                let _connection_guard = connection_guard;
+                if let Err(err) = flv_hanlder.send_media_stream().await {
                     log::error!("flv handler run error {}", err);
                 }
             });
@@ -86,6 +265,8 @@ pub async fn run(
     event_producer: StreamHubEventSender,
     port: usize,
     auth: Option<Auth>,
+    vod_setting: Option<VodSetting>,
+    access_control: Option<Arc<AccessControl>>,
 ) -> Result<()> {
     let listen_address = format!("0.0.0.0:{port}");
     let sock_addr: SocketAddr = listen_address.parse().unwrap();
@@ -94,7 +275,12 @@ pub async fn run(
 
     log::info!("Httpflv server listening on http://{}", sock_addr);
 
-    let handle_connection = handle_connection.with_state((event_producer.clone(), auth));
+    let handle_connection = handle_connection.with_state(HttpFlvServerState {
+        event_producer,
+        auth,
+        vod_setting,
+        access_control,
+    });
 
     axum::serve(
         listener,
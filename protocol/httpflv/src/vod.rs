@@ -0,0 +1,88 @@
+use {
+    super::{
+        define::{tag_type, HttpResponseDataProducer},
+        errors::{HttpFLvError, HttpFLvErrorValue},
+    },
+    record::{define::VodDataReceiver, vod::FlvVodReader},
+    xflv::{
+        define::FlvData,
+        muxer::{FlvMuxer, HEADER_LENGTH},
+    },
+};
+
+/*Plays a recorded flv file, the file is sent at the pace of the timestamps
+from the start position(the "start" query parameter).*/
+pub struct HttpFlvVod {
+    muxer: FlvMuxer,
+    has_audio: bool,
+    has_video: bool,
+    data_receiver: VodDataReceiver,
+    http_response_data_producer: HttpResponseDataProducer,
+}
+
+impl HttpFlvVod {
+    //start: the position(ms) to play from
+    pub fn new(
+        reader: FlvVodReader,
+        start: u32,
+        http_response_data_producer: HttpResponseDataProducer,
+    ) -> Self {
+        let has_audio = reader.has_audio();
+        let has_video = reader.has_video();
+        //http-flv cannot seek during playing, so the command sender is not needed
+        let (data_receiver, _) = reader.spawn(start);
+
+        Self {
+            muxer: FlvMuxer::new(),
+            has_audio,
+            has_video,
+            data_receiver,
+            http_response_data_producer,
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), HttpFLvError> {
+        self.muxer
+            .write_flv_header(self.has_audio, self.has_video)?;
+        self.muxer.write_previous_tag_size(0)?;
+        self.flush_response_data()?;
+
+        while let Some(tag) = self.data_receiver.recv().await {
+            if let Err(err) = self.write_flv_tag(tag) {
+                if let HttpFLvErrorValue::MpscSendError(err_in) = &err.value {
+                    if err_in.is_disconnected() {
+                        log::info!("write_flv_tag: {}", err_in);
+                        break;
+                    }
+                }
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_flv_tag(&mut self, tag: FlvData) -> Result<(), HttpFLvError> {
+        let (data, timestamp, tag_type) = match tag {
+            FlvData::Audio { timestamp, data } => (data, timestamp, tag_type::AUDIO),
+            FlvData::Video { timestamp, data } => (data, timestamp, tag_type::VIDEO),
+            FlvData::MetaData { timestamp, data } => (data, timestamp, tag_type::SCRIPT_DATA_AMF),
+        };
+
+        let data_len = data.len() as u32;
+        self.muxer
+            .write_flv_tag_header(tag_type, data_len, timestamp)?;
+        self.muxer.write_flv_tag_body(data)?;
+        self.muxer
+            .write_previous_tag_size(data_len + HEADER_LENGTH)?;
+
+        self.flush_response_data()
+    }
+
+    fn flush_response_data(&mut self) -> Result<(), HttpFLvError> {
+        let data = self.muxer.writer.extract_current_bytes();
+        self.http_response_data_producer.start_send(Ok(data))?;
+
+        Ok(())
+    }
+}
@@ -15,6 +15,7 @@ use {
         flv_tag_header::{AudioTagHeader, VideoTagHeader},
         mpeg4_aac::Mpeg4AacProcessor,
         mpeg4_avc::Mpeg4AvcProcessor,
+        mpeg4_hevc::Mpeg4HevcProcessor,
         Unmarshal,
     },
 };
@@ -161,15 +162,29 @@ impl Cache {
             self.video_timestamp = timestamp;
 
             if let Some(statistic_data_sender) = &self.statistic_data_sender {
-                let mut avc_processor = Mpeg4AvcProcessor::default();
-                avc_processor.decoder_configuration_record_load(&mut reader)?;
-
-                let statistic_video_codec = StatisticData::VideoCodec {
-                    codec: define::AvcCodecId::H264,
-                    profile: define::u8_2_avc_profile(avc_processor.mpeg4_avc.profile),
-                    level: define::u8_2_avc_level(avc_processor.mpeg4_avc.level),
-                    width: avc_processor.mpeg4_avc.width,
-                    height: avc_processor.mpeg4_avc.height,
+                let is_hevc = tag_header.codec_id == define::AvcCodecId::HEVC as u8;
+                let statistic_video_codec = if is_hevc {
+                    let mut hevc_processor = Mpeg4HevcProcessor::default();
+                    hevc_processor.decoder_configuration_record_load(&mut reader)?;
+
+                    StatisticData::VideoCodec {
+                        codec: define::AvcCodecId::HEVC,
+                        profile: define::AvcProfile::UNKNOWN,
+                        level: define::AvcLevel::UNKNOWN,
+                        width: hevc_processor.mpeg4_hevc.width,
+                        height: hevc_processor.mpeg4_hevc.height,
+                    }
+                } else {
+                    let mut avc_processor = Mpeg4AvcProcessor::default();
+                    avc_processor.decoder_configuration_record_load(&mut reader)?;
+
+                    StatisticData::VideoCodec {
+                        codec: define::AvcCodecId::H264,
+                        profile: define::u8_2_avc_profile(avc_processor.mpeg4_avc.profile),
+                        level: define::u8_2_avc_level(avc_processor.mpeg4_avc.level),
+                        width: avc_processor.mpeg4_avc.width,
+                        height: avc_processor.mpeg4_avc.height,
+                    }
                 };
                 if let Err(err) = statistic_data_sender.send(statistic_video_codec) {
                     log::error!("send statistic_data err: {}", err);
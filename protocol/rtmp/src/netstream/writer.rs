@@ -223,4 +223,41 @@ impl NetStreamWriter {
 
         self.write_chunk(1).await
     }
+
+    //rejects the client with the url of another server, the client may reconnect to it
+    pub async fn write_on_status_redirect(
+        &mut self,
+        transaction_id: &f64,
+        redirect_url: &str,
+    ) -> Result<(), NetStreamError> {
+        self.amf0_writer.write_string(&String::from("onStatus"))?;
+        self.amf0_writer.write_number(transaction_id)?;
+        self.amf0_writer.write_null()?;
+
+        let mut ex_map = IndexMap::new();
+        ex_map.insert(String::from("code"), Amf0ValueType::Number(302.0));
+        ex_map.insert(
+            String::from("redirect"),
+            Amf0ValueType::UTF8String(redirect_url.to_owned()),
+        );
+
+        let mut properties_map = IndexMap::new();
+        properties_map.insert(
+            String::from("level"),
+            Amf0ValueType::UTF8String(String::from("error")),
+        );
+        properties_map.insert(
+            String::from("code"),
+            Amf0ValueType::UTF8String(String::from("NetConnection.Connect.Rejected")),
+        );
+        properties_map.insert(
+            String::from("description"),
+            Amf0ValueType::UTF8String(format!("redirect to {redirect_url}")),
+        );
+        properties_map.insert(String::from("ex"), Amf0ValueType::Object(ex_map));
+
+        self.amf0_writer.write_object(&properties_map)?;
+
+        self.write_chunk(1).await
+    }
 }
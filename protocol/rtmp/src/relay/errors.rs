@@ -1,5 +1,6 @@
 #![allow(non_local_definitions)]
 use {
+    crate::session::errors::SessionError,
     failure::Fail,
     std::{fmt, io::Error},
     tokio::sync::broadcast::error::RecvError,
@@ -25,6 +26,8 @@ pub enum PushClientErrorValue {
     SendError,
     #[fail(display = "io error")]
     IOError(Error),
+    #[fail(display = "session error: {}", _0)]
+    SessionError(#[cause] SessionError),
 }
 
 impl From<Error> for ClientError {
@@ -35,6 +38,14 @@ impl From<Error> for ClientError {
     }
 }
 
+impl From<SessionError> for ClientError {
+    fn from(error: SessionError) -> Self {
+        ClientError {
+            value: PushClientErrorValue::SessionError(error),
+        }
+    }
+}
+
 impl From<RecvError> for ClientError {
     fn from(error: RecvError) -> Self {
         ClientError {
@@ -1,3 +1,4 @@
 pub mod pull_client;
 pub mod push_client;
+pub mod push_target;
 pub mod errors;
\ No newline at end of file
@@ -3,27 +3,32 @@ use streamhub::stream::StreamIdentifier;
 use {
     super::errors::ClientError,
     crate::session::client_session::{ClientSession, ClientSessionType},
+    std::collections::HashMap,
     streamhub::define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
-    tokio::net::TcpStream,
+    tokio::{net::TcpStream, task::JoinHandle},
 };
 
 pub struct PullClient {
-    address: String,
+    //the origin addresses, tried in order until one can be connected
+    addresses: Vec<String>,
     client_event_consumer: BroadcastEventReceiver,
     channel_event_producer: StreamHubEventSender,
+    //the pulling sessions keyed by the relay id
+    sessions: HashMap<String, JoinHandle<()>>,
 }
 
 impl PullClient {
     pub fn new(
-        address: String,
+        addresses: Vec<String>,
         consumer: BroadcastEventReceiver,
         producer: StreamHubEventSender,
     ) -> Self {
         Self {
-            address,
+            addresses,
 
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            sessions: HashMap::new(),
         }
     }
 
@@ -31,40 +36,97 @@ impl PullClient {
         loop {
             let event = self.client_event_consumer.recv().await?;
 
-            if let BroadcastEvent::Subscribe {
-                id: _,
-                identifier:
-                    StreamIdentifier::Rtmp {
+            match event {
+                BroadcastEvent::Subscribe {
+                    id,
+                    identifier:
+                        StreamIdentifier::Rtmp {
+                            app_name,
+                            stream_name,
+                        },
+                    server_address,
+                    result_sender: _,
+                } => {
+                    log::info!(
+                        "receive pull event, app_name :{}, stream_name: {}",
                         app_name,
-                        stream_name,
-                    },
-                server_address: _,
-                result_sender: _,
-            } = event
-            {
-                log::info!(
-                    "receive pull event, app_name :{}, stream_name: {}",
-                    app_name,
-                    stream_name
-                );
-                let stream = TcpStream::connect(self.address.clone()).await?;
+                        stream_name
+                    );
 
-                let mut client_session = ClientSession::new(
-                    stream,
-                    ClientSessionType::Pull,
-                    self.address.clone(),
-                    app_name.clone(),
-                    stream_name.clone(),
-                    self.channel_event_producer.clone(),
-                    0,
-                );
+                    if self
+                        .sessions
+                        .get(&id)
+                        .is_some_and(|session| !session.is_finished())
+                    {
+                        log::warn!("the pull session with id: {} exists", id);
+                        continue;
+                    }
 
-                tokio::spawn(async move {
-                    if let Err(err) = client_session.run().await {
-                        log::error!("client_session as pull client run error: {}", err);
+                    //pull from the node given by the cluster registry if any
+                    let addresses = match server_address {
+                        Some(address) => vec![address],
+                        None => self.addresses.clone(),
+                    };
+                    let session = tokio::spawn(Self::pull(
+                        addresses,
+                        app_name,
+                        stream_name,
+                        self.channel_event_producer.clone(),
+                    ));
+                    self.sessions.insert(id, session);
+                }
+                BroadcastEvent::UnSubscribe {
+                    id,
+                    result_sender: _,
+                } => {
+                    if let Some(session) = self.sessions.remove(&id) {
+                        log::info!("stop the pull session with id: {}", id);
+                        session.abort();
                     }
-                });
+                }
+                _ => {}
             }
         }
     }
+
+    async fn pull(
+        addresses: Vec<String>,
+        app_name: String,
+        stream_name: String,
+        producer: StreamHubEventSender,
+    ) {
+        for address in addresses {
+            let stream = match TcpStream::connect(address.clone()).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::error!("connect to origin address: {} error: {}", address, err);
+                    continue;
+                }
+            };
+
+            let mut client_session = ClientSession::new(
+                stream,
+                ClientSessionType::Pull,
+                address,
+                app_name.clone(),
+                stream_name.clone(),
+                producer,
+                0,
+            );
+
+            if let Err(err) = client_session.run().await {
+                log::error!("client_session as pull client run error: {}", err);
+            }
+            if let Err(err) = client_session.unpublish_from_stream_hub().await {
+                log::error!("client_session as pull client unpublish error: {}", err);
+            }
+            return;
+        }
+
+        log::error!(
+            "cannot pull app_name: {}, stream_name: {} from any origin",
+            app_name,
+            stream_name
+        );
+    }
 }
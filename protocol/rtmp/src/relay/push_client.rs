@@ -1,30 +1,38 @@
 use {
-    super::errors::ClientError,
+    super::{errors::ClientError, push_target::PushTarget},
     crate::session::client_session::{ClientSession, ClientSessionType},
+    std::collections::HashMap,
     streamhub::{
-        define::{StreamHubEventSender, BroadcastEvent, BroadcastEventReceiver},
+        define::{BroadcastEvent, BroadcastEventReceiver, StreamHubEventSender},
         stream::StreamIdentifier,
     },
-    tokio::net::TcpStream,
+    tokio::{
+        net::TcpStream,
+        task::JoinHandle,
+        time::{sleep, Instant},
+    },
 };
 
 pub struct PushClient {
-    address: String,
+    target: PushTarget,
     client_event_consumer: BroadcastEventReceiver,
     channel_event_producer: StreamHubEventSender,
+    //the relay tasks of the published streams, keyed by "app_name/stream_name"
+    relays: HashMap<String, JoinHandle<()>>,
 }
 
 impl PushClient {
     pub fn new(
-        address: String,
+        target: PushTarget,
         consumer: BroadcastEventReceiver,
         producer: StreamHubEventSender,
     ) -> Self {
         Self {
-            address,
+            target,
 
             client_event_consumer: consumer,
             channel_event_producer: producer,
+            relays: HashMap::new(),
         }
     }
 
@@ -35,35 +43,49 @@ impl PushClient {
             let val = self.client_event_consumer.recv().await?;
 
             match val {
-                BroadcastEvent::Publish { identifier } => {
-                    if let StreamIdentifier::Rtmp {
+                BroadcastEvent::Publish {
+                    identifier:
+                        StreamIdentifier::Rtmp {
+                            app_name,
+                            stream_name,
+                        },
+                } => {
+                    if !self.target.is_match(&app_name, &stream_name) {
+                        continue;
+                    }
+                    log::info!(
+                        "publish app_name: {} stream_name: {} address: {}",
                         app_name,
                         stream_name,
-                    } = identifier
-                    {
-                        log::info!(
-                            "publish app_name: {} stream_name: {} address: {}",
-                            app_name.clone(),
-                            stream_name.clone(),
-                            self.address.clone()
-                        );
-                        let stream = TcpStream::connect(self.address.clone()).await?;
+                        self.target.address
+                    );
 
-                        let mut client_session = ClientSession::new(
-                            stream,
-                            ClientSessionType::Push,
-                            self.address.clone(),
+                    let key = format!("{app_name}/{stream_name}");
+                    let relay = tokio::spawn(Self::relay(
+                        self.target.clone(),
+                        app_name,
+                        stream_name,
+                        self.channel_event_producer.clone(),
+                    ));
+                    if let Some(old_relay) = self.relays.insert(key, relay) {
+                        old_relay.abort();
+                    }
+                }
+                BroadcastEvent::UnPublish {
+                    identifier:
+                        StreamIdentifier::Rtmp {
                             app_name,
                             stream_name,
-                            self.channel_event_producer.clone(),
-                            0,
+                        },
+                } => {
+                    if let Some(relay) = self.relays.remove(&format!("{app_name}/{stream_name}")) {
+                        log::info!(
+                            "unpublish app_name: {} stream_name: {}, stop pushing to address: {}",
+                            app_name,
+                            stream_name,
+                            self.target.address
                         );
-
-                        tokio::spawn(async move {
-                            if let Err(err) = client_session.run().await {
-                                log::error!("client_session as push client run error: {}", err);
-                            }
-                        });
+                        relay.abort();
                     }
                 }
 
@@ -73,4 +95,73 @@ impl PushClient {
             }
         }
     }
+
+    //pushes the local stream to the remote server until it is unpublished
+    async fn relay(
+        target: PushTarget,
+        app_name: String,
+        stream_name: String,
+        producer: StreamHubEventSender,
+    ) {
+        let (remote_app_name, remote_stream_name) = target.remote_names(&app_name, &stream_name);
+        let mut backoff = target.backoff.clone();
+
+        loop {
+            let start_time = Instant::now();
+            if let Err(err) = Self::push(
+                &target.address,
+                &remote_app_name,
+                &remote_stream_name,
+                &app_name,
+                &stream_name,
+                producer.clone(),
+            )
+            .await
+            {
+                log::error!(
+                    "push {}/{} to address: {} error: {}",
+                    app_name,
+                    stream_name,
+                    target.address,
+                    err
+                );
+            }
+
+            if start_time.elapsed() > backoff.max {
+                backoff.reset();
+            }
+            let interval = backoff.next_interval();
+            log::info!(
+                "reconnect to address: {} after {} ms",
+                target.address,
+                interval.as_millis()
+            );
+            sleep(interval).await;
+        }
+    }
+
+    async fn push(
+        address: &str,
+        remote_app_name: &str,
+        remote_stream_name: &str,
+        app_name: &str,
+        stream_name: &str,
+        producer: StreamHubEventSender,
+    ) -> Result<(), ClientError> {
+        let stream = TcpStream::connect(address).await?;
+
+        let mut client_session = ClientSession::new(
+            stream,
+            ClientSessionType::Push,
+            address.to_string(),
+            remote_app_name.to_string(),
+            remote_stream_name.to_string(),
+            producer,
+            0,
+        );
+        client_session.subscribe(app_name.to_string(), stream_name.to_string());
+        client_session.run().await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+const DEFAULT_RECONNECT_INTERVAL_MIN: Duration = Duration::from_secs(1);
+const DEFAULT_RECONNECT_INTERVAL_MAX: Duration = Duration::from_secs(30);
+
+//A remote rtmp server which the local streams are relayed(pushed) to.
+//The streams can be filtered by a pattern of "app_name/stream_name" in which
+//'*' matches any characters, e.g. "live/*" only pushes the streams of the live
+//app. The remote app/stream names can be rewritten, the "{app}" and "{stream}"
+//in them are replaced by the local app/stream names.
+#[derive(Debug, Clone)]
+pub struct PushTarget {
+    //remote server address with port
+    pub address: String,
+    pub stream_pattern: Option<String>,
+    pub remote_app_name: Option<String>,
+    pub remote_stream_name: Option<String>,
+    //appended to the remote stream name, e.g. the auth token
+    pub query: Option<String>,
+    pub backoff: ReconnectBackoff,
+}
+
+impl PushTarget {
+    pub fn new(address: String) -> Self {
+        Self {
+            address,
+            stream_pattern: None,
+            remote_app_name: None,
+            remote_stream_name: None,
+            query: None,
+            backoff: ReconnectBackoff::default(),
+        }
+    }
+
+    pub fn set_stream_pattern(&mut self, pattern: String) {
+        self.stream_pattern = Some(pattern);
+    }
+
+    pub fn set_remote_app_name(&mut self, app_name: String) {
+        self.remote_app_name = Some(app_name);
+    }
+
+    pub fn set_remote_stream_name(&mut self, stream_name: String) {
+        self.remote_stream_name = Some(stream_name);
+    }
+
+    pub fn set_query(&mut self, query: String) {
+        self.query = Some(query);
+    }
+
+    pub fn set_backoff(&mut self, backoff: ReconnectBackoff) {
+        self.backoff = backoff;
+    }
+
+    pub fn is_match(&self, app_name: &str, stream_name: &str) -> bool {
+        self.stream_pattern.as_ref().is_none_or(|pattern| {
+            wildcard_match(
+                pattern.as_bytes(),
+                format!("{app_name}/{stream_name}").as_bytes(),
+            )
+        })
+    }
+
+    //returns the remote app name and the remote stream name with the query
+    pub fn remote_names(&self, app_name: &str, stream_name: &str) -> (String, String) {
+        let rewrite = |template: &Option<String>, default: &str| match template {
+            Some(template) => template
+                .replace("{app}", app_name)
+                .replace("{stream}", stream_name),
+            None => String::from(default),
+        };
+
+        let remote_app_name = rewrite(&self.remote_app_name, app_name);
+        let mut remote_stream_name = rewrite(&self.remote_stream_name, stream_name);
+        if let Some(query) = self.query.as_ref().filter(|query| !query.is_empty()) {
+            remote_stream_name = format!("{remote_stream_name}?{query}");
+        }
+
+        (remote_app_name, remote_stream_name)
+    }
+}
+
+/*The interval before reconnecting to the remote server is doubled after each
+failure from min to max, and is reset once a connection has been kept for
+longer than max.*/
+#[derive(Debug, Clone)]
+pub struct ReconnectBackoff {
+    pub min: Duration,
+    pub max: Duration,
+    current: Duration,
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_RECONNECT_INTERVAL_MIN,
+            DEFAULT_RECONNECT_INTERVAL_MAX,
+        )
+    }
+}
+
+impl ReconnectBackoff {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        let max = max.max(min);
+        Self {
+            min,
+            max,
+            current: min,
+        }
+    }
+
+    //returns the interval to wait before the next reconnection
+    pub fn next_interval(&mut self) -> Duration {
+        let interval = self.current;
+        self.current = (self.current * 2).min(self.max);
+        interval
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.min;
+    }
+}
+
+//'*' matches any sequence of characters(including the empty one)
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    //the position of the last '*' in pattern and the text position it matches from
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PushTarget, ReconnectBackoff};
+    use std::time::Duration;
+
+    #[test]
+    fn test_is_match() {
+        let mut target = PushTarget::new(String::from("localhost:1935"));
+        assert!(target.is_match("live", "test"));
+
+        target.set_stream_pattern(String::from("live/*"));
+        assert!(target.is_match("live", "test"));
+        assert!(!target.is_match("vod", "test"));
+        assert!(!target.is_match("live2", "test"));
+
+        target.set_stream_pattern(String::from("*/cam*_hd"));
+        assert!(target.is_match("live", "cam1_hd"));
+        assert!(target.is_match("app", "cam_12_hd"));
+        assert!(!target.is_match("live", "cam1_sd"));
+    }
+
+    #[test]
+    fn test_remote_names() {
+        let mut target = PushTarget::new(String::from("localhost:1935"));
+        assert_eq!(
+            target.remote_names("live", "test"),
+            (String::from("live"), String::from("test"))
+        );
+
+        target.set_remote_app_name(String::from("cdn"));
+        target.set_remote_stream_name(String::from("{app}_{stream}"));
+        target.set_query(String::from("token=123"));
+        assert_eq!(
+            target.remote_names("live", "test"),
+            (String::from("cdn"), String::from("live_test?token=123"))
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        let intervals: Vec<u64> = (0..5).map(|_| backoff.next_interval().as_secs()).collect();
+        assert_eq!(intervals, vec![1, 2, 4, 5, 5]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_interval(), Duration::from_secs(1));
+    }
+}
@@ -2,9 +2,18 @@ use bytes::BytesMut;
 use bytesio::bytes_writer::BytesWriter;
 use indexmap::IndexMap;
 
+use streamhub::define::{AudioCodecType, VideoCodecType};
 use xflv::{
+    define::{
+        fourcc,
+        hevc_nal_type::{
+            HEVC_NAL_BLA_W_LP, HEVC_NAL_PPS, HEVC_NAL_RSV_IRAP_23, HEVC_NAL_SPS, HEVC_NAL_VPS,
+        },
+        AvcCodecId, SoundFormat,
+    },
     flv_tag_header::{AudioTagHeader, VideoTagHeader},
     mpeg4_avc::{Mpeg4Avc, Mpeg4AvcProcessor, Pps, Sps},
+    mpeg4_hevc::Mpeg4HevcProcessor,
     Marshal,
 };
 
@@ -14,17 +23,58 @@ use xflv::amf0::{amf0_writer::Amf0Writer, Amf0ValueType};
 #[derive(Default)]
 pub struct RtmpCooker {}
 
+//the h265 parameter sets received by a remuxer, the sequence header is
+//generated again when they are changed
+#[derive(Default)]
+pub struct HevcParameterSets {
+    vps: Option<BytesMut>,
+    sps: Option<BytesMut>,
+    pps: Option<BytesMut>,
+    seq_header_generated: bool,
+}
+
+//the rtmp data generated from the nalus of a h265 frame
+#[derive(Default)]
+pub struct HevcVideoData {
+    //the meta data and the sequence header
+    pub seq_header: Option<(BytesMut, BytesMut)>,
+    pub frame_data: Option<BytesMut>,
+}
+
 impl RtmpCooker {
-    pub fn gen_meta_data(&self, width: u32, height: u32) -> Result<BytesMut, RtmpRemuxerError> {
+    pub fn gen_meta_data(
+        &self,
+        width: u32,
+        height: u32,
+        vcodec: VideoCodecType,
+        acodec: AudioCodecType,
+    ) -> Result<BytesMut, RtmpRemuxerError> {
         let mut amf_writer = Amf0Writer::new();
         amf_writer.write_string(&String::from("@setDataFrame"))?;
         amf_writer.write_string(&String::from("onMetaData"))?;
 
+        //Enhanced RTMP uses the FourCC as the videocodecid
+        let video_codec_id = match vcodec {
+            VideoCodecType::H264 => AvcCodecId::H264 as u32,
+            VideoCodecType::H265 => u32::from_be_bytes(*fourcc::HVC1),
+        };
+        let audio_codec_id = match acodec {
+            AudioCodecType::G711A => SoundFormat::G711A,
+            AudioCodecType::G711U => SoundFormat::G711U,
+            AudioCodecType::AAC | AudioCodecType::Opus => SoundFormat::AAC,
+        };
+
         let mut properties = IndexMap::new();
         properties.insert(String::from("width"), Amf0ValueType::Number(width as f64));
         properties.insert(String::from("height"), Amf0ValueType::Number(height as f64));
-        properties.insert(String::from("videocodecid"), Amf0ValueType::Number(7.));
-        properties.insert(String::from("audiocodecid"), Amf0ValueType::Number(10.));
+        properties.insert(
+            String::from("videocodecid"),
+            Amf0ValueType::Number(video_codec_id as f64),
+        );
+        properties.insert(
+            String::from("audiocodecid"),
+            Amf0ValueType::Number(audio_codec_id as u8 as f64),
+        );
         amf_writer.write_eacm_array(&properties)?;
 
         Ok(amf_writer.extract_current_bytes())
@@ -41,6 +91,7 @@ impl RtmpCooker {
             codec_id: 7,
             avc_packet_type: 0,
             composition_time: 0,
+            is_ex_header: false,
         };
         let tag_header_data = video_tag_header.marshal()?;
 
@@ -77,6 +128,7 @@ impl RtmpCooker {
             codec_id: 7,
             avc_packet_type: 1,
             composition_time: 0,
+            is_ex_header: false,
         };
         let tag_header_data = video_tag_header.marshal()?;
 
@@ -94,23 +146,190 @@ impl RtmpCooker {
 
         Ok(writer.extract_current_bytes())
     }
+    /*Generates the rtmp data of the nalus of a h265 frame. The parameter sets are
+    collected from the nalus, the meta data and the sequence header are generated
+    when they are changed. The frames are dropped until a sequence header is
+    generated, a sps which cannot be parsed is skipped and the next one is waited for.*/
+    pub fn gen_hevc_video_data(
+        &self,
+        parameter_sets: &mut HevcParameterSets,
+        mut nalus: Vec<BytesMut>,
+        acodec: AudioCodecType,
+    ) -> Result<HevcVideoData, RtmpRemuxerError> {
+        let mut contains_irap = false;
+        let mut parameter_sets_changed = false;
+
+        for nalu in &nalus {
+            if nalu.is_empty() {
+                continue;
+            }
+            let parameter_set = match (nalu[0] >> 1) & 0x3F {
+                HEVC_NAL_VPS => &mut parameter_sets.vps,
+                HEVC_NAL_SPS => &mut parameter_sets.sps,
+                HEVC_NAL_PPS => &mut parameter_sets.pps,
+                HEVC_NAL_BLA_W_LP..=HEVC_NAL_RSV_IRAP_23 => {
+                    contains_irap = true;
+                    continue;
+                }
+                _ => continue,
+            };
+
+            if parameter_set.as_ref() != Some(nalu) {
+                *parameter_set = Some(nalu.clone());
+                parameter_sets_changed = true;
+            }
+        }
+
+        nalus.retain(|nalu| {
+            !nalu.is_empty()
+                && !matches!(
+                    (nalu[0] >> 1) & 0x3F,
+                    HEVC_NAL_VPS | HEVC_NAL_SPS | HEVC_NAL_PPS
+                )
+        });
+
+        let mut video_data = HevcVideoData::default();
+
+        if parameter_sets_changed {
+            if let (Some(vps), Some(sps), Some(pps)) = (
+                &parameter_sets.vps,
+                &parameter_sets.sps,
+                &parameter_sets.pps,
+            ) {
+                match self.gen_hevc_meta_data_and_seq_header(vps, sps, pps, acodec) {
+                    Ok(seq_header) => {
+                        video_data.seq_header = Some(seq_header);
+                        parameter_sets.seq_header_generated = true;
+                    }
+                    Err(err) => {
+                        log::warn!("generate h265 sequence header error: {}", err);
+                        parameter_sets.seq_header_generated = false;
+                    }
+                }
+            }
+        }
+
+        if parameter_sets.seq_header_generated && !nalus.is_empty() {
+            video_data.frame_data = Some(self.gen_hevc_video_frame_data(nalus, contains_irap)?);
+        }
+
+        Ok(video_data)
+    }
+
+    fn gen_hevc_meta_data_and_seq_header(
+        &self,
+        vps: &BytesMut,
+        sps: &BytesMut,
+        pps: &BytesMut,
+        acodec: AudioCodecType,
+    ) -> Result<(BytesMut, BytesMut), RtmpRemuxerError> {
+        let mut processor = Mpeg4HevcProcessor::new();
+        processor.sps_parse(sps)?;
+        log::info!(
+            "width:{}x{}",
+            processor.mpeg4_hevc.width,
+            processor.mpeg4_hevc.height
+        );
+
+        let meta_data = self.gen_meta_data(
+            processor.mpeg4_hevc.width,
+            processor.mpeg4_hevc.height,
+            VideoCodecType::H265,
+            acodec,
+        )?;
+        let seq_header = self.gen_hevc_video_seq_header(vps.clone(), sps.clone(), pps.clone())?;
+
+        Ok((meta_data, seq_header))
+    }
+
+    //generate the Enhanced RTMP(FourCC hvc1) sequence start
+    pub fn gen_hevc_video_seq_header(
+        &self,
+        vps: BytesMut,
+        sps: BytesMut,
+        pps: BytesMut,
+    ) -> Result<BytesMut, RtmpRemuxerError> {
+        let video_tag_header = VideoTagHeader {
+            frame_type: 1,
+            codec_id: AvcCodecId::HEVC as u8,
+            avc_packet_type: 0,
+            composition_time: 0,
+            is_ex_header: true,
+        };
+        let tag_header_data = video_tag_header.marshal()?;
+
+        let mut processor = Mpeg4HevcProcessor::new();
+        processor.sps_parse(&sps)?;
+        processor.mpeg4_hevc.vps.push(vps);
+        processor.mpeg4_hevc.sps.push(sps);
+        processor.mpeg4_hevc.pps.push(pps);
+        let mpeghevc_data = processor.decoder_configuration_record_save()?;
+
+        let mut writer = BytesWriter::new();
+        writer.write(&tag_header_data)?;
+        writer.write(&mpeghevc_data)?;
+
+        Ok(writer.extract_current_bytes())
+    }
+
+    pub fn gen_hevc_video_frame_data(
+        &self,
+        nalus: Vec<BytesMut>,
+        contains_irap: bool,
+    ) -> Result<BytesMut, RtmpRemuxerError> {
+        let frame_type = if contains_irap { 1 } else { 2 };
+        let video_tag_header = VideoTagHeader {
+            frame_type,
+            codec_id: AvcCodecId::HEVC as u8,
+            avc_packet_type: 1,
+            composition_time: 0,
+            is_ex_header: true,
+        };
+        let tag_header_data = video_tag_header.marshal()?;
+
+        let mut processor = Mpeg4HevcProcessor::new();
+        let mpeghevc_data = processor.nalus_to_mpeg4hevc(nalus)?;
+
+        let mut writer = BytesWriter::new();
+        writer.write(&tag_header_data)?;
+        writer.write(&mpeghevc_data)?;
+
+        Ok(writer.extract_current_bytes())
+    }
     //generate audio rtmp frame (including seq header and common frame)
     pub fn gen_audio_frame_data(
         &self,
         audio_data: &BytesMut,
+        acodec: AudioCodecType,
     ) -> Result<BytesMut, RtmpRemuxerError> {
-        let mut aac_packet_type: u8 = 0;
+        let audio_tag_header = match acodec {
+            //the sound rate is ignored for G.711 which is always 8000Hz mono
+            AudioCodecType::G711A | AudioCodecType::G711U => AudioTagHeader {
+                sound_format: if acodec == AudioCodecType::G711A {
+                    SoundFormat::G711A as u8
+                } else {
+                    SoundFormat::G711U as u8
+                },
+                sound_rate: 0,
+                sound_size: 1,
+                sound_type: 0,
+                aac_packet_type: 0,
+            },
+            AudioCodecType::AAC | AudioCodecType::Opus => {
+                let mut aac_packet_type: u8 = 0;
 
-        if audio_data.len() > 5 {
-            aac_packet_type = 1;
-        }
+                if audio_data.len() > 5 {
+                    aac_packet_type = 1;
+                }
 
-        let audio_tag_header = AudioTagHeader {
-            sound_format: 10,
-            sound_rate: 3,
-            sound_size: 1,
-            sound_type: 1,
-            aac_packet_type,
+                AudioTagHeader {
+                    sound_format: 10,
+                    sound_rate: 3,
+                    sound_size: 1,
+                    sound_type: 1,
+                    aac_packet_type,
+                }
+            }
         };
 
         let tag_header_data = audio_tag_header.marshal()?;
@@ -122,3 +341,33 @@ impl RtmpCooker {
         Ok(writer.extract_current_bytes())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{HevcParameterSets, RtmpCooker};
+    use bytes::BytesMut;
+    use streamhub::define::AudioCodecType;
+
+    #[test]
+    fn test_hevc_malformed_sps() {
+        let cooker = RtmpCooker::default();
+        let mut parameter_sets = HevcParameterSets::default();
+        let nalus = vec![
+            //vps, a truncated sps and pps
+            BytesMut::from(&[0x40, 0x01, 0x0C][..]),
+            BytesMut::from(&[0x42, 0x01][..]),
+            BytesMut::from(&[0x44, 0x01, 0xC1][..]),
+            //IDR_W_RADL
+            BytesMut::from(&[0x26, 0x01, 0xAF][..]),
+        ];
+
+        //the remuxing goes on without a sequence header, the frame is dropped
+        let video_data =
+            match cooker.gen_hevc_video_data(&mut parameter_sets, nalus, AudioCodecType::AAC) {
+                Ok(video_data) => video_data,
+                Err(err) => panic!("gen hevc video data error: {}", err),
+            };
+        assert!(video_data.seq_header.is_none());
+        assert!(video_data.frame_data.is_none());
+    }
+}
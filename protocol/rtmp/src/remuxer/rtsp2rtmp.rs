@@ -1,7 +1,7 @@
 use bytes::BytesMut;
 use bytesio::bytes_reader::BytesReader;
 use h264_decoder::sps::SpsParser;
-use streamhub::define::VideoCodecType;
+use streamhub::define::{AudioCodecType, VideoCodecType};
 use tokio::sync::oneshot;
 use xflv::define::h264_nal_type::{H264_NAL_IDR, H264_NAL_PPS, H264_NAL_SPS};
 
@@ -9,7 +9,7 @@ use crate::session::define::SessionType;
 
 use super::{
     errors::{RtmpRemuxerError, RtmpRemuxerErrorValue},
-    rtmp_cooker::RtmpCooker,
+    rtmp_cooker::{HevcParameterSets, RtmpCooker},
 };
 
 use {
@@ -20,10 +20,11 @@ use {
             FrameData, FrameDataReceiver, NotifyInfo, StreamHubEvent, StreamHubEventSender,
             SubscribeType, SubscriberInfo,
         },
+        queue,
         stream::StreamIdentifier,
         utils::{RandomDigitCount, Uuid},
     },
-    tokio::{sync::mpsc, time::sleep},
+    tokio::time::sleep,
 };
 pub struct Rtsp2RtmpRemuxerSession {
     event_producer: StreamHubEventSender,
@@ -39,6 +40,9 @@ pub struct Rtsp2RtmpRemuxerSession {
     audio_clock_rate: u32,
     base_video_timestamp: u32,
     base_audio_timestamp: u32,
+    vcodec: VideoCodecType,
+    acodec: AudioCodecType,
+    hevc_parameter_sets: HevcParameterSets,
 
     rtmp_handler: Common,
     rtmp_cooker: RtmpCooker,
@@ -51,7 +55,7 @@ pub fn find_start_code(nalus: &[u8]) -> Option<usize> {
 
 impl Rtsp2RtmpRemuxerSession {
     pub fn new(stream_path: String, event_producer: StreamHubEventSender) -> Self {
-        let (_, data_consumer) = mpsc::unbounded_channel();
+        let (_, data_consumer) = queue::unbounded();
 
         let eles: Vec<&str> = stream_path.splitn(2, '/').collect();
         let (app_name, stream_name) = if eles.len() < 2 {
@@ -77,7 +81,10 @@ impl Rtsp2RtmpRemuxerSession {
             audio_clock_rate: 1000,
             base_audio_timestamp: 0,
             base_video_timestamp: 0,
-            rtmp_handler: Common::new(None, event_producer, SessionType::Server, None),
+            vcodec: VideoCodecType::H264,
+            acodec: AudioCodecType::AAC,
+            hevc_parameter_sets: HevcParameterSets::default(),
+            rtmp_handler: Common::new(None, event_producer, SessionType::Remuxer, None),
             rtmp_cooker: RtmpCooker::default(),
         }
     }
@@ -182,13 +189,8 @@ impl Rtsp2RtmpRemuxerSession {
                             self.audio_clock_rate,
                             self.video_clock_rate
                         );
-
-                        if media_info.vcodec == VideoCodecType::H265 {
-                            log::warn!(
-                                "h265 rtsp to rtmp is not supported now!!! will come soon!!"
-                            );
-                            break;
-                        }
+                        self.vcodec = media_info.vcodec;
+                        self.acodec = media_info.acodec;
                     }
                     _ => continue,
                 };
@@ -216,7 +218,9 @@ impl Rtsp2RtmpRemuxerSession {
             self.base_audio_timestamp = timestamp;
         }
 
-        let mut audio_frame = self.rtmp_cooker.gen_audio_frame_data(audio_data)?;
+        let mut audio_frame = self
+            .rtmp_cooker
+            .gen_audio_frame_data(audio_data, self.acodec)?;
 
         let timestamp_adjust =
             (timestamp - self.base_audio_timestamp) / (self.audio_clock_rate / 1000);
@@ -256,6 +260,10 @@ impl Rtsp2RtmpRemuxerSession {
             }
         }
 
+        if self.vcodec == VideoCodecType::H265 {
+            return self.on_rtsp_hevc_video(nalu_vec, timestamp).await;
+        }
+
         let mut width: u32 = 0;
         let mut height: u32 = 0;
         let mut level: u8 = 0;
@@ -291,16 +299,15 @@ impl Rtsp2RtmpRemuxerSession {
             }
         }
 
-        if sps.is_some() && pps.is_some() {
-            let mut meta_data = self.rtmp_cooker.gen_meta_data(width, height)?;
+        if let (Some(sps), Some(pps)) = (sps, pps) {
+            let mut meta_data =
+                self.rtmp_cooker
+                    .gen_meta_data(width, height, VideoCodecType::H264, self.acodec)?;
             self.rtmp_handler.on_meta_data(&mut meta_data, &0).await?;
 
-            let mut seq_header = self.rtmp_cooker.gen_video_seq_header(
-                sps.unwrap(),
-                pps.unwrap(),
-                profile,
-                level,
-            )?;
+            let mut seq_header = self
+                .rtmp_cooker
+                .gen_video_seq_header(sps, pps, profile, level)?;
             self.rtmp_handler.on_video_data(&mut seq_header, &0).await?;
         } else {
             let mut frame_data = self
@@ -316,4 +323,31 @@ impl Rtsp2RtmpRemuxerSession {
 
         Ok(())
     }
+
+    async fn on_rtsp_hevc_video(
+        &mut self,
+        nalu_vec: Vec<BytesMut>,
+        timestamp: u32,
+    ) -> Result<(), RtmpRemuxerError> {
+        let video_data = self.rtmp_cooker.gen_hevc_video_data(
+            &mut self.hevc_parameter_sets,
+            nalu_vec,
+            self.acodec,
+        )?;
+
+        if let Some((mut meta_data, mut seq_header)) = video_data.seq_header {
+            self.rtmp_handler.on_meta_data(&mut meta_data, &0).await?;
+            self.rtmp_handler.on_video_data(&mut seq_header, &0).await?;
+        }
+
+        if let Some(mut frame_data) = video_data.frame_data {
+            let timestamp_adjust =
+                (timestamp - self.base_video_timestamp) / (self.video_clock_rate / 1000);
+            self.rtmp_handler
+                .on_video_data(&mut frame_data, &timestamp_adjust)
+                .await?;
+        }
+
+        Ok(())
+    }
 }
@@ -1,7 +1,7 @@
 use bytes::BytesMut;
 use bytesio::bytes_reader::BytesReader;
 use h264_decoder::sps::SpsParser;
-use streamhub::define::VideoCodecType;
+use streamhub::define::{AudioCodecType, VideoCodecType};
 use tokio::sync::oneshot;
 use xflv::define::h264_nal_type::{H264_NAL_IDR, H264_NAL_PPS, H264_NAL_SPS};
 
@@ -9,7 +9,7 @@ use crate::session::define::SessionType;
 
 use super::{
     errors::{RtmpRemuxerError, RtmpRemuxerErrorValue},
-    rtmp_cooker::RtmpCooker,
+    rtmp_cooker::{HevcParameterSets, RtmpCooker},
 };
 
 use {
@@ -20,10 +20,11 @@ use {
             FrameData, FrameDataReceiver, NotifyInfo, StreamHubEvent, StreamHubEventSender,
             SubscribeType, SubscriberInfo,
         },
+        queue,
         stream::StreamIdentifier,
         utils::{RandomDigitCount, Uuid},
     },
-    tokio::{sync::mpsc, time::sleep},
+    tokio::time::sleep,
 };
 pub struct Whip2RtmpRemuxerSession {
     event_producer: StreamHubEventSender,
@@ -44,9 +45,12 @@ pub struct Whip2RtmpRemuxerSession {
     rtmp_handler: Common,
     rtmp_cooker: RtmpCooker,
 
+    vcodec: VideoCodecType,
+    //the parameter sets of h264
     sps: Option<BytesMut>,
     pps: Option<BytesMut>,
     video_seq_header_generated: bool,
+    hevc_parameter_sets: HevcParameterSets,
 }
 
 pub fn find_start_code(nalus: &[u8]) -> Option<usize> {
@@ -74,7 +78,7 @@ impl Whip2RtmpRemuxerSession {
         stream_name: String,
         event_producer: StreamHubEventSender,
     ) -> Self {
-        let (_, data_consumer) = mpsc::unbounded_channel();
+        let (_, data_consumer) = queue::unbounded();
 
         Self {
             app_name,
@@ -87,11 +91,13 @@ impl Whip2RtmpRemuxerSession {
             audio_clock_rate: 1000,
             base_audio_timestamp: 0,
             base_video_timestamp: 0,
-            rtmp_handler: Common::new(None, event_producer, SessionType::Server, None),
+            rtmp_handler: Common::new(None, event_producer, SessionType::Remuxer, None),
             rtmp_cooker: RtmpCooker::default(),
+            vcodec: VideoCodecType::H264,
             sps: None,
             pps: None,
             video_seq_header_generated: false,
+            hevc_parameter_sets: HevcParameterSets::default(),
         }
     }
 
@@ -198,13 +204,7 @@ impl Whip2RtmpRemuxerSession {
                             self.audio_clock_rate,
                             self.video_clock_rate
                         );
-
-                        if media_info.vcodec == VideoCodecType::H265 {
-                            log::warn!(
-                                "h265 rtsp to rtmp is not supported now!!! will come soon!!"
-                            );
-                            break;
-                        }
+                        self.vcodec = media_info.vcodec;
                     }
                     _ => continue,
                 };
@@ -232,7 +232,9 @@ impl Whip2RtmpRemuxerSession {
             self.base_audio_timestamp = timestamp;
         }
 
-        let mut audio_frame = self.rtmp_cooker.gen_audio_frame_data(audio_data)?;
+        let mut audio_frame = self
+            .rtmp_cooker
+            .gen_audio_frame_data(audio_data, AudioCodecType::AAC)?;
 
         let timestamp_adjust =
             (timestamp - self.base_audio_timestamp) / (self.audio_clock_rate / 1000);
@@ -273,6 +275,10 @@ impl Whip2RtmpRemuxerSession {
             }
         }
 
+        if self.vcodec == VideoCodecType::H265 {
+            return self.on_whip_hevc_video(nalu_vec, timestamp).await;
+        }
+
         let mut width: u32 = 0;
         let mut height: u32 = 0;
         let mut level: u8 = 0;
@@ -313,7 +319,12 @@ impl Whip2RtmpRemuxerSession {
 
         if !self.video_seq_header_generated {
             if self.sps.is_some() && self.pps.is_some() {
-                let mut meta_data = self.rtmp_cooker.gen_meta_data(width, height)?;
+                let mut meta_data = self.rtmp_cooker.gen_meta_data(
+                    width,
+                    height,
+                    VideoCodecType::H264,
+                    AudioCodecType::AAC,
+                )?;
                 self.rtmp_handler.on_meta_data(&mut meta_data, &0).await?;
 
                 let mut seq_header = self.rtmp_cooker.gen_video_seq_header(
@@ -340,4 +351,31 @@ impl Whip2RtmpRemuxerSession {
 
         Ok(())
     }
+
+    async fn on_whip_hevc_video(
+        &mut self,
+        nalu_vec: Vec<BytesMut>,
+        timestamp: u32,
+    ) -> Result<(), RtmpRemuxerError> {
+        let video_data = self.rtmp_cooker.gen_hevc_video_data(
+            &mut self.hevc_parameter_sets,
+            nalu_vec,
+            AudioCodecType::AAC,
+        )?;
+
+        if let Some((mut meta_data, mut seq_header)) = video_data.seq_header {
+            self.rtmp_handler.on_meta_data(&mut meta_data, &0).await?;
+            self.rtmp_handler.on_video_data(&mut seq_header, &0).await?;
+        }
+
+        if let Some(mut frame_data) = video_data.frame_data {
+            let timestamp_adjust =
+                (timestamp - self.base_video_timestamp) / (self.video_clock_rate / 1000);
+            self.rtmp_handler
+                .on_video_data(&mut frame_data, &timestamp_adjust)
+                .await?;
+        }
+
+        Ok(())
+    }
 }
@@ -1,8 +1,11 @@
 use streamhub::define::StreamHubEventSender;
 
 use super::session::server_session;
-use commonlib::auth::Auth;
+use commonlib::{acl::AccessControl, auth::Auth};
+use record::define::VodSetting;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use streamhub::statistics::metrics;
 use tokio::io::Error;
 use tokio::net::TcpListener;
 
@@ -11,6 +14,8 @@ pub struct RtmpServer {
     event_producer: StreamHubEventSender,
     gop_num: usize,
     auth: Option<Auth>,
+    vod_setting: Option<VodSetting>,
+    access_control: Option<Arc<AccessControl>>,
 }
 
 impl RtmpServer {
@@ -25,17 +30,39 @@ impl RtmpServer {
             event_producer,
             gop_num,
             auth,
+            vod_setting: None,
+            access_control: None,
         }
     }
 
+    //play the recorded flv files for the vod app
+    pub fn set_vod_setting(&mut self, vod_setting: VodSetting) {
+        self.vod_setting = Some(vod_setting);
+    }
+
+    pub fn set_access_control(&mut self, access_control: Arc<AccessControl>) {
+        self.access_control = Some(access_control);
+    }
+
     pub async fn run(&mut self) -> Result<(), Error> {
         let socket_addr: &SocketAddr = &self.address.parse().unwrap();
         let listener = TcpListener::bind(socket_addr).await?;
 
         log::info!("Rtmp server listening on tcp://{}", socket_addr);
         loop {
-            let (tcp_stream, _) = listener.accept().await?;
+            let (tcp_stream, remote_addr) = listener.accept().await?;
             //tcp_stream.set_keepalive(Some(Duration::from_secs(30)))?;
+            let connection_guard = match &self.access_control {
+                Some(access_control) => match access_control.check_connection(remote_addr.ip()) {
+                    Ok(guard) => Some(guard),
+                    Err(err) => {
+                        log::warn!("rtmp connection from {} is rejected: {}", remote_addr, err);
+                        metrics::inc_acl_rejection("rtmp");
+                        continue;
+                    }
+                },
+                None => None,
+            };
 
             let mut session = server_session::ServerSession::new(
                 tcp_stream,
@@ -43,7 +70,15 @@ impl RtmpServer {
                 self.gop_num,
                 self.auth.clone(),
             );
+            if let Some(vod_setting) = &self.vod_setting {
+                session.set_vod_setting(vod_setting.clone());
+            }
+            if let Some(access_control) = &self.access_control {
+                session.set_access_control(access_control.clone());
+            }
             tokio::spawn(async move {
+                //the connection is counted until the session exits
+                let _connection_guard = connection_guard;
                 if let Err(err) = session.run().await {
                     log::info!(
                         "session run error: session_type: {}, app_name: {}, stream_name: {}, err: {}",
@@ -84,6 +84,8 @@ pub struct ClientSession {
     sub_stream_name: Option<String>,
     /*configure how many gops will be cached.*/
     gop_num: usize,
+    //the pulled stream is published to the local stream hub
+    is_published: bool,
 }
 
 impl ClientSession {
@@ -130,6 +132,7 @@ impl ClientSession {
             sub_app_name: None,
             sub_stream_name: None,
             gop_num,
+            is_published: false,
         }
     }
     
@@ -548,7 +551,8 @@ impl ClientSession {
                             self.stream_name.clone(),
                             self.gop_num,
                         )
-                        .await?
+                        .await?;
+                    self.is_published = true;
                 }
                 _ => {}
             }
@@ -561,4 +565,15 @@ impl ClientSession {
         self.sub_app_name = Some(app_name);
         self.sub_stream_name = Some(stream_name);
     }
+
+    //unpublish the pulled stream from the local stream hub after the session ends
+    pub async fn unpublish_from_stream_hub(&mut self) -> Result<(), SessionError> {
+        if !self.is_published {
+            return Ok(());
+        }
+        self.is_published = false;
+        self.common
+            .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
+            .await
+    }
 }
@@ -27,11 +27,12 @@ use {
             SubscriberInfo, TStreamHandler,
         },
         errors::{StreamHubError, StreamHubErrorValue},
+        queue,
         statistics::StatisticsStream,
         stream::StreamIdentifier,
         utils::Uuid,
     },
-    tokio::sync::{mpsc, Mutex},
+    tokio::sync::Mutex,
 };
 
 pub struct Common {
@@ -65,7 +66,7 @@ impl Common {
         remote_addr: Option<SocketAddr>,
     ) -> Self {
         //only used for init,since I don't found a better way to deal with this.
-        let (init_producer, init_consumer) = mpsc::unbounded_channel();
+        let (init_producer, init_consumer) = queue::unbounded();
 
         Self {
             session_id: Uuid::new(streamhub::utils::RandomDigitCount::Four),
@@ -287,7 +288,7 @@ impl Common {
 
         let sub_type = match self.session_type {
             SessionType::Client => SubscribeType::RtmpRelay,
-            SessionType::Server => SubscribeType::RtmpPull,
+            SessionType::Server | SessionType::Remuxer => SubscribeType::RtmpPull,
         };
 
         SubscriberInfo {
@@ -313,6 +314,7 @@ impl Common {
         let pub_type = match self.session_type {
             SessionType::Client => PublishType::RtmpRelay,
             SessionType::Server => PublishType::RtmpPush,
+            SessionType::Remuxer => PublishType::RtmpRemux,
         };
 
         PublisherInfo {
@@ -579,7 +581,11 @@ impl TStreamHandler for RtmpStreamHandler {
             match sub_type {
                 SubscribeType::RtmpPull
                 | SubscribeType::RtmpRemux2HttpFlv
-                | SubscribeType::RtmpRemux2Hls => {
+                | SubscribeType::RtmpRemux2Hls
+                | SubscribeType::RtmpRemux2Dash
+                | SubscribeType::RtmpRemux2Record
+                | SubscribeType::RtmpRemux2Rtsp
+                | SubscribeType::RtmpRemux2WebRTC => {
                     if let Some(gops_data) = cache.get_gops_data() {
                         for gop in gops_data {
                             for channel_data in gop.get_frame_data() {
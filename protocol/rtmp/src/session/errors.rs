@@ -11,8 +11,9 @@ use {
         user_control_messages::errors::EventMessagesError,
     },
     bytesio::{bytes_errors::BytesWriteError, bytesio_errors::BytesIOError},
-    commonlib::errors::AuthError,
+    commonlib::errors::{AclError, AuthError},
     failure::{Backtrace, Fail},
+    record::errors::RecordError,
     std::fmt,
     streamhub::errors::StreamHubError,
     tokio::sync::oneshot::error::RecvError,
@@ -83,6 +84,12 @@ pub enum SessionErrorValue {
     Finish,
     #[fail(display = "Auth err: {}", _0)]
     AuthError(#[cause] AuthError),
+    #[fail(display = "Acl err: {}", _0)]
+    AclError(#[cause] AclError),
+    #[fail(display = "vod err: {}", _0)]
+    VodError(#[cause] RecordError),
+    #[fail(display = "vod file not found.")]
+    VodFileNotFound,
 }
 
 impl From<Amf0WriteError> for SessionError {
@@ -212,6 +219,22 @@ impl From<AuthError> for SessionError {
     }
 }
 
+impl From<AclError> for SessionError {
+    fn from(error: AclError) -> Self {
+        SessionError {
+            value: SessionErrorValue::AclError(error),
+        }
+    }
+}
+
+impl From<RecordError> for SessionError {
+    fn from(error: RecordError) -> Self {
+        SessionError {
+            value: SessionErrorValue::VodError(error),
+        }
+    }
+}
+
 impl fmt::Display for SessionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&self.value, f)
@@ -28,12 +28,20 @@ use {
         bytes_writer::AsyncBytesWriter,
         bytesio::{TNetIO, TcpIO},
     },
-    commonlib::auth::Auth,
+    commonlib::{acl::AccessControl, auth::Auth},
     indexmap::IndexMap,
-    std::{sync::Arc, time::Duration},
-    streamhub::define::StreamHubEventSender,
+    record::{
+        define::{VodCommand, VodCommandSender, VodDataReceiver, VodSetting},
+        vod::FlvVodReader,
+    },
+    std::{net::SocketAddr, sync::Arc, time::Duration},
+    streamhub::{
+        define::StreamHubEventSender,
+        errors::{StreamHubError, StreamHubErrorValue},
+        statistics::metrics,
+    },
     tokio::{net::TcpStream, sync::Mutex},
-    xflv::amf0::Amf0ValueType,
+    xflv::{amf0::Amf0ValueType, define::FlvData},
 };
 
 enum ServerSessionState {
@@ -44,6 +52,7 @@ enum ServerSessionState {
     //Publish,
     DeleteStream,
     Play,
+    PlayVod,
 }
 
 pub struct ServerSession {
@@ -61,6 +70,13 @@ pub struct ServerSession {
     /*configure how many gops will be cached.*/
     gop_num: usize,
     auth: Option<Auth>,
+    /*play the recorded files if the app name is the vod app*/
+    vod_setting: Option<VodSetting>,
+    vod_data_receiver: Option<VodDataReceiver>,
+    vod_command_sender: Option<VodCommandSender>,
+    //the publish/play directions of the remote ip are checked if set
+    access_control: Option<Arc<AccessControl>>,
+    remote_addr: Option<SocketAddr>,
 }
 
 impl ServerSession {
@@ -100,14 +116,39 @@ impl ServerSession {
             connect_properties: ConnectProperties::default(),
             gop_num,
             auth,
+            vod_setting: None,
+            vod_data_receiver: None,
+            vod_command_sender: None,
+            access_control: None,
+            remote_addr,
         }
     }
 
+    pub fn set_vod_setting(&mut self, vod_setting: VodSetting) {
+        self.vod_setting = Some(vod_setting);
+    }
+
+    pub fn set_access_control(&mut self, access_control: Arc<AccessControl>) {
+        self.access_control = Some(access_control);
+    }
+
+    fn check_access(&self, is_pull: bool) -> Result<(), SessionError> {
+        if let (Some(access_control), Some(remote_addr)) = (&self.access_control, self.remote_addr)
+        {
+            access_control
+                .check(remote_addr.ip(), is_pull)
+                .inspect_err(|_| metrics::inc_acl_rejection("rtmp"))?;
+        }
+        Ok(())
+    }
+
     pub async fn run(&mut self) -> Result<(), SessionError> {
         loop {
             match self.state {
                 ServerSessionState::Handshake => {
-                    self.handshake().await?;
+                    self.handshake()
+                        .await
+                        .inspect_err(|_| metrics::inc_handshake_failure("rtmp"))?;
                 }
                 ServerSessionState::ReadChunk => {
                     self.read_parse_chunks().await?;
@@ -115,6 +156,9 @@ impl ServerSession {
                 ServerSessionState::Play => {
                     self.play().await?;
                 }
+                ServerSessionState::PlayVod => {
+                    self.play_vod().await?;
+                }
                 ServerSessionState::DeleteStream => {
                     return Ok(());
                 }
@@ -196,7 +240,10 @@ impl ServerSession {
                 Err(err) => {
                     if let UnpackErrorValue::CannotParse = err.value {
                         self.common
-                            .unpublish_to_stream_hub(self.app_name.clone(), self.stream_name.clone())
+                            .unpublish_to_stream_hub(
+                                self.app_name.clone(),
+                                self.stream_name.clone(),
+                            )
                             .await?;
                         return Err(err)?;
                     }
@@ -221,6 +268,48 @@ impl ServerSession {
         Ok(())
     }
 
+    /*Unlike the live stream, the client's messages(e.g. seek) must be
+    read while playing a vod file.*/
+    async fn play_vod(&mut self) -> Result<(), SessionError> {
+        let io = self.io.clone();
+        let data_receiver = match &mut self.vod_data_receiver {
+            Some(data_receiver) => data_receiver,
+            None => {
+                return Err(SessionError {
+                    value: SessionErrorValue::NoneFrameDataReceiver,
+                })
+            }
+        };
+
+        tokio::select! {
+            data = async move { io.lock().await.read().await } => {
+                self.unpacketizer.extend_data(&data?[..]);
+                self.has_remaing_data = true;
+                self.read_parse_chunks().await?;
+            }
+            tag = data_receiver.recv() => {
+                match tag {
+                    Some(FlvData::Audio { timestamp, data }) => {
+                        self.common.send_audio(data, timestamp).await?;
+                    }
+                    Some(FlvData::Video { timestamp, data }) => {
+                        self.common.send_video(data, timestamp).await?;
+                    }
+                    Some(FlvData::MetaData { timestamp, data }) => {
+                        self.common.send_metadata(data, timestamp).await?;
+                    }
+                    None => {
+                        return Err(SessionError {
+                            value: SessionErrorValue::Finish,
+                        })
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub async fn send_set_chunk_size(&mut self) -> Result<(), SessionError> {
         let mut controlmessage =
             ProtocolControlMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
@@ -303,22 +392,20 @@ impl ServerSession {
                 log::info!("[ S<-C ] [create stream] ");
                 self.on_create_stream(transaction_id).await?;
             }
-            "deleteStream" => {
-                if !others.is_empty() {
-                    let stream_id = match others.pop() {
-                        Some(Amf0ValueType::Number(streamid)) => streamid,
-                        _ => 0.0,
-                    };
-
-                    log::info!(
-                        "[ S<-C ] [delete stream] app_name: {}, stream_name: {}",
-                        self.app_name,
-                        self.stream_name
-                    );
-
-                    self.on_delete_stream(transaction_id, &stream_id).await?;
-                    self.state = ServerSessionState::DeleteStream;
-                }
+            "deleteStream" if !others.is_empty() => {
+                let stream_id = match others.pop() {
+                    Some(Amf0ValueType::Number(streamid)) => streamid,
+                    _ => 0.0,
+                };
+
+                log::info!(
+                    "[ S<-C ] [delete stream] app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+
+                self.on_delete_stream(transaction_id, &stream_id).await?;
+                self.state = ServerSessionState::DeleteStream;
             }
             "play" => {
                 log::info!(
@@ -333,6 +420,14 @@ impl ServerSession {
                 self.unpacketizer.session_type = config::SERVER_PUSH;
                 self.on_publish(transaction_id, stream_id, others).await?;
             }
+            "seek" => {
+                log::info!(
+                    "[ S<-C ] [seek]  app_name: {}, stream_name: {}",
+                    self.app_name,
+                    self.stream_name
+                );
+                self.on_seek(transaction_id, others).await?;
+            }
             _ => {}
         }
 
@@ -575,6 +670,88 @@ impl ServerSession {
             break;
         }
 
+        log::trace!(
+            "{} {} {}",
+            start.is_some(),
+            duration.is_some(),
+            reset.is_some()
+        );
+
+        let raw_stream_name = stream_name.unwrap();
+
+        (self.stream_name, self.query) =
+            RtmpUrlParser::parse_stream_name_with_query(&raw_stream_name);
+        self.check_access(true)?;
+        if let Some(auth) = &self.auth {
+            auth.authenticate(
+                &self.app_name,
+                &self.stream_name,
+                &self
+                    .query
+                    .as_ref()
+                    .map(|q| SecretCarrier::Query(q.to_string())),
+                true,
+            )
+            .inspect_err(|_| metrics::inc_auth_failure("rtmp"))?
+        }
+
+        let query = if let Some(query_val) = &self.query {
+            query_val.clone()
+        } else {
+            String::from("none")
+        };
+
+        log::info!(
+            "[ C->S ] [play]  app_name: {}, stream_name: {}, query: {}",
+            self.app_name,
+            self.stream_name,
+            query
+        );
+
+        /*Now it can update the request url*/
+        self.common.request_url = self.get_request_url(raw_stream_name.clone());
+
+        if let Some(vod_setting) = &self.vod_setting {
+            if vod_setting.app_name == self.app_name {
+                //the start of the play command is in seconds
+                let start = (start.unwrap_or(0.).max(0.) * 1000.) as u32;
+                return self.on_play_vod(transaction_id, stream_id, start).await;
+            }
+        }
+
+        if let Err(err) = self
+            .common
+            .subscribe_from_stream_hub(self.app_name.clone(), self.stream_name.clone())
+            .await
+        {
+            //the stream is published on another node of the cluster
+            if let SessionErrorValue::ChannelError(StreamHubError {
+                value: StreamHubErrorValue::Redirect(node),
+            }) = &err.value
+            {
+                if let Some(address) = &node.rtmp_address {
+                    let redirect_url =
+                        format!("rtmp://{}/{}/{}", address, self.app_name, raw_stream_name);
+                    NetStreamWriter::new(Arc::clone(&self.io))
+                        .write_on_status_redirect(transaction_id, &redirect_url)
+                        .await?;
+                }
+            }
+            return Err(err);
+        }
+
+        self.write_play_status(transaction_id, stream_id).await?;
+        self.state = ServerSessionState::Play;
+
+        Ok(())
+    }
+
+    //the play status is only sent after the stream is found, a redirected player never starts playing
+    async fn write_play_status(
+        &self,
+        transaction_id: &f64,
+        stream_id: &u32,
+    ) -> Result<(), SessionError> {
         let mut event_messages = EventMessagesWriter::new(AsyncBytesWriter::new(self.io.clone()));
         event_messages.write_stream_begin(*stream_id).await?;
         log::info!(
@@ -582,12 +759,6 @@ impl ServerSession {
             self.app_name,
             self.stream_name
         );
-        log::trace!(
-            "{} {} {}",
-            start.is_some(),
-            duration.is_some(),
-            reset.is_some()
-        );
 
         let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
         netstream
@@ -623,41 +794,102 @@ impl ServerSession {
 
         event_messages.write_stream_is_record(*stream_id).await?;
 
-        let raw_stream_name = stream_name.unwrap();
+        log::info!(
+            "[ S->C ] [stream is record]  app_name: {}, stream_name: {}",
+            self.app_name,
+            self.stream_name
+        );
 
-        (self.stream_name, self.query) =
-            RtmpUrlParser::parse_stream_name_with_query(&raw_stream_name);
-        if let Some(auth) = &self.auth {
-            auth.authenticate(
-                &self.stream_name,
-                &self
-                    .query
-                    .as_ref()
-                    .map(|q| SecretCarrier::Query(q.to_string())),
-                true,
-            )?
-        }
+        Ok(())
+    }
 
-        let query = if let Some(query_val) = &self.query {
-            query_val.clone()
-        } else {
-            String::from("none")
+    async fn on_play_vod(
+        &mut self,
+        transaction_id: &f64,
+        stream_id: &u32,
+        start: u32,
+    ) -> Result<(), SessionError> {
+        let file_path = self
+            .vod_setting
+            .as_ref()
+            .and_then(|vod_setting| vod_setting.get_file_path(&self.stream_name));
+
+        let reader = match file_path {
+            Some(file_path) => FlvVodReader::open(&file_path).await.ok(),
+            None => None,
         };
 
-        log::info!(
-            "[ S->C ] [stream is record]  app_name: {}, stream_name: {}, query: {}",
-            self.app_name,
-            self.stream_name,
-            query
-        );
+        let reader = match reader {
+            Some(reader) => reader,
+            None => {
+                let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+                netstream
+                    .write_on_status(
+                        transaction_id,
+                        "error",
+                        "NetStream.Play.StreamNotFound",
+                        "vod file not found.",
+                    )
+                    .await?;
 
-        /*Now it can update the request url*/
-        self.common.request_url = self.get_request_url(raw_stream_name);
-        self.common
-            .subscribe_from_stream_hub(self.app_name.clone(), self.stream_name.clone())
-            .await?;
+                return Err(SessionError {
+                    value: SessionErrorValue::VodFileNotFound,
+                });
+            }
+        };
 
-        self.state = ServerSessionState::Play;
+        self.write_play_status(transaction_id, stream_id).await?;
+
+        let (data_receiver, command_sender) = reader.spawn(start);
+        self.vod_data_receiver = Some(data_receiver);
+        self.vod_command_sender = Some(command_sender);
+        self.state = ServerSessionState::PlayVod;
+
+        Ok(())
+    }
+
+    async fn on_seek(
+        &mut self,
+        transaction_id: &f64,
+        other_values: &[Amf0ValueType],
+    ) -> Result<(), SessionError> {
+        let timestamp = match other_values.first() {
+            Some(Amf0ValueType::Number(val)) => val.max(0.) as u32,
+            _ => {
+                return Err(SessionError {
+                    value: SessionErrorValue::Amf0ValueTypeNotCorrect,
+                })
+            }
+        };
+
+        let command_sender = match &self.vod_command_sender {
+            Some(command_sender) => command_sender,
+            //a live stream cannot be seeked
+            None => return Ok(()),
+        };
+        if command_sender.send(VodCommand::Seek { timestamp }).is_err() {
+            return Err(SessionError {
+                value: SessionErrorValue::Finish,
+            });
+        }
+
+        let mut netstream = NetStreamWriter::new(Arc::clone(&self.io));
+        netstream
+            .write_on_status(
+                transaction_id,
+                "status",
+                "NetStream.Seek.Notify",
+                "seek notify.",
+            )
+            .await?;
+        netstream
+            .write_on_status(
+                transaction_id,
+                "status",
+                "NetStream.Play.Start",
+                "play start",
+            )
+            .await?;
 
         Ok(())
     }
@@ -707,15 +939,18 @@ impl ServerSession {
                 }
             }
         }
+        self.check_access(false)?;
         if let Some(auth) = &self.auth {
             auth.authenticate(
+                &self.app_name,
                 &self.stream_name,
                 &self
                     .query
                     .as_ref()
                     .map(|q| SecretCarrier::Query(q.to_string())),
                 false,
-            )?
+            )
+            .inspect_err(|_| metrics::inc_auth_failure("rtmp"))?
         }
 
         /*Now it can update the request url*/
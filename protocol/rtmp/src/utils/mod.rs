@@ -116,15 +116,15 @@ mod tests {
         parser.parse_url().unwrap();
 
         println!(" raw_domain_name: {}", parser.host_with_port);
-        if parser.port.is_some() {
-            println!(" port: {}", parser.port.unwrap());
+        if let Some(port) = &parser.port {
+            println!(" port: {}", port);
         }
         println!(" domain_name: {}", parser.host);
         println!(" app_name: {}", parser.app_name);
         println!(" stream_name_with_query: {}", parser.stream_name_with_query);
         println!(" stream_name: {}", parser.stream_name);
-        if parser.query.is_some() {
-            println!(" query: {}", parser.query.unwrap());
+        if let Some(query) = &parser.query {
+            println!(" query: {}", query);
         }
     }
     #[test]
@@ -135,15 +135,15 @@ mod tests {
         parser.parse_url().unwrap();
 
         println!(" raw_domain_name: {}", parser.host_with_port);
-        if parser.port.is_some() {
-            println!(" port: {}", parser.port.unwrap());
+        if let Some(port) = &parser.port {
+            println!(" port: {}", port);
         }
         println!(" domain_name: {}", parser.host);
         println!(" app_name: {}", parser.app_name);
         println!(" stream_name_with_query: {}", parser.stream_name_with_query);
         println!(" stream_name: {}", parser.stream_name);
-        if parser.query.is_some() {
-            println!(" query: {}", parser.query.unwrap());
+        if let Some(query) = &parser.query {
+            println!(" query: {}", query);
         }
     }
 }
@@ -1,10 +1,14 @@
 pub mod global_trait;
 // pub mod http;
 pub mod relay;
+pub mod remuxer;
 pub mod rtp;
 pub mod rtsp;
+pub mod rtsp_auth;
 pub mod rtsp_channel;
 pub mod rtsp_codec;
+pub mod rtsp_http_tunnel;
+pub mod rtsp_multicast;
 pub mod rtsp_range;
 pub mod rtsp_track;
 pub mod rtsp_transport;
@@ -44,7 +44,11 @@ impl RtspPullClientManager {
                     server_address,
                     result_sender,
                 } => {
-                    let sender = result_sender.unwrap();
+                    //the events without result sender are sent by the stream hub
+                    //to pull the rtmp streams from origin
+                    let Some(sender) = result_sender else {
+                        continue;
+                    };
 
                     if let StreamIdentifier::Rtsp { stream_path } = identifier {
                         if let Some(server_address) = server_address {
@@ -138,7 +142,9 @@ impl RtspPullClientManager {
                 }
 
                 BroadcastEvent::UnSubscribe { id, result_sender } => {
-                    let sender = result_sender.unwrap();
+                    let Some(sender) = result_sender else {
+                        continue;
+                    };
                     /* judge if the server address / stream path exists */
                     if let Some(client) = self.clients.get_mut(&id) {
                         client.store(false, std::sync::atomic::Ordering::Release);
@@ -0,0 +1,97 @@
+#![allow(non_local_definitions)]
+use {
+    bytesio::bytes_errors::BytesReadError,
+    failure::Fail,
+    std::fmt,
+    streamhub::errors::StreamHubError,
+    tokio::sync::broadcast::error::RecvError,
+    tokio::sync::oneshot::error::RecvError as OneshotRecvError,
+    xflv::errors::{FlvDemuxerError, Mpeg4AvcHevcError, MpegAacError},
+};
+
+#[derive(Debug)]
+pub struct RtspRemuxerError {
+    pub value: RtspRemuxerErrorValue,
+}
+
+#[derive(Debug, Fail)]
+pub enum RtspRemuxerErrorValue {
+    #[fail(display = "receive error:{}", _0)]
+    RecvError(#[cause] RecvError),
+    #[fail(display = "bytes read error:{}", _0)]
+    BytesReadError(#[cause] BytesReadError),
+    #[fail(display = "flv demuxer error:{}", _0)]
+    FlvDemuxerError(#[cause] FlvDemuxerError),
+    #[fail(display = "mpeg avc error")]
+    MpegAvcError(#[cause] Mpeg4AvcHevcError),
+    #[fail(display = "mpeg aac error")]
+    MpegAacError(#[cause] MpegAacError),
+    #[fail(display = "stream hub event send error")]
+    StreamHubEventSendErr,
+    #[fail(display = "event execute error: {}", _0)]
+    ChannelError(#[cause] StreamHubError),
+    #[fail(display = "tokio: oneshot receiver err: {}", _0)]
+    OneshotRecvError(#[cause] OneshotRecvError),
+}
+
+impl From<RecvError> for RtspRemuxerError {
+    fn from(error: RecvError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::RecvError(error),
+        }
+    }
+}
+
+impl From<BytesReadError> for RtspRemuxerError {
+    fn from(error: BytesReadError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::BytesReadError(error),
+        }
+    }
+}
+
+impl From<FlvDemuxerError> for RtspRemuxerError {
+    fn from(error: FlvDemuxerError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::FlvDemuxerError(error),
+        }
+    }
+}
+
+impl From<Mpeg4AvcHevcError> for RtspRemuxerError {
+    fn from(error: Mpeg4AvcHevcError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::MpegAvcError(error),
+        }
+    }
+}
+
+impl From<MpegAacError> for RtspRemuxerError {
+    fn from(error: MpegAacError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::MpegAacError(error),
+        }
+    }
+}
+
+impl From<StreamHubError> for RtspRemuxerError {
+    fn from(error: StreamHubError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::ChannelError(error),
+        }
+    }
+}
+
+impl From<OneshotRecvError> for RtspRemuxerError {
+    fn from(error: OneshotRecvError) -> Self {
+        RtspRemuxerError {
+            value: RtspRemuxerErrorValue::OneshotRecvError(error),
+        }
+    }
+}
+
+impl fmt::Display for RtspRemuxerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.value, f)
+    }
+}
@@ -0,0 +1,621 @@
+use {
+    super::errors::{RtspRemuxerError, RtspRemuxerErrorValue},
+    crate::{global_trait::Unmarshal, sdp::Sdp, session::server_session::RtspStreamHandler},
+    base64::{engine::general_purpose, Engine as _},
+    bytes::BytesMut,
+    bytesio::bytes_reader::BytesReader,
+    std::{
+        sync::Arc,
+        time::{Duration, Instant},
+    },
+    streamhub::{
+        define::{
+            FrameData, FrameDataReceiver, FrameDataSender, NotifyInfo, PublishType, PublisherInfo,
+            StreamHubEvent, StreamHubEventSender, SubscribeType, SubscriberInfo,
+        },
+        errors::{StreamHubError, StreamHubErrorValue},
+        queue,
+        stream::StreamIdentifier,
+        utils::{RandomDigitCount, Uuid},
+    },
+    tokio::{sync::oneshot, time::sleep},
+    xflv::{
+        define::{aac_packet_type, avc_packet_type, AvcCodecId, SoundFormat},
+        flv_tag_header::{AudioTagHeader, VideoTagHeader},
+        mpeg4_aac::Mpeg4AacProcessor,
+        mpeg4_avc::Mpeg4AvcProcessor,
+        Unmarshal as FlvUnmarshal,
+    },
+};
+
+const VIDEO_PAYLOAD_TYPE: u16 = 96;
+const AUDIO_PAYLOAD_TYPE: u16 = 97;
+const VIDEO_CLOCK_RATE: u32 = 90000;
+const G711_SAMPLE_RATE: u32 = 8000;
+//how long the audio frames wait for the avc sequence header before an audio only sdp is built
+const VIDEO_SEQHDR_WAIT: Duration = Duration::from_secs(1);
+
+//the parameters of the aac track in the sdp
+pub struct AacTrackInfo {
+    pub asc: BytesMut,
+    pub sample_rate: u32,
+    pub channels: u8,
+}
+
+pub enum AudioTrackInfo {
+    Aac(AacTrackInfo),
+    //G.711 A-law/mu-law, 8000Hz mono
+    G711A,
+    G711U,
+}
+
+impl AudioTrackInfo {
+    fn sample_rate(&self) -> u32 {
+        match self {
+            AudioTrackInfo::Aac(aac) => aac.sample_rate,
+            AudioTrackInfo::G711A | AudioTrackInfo::G711U => G711_SAMPLE_RATE,
+        }
+    }
+}
+
+/*Generates the sdp of the remuxed stream, the sps/pps of h264 are carried in
+sprop-parameter-sets and the audio specific config of aac is carried in config.*/
+pub fn gen_sdp(sps_pps: Option<(&BytesMut, &BytesMut)>, audio: Option<&AudioTrackInfo>) -> String {
+    let mut sdp = String::from(
+        "v=0\r\n\
+         o=- 0 0 IN IP4 127.0.0.1\r\n\
+         s=xiu\r\n\
+         c=IN IP4 0.0.0.0\r\n\
+         t=0 0\r\n",
+    );
+
+    if let Some((sps, pps)) = sps_pps {
+        //profile_idc, constraint flags and level_idc follow the nalu header
+        let profile_level_id = sps
+            .get(1..4)
+            .map_or(String::from("42E01F"), hex::encode_upper);
+        sdp += format!(
+            "m=video 0 RTP/AVP {VIDEO_PAYLOAD_TYPE}\r\n\
+             a=rtpmap:{VIDEO_PAYLOAD_TYPE} H264/{VIDEO_CLOCK_RATE}\r\n\
+             a=fmtp:{VIDEO_PAYLOAD_TYPE} packetization-mode=1; sprop-parameter-sets={},{}; profile-level-id={}\r\n\
+             a=control:streamid=0\r\n",
+            general_purpose::STANDARD.encode(sps),
+            general_purpose::STANDARD.encode(pps),
+            profile_level_id
+        )
+        .as_str();
+    }
+
+    match audio {
+        Some(AudioTrackInfo::Aac(aac)) => {
+            sdp += format!(
+                "m=audio 0 RTP/AVP {AUDIO_PAYLOAD_TYPE}\r\n\
+                 a=rtpmap:{AUDIO_PAYLOAD_TYPE} MPEG4-GENERIC/{}/{}\r\n\
+                 a=fmtp:{AUDIO_PAYLOAD_TYPE} profile-level-id=1;mode=AAC-hbr;sizelength=13;indexlength=3;indexdeltalength=3; config={}\r\n\
+                 a=control:streamid=1\r\n",
+                aac.sample_rate,
+                aac.channels,
+                hex::encode(&aac.asc)
+            )
+            .as_str();
+        }
+        //the static payload types of RFC 3551
+        Some(AudioTrackInfo::G711A) => {
+            sdp += format!(
+                "m=audio 0 RTP/AVP 8\r\n\
+                 a=rtpmap:8 PCMA/{G711_SAMPLE_RATE}\r\n\
+                 a=control:streamid=1\r\n"
+            )
+            .as_str();
+        }
+        Some(AudioTrackInfo::G711U) => {
+            sdp += format!(
+                "m=audio 0 RTP/AVP 0\r\n\
+                 a=rtpmap:0 PCMU/{G711_SAMPLE_RATE}\r\n\
+                 a=control:streamid=1\r\n"
+            )
+            .as_str();
+        }
+        None => {}
+    }
+
+    sdp
+}
+
+/*Subscribes the frames of a rtmp stream and publishes them as a rtsp stream,
+the rtsp stream is published when the first video frame arrives, so its sdp
+can be built from the avc/aac sequence headers(or the first G.711 frame) received
+before. If only audio frames arrive, the stream is published without video after
+VIDEO_SEQHDR_WAIT. The video frames are converted to annexb and the timestamps to
+the rtp clock rates, then the rtsp players packetize them by the
+RtpH264Packer/RtpAacPacker/RtpG711Packer.*/
+pub struct Rtmp2RtspRemuxerSession {
+    event_producer: StreamHubEventSender,
+    //RTMP
+    app_name: String,
+    stream_name: String,
+    data_receiver: FrameDataReceiver,
+    subscribe_id: Uuid,
+
+    //RTSP
+    stream_path: String,
+    publish_id: Uuid,
+    data_sender: Option<FrameDataSender>,
+    stream_handler: Arc<RtspStreamHandler>,
+    //the tracks in the sdp, decided when publishing
+    has_video: bool,
+    has_audio: bool,
+    //when the first audio frame arrived without the avc sequence header
+    first_audio_time: Option<Instant>,
+
+    avc_processor: Mpeg4AvcProcessor,
+    audio: Option<AudioTrackInfo>,
+}
+
+impl Rtmp2RtspRemuxerSession {
+    pub fn new(
+        app_name: String,
+        stream_name: String,
+        event_producer: StreamHubEventSender,
+    ) -> Self {
+        let (_, data_consumer) = queue::unbounded();
+        let stream_path = format!("{app_name}/{stream_name}");
+
+        Self {
+            event_producer,
+            app_name,
+            stream_name,
+            data_receiver: data_consumer,
+            subscribe_id: Uuid::new(RandomDigitCount::Four),
+
+            stream_path,
+            publish_id: Uuid::new(RandomDigitCount::Four),
+            data_sender: None,
+            stream_handler: Arc::new(RtspStreamHandler::new()),
+            has_video: false,
+            has_audio: false,
+            first_audio_time: None,
+
+            avc_processor: Mpeg4AvcProcessor::new(),
+            audio: None,
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), RtspRemuxerError> {
+        self.subscribe_rtmp().await?;
+        let result = self.receive_rtmp_data().await;
+
+        self.unsubscribe_rtmp().await?;
+        if self.data_sender.is_some() {
+            self.unpublish_rtsp().await?;
+        }
+
+        match result {
+            //the rtsp stream is published by a rtsp client and remuxed to rtmp
+            Err(RtspRemuxerError {
+                value:
+                    RtspRemuxerErrorValue::ChannelError(StreamHubError {
+                        value: StreamHubErrorValue::Exists,
+                    }),
+            }) => {
+                log::info!("rtmp2rtsp: the rtsp stream {} exists", self.stream_path);
+                Ok(())
+            }
+            _ => result,
+        }
+    }
+
+    pub async fn subscribe_rtmp(&mut self) -> Result<(), RtspRemuxerError> {
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+
+        let subscribe_event = StreamHubEvent::Subscribe {
+            identifier: StreamIdentifier::Rtmp {
+                app_name: self.app_name.clone(),
+                stream_name: self.stream_name.clone(),
+            },
+            info: self.get_subscriber_info(),
+            result_sender: event_result_sender,
+        };
+
+        if self.event_producer.send(subscribe_event).is_err() {
+            return Err(RtspRemuxerError {
+                value: RtspRemuxerErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        let receiver = event_result_receiver.await??.0;
+        self.data_receiver = receiver.frame_receiver.unwrap();
+        Ok(())
+    }
+
+    pub async fn unsubscribe_rtmp(&mut self) -> Result<(), RtspRemuxerError> {
+        let subscribe_event = StreamHubEvent::UnSubscribe {
+            identifier: StreamIdentifier::Rtmp {
+                app_name: self.app_name.clone(),
+                stream_name: self.stream_name.clone(),
+            },
+            info: self.get_subscriber_info(),
+        };
+        if let Err(err) = self.event_producer.send(subscribe_event) {
+            log::error!("unsubscribe_rtmp err {}", err);
+        }
+
+        Ok(())
+    }
+
+    pub async fn publish_rtsp(&mut self) -> Result<(), RtspRemuxerError> {
+        let sps_pps = match (
+            self.avc_processor.mpeg4_avc.sps.first(),
+            self.avc_processor.mpeg4_avc.pps.first(),
+        ) {
+            (Some(sps), Some(pps)) => Some((&sps.data, &pps.data)),
+            _ => None,
+        };
+        self.has_video = sps_pps.is_some();
+        self.has_audio = self.audio.is_some();
+
+        let sdp_data = gen_sdp(sps_pps, self.audio.as_ref());
+        log::info!("rtmp2rtsp sdp: {}", sdp_data);
+        if let Some(sdp) = Sdp::unmarshal(&sdp_data) {
+            self.stream_handler.set_sdp(sdp).await;
+        }
+
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+        let publish_event = StreamHubEvent::Publish {
+            identifier: StreamIdentifier::Rtsp {
+                stream_path: self.stream_path.clone(),
+            },
+            info: self.get_publisher_info(),
+            result_sender: event_result_sender,
+            stream_handler: self.stream_handler.clone(),
+        };
+
+        if self.event_producer.send(publish_event).is_err() {
+            return Err(RtspRemuxerError {
+                value: RtspRemuxerErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        self.data_sender = event_result_receiver.await??.0;
+        Ok(())
+    }
+
+    pub async fn unpublish_rtsp(&mut self) -> Result<(), RtspRemuxerError> {
+        let unpublish_event = StreamHubEvent::UnPublish {
+            identifier: StreamIdentifier::Rtsp {
+                stream_path: self.stream_path.clone(),
+            },
+            info: self.get_publisher_info(),
+        };
+        if let Err(err) = self.event_producer.send(unpublish_event) {
+            log::error!("unpublish_rtsp err {}", err);
+        }
+
+        Ok(())
+    }
+
+    pub async fn receive_rtmp_data(&mut self) -> Result<(), RtspRemuxerError> {
+        let mut retry_count = 0;
+
+        loop {
+            if let Some(data) = self.data_receiver.recv().await {
+                match data {
+                    FrameData::Audio { timestamp, data } => {
+                        self.on_rtmp_audio(data, timestamp).await?
+                    }
+                    FrameData::Video { timestamp, data } => {
+                        self.on_rtmp_video(data, timestamp).await?
+                    }
+                    _ => continue,
+                };
+                retry_count = 0;
+            } else {
+                sleep(Duration::from_millis(100)).await;
+                retry_count += 1;
+            }
+
+            if retry_count > 10 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn on_rtmp_audio(
+        &mut self,
+        data: BytesMut,
+        timestamp: u32,
+    ) -> Result<(), RtspRemuxerError> {
+        let mut reader = BytesReader::new(data);
+        let tag_header = AudioTagHeader::unmarshal(&mut reader)?;
+
+        let g711 = match tag_header.sound_format {
+            sound_format if sound_format == SoundFormat::G711A as u8 => Some(AudioTrackInfo::G711A),
+            sound_format if sound_format == SoundFormat::G711U as u8 => Some(AudioTrackInfo::G711U),
+            sound_format if sound_format == SoundFormat::AAC as u8 => None,
+            _ => return Ok(()),
+        };
+        if let Some(g711) = g711 {
+            if self.data_sender.is_none() && self.audio.is_none() {
+                self.audio = Some(g711);
+            }
+        } else if tag_header.aac_packet_type == aac_packet_type::AAC_SEQHDR {
+            if self.data_sender.is_none() {
+                let asc = reader.extract_remaining_bytes();
+                let mut aac_processor = Mpeg4AacProcessor::new();
+                aac_processor
+                    .extend_data(asc.clone())
+                    .audio_specific_config_load()?;
+                self.audio = Some(AudioTrackInfo::Aac(AacTrackInfo {
+                    asc,
+                    sample_rate: aac_processor.mpeg4_aac.sampling_frequency,
+                    channels: aac_processor.mpeg4_aac.channels,
+                }));
+            }
+            return Ok(());
+        }
+
+        if !self.try_publish_rtsp().await? || !self.has_audio {
+            return Ok(());
+        }
+
+        let sample_rate = self
+            .audio
+            .as_ref()
+            .map_or(1000, |audio| audio.sample_rate());
+        self.send_frame(FrameData::Audio {
+            timestamp: (timestamp as u64 * sample_rate as u64 / 1000) as u32,
+            data: reader.extract_remaining_bytes(),
+        });
+
+        Ok(())
+    }
+
+    async fn on_rtmp_video(
+        &mut self,
+        data: BytesMut,
+        timestamp: u32,
+    ) -> Result<(), RtspRemuxerError> {
+        let mut reader = BytesReader::new(data);
+        let tag_header = VideoTagHeader::unmarshal(&mut reader)?;
+        if tag_header.codec_id != AvcCodecId::H264 as u8 {
+            return Ok(());
+        }
+
+        match tag_header.avc_packet_type {
+            avc_packet_type::AVC_SEQHDR => {
+                //the sdp cannot be changed after publishing, and the
+                //parameter sets are sent in band before each idr frame
+                self.avc_processor
+                    .decoder_configuration_record_load(&mut reader)?;
+            }
+            avc_packet_type::AVC_NALU => {
+                if !self.try_publish_rtsp().await? || !self.has_video {
+                    return Ok(());
+                }
+
+                let nalus = self.avc_processor.h264_mp4toannexb(&mut reader)?;
+                let pts = timestamp as i64 + tag_header.composition_time as i64;
+                self.send_frame(FrameData::Video {
+                    timestamp: (pts * (VIDEO_CLOCK_RATE / 1000) as i64) as u32,
+                    data: nalus,
+                });
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    //returns false if the sdp cannot be built yet
+    async fn try_publish_rtsp(&mut self) -> Result<bool, RtspRemuxerError> {
+        if self.data_sender.is_some() {
+            return Ok(true);
+        }
+        if !self.is_sdp_ready(Instant::now()) {
+            return Ok(false);
+        }
+
+        self.publish_rtsp().await?;
+        Ok(true)
+    }
+
+    /*The tracks cannot be added after publishing, so the audio frames arriving
+    before the avc sequence header are dropped until VIDEO_SEQHDR_WAIT elapses.*/
+    fn is_sdp_ready(&mut self, now: Instant) -> bool {
+        if !self.avc_processor.mpeg4_avc.sps.is_empty() {
+            return true;
+        }
+        if self.audio.is_none() {
+            return false;
+        }
+
+        let first_audio_time = *self.first_audio_time.get_or_insert(now);
+        now.duration_since(first_audio_time) >= VIDEO_SEQHDR_WAIT
+    }
+
+    fn send_frame(&mut self, frame_data: FrameData) {
+        if let Some(sender) = &self.data_sender {
+            if let Err(err) = sender.send(frame_data) {
+                log::error!("rtmp2rtsp send frame error: {}", err);
+            }
+        }
+    }
+
+    fn get_subscriber_info(&self) -> SubscriberInfo {
+        SubscriberInfo {
+            id: self.subscribe_id,
+            sub_type: SubscribeType::RtmpRemux2Rtsp,
+            sub_data_type: streamhub::define::SubDataType::Frame,
+            notify_info: NotifyInfo {
+                request_url: String::from(""),
+                remote_addr: String::from(""),
+            },
+        }
+    }
+
+    fn get_publisher_info(&self) -> PublisherInfo {
+        PublisherInfo {
+            id: self.publish_id,
+            pub_type: PublishType::RtspRemux,
+            pub_data_type: streamhub::define::PubDataType::Frame,
+            notify_info: NotifyInfo {
+                request_url: String::from(""),
+                remote_addr: String::from(""),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::{
+            gen_sdp, AacTrackInfo, AudioTrackInfo, Rtmp2RtspRemuxerSession, VIDEO_SEQHDR_WAIT,
+        },
+        crate::{global_trait::Unmarshal, sdp::fmtp::Fmtp, sdp::Sdp},
+        bytes::BytesMut,
+        std::time::Instant,
+        streamhub::{define::StreamHubEvent, queue},
+        tokio::sync::mpsc,
+    };
+
+    const AAC_SEQHDR: [u8; 4] = [0xAF, 0x00, 0x11, 0x90];
+    const AAC_RAW: [u8; 4] = [0xAF, 0x01, 0x21, 0x00];
+    const AVC_SEQHDR: [u8; 48] = [
+        0x17, 0x00, 0x00, 0x00, 0x00, 0x01, 0x64, 0x00, 0x1f, 0xff, 0xe1, 0x00, 0x1a, 0x67, 0x64,
+        0x00, 0x1f, 0xac, 0xd9, 0x40, 0x50, 0x05, 0xbb, 0x01, 0x10, 0x00, 0x00, 0x03, 0x00, 0x10,
+        0x00, 0x00, 0x03, 0x03, 0xc0, 0xf1, 0x83, 0x19, 0x60, 0x01, 0x00, 0x06, 0x68, 0xeb, 0xe3,
+        0xcb, 0x22, 0xc0,
+    ];
+    const AVC_NALU: [u8; 11] = [
+        0x17, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x65, 0x88,
+    ];
+
+    fn new_session() -> (
+        Rtmp2RtspRemuxerSession,
+        mpsc::UnboundedReceiver<StreamHubEvent>,
+    ) {
+        let (event_producer, event_consumer) = mpsc::unbounded_channel();
+        let session = Rtmp2RtspRemuxerSession::new(
+            String::from("live"),
+            String::from("test"),
+            event_producer,
+        );
+        (session, event_consumer)
+    }
+
+    //answers the publish event like the stream hub
+    fn accept_publish(mut event_consumer: mpsc::UnboundedReceiver<StreamHubEvent>) {
+        tokio::spawn(async move {
+            while let Some(event) = event_consumer.recv().await {
+                if let StreamHubEvent::Publish { result_sender, .. } = event {
+                    let (sender, _) = queue::unbounded();
+                    let _ = result_sender.send(Ok((Some(sender), None, None)));
+                }
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_audio_before_video_seqhdr() {
+        let (mut session, event_consumer) = new_session();
+        accept_publish(event_consumer);
+
+        session
+            .on_rtmp_audio(BytesMut::from(&AAC_SEQHDR[..]), 0)
+            .await
+            .unwrap();
+        session
+            .on_rtmp_audio(BytesMut::from(&AAC_RAW[..]), 0)
+            .await
+            .unwrap();
+        //the audio frame arrives before the avc sequence header
+        assert!(session.data_sender.is_none());
+
+        session
+            .on_rtmp_video(BytesMut::from(&AVC_SEQHDR[..]), 0)
+            .await
+            .unwrap();
+        session
+            .on_rtmp_video(BytesMut::from(&AVC_NALU[..]), 0)
+            .await
+            .unwrap();
+        assert!(session.data_sender.is_some());
+        assert!(session.has_video);
+        assert!(session.has_audio);
+    }
+
+    #[tokio::test]
+    async fn test_audio_only_after_wait() {
+        let (mut session, event_consumer) = new_session();
+        accept_publish(event_consumer);
+
+        session
+            .on_rtmp_audio(BytesMut::from(&AAC_SEQHDR[..]), 0)
+            .await
+            .unwrap();
+        let now = Instant::now();
+        assert!(!session.is_sdp_ready(now));
+        assert!(session.is_sdp_ready(now + VIDEO_SEQHDR_WAIT));
+
+        session.first_audio_time = Some(now - VIDEO_SEQHDR_WAIT);
+        session
+            .on_rtmp_audio(BytesMut::from(&AAC_RAW[..]), 0)
+            .await
+            .unwrap();
+        assert!(session.data_sender.is_some());
+        assert!(!session.has_video);
+        assert!(session.has_audio);
+    }
+
+    #[test]
+    fn test_gen_sdp() {
+        let sps = BytesMut::from(&[0x67, 0x64, 0x00, 0x1f, 0xac, 0xd9][..]);
+        let pps = BytesMut::from(&[0x68, 0xeb, 0xe3, 0xcb][..]);
+        let aac = AacTrackInfo {
+            asc: BytesMut::from(&[0x11, 0x90][..]),
+            sample_rate: 48000,
+            channels: 2,
+        };
+        let asc = aac.asc.clone();
+        let aac = AudioTrackInfo::Aac(aac);
+
+        let sdp_data = gen_sdp(Some((&sps, &pps)), Some(&aac));
+        assert!(sdp_data.contains("profile-level-id=64001F"));
+        let sdp = Sdp::unmarshal(&sdp_data).unwrap();
+        assert_eq!(sdp.medias.len(), 2);
+
+        let video = &sdp.medias[0];
+        assert_eq!(video.media_type, "video");
+        assert_eq!(video.rtpmap.encoding_name, "H264");
+        assert_eq!(video.rtpmap.clock_rate, 90000);
+        match &video.fmtp {
+            Some(Fmtp::H264(h264_fmtp)) => {
+                assert_eq!(h264_fmtp.sps, sps);
+                assert_eq!(h264_fmtp.pps, pps);
+            }
+            _ => panic!("expect h264 fmtp"),
+        }
+
+        let audio = &sdp.medias[1];
+        assert_eq!(audio.rtpmap.clock_rate, 48000);
+        assert_eq!(audio.rtpmap.encoding_param, "2");
+        match &audio.fmtp {
+            Some(Fmtp::Mpeg4(mpeg4_fmtp)) => assert_eq!(mpeg4_fmtp.asc, asc),
+            _ => panic!("expect mpeg4 fmtp"),
+        }
+
+        //audio only
+        let sdp = Sdp::unmarshal(&gen_sdp(None, Some(&aac))).unwrap();
+        assert_eq!(sdp.medias.len(), 1);
+        assert_eq!(sdp.medias[0].media_type, "audio");
+
+        let sdp = Sdp::unmarshal(&gen_sdp(None, Some(&AudioTrackInfo::G711A))).unwrap();
+        assert_eq!(sdp.medias[0].rtpmap.payload_type, 8);
+        assert_eq!(sdp.medias[0].rtpmap.encoding_name, "PCMA");
+        assert_eq!(sdp.medias[0].rtpmap.clock_rate, 8000);
+        assert!(sdp.medias[0].fmtp.is_none());
+    }
+}
@@ -2,6 +2,7 @@ pub mod define;
 pub mod errors;
 pub mod rtcp;
 pub mod rtp_aac;
+pub mod rtp_g711;
 pub mod rtp_h264;
 pub mod rtp_h265;
 pub mod rtp_header;
@@ -125,7 +125,7 @@ impl TUnPacker for RtpAacUnPacker {
 
         let mut reader_payload = BytesReader::new(rtp_packet.payload);
 
-        let au_headers_length = (reader_payload.read_u16::<BigEndian>()? + 7) / 8;
+        let au_headers_length = reader_payload.read_u16::<BigEndian>()?.div_ceil(8);
         let au_header_length = 2;
         let aus_number = au_headers_length / au_header_length;
 
@@ -1,8 +1,16 @@
 use streamhub::define::StreamHubEventSender;
 
 use super::session::server_session::RtspServerSession;
-use commonlib::auth::Auth;
+use crate::rtsp_auth::ServerAuthenticator;
+use crate::rtsp_http_tunnel::{HttpTunnels, RtspConnection};
+use crate::rtsp_multicast::{MulticastConfig, MulticastManager};
+use bytes::BytesMut;
+use bytesio::bytesio::{TNetIO, TcpIO};
+use commonlib::{acl::AccessControl, auth::Auth};
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use streamhub::statistics::metrics;
 use tokio::io::Error;
 use tokio::net::TcpListener;
 
@@ -10,6 +18,12 @@ pub struct RtspServer {
     address: String,
     event_producer: StreamHubEventSender,
     auth: Option<Auth>,
+    access_control: Option<Arc<AccessControl>>,
+    authenticator: Option<ServerAuthenticator>,
+    session_timeout: Option<Duration>,
+    multicast_manager: Option<Arc<MulticastManager>>,
+    //the RTSP over HTTP tunnels accepted by the listener
+    http_tunnels: Arc<HttpTunnels>,
 }
 
 impl RtspServer {
@@ -18,20 +32,99 @@ impl RtspServer {
             address,
             event_producer,
             auth,
+            access_control: None,
+            authenticator: None,
+            session_timeout: None,
+            multicast_manager: None,
+            http_tunnels: Arc::new(HttpTunnels::default()),
         }
     }
 
+    //the Digest/Basic authentication of all the rtsp requests except OPTIONS
+    pub fn set_authenticator(&mut self, authenticator: ServerAuthenticator) {
+        self.authenticator = Some(authenticator);
+    }
+
+    pub fn set_access_control(&mut self, access_control: Arc<AccessControl>) {
+        self.access_control = Some(access_control);
+    }
+
+    //the sessions without RTSP or RTCP in this duration are torn down, 60s by default
+    pub fn set_session_timeout(&mut self, session_timeout: Duration) {
+        self.session_timeout = Some(session_timeout);
+    }
+
+    //the players requesting multicast are served by the groups of the configured range
+    pub fn set_multicast(&mut self, config: MulticastConfig) {
+        self.multicast_manager = Some(Arc::new(MulticastManager::new(config)));
+    }
+
     pub async fn run(&mut self) -> Result<(), Error> {
         let socket_addr: &SocketAddr = &self.address.parse().unwrap();
         let listener = TcpListener::bind(socket_addr).await?;
 
         log::info!("Rtsp server listening on tcp://{}", socket_addr);
         loop {
-            let (tcp_stream, _) = listener.accept().await?;
-            let mut session =
-                RtspServerSession::new(tcp_stream, self.event_producer.clone(), self.auth.clone());
+            let (tcp_stream, remote_addr) = listener.accept().await?;
+            let connection_guard = match &self.access_control {
+                Some(access_control) => match access_control.check_connection(remote_addr.ip()) {
+                    Ok(guard) => Some(guard),
+                    Err(err) => {
+                        log::warn!("rtsp connection from {} is rejected: {}", remote_addr, err);
+                        metrics::inc_acl_rejection("rtsp");
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let event_producer = self.event_producer.clone();
+            let auth = self.auth.clone();
+            let access_control = self.access_control.clone();
+            let authenticator = self.authenticator.clone();
+            let session_timeout = self.session_timeout;
+            let multicast_manager = self.multicast_manager.clone();
+            let http_tunnels = self.http_tunnels.clone();
+
             tokio::spawn(async move {
-                if let Err(err) = session.run().await {
+                //the connection is counted until the session exits
+                let _connection_guard = connection_guard;
+
+                let (net_io, received_data, tunnel_cookie): (Box<dyn TNetIO + Send + Sync>, _, _) =
+                    match http_tunnels.accept(tcp_stream).await {
+                        Ok(RtspConnection::Rtsp(stream, data)) => {
+                            (Box::new(TcpIO::new(stream)), data, None)
+                        }
+                        Ok(RtspConnection::Tunnel(tunnel_io, cookie)) => {
+                            (Box::new(tunnel_io), BytesMut::new(), Some(cookie))
+                        }
+                        Ok(RtspConnection::Closed) => return,
+                        Err(err) => {
+                            log::error!("rtsp connection from {} error: {}", remote_addr, err);
+                            return;
+                        }
+                    };
+
+                let mut session =
+                    RtspServerSession::new_with_io(net_io, remote_addr, event_producer, auth);
+                session.set_received_data(&received_data);
+                if let Some(access_control) = access_control {
+                    session.set_access_control(access_control);
+                }
+                if let Some(authenticator) = authenticator {
+                    session.set_authenticator(authenticator);
+                }
+                if let Some(session_timeout) = session_timeout {
+                    session.set_session_timeout(session_timeout);
+                }
+                if let Some(multicast_manager) = multicast_manager {
+                    session.set_multicast_manager(multicast_manager);
+                }
+
+                let result = session.run().await;
+                if let Some(cookie) = tunnel_cookie {
+                    http_tunnels.remove(&cookie);
+                }
+                if let Err(err) = result {
                     let session_id = if let Some(id) = session.session_id {
                         id.to_string()
                     } else {
@@ -9,10 +9,12 @@ use crate::rtp::utils::OnRtpPacketFn2;
 use crate::rtp::RtpPacket;
 
 use super::rtp::rtp_aac::RtpAacPacker;
+use super::rtp::rtp_g711::RtpG711Packer;
 use super::rtp::rtp_h264::RtpH264Packer;
 use super::rtp::rtp_h265::RtpH265Packer;
 
 use super::rtp::rtp_aac::RtpAacUnPacker;
+use super::rtp::rtp_g711::RtpG711UnPacker;
 use super::rtp::rtp_h264::RtpH264UnPacker;
 use super::rtp::rtp_h265::RtpH265UnPacker;
 
@@ -68,6 +70,10 @@ impl RtpChannel {
         rtp_channel
     }
 
+    pub fn codec_info(&self) -> &RtspCodecInfo {
+        &self.codec_info
+    }
+
     //Receive av frame from network -> pack AV frame to RTP packet -> send to stream hub
     pub async fn on_packet(&mut self, reader: &mut BytesReader) -> Result<(), UnPackerError> {
         if let Some(unpacker) = &mut self.rtp_unpacker {
@@ -123,7 +129,9 @@ impl TRtpFunc for RtpChannel {
             RtspCodecId::AAC => {
                 self.rtp_unpacker = Some(Box::new(RtpAacUnPacker::new()));
             }
-            RtspCodecId::G711A => {}
+            RtspCodecId::G711A | RtspCodecId::G711U => {
+                self.rtp_unpacker = Some(Box::new(RtpG711UnPacker::new()));
+            }
         }
     }
     fn create_packer(&mut self, io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>) {
@@ -154,7 +162,15 @@ impl TRtpFunc for RtpChannel {
                     io,
                 )));
             }
-            RtspCodecId::G711A => {}
+            RtspCodecId::G711A | RtspCodecId::G711U => {
+                self.rtp_packer = Some(Box::new(RtpG711Packer::new(
+                    self.codec_info.payload_type,
+                    self.ssrc,
+                    self.init_sequence,
+                    1400,
+                    io,
+                )));
+            }
         }
     }
 }
@@ -8,6 +8,7 @@ pub enum RtspCodecId {
     H265,
     AAC,
     G711A,
+    G711U,
 }
 
 lazy_static! {
@@ -17,6 +18,7 @@ lazy_static! {
         m.insert(RtspCodecId::H265, "h265");
         m.insert(RtspCodecId::AAC, "mpeg4-generic");
         m.insert(RtspCodecId::G711A, "pcma");
+        m.insert(RtspCodecId::G711U, "pcmu");
         m
     };
     pub static ref RTSP_CODEC_NAME_2_ID: HashMap<&'static str, RtspCodecId> = {
@@ -25,6 +27,7 @@ lazy_static! {
         m.insert("h265", RtspCodecId::H265);
         m.insert("mpeg4-generic", RtspCodecId::AAC);
         m.insert("pcma", RtspCodecId::G711A);
+        m.insert("pcmu", RtspCodecId::G711U);
         m
     };
 }
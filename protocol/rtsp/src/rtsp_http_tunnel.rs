@@ -0,0 +1,391 @@
+use crate::session::errors::SessionError;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
+use bytes::{Bytes, BytesMut};
+use bytesio::bytesio::{NetType, TNetIO};
+use bytesio::bytesio_errors::{BytesIOError, BytesIOErrorValue};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+const MAX_HTTP_HEADER_SIZE: usize = 8192;
+//the connection is closed if the request line and the HTTP header are not received in time
+const HTTP_HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+const HTTP_TUNNEL_RESPONSE: &str = "HTTP/1.0 200 OK\r\n\
+                                    Content-Type: application/x-rtsp-tunnelled\r\n\
+                                    Cache-Control: no-cache\r\n\
+                                    Pragma: no-cache\r\n\
+                                    Connection: close\r\n\r\n";
+const HTTP_BAD_REQUEST_RESPONSE: &str = "HTTP/1.0 400 Bad Request\r\n\r\n";
+
+pub enum RtspConnection {
+    //the plain RTSP connection and the data read to detect the connection type
+    Rtsp(TcpStream, BytesMut),
+    //the GET connection of a tunnel and its session cookie
+    Tunnel(HttpTunnelIO, String),
+    //the POST connection has been consumed or the connection is closed
+    Closed,
+}
+
+/*RTSP over HTTP(Apple QuickTime style): the client opens a GET connection which
+carries the data from the server to the client, and a POST connection which carries
+the base64 encoded requests from the client to the server. The two connections are
+paired by the x-sessioncookie header, and the POST connection must come from the
+address of the GET connection.*/
+#[derive(Default)]
+pub struct HttpTunnels {
+    //the GET connections by their session cookies
+    tunnels: std::sync::Mutex<HashMap<String, Tunnel>>,
+}
+
+struct Tunnel {
+    peer_ip: IpAddr,
+    //the sender of the decoded POST data
+    sender: mpsc::UnboundedSender<BytesMut>,
+}
+
+impl HttpTunnels {
+    //the POST connections are consumed here until they are closed
+    pub async fn accept(&self, mut stream: TcpStream) -> Result<RtspConnection, SessionError> {
+        let peer_ip = stream.peer_addr()?.ip();
+        let deadline = Instant::now() + HTTP_HEADER_TIMEOUT;
+
+        let mut data = BytesMut::new();
+        //every RTSP or HTTP request line is longer than this
+        while data.len() < 5 {
+            if !read_header_more(&mut stream, &mut data, deadline).await? {
+                return Ok(RtspConnection::Closed);
+            }
+        }
+
+        let is_get = data.starts_with(b"GET ");
+        if !is_get && !data.starts_with(b"POST ") {
+            return Ok(RtspConnection::Rtsp(stream, data));
+        }
+
+        let header_size = loop {
+            if let Some(pos) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+                break pos + 4;
+            }
+            if data.len() > MAX_HTTP_HEADER_SIZE
+                || !read_header_more(&mut stream, &mut data, deadline).await?
+            {
+                return Ok(RtspConnection::Closed);
+            }
+        };
+        let body = data.split_off(header_size);
+
+        let Some(cookie) = parse_session_cookie(&String::from_utf8_lossy(&data)) else {
+            stream
+                .write_all(HTTP_BAD_REQUEST_RESPONSE.as_bytes())
+                .await?;
+            return Ok(RtspConnection::Closed);
+        };
+
+        if is_get {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let is_inserted = match self.tunnels.lock().unwrap().entry(cookie.clone()) {
+                Entry::Occupied(_) => false,
+                Entry::Vacant(entry) => {
+                    entry.insert(Tunnel { peer_ip, sender });
+                    true
+                }
+            };
+            //the cookie of an existing tunnel cannot be taken over
+            if !is_inserted {
+                log::warn!(
+                    "http tunnel: session cookie {} from {} is in use",
+                    cookie,
+                    peer_ip
+                );
+                stream
+                    .write_all(HTTP_BAD_REQUEST_RESPONSE.as_bytes())
+                    .await?;
+                return Ok(RtspConnection::Closed);
+            }
+
+            stream.write_all(HTTP_TUNNEL_RESPONSE.as_bytes()).await?;
+            log::info!("http tunnel: new tunnel with session cookie {}", cookie);
+            return Ok(RtspConnection::Tunnel(
+                HttpTunnelIO::new(stream, receiver),
+                cookie,
+            ));
+        }
+
+        let sender = self
+            .tunnels
+            .lock()
+            .unwrap()
+            .get(&cookie)
+            .filter(|tunnel| tunnel.peer_ip == peer_ip)
+            .map(|tunnel| tunnel.sender.clone());
+        let Some(sender) = sender else {
+            log::warn!(
+                "http tunnel: no GET connection of session cookie {} from {}",
+                cookie,
+                peer_ip
+            );
+            return Ok(RtspConnection::Closed);
+        };
+
+        //the POST connection may be reopened for the next requests, it does not end the tunnel
+        let mut decoder = TunnelDecoder::default();
+        let mut data = body;
+        loop {
+            match decoder.decode(&data) {
+                Ok(decoded) => {
+                    if !decoded.is_empty() && sender.send(decoded).is_err() {
+                        break;
+                    }
+                }
+                Err(err) => {
+                    log::error!("http tunnel: base64 decode error: {}", err);
+                    break;
+                }
+            }
+
+            data.clear();
+            if !read_more(&mut stream, &mut data).await? {
+                break;
+            }
+        }
+
+        Ok(RtspConnection::Closed)
+    }
+
+    pub fn remove(&self, cookie: &str) {
+        self.tunnels.lock().unwrap().remove(cookie);
+    }
+}
+
+//returns false if the connection is closed or the deadline is reached
+async fn read_header_more(
+    stream: &mut TcpStream,
+    data: &mut BytesMut,
+    deadline: Instant,
+) -> Result<bool, SessionError> {
+    match tokio::time::timeout_at(deadline, read_more(stream, data)).await {
+        Ok(result) => result,
+        Err(_) => {
+            log::warn!("http tunnel: read the request header timeout");
+            Ok(false)
+        }
+    }
+}
+
+//returns false if the connection is closed
+async fn read_more(stream: &mut TcpStream, data: &mut BytesMut) -> Result<bool, SessionError> {
+    let mut buf = [0u8; 4096];
+    let size = stream.read(&mut buf).await?;
+    data.extend_from_slice(&buf[..size]);
+    Ok(size > 0)
+}
+
+fn parse_session_cookie(header: &str) -> Option<String> {
+    header.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name.trim().eq_ignore_ascii_case("x-sessioncookie") {
+            Some(value.trim().to_string())
+        } else {
+            None
+        }
+    })
+}
+
+//the base64 data of the POST connection can be split anywhere, it is decoded by 4 characters.
+//Each rtsp message is encoded separately, so a padded quantum ends a message and the next
+//quantum starts a new one.
+#[derive(Default)]
+struct TunnelDecoder {
+    pending: Vec<u8>,
+}
+
+impl TunnelDecoder {
+    fn decode(&mut self, data: &[u8]) -> Result<BytesMut, base64::DecodeError> {
+        self.pending.extend(
+            data.iter()
+                .filter(|character| !character.is_ascii_whitespace()),
+        );
+
+        let size = self.pending.len() / 4 * 4;
+        let mut decoded = BytesMut::with_capacity(size / 4 * 3);
+        for quantum in self.pending[..size].chunks_exact(4) {
+            decoded.extend_from_slice(&general_purpose::STANDARD.decode(quantum)?);
+        }
+        self.pending.drain(..size);
+
+        Ok(decoded)
+    }
+}
+
+//writes to the GET connection and reads the decoded data of the POST connections
+pub struct HttpTunnelIO {
+    stream: TcpStream,
+    receiver: mpsc::UnboundedReceiver<BytesMut>,
+}
+
+impl HttpTunnelIO {
+    pub fn new(stream: TcpStream, receiver: mpsc::UnboundedReceiver<BytesMut>) -> Self {
+        Self { stream, receiver }
+    }
+}
+
+#[async_trait]
+impl TNetIO for HttpTunnelIO {
+    fn get_net_type(&self) -> NetType {
+        NetType::TCP
+    }
+
+    async fn write(&mut self, bytes: Bytes) -> Result<(), BytesIOError> {
+        self.stream.write_all(bytes.as_ref()).await?;
+        Ok(())
+    }
+
+    async fn read_timeout(&mut self, duration: Duration) -> Result<BytesMut, BytesIOError> {
+        match tokio::time::timeout(duration, self.read()).await {
+            Ok(data) => data,
+            Err(err) => Err(BytesIOError {
+                value: BytesIOErrorValue::TimeoutError(err),
+            }),
+        }
+    }
+
+    //the tunnel ends when the GET connection is closed
+    async fn read(&mut self) -> Result<BytesMut, BytesIOError> {
+        let mut buf = [0u8; 1024];
+        loop {
+            tokio::select! {
+                data = self.receiver.recv() => {
+                    return data.ok_or(BytesIOError {
+                        value: BytesIOErrorValue::NoneReturn,
+                    });
+                }
+                size = self.stream.read(&mut buf) => {
+                    if size? == 0 {
+                        return Err(BytesIOError {
+                            value: BytesIOErrorValue::NoneReturn,
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_session_cookie, HttpTunnels, RtspConnection, TunnelDecoder};
+    use bytesio::bytesio::TNetIO;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+    async fn connect(address: SocketAddr, local_ip: &str, request: &str) -> TcpStream {
+        let socket = TcpSocket::new_v4().unwrap();
+        socket
+            .bind(format!("{local_ip}:0").parse().unwrap())
+            .unwrap();
+        let mut stream = socket.connect(address).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        stream
+    }
+
+    async fn read_response(stream: &mut TcpStream) -> String {
+        let mut buf = [0u8; 1024];
+        let size = stream.read(&mut buf).await.unwrap();
+        String::from_utf8_lossy(&buf[..size]).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_tunnel_pairing() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let tunnels = Arc::new(HttpTunnels::default());
+
+        let get_request = "GET /live/test HTTP/1.0\r\nx-sessioncookie: abc\r\n\r\n";
+        let mut get_stream = connect(address, "127.0.0.1", get_request).await;
+        let (stream, _) = listener.accept().await.unwrap();
+        let Ok(RtspConnection::Tunnel(mut tunnel_io, cookie)) = tunnels.accept(stream).await else {
+            panic!("the GET connection is not a tunnel");
+        };
+        assert_eq!(cookie, "abc");
+        assert!(read_response(&mut get_stream)
+            .await
+            .starts_with("HTTP/1.0 200"));
+
+        //the cookie in use is rejected
+        let mut second_stream = connect(address, "127.0.0.1", get_request).await;
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(matches!(
+            tunnels.accept(stream).await,
+            Ok(RtspConnection::Closed)
+        ));
+        assert!(read_response(&mut second_stream)
+            .await
+            .starts_with("HTTP/1.0 400"));
+
+        //the POST connection from another address is not paired
+        let other_request = "POST /live/test HTTP/1.0\r\nx-sessioncookie: abc\r\n\r\n\
+                             T1BUSU9OUyAqIFJUU1AvMS4wDQo=";
+        let _other_stream = connect(address, "127.0.0.2", other_request).await;
+        let (stream, _) = listener.accept().await.unwrap();
+        assert!(matches!(
+            tunnels.accept(stream).await,
+            Ok(RtspConnection::Closed)
+        ));
+
+        //"OPTIONS * RTSP/1.0\r\n"
+        let post_request = "POST /live/test HTTP/1.0\r\nx-sessioncookie: abc\r\n\r\n\
+                            T1BUSU9OUyAqIFJUU1AvMS4wDQo=";
+        let post_stream = connect(address, "127.0.0.1", post_request).await;
+        let (stream, _) = listener.accept().await.unwrap();
+        let post_tunnels = tunnels.clone();
+        tokio::spawn(async move { post_tunnels.accept(stream).await });
+
+        let data = tunnel_io.read().await.unwrap();
+        assert_eq!(&data[..], b"OPTIONS * RTSP/1.0\r\n");
+        drop(post_stream);
+    }
+
+    #[test]
+    fn test_parse_session_cookie() {
+        let header = "GET /live/test HTTP/1.0\r\n\
+                      X-SessionCookie: 9a4fc5c2e3b1\r\n\
+                      Accept: application/x-rtsp-tunnelled\r\n\r\n";
+        assert_eq!(
+            parse_session_cookie(header),
+            Some(String::from("9a4fc5c2e3b1"))
+        );
+        assert_eq!(parse_session_cookie("GET / HTTP/1.0\r\n\r\n"), None);
+    }
+
+    #[test]
+    fn test_tunnel_decoder() {
+        //"OPTIONS * RTSP/1.0\r\n" split in the middle of the base64 characters
+        let mut decoder = TunnelDecoder::default();
+        let first = decoder.decode(b"T1BUSU9OUyAqIF").unwrap();
+        assert_eq!(&first[..], b"OPTIONS *");
+        let second = decoder.decode(b"JUU1AvMS4wDQo=\r\n").unwrap();
+        assert_eq!(&second[..], b" RTSP/1.0\r\n");
+    }
+
+    #[test]
+    fn test_tunnel_decoder_padded_messages() {
+        //two separately encoded messages are received in one read
+        let mut decoder = TunnelDecoder::default();
+        let data = decoder
+            .decode(b"T1BUSU9OUyAqIFJUU1AvMS4wDQoNCg==REVTQ1JJQkUgKiBSVFNQLzEuMA0KDQo=")
+            .unwrap();
+        assert_eq!(
+            &data[..],
+            b"OPTIONS * RTSP/1.0\r\n\r\nDESCRIBE * RTSP/1.0\r\n\r\n"
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use crate::rtp::utils::Marshal as RtpMarshal;
+use crate::rtp::RtpPacket;
+use crate::rtsp_channel::{RtpChannel, TRtpFunc};
+use crate::rtsp_codec::RtspCodecInfo;
+use crate::rtsp_track::TrackType;
+use crate::session::errors::{SessionError, SessionErrorValue};
+
+use bytesio::bytes_writer::AsyncBytesWriter;
+use bytesio::bytesio::{TNetIO, UdpIO};
+use std::collections::{HashMap, HashSet};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use streamhub::{
+    define::{
+        FrameData, NotifyInfo, StreamHubEvent, StreamHubEventSender, SubDataType, SubscribeType,
+        SubscriberInfo,
+    },
+    stream::StreamIdentifier,
+    utils::{RandomDigitCount, Uuid},
+};
+use tokio::sync::{broadcast, oneshot, Mutex};
+
+//the multicast addresses and ports which are allocated to the groups
+#[derive(Debug, Clone)]
+pub struct MulticastConfig {
+    pub address_start: Ipv4Addr,
+    pub address_end: Ipv4Addr,
+    //a group uses two continuous ports for rtp/rtcp
+    pub port_start: u16,
+    pub port_end: u16,
+    pub ttl: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastGroup {
+    pub address: Ipv4Addr,
+    pub rtp_port: u16,
+    pub rtcp_port: u16,
+    pub ttl: u32,
+}
+
+struct MulticastTrack {
+    group: MulticastGroup,
+    codec_info: RtspCodecInfo,
+}
+
+struct Forwarder {
+    //the forwarding task stops when the sender is dropped
+    _stop_sender: oneshot::Sender<()>,
+    track_count: usize,
+    //the task only clears the forwarder it started
+    generation: u64,
+}
+
+struct MulticastStream {
+    tracks: HashMap<TrackType, MulticastTrack>,
+    viewers: HashSet<u64>,
+    forwarder: Option<Forwarder>,
+    //notifies the viewers that the stream cannot be forwarded(e.g. the publisher leaves)
+    end_sender: broadcast::Sender<()>,
+}
+
+/*The play sessions requesting multicast share one group per track of a stream,
+the frames of the stream are subscribed once and sent to the groups until the
+last viewer leaves.*/
+pub struct MulticastManager {
+    config: MulticastConfig,
+    streams: std::sync::Mutex<HashMap<String, MulticastStream>>,
+    //the session ids are only unique in one second, so the viewers are numbered here
+    next_viewer: AtomicU64,
+    next_generation: AtomicU64,
+}
+
+impl MulticastManager {
+    pub fn new(config: MulticastConfig) -> Self {
+        Self {
+            config,
+            streams: std::sync::Mutex::new(HashMap::new()),
+            next_viewer: AtomicU64::new(0),
+            next_generation: AtomicU64::new(0),
+        }
+    }
+
+    pub fn new_viewer(&self) -> u64 {
+        self.next_viewer.fetch_add(1, Ordering::Relaxed)
+    }
+
+    //returns None if all the addresses and ports of the range are in use
+    pub fn join(
+        &self,
+        stream_path: &str,
+        track_type: TrackType,
+        codec_info: RtspCodecInfo,
+        viewer: u64,
+    ) -> Option<MulticastGroup> {
+        let mut streams = self.streams.lock().unwrap();
+
+        let group = match streams
+            .get(stream_path)
+            .and_then(|stream| stream.tracks.get(&track_type))
+        {
+            Some(track) => track.group,
+            None => {
+                let group = self.allocate_group(&streams)?;
+                log::info!(
+                    "multicast: new group {}:{} for {} {:?}",
+                    group.address,
+                    group.rtp_port,
+                    stream_path,
+                    track_type
+                );
+                streams
+                    .entry(stream_path.to_string())
+                    .or_insert_with(|| MulticastStream {
+                        tracks: HashMap::new(),
+                        viewers: HashSet::new(),
+                        forwarder: None,
+                        end_sender: broadcast::channel(1).0,
+                    })
+                    .tracks
+                    .insert(track_type, MulticastTrack { group, codec_info });
+                group
+            }
+        };
+
+        if let Some(stream) = streams.get_mut(stream_path) {
+            stream.viewers.insert(viewer);
+        }
+        Some(group)
+    }
+
+    /*Starts forwarding the stream to the groups, it is restarted if the tracks are
+    changed or the last forwarding ended. The returned receiver is notified when the
+    stream cannot be forwarded any more, e.g. the publisher leaves.*/
+    pub fn start(
+        self: &Arc<Self>,
+        stream_path: &str,
+        event_producer: StreamHubEventSender,
+    ) -> Option<broadcast::Receiver<()>> {
+        let mut streams = self.streams.lock().unwrap();
+        let stream = streams.get_mut(stream_path)?;
+        let end_receiver = stream.end_sender.subscribe();
+
+        let track_count = stream.tracks.len();
+        if matches!(&stream.forwarder, Some(forwarder) if forwarder.track_count == track_count) {
+            return Some(end_receiver);
+        }
+
+        let tracks: Vec<(TrackType, MulticastGroup, RtspCodecInfo)> = stream
+            .tracks
+            .iter()
+            .map(|(track_type, track)| (track_type.clone(), track.group, track.codec_info.clone()))
+            .collect();
+
+        let (stop_sender, stop_receiver) = oneshot::channel();
+        let generation = self.next_generation.fetch_add(1, Ordering::Relaxed);
+        stream.forwarder = Some(Forwarder {
+            _stop_sender: stop_sender,
+            track_count,
+            generation,
+        });
+
+        let manager = self.clone();
+        let stream_path = stream_path.to_string();
+        tokio::spawn(async move {
+            let result = forward(&stream_path, tracks, event_producer, stop_receiver).await;
+            if let Err(err) = &result {
+                log::error!("multicast: forward {} error: {}", stream_path, err);
+            }
+            manager.on_forward_end(&stream_path, generation, result.is_err());
+        });
+
+        Some(end_receiver)
+    }
+
+    fn on_forward_end(&self, stream_path: &str, generation: u64, is_error: bool) {
+        let mut streams = self.streams.lock().unwrap();
+        let Some(stream) = streams.get_mut(stream_path) else {
+            return;
+        };
+        //a newer forwarder has been started
+        if !matches!(&stream.forwarder, Some(forwarder) if forwarder.generation == generation) {
+            return;
+        }
+
+        stream.forwarder = None;
+        if is_error {
+            let _ = stream.end_sender.send(());
+        }
+    }
+
+    //the groups are released when the last viewer leaves
+    pub fn leave(&self, stream_path: &str, viewer: u64) {
+        let mut streams = self.streams.lock().unwrap();
+        if let Some(stream) = streams.get_mut(stream_path) {
+            stream.viewers.remove(&viewer);
+            if stream.viewers.is_empty() {
+                log::info!("multicast: the last viewer of {} leaves", stream_path);
+                streams.remove(stream_path);
+            }
+        }
+    }
+
+    fn allocate_group(&self, streams: &HashMap<String, MulticastStream>) -> Option<MulticastGroup> {
+        let used: HashSet<(Ipv4Addr, u16)> = streams
+            .values()
+            .flat_map(|stream| stream.tracks.values())
+            .map(|track| (track.group.address, track.group.rtp_port))
+            .collect();
+
+        let address_start = u32::from(self.config.address_start);
+        let address_end = u32::from(self.config.address_end);
+
+        for address in (address_start..=address_end).map(Ipv4Addr::from) {
+            for rtp_port in (self.config.port_start..self.config.port_end).step_by(2) {
+                if !used.contains(&(address, rtp_port)) {
+                    return Some(MulticastGroup {
+                        address,
+                        rtp_port,
+                        rtcp_port: rtp_port + 1,
+                        ttl: self.config.ttl,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
+
+async fn forward(
+    stream_path: &str,
+    tracks: Vec<(TrackType, MulticastGroup, RtspCodecInfo)>,
+    event_producer: StreamHubEventSender,
+    mut stop_receiver: oneshot::Receiver<()>,
+) -> Result<(), SessionError> {
+    let mut channels: HashMap<TrackType, RtpChannel> = HashMap::new();
+
+    for (track_type, group, codec_info) in tracks {
+        let Some(rtp_io) = UdpIO::new(group.address.to_string(), group.rtp_port, 0).await else {
+            log::error!("multicast: cannot create the socket of {}", group.address);
+            continue;
+        };
+        rtp_io.set_multicast_ttl(group.ttl)?;
+
+        let mut rtp_channel = RtpChannel::new(codec_info);
+        let box_udp_io: Box<dyn TNetIO + Send + Sync> = Box::new(rtp_io);
+        rtp_channel.create_packer(Arc::new(Mutex::new(box_udp_io)));
+        rtp_channel.on_packet_handler(Box::new(
+            move |io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>, packet: RtpPacket| {
+                Box::pin(async move {
+                    let mut bytes_writer = AsyncBytesWriter::new(io);
+
+                    let msg = packet.marshal()?;
+                    bytes_writer.write(&msg)?;
+                    bytes_writer.flush().await?;
+                    Ok(())
+                })
+            },
+        ));
+        channels.insert(track_type, rtp_channel);
+    }
+
+    let identifier = StreamIdentifier::Rtsp {
+        stream_path: stream_path.to_string(),
+    };
+    let info = SubscriberInfo {
+        id: Uuid::new(RandomDigitCount::Four),
+        sub_type: SubscribeType::RtspPull,
+        sub_data_type: SubDataType::Frame,
+        notify_info: NotifyInfo {
+            request_url: String::from(""),
+            remote_addr: String::from(""),
+        },
+    };
+
+    let (event_result_sender, event_result_receiver) = oneshot::channel();
+    let subscribe_event = StreamHubEvent::Subscribe {
+        identifier: identifier.clone(),
+        info: info.clone(),
+        result_sender: event_result_sender,
+    };
+    if event_producer.send(subscribe_event).is_err() {
+        return Err(SessionError {
+            value: SessionErrorValue::StreamHubEventSendErr,
+        });
+    }
+
+    let Some(mut receiver) = event_result_receiver.await??.0.frame_receiver else {
+        return Err(SessionError {
+            value: SessionErrorValue::CannotReceiveFrameData,
+        });
+    };
+
+    let result = loop {
+        let frame_data = tokio::select! {
+            frame_data = receiver.recv() => frame_data,
+            _ = &mut stop_receiver => break Ok(()),
+        };
+
+        let (track_type, timestamp, mut data) = match frame_data {
+            Some(FrameData::Audio { timestamp, data }) => (TrackType::Audio, timestamp, data),
+            Some(FrameData::Video { timestamp, data }) => (TrackType::Video, timestamp, data),
+            Some(_) => continue,
+            None => {
+                break Err(SessionError {
+                    value: SessionErrorValue::CannotReceiveFrameData,
+                })
+            }
+        };
+
+        if let Some(rtp_channel) = channels.get_mut(&track_type) {
+            if let Err(err) = rtp_channel.on_frame(&mut data, timestamp).await {
+                break Err(err.into());
+            }
+        }
+    };
+
+    let unsubscribe_event = StreamHubEvent::UnSubscribe { identifier, info };
+    if let Err(err) = event_producer.send(unsubscribe_event) {
+        log::error!("multicast: unsubscribe {} error: {}", stream_path, err);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MulticastConfig, MulticastManager};
+    use crate::rtsp_codec::RtspCodecInfo;
+    use crate::rtsp_track::TrackType;
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+    use tokio::sync::mpsc;
+
+    #[tokio::test]
+    async fn test_forward_end() {
+        let manager = Arc::new(MulticastManager::new(MulticastConfig {
+            address_start: Ipv4Addr::new(239, 0, 0, 1),
+            address_end: Ipv4Addr::new(239, 0, 0, 1),
+            port_start: 20000,
+            port_end: 20001,
+            ttl: 16,
+        }));
+        let viewer = manager.new_viewer();
+        manager
+            .join(
+                "/live/a",
+                TrackType::Video,
+                RtspCodecInfo::default(),
+                viewer,
+            )
+            .unwrap();
+
+        for _ in 0..2 {
+            //the stream hub is gone, so the forwarding fails
+            let (event_producer, _) = mpsc::unbounded_channel();
+            let mut end_receiver = manager.start("/live/a", event_producer).unwrap();
+            assert!(end_receiver.recv().await.is_ok());
+            //the next play restarts the forwarding
+            assert!(manager.streams.lock().unwrap()["/live/a"]
+                .forwarder
+                .is_none());
+        }
+
+        manager.leave("/live/a", viewer);
+        let (event_producer, _) = mpsc::unbounded_channel();
+        assert!(manager.start("/live/a", event_producer).is_none());
+    }
+
+    #[test]
+    fn test_join_and_leave() {
+        let manager = MulticastManager::new(MulticastConfig {
+            address_start: Ipv4Addr::new(239, 0, 0, 1),
+            address_end: Ipv4Addr::new(239, 0, 0, 1),
+            port_start: 20000,
+            port_end: 20003,
+            ttl: 16,
+        });
+
+        let viewer_0 = manager.new_viewer();
+        let viewer_1 = manager.new_viewer();
+        let codec_info = RtspCodecInfo::default();
+
+        let video = manager
+            .join("/live/a", TrackType::Video, codec_info.clone(), viewer_0)
+            .unwrap();
+        assert_eq!(video.address, Ipv4Addr::new(239, 0, 0, 1));
+        assert_eq!((video.rtp_port, video.rtcp_port), (20000, 20001));
+        //the viewers of a stream share the group of a track
+        assert_eq!(
+            manager.join("/live/a", TrackType::Video, codec_info.clone(), viewer_1),
+            Some(video)
+        );
+
+        let audio = manager
+            .join("/live/a", TrackType::Audio, codec_info.clone(), viewer_0)
+            .unwrap();
+        assert_eq!(audio.rtp_port, 20002);
+        //the range is used up
+        assert_eq!(
+            manager.join("/live/b", TrackType::Video, codec_info.clone(), viewer_0),
+            None
+        );
+
+        manager.leave("/live/a", viewer_0);
+        assert!(manager
+            .join("/live/b", TrackType::Video, codec_info.clone(), viewer_0)
+            .is_none());
+        //the groups are released after the last viewer leaves
+        manager.leave("/live/a", viewer_1);
+        assert_eq!(
+            manager.join("/live/b", TrackType::Video, codec_info, viewer_0),
+            Some(video)
+        );
+    }
+}
@@ -7,6 +7,7 @@ use crate::rtsp_channel::TRtpFunc;
 use bytes::BytesMut;
 use bytesio::bytes_reader::BytesReader;
 use bytesio::bytesio::TNetIO;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -38,6 +39,8 @@ pub struct RtspTrack {
 
     pub rtp_channel: Arc<Mutex<RtpChannel>>,
     pub rtcp_channel: Arc<Mutex<RtcpChannel>>,
+    //set when the RTP or RTCP is received over UDP, the session keepalive checks and clears it
+    pub udp_received: Arc<AtomicBool>,
 }
 
 impl RtspTrack {
@@ -51,18 +54,21 @@ impl RtspTrack {
             uri: String::default(),
             rtp_channel: Arc::new(Mutex::new(rtp_channel)),
             rtcp_channel: Arc::new(Mutex::default()),
+            udp_received: Arc::new(AtomicBool::new(false)),
         }
     }
 
     pub async fn rtp_receive_loop(&mut self, mut rtp_io: Box<dyn TNetIO + Send + Sync>) {
         let rtp_channel_out = self.rtp_channel.clone();
+        let udp_received = self.udp_received.clone();
         tokio::spawn(async move {
             let mut reader = BytesReader::new(BytesMut::new());
             let mut rtp_channel_in = rtp_channel_out.lock().await;
             loop {
                 match rtp_io.read().await {
                     Ok(data) => {
-                        //log::info!("read rtp data");
+                        //the publishers which send no RTCP are kept alive by the RTP
+                        udp_received.store(true, Ordering::Relaxed);
                         reader.extend_from_slice(&data[..]);
                         if let Err(err) = rtp_channel_in.on_packet(&mut reader).await {
                             log::error!("rtp_receive_loop on_packet error: {}", err);
@@ -79,6 +85,7 @@ impl RtspTrack {
     //send and receive rtcp data in a UDP channel
     pub async fn rtcp_receive_loop(&mut self, rtcp_io: Arc<Mutex<Box<dyn TNetIO + Send + Sync>>>) {
         let rtcp_channel_out = self.rtcp_channel.clone();
+        let udp_received = self.udp_received.clone();
 
         tokio::spawn(async move {
             let mut reader = BytesReader::new(BytesMut::new());
@@ -92,6 +99,7 @@ impl RtspTrack {
                         break;
                     }
                 };
+                udp_received.store(true, Ordering::Relaxed);
                 reader.extend_from_slice(&data[..]);
                 rtcp_channel_in.on_rtcp(&mut reader, rtcp_io.clone()).await;
             }
@@ -25,6 +25,10 @@ pub struct RtspTransport {
     pub client_port: Option<[u16; 2]>,
     pub server_port: Option<[u16; 2]>,
     pub ssrc: Option<u32>,
+    //the multicast group address, the rtp/rtcp ports and the ttl of the group
+    pub destination: Option<String>,
+    pub port: Option<[u16; 2]>,
+    pub ttl: Option<u32>,
 }
 
 impl Unmarshal for RtspTransport {
@@ -94,6 +98,27 @@ impl Unmarshal for RtspTransport {
                         rtsp_transport.ssrc = Some(ssrc);
                     }
                 }
+                "destination" => {
+                    rtsp_transport.destination = Some(kv[1].to_string());
+                }
+                "port" => {
+                    let ports = scanf!(kv[1], '-', u16, u16);
+
+                    let mut multicast_ports: [u16; 2] = [0, 0];
+                    if let Some(port) = ports.0 {
+                        multicast_ports[0] = port;
+                    }
+                    if let Some(port) = ports.1 {
+                        multicast_ports[1] = port;
+                    }
+
+                    rtsp_transport.port = Some(multicast_ports);
+                }
+                "ttl" => {
+                    if let Ok(ttl) = kv[1].parse::<u32>() {
+                        rtsp_transport.ttl = Some(ttl);
+                    }
+                }
 
                 _ => {}
             }
@@ -115,6 +140,24 @@ impl Marshal for RtspTransport {
             CastType::Unicast => "unicast",
         };
 
+        let destination = if let Some(destination) = &self.destination {
+            format!("destination={destination};")
+        } else {
+            String::from("")
+        };
+
+        let port = if let Some(ports) = self.port {
+            format!("port={}-{};", ports[0], ports[1])
+        } else {
+            String::from("")
+        };
+
+        let ttl = if let Some(ttl) = self.ttl {
+            format!("ttl={ttl};")
+        } else {
+            String::from("")
+        };
+
         let client_port = if let Some(client_ports) = self.client_port {
             format!("client_port={}-{};", client_ports[0], client_ports[1])
         } else {
@@ -145,7 +188,9 @@ impl Marshal for RtspTransport {
             String::from("")
         };
 
-        format!("{protocol_type};{cast_type};{client_port}{server_port}{interleaved}{ssrc}{mode}")
+        format!(
+            "{protocol_type};{cast_type};{destination}{port}{ttl}{client_port}{server_port}{interleaved}{ssrc}{mode}"
+        )
     }
 }
 
@@ -155,6 +200,7 @@ mod tests {
     use crate::global_trait::Marshal;
     use crate::global_trait::Unmarshal;
 
+    use super::CastType;
     use super::RtspTransport;
 
     #[test]
@@ -175,4 +221,21 @@ mod tests {
 
         println!("marshal reslut: {}", parser.marshal());
     }
+
+    #[test]
+    fn test_multicast_transport() {
+        let transport = RtspTransport::unmarshal(
+            "RTP/AVP;multicast;destination=239.0.0.1;port=20000-20001;ttl=16",
+        )
+        .unwrap();
+
+        assert_eq!(transport.cast_type, CastType::Multicast);
+        assert_eq!(transport.destination, Some(String::from("239.0.0.1")));
+        assert_eq!(transport.port, Some([20000, 20001]));
+        assert_eq!(transport.ttl, Some(16));
+        assert_eq!(
+            transport.marshal(),
+            "RTP/AVP/UDP;multicast;destination=239.0.0.1;port=20000-20001;ttl=16;"
+        );
+    }
 }
@@ -125,6 +125,20 @@ impl Unmarshal for SdpMediaInfo {
             cur_param_idx += 1;
         }
 
+        //the static payload types of RFC 3551 may have no rtpmap attribute
+        if let Some(encoding_name) = sdp_media.fmts.first().and_then(|fmt| match fmt {
+            0 => Some("PCMU"),
+            8 => Some("PCMA"),
+            _ => None,
+        }) {
+            sdp_media.rtpmap = RtpMap {
+                payload_type: sdp_media.fmts[0] as u16,
+                encoding_name: encoding_name.to_string(),
+                clock_rate: 8000,
+                encoding_param: String::new(),
+            };
+        }
+
         Some(sdp_media)
     }
 }
@@ -184,7 +198,7 @@ impl Unmarshal for Sdp {
             ..Default::default()
         };
 
-        let lines: Vec<&str> = raw_data.split(|c| c == '\r' || c == '\n').collect();
+        let lines: Vec<&str> = raw_data.split(['\r', '\n']).collect();
         for line in lines {
             if line.is_empty() {
                 continue;
@@ -377,6 +391,24 @@ mod tests {
         }
     }
     #[test]
+    fn test_static_payload_type() {
+        let data = "v=0\r\n\
+        o=- 0 0 IN IP4 127.0.0.1\r\n\
+        s=IP Camera\r\n\
+        t=0 0\r\n\
+        m=audio 0 RTP/AVP 8\r\n\
+        a=control:trackID=2\r\n\
+        m=audio 0 RTP/AVP 0\r\n\
+        a=rtpmap:0 PCMU/8000/1\r\n";
+
+        let sdp = Sdp::unmarshal(data).unwrap();
+        assert_eq!(sdp.medias[0].rtpmap.payload_type, 8);
+        assert_eq!(sdp.medias[0].rtpmap.encoding_name, "PCMA");
+        assert_eq!(sdp.medias[0].rtpmap.clock_rate, 8000);
+        assert_eq!(sdp.medias[1].rtpmap.encoding_name, "PCMU");
+        assert_eq!(sdp.medias[1].rtpmap.encoding_param, "1");
+    }
+    #[test]
     fn test_str() {
         //let fmts: Vec<u8> = vec![5];
         //// fmts.push(6);
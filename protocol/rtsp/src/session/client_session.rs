@@ -3,6 +3,7 @@ use super::define::ClientSessionType;
 
 use crate::global_trait::Marshal;
 use crate::global_trait::Unmarshal;
+use crate::rtsp_auth::{ClientAuthenticator, Credentials};
 use crate::rtsp_codec;
 
 use crate::rtsp_transport::CastType;
@@ -80,6 +81,11 @@ pub struct RtspClientSession {
 
     event_producer: StreamHubEventSender,
     pub is_running: Arc<AtomicBool>,
+
+    //parsed from the server address: user:pass@host:port
+    credentials: Option<Credentials>,
+    //generates the Authorization headers after the server challenges
+    authenticator: Option<ClientAuthenticator>,
 }
 
 impl RtspClientSession {
@@ -90,6 +96,7 @@ impl RtspClientSession {
         event_producer: StreamHubEventSender,
         client_type: ClientSessionType,
     ) -> Result<Self, SessionError> {
+        let (credentials, address) = Credentials::split_address(&address);
         let stream = TcpStream::connect(address.clone()).await?;
 
         let net_io: Box<dyn TNetIO + Send + Sync> = Box::new(TcpIO::new(stream));
@@ -112,6 +119,8 @@ impl RtspClientSession {
 
             stream_handler: Arc::new(RtspStreamHandler::new()),
             is_running: Arc::new(AtomicBool::new(true)),
+            credentials,
+            authenticator: None,
         })
     }
 
@@ -178,9 +187,9 @@ impl RtspClientSession {
     async fn send_options(&mut self) -> Result<(), SessionError> {
         log::info!("rtsp client: send_options");
         let uri_path = format!("rtsp://{}/{}", self.address, self.stream_name);
-        let request = self.gen_request(rtsp_method_name::OPTIONS, uri_path);
+        let mut request = self.gen_request(rtsp_method_name::OPTIONS, uri_path);
         self.send_resquest(&request).await?;
-        self.receive_response(rtsp_method_name::OPTIONS).await
+        self.receive_response(&mut request).await
     }
 
     async fn send_announce(&mut self) -> Result<(), SessionError> {
@@ -198,7 +207,7 @@ impl RtspClientSession {
             .headers
             .insert("Accept".to_string(), "application/sdp".to_string());
         self.send_resquest(&request).await?;
-        self.receive_response(rtsp_method_name::DESCRIBE).await
+        self.receive_response(&mut request).await
     }
 
     async fn send_setup(&mut self) -> Result<(), SessionError> {
@@ -290,7 +299,7 @@ impl RtspClientSession {
             }
 
             self.send_resquest(&request).await?;
-            self.receive_response(rtsp_method_name::SETUP).await?;
+            self.receive_response(&mut request).await?;
         }
         Ok(())
     }
@@ -304,7 +313,7 @@ impl RtspClientSession {
             .insert("Range".to_string(), "npt=0.000".to_string());
 
         self.send_resquest(&request).await?;
-        self.receive_response(rtsp_method_name::PLAY).await?;
+        self.receive_response(&mut request).await?;
 
         Ok(())
     }
@@ -350,8 +359,48 @@ impl RtspClientSession {
                 .headers
                 .insert("Session".to_string(), session_id.to_string());
         }
+        self.authorize(&mut request);
+
+        request
+    }
+
+    fn authorize(&mut self, request: &mut RtspRequest) {
+        if let Some(authenticator) = &mut self.authenticator {
+            let authorization =
+                authenticator.authorization(&request.method, &request.uri.marshal());
+            request
+                .headers
+                .insert("Authorization".to_string(), authorization);
+        }
+    }
+
+    /*Answer the 401 challenge of a password protected server(or a stale nonce) by
+    resending the request with the credentials, returns false if it cannot be answered.*/
+    async fn answer_challenge(
+        &mut self,
+        request: &mut RtspRequest,
+        rtsp_response: &RtspResponse,
+    ) -> Result<bool, SessionError> {
+        let (Some(credentials), Some(www_authenticate)) = (
+            &self.credentials,
+            rtsp_response.get_header(&"WWW-Authenticate".to_string()),
+        ) else {
+            return Ok(false);
+        };
+        self.authenticator = ClientAuthenticator::new(credentials.clone(), www_authenticate);
+        if self.authenticator.is_none() {
+            log::error!("unsupported rtsp authentication: {}", www_authenticate);
+            return Ok(false);
+        }
 
         request
+            .headers
+            .insert("CSeq".to_string(), self.cseq.to_string());
+        self.cseq += 1;
+        self.authorize(request);
+        self.send_resquest(request).await?;
+
+        Ok(true)
     }
 
     fn get_subscriber_info(&mut self) -> SubscriberInfo {
@@ -401,15 +450,19 @@ impl RtspClientSession {
             let media_name = &media.media_type;
             match media_name.as_str() {
                 "audio" => {
-                    let codec_id = rtsp_codec::RTSP_CODEC_NAME_2_ID
+                    let Some(codec_id) = rtsp_codec::RTSP_CODEC_NAME_2_ID
                         .get(&media.rtpmap.encoding_name.to_lowercase().as_str())
-                        .unwrap()
-                        .clone();
+                        .cloned()
+                    else {
+                        log::warn!("not supported audio codec: {}", media.rtpmap.encoding_name);
+                        continue;
+                    };
                     let codec_info = RtspCodecInfo {
                         codec_id,
                         payload_type: media.rtpmap.payload_type as u8,
                         sample_rate: media.rtpmap.clock_rate,
-                        channel_count: media.rtpmap.encoding_param.parse().unwrap(),
+                        //the channels are omitted for the mono audio, e.g. PCMA/8000
+                        channel_count: media.rtpmap.encoding_param.parse().unwrap_or(1),
                     };
 
                     log::info!("audio codec info: {:?}", codec_info);
@@ -445,12 +498,11 @@ impl RtspClientSession {
         Ok(())
     }
 
-    async fn receive_response(&mut self, method_name: &str) -> Result<(), SessionError> {
+    async fn read_response(&mut self) -> Result<Option<RtspResponse>, SessionError> {
         let data = self.io.lock().await.read().await?;
         self.reader.extend_from_slice(&data[..]);
 
         let mut retry_count = 0;
-        let rtsp_response;
 
         loop {
             let data = self.reader.get_remaining_bytes();
@@ -469,7 +521,7 @@ impl RtspClientSession {
                                     "corrupted rtsp message={}",
                                     std::str::from_utf8(&data)?
                                 );
-                                return Ok(());
+                                return Ok(None);
                             }
                             retry_count += 1;
                             let data_recv = self.io.lock().await.read().await?;
@@ -478,14 +530,27 @@ impl RtspClientSession {
                         }
                     }
                 }
-                rtsp_response = rtsp_response_data;
                 self.reader.extract_remaining_bytes();
-                break;
+                return Ok(Some(rtsp_response_data));
             } else {
                 log::error!("corrupted rtsp message={}", std::str::from_utf8(&data)?);
-                return Ok(());
+                return Ok(None);
             }
         }
+    }
+
+    async fn receive_response(&mut self, request: &mut RtspRequest) -> Result<(), SessionError> {
+        let Some(mut rtsp_response) = self.read_response().await? else {
+            return Ok(());
+        };
+        if rtsp_response.status_code == http::StatusCode::UNAUTHORIZED
+            && self.answer_challenge(request, &rtsp_response).await?
+        {
+            let Some(response) = self.read_response().await? else {
+                return Ok(());
+            };
+            rtsp_response = response;
+        }
 
         if rtsp_response.status_code != http::StatusCode::OK {
             log::error!("rtsp response error: {}", rtsp_response.marshal());
@@ -494,7 +559,7 @@ impl RtspClientSession {
             });
         }
 
-        match method_name {
+        match request.method.as_str() {
             rtsp_method_name::OPTIONS => {
                 if let Some(public) = rtsp_response.get_header(&"Public".to_string()) {
                     log::info!("support methods: {}", public);
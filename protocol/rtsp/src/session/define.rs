@@ -49,3 +49,12 @@ impl fmt::Display for ServerSessionType {
 }
 
 pub const USER_AGENT: &str = "xiu 0.12.8";
+
+//the timeout in the Session header, the idle sessions are torn down after it
+pub const DEFAULT_SESSION_TIMEOUT_SECS: u64 = 60;
+
+//the RTSP specific status codes which are not defined in http
+pub const STATUS_CODE_PARAMETER_NOT_UNDERSTOOD: u16 = 451;
+pub const STATUS_CODE_NOT_ENOUGH_BANDWIDTH: u16 = 453;
+pub const STATUS_CODE_SESSION_NOT_FOUND: u16 = 454;
+pub const STATUS_CODE_UNSUPPORTED_TRANSPORT: u16 = 461;
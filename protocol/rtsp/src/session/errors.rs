@@ -3,7 +3,7 @@ use {
     crate::rtp::errors::{PackerError, UnPackerError},
     bytesio::bytes_errors::BytesReadError,
     bytesio::{bytes_errors::BytesWriteError, bytesio_errors::BytesIOError},
-    commonlib::errors::AuthError,
+    commonlib::errors::{AclError, AuthError},
     failure::{Backtrace, Fail},
     std::fmt,
     std::io::Error,
@@ -41,12 +41,16 @@ pub enum SessionErrorValue {
     RecvError(#[cause] RecvError),
     #[fail(display = "auth err: {}", _0)]
     AuthError(#[cause] AuthError),
+    #[fail(display = "acl err: {}", _0)]
+    AclError(#[cause] AclError),
     #[fail(display = "Channel receive error")]
     ChannelRecvError,
     #[fail(display = "io error")]
     IOError(#[cause] Error),
     #[fail(display = "RTSP response status error")]
     RtspResponseStatusError,
+    #[fail(display = "session timeout")]
+    SessionTimeout,
 }
 
 impl From<BytesIOError> for SessionError {
@@ -121,6 +125,14 @@ impl From<AuthError> for SessionError {
     }
 }
 
+impl From<AclError> for SessionError {
+    fn from(error: AclError) -> Self {
+        SessionError {
+            value: SessionErrorValue::AclError(error),
+        }
+    }
+}
+
 impl From<Error> for SessionError {
     fn from(error: Error) -> Self {
         SessionError {
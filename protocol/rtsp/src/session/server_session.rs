@@ -17,8 +17,10 @@ use crate::rtsp_range::RtspRange;
 use crate::sdp::fmtp::Fmtp;
 
 use crate::rtsp_codec::RtspCodecInfo;
+use crate::rtsp_multicast::MulticastManager;
 use crate::rtsp_track::RtspTrack;
 use crate::rtsp_track::TrackType;
+use crate::rtsp_transport::CastType;
 use crate::rtsp_transport::ProtocolType;
 use crate::rtsp_transport::RtspTransport;
 
@@ -32,9 +34,12 @@ use super::errors::SessionErrorValue;
 use bytesio::bytes_writer::BytesWriter;
 use bytesio::bytesio::UdpIO;
 use http::StatusCode;
+use streamhub::define::AudioCodecType;
 use streamhub::define::DataSender;
+use streamhub::define::FrameDataReceiver;
 use streamhub::define::MediaInfo;
 use streamhub::define::VideoCodecType;
+use tokio::sync::broadcast;
 use tokio::sync::oneshot;
 
 use crate::rtp::errors::UnPackerError;
@@ -43,22 +48,27 @@ use crate::sdp::Sdp;
 use super::define;
 use super::define::rtsp_method_name;
 use async_trait::async_trait;
+use base64::{engine::general_purpose, Engine as _};
 use bytesio::bytesio::TNetIO;
 use bytesio::bytesio::TcpIO;
 
 use std::collections::HashMap;
-use std::sync::Arc;
 use std::net::SocketAddr;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::time::Instant;
 
-use commonlib::auth::Auth;
+use crate::rtsp_auth::ServerAuthenticator;
+use commonlib::{acl::AccessControl, auth::Auth};
 use streamhub::{
     define::{
         FrameData, Information, InformationSender, NotifyInfo, PublishType, PublisherInfo,
         StreamHubEvent, StreamHubEventSender, SubscribeType, SubscriberInfo, TStreamHandler,
     },
     errors::{StreamHubError, StreamHubErrorValue},
-    statistics::StatisticsStream,
+    statistics::{metrics, StatisticsStream},
     stream::StreamIdentifier,
     utils::{RandomDigitCount, Uuid},
 };
@@ -79,12 +89,38 @@ pub struct RtspServerSession {
     event_producer: StreamHubEventSender,
 
     auth: Option<Auth>,
+    //the publish/play directions of the remote ip are checked if set
+    access_control: Option<Arc<AccessControl>>,
+    //challenge the requests with Digest/Basic authentication if set
+    authenticator: Option<ServerAuthenticator>,
+    //the digest nonce of this connection
+    auth_nonce: Option<String>,
+
+    //the frames subscribed by PLAY, they are forwarded in the read loop
+    frame_receiver: Option<FrameDataReceiver>,
+    is_paused: bool,
+    //the session is torn down if neither RTSP nor RTCP is received in this duration
+    session_timeout: Duration,
+    last_active_time: Instant,
+
+    //the tracks set up with multicast are sent by the manager
+    multicast_manager: Option<Arc<MulticastManager>>,
+    multicast_viewer: Option<u64>,
+    //notified when the multicast forwarding ends, e.g. the publisher leaves
+    multicast_end_receiver: Option<broadcast::Receiver<()>>,
 
     pub stream_identifier: Option<StreamIdentifier>,
     pub is_normal_exit: bool,
     remote_addr: SocketAddr,
 }
 
+enum SessionEvent {
+    Data(BytesMut),
+    Frame(Option<FrameData>),
+    MulticastEnd,
+    Timeout,
+}
+
 pub struct InterleavedBinaryData {
     pub channel_identifier: u8,
     pub length: u16,
@@ -129,6 +165,16 @@ impl RtspServerSession {
 
         let remote_addr = stream.peer_addr().unwrap_or(stream.local_addr().unwrap());
         let net_io: Box<dyn TNetIO + Send + Sync> = Box::new(TcpIO::new(stream));
+        Self::new_with_io(net_io, remote_addr, event_producer, auth)
+    }
+
+    //the session over an io other than the TCP connection, e.g. the HTTP tunnel
+    pub fn new_with_io(
+        net_io: Box<dyn TNetIO + Send + Sync>,
+        remote_addr: SocketAddr,
+        event_producer: StreamHubEventSender,
+        auth: Option<Auth>,
+    ) -> Self {
         let io = Arc::new(Mutex::new(net_io));
 
         Self {
@@ -145,13 +191,53 @@ impl RtspServerSession {
             stream_identifier: None,
             is_normal_exit: false,
             remote_addr,
+            access_control: None,
+            authenticator: None,
+            auth_nonce: None,
+            frame_receiver: None,
+            is_paused: false,
+            session_timeout: Duration::from_secs(define::DEFAULT_SESSION_TIMEOUT_SECS),
+            last_active_time: Instant::now(),
+            multicast_manager: None,
+            multicast_viewer: None,
+            multicast_end_receiver: None,
         }
     }
 
+    pub fn set_multicast_manager(&mut self, multicast_manager: Arc<MulticastManager>) {
+        self.multicast_manager = Some(multicast_manager);
+    }
+
+    //the data which has been read from the connection before the session is created
+    pub fn set_received_data(&mut self, data: &[u8]) {
+        self.reader.extend_from_slice(data);
+    }
+
+    pub fn set_session_timeout(&mut self, session_timeout: Duration) {
+        self.session_timeout = session_timeout;
+    }
+
+    pub fn set_authenticator(&mut self, authenticator: ServerAuthenticator) {
+        self.authenticator = Some(authenticator);
+    }
+
+    pub fn set_access_control(&mut self, access_control: Arc<AccessControl>) {
+        self.access_control = Some(access_control);
+    }
+
+    fn check_access(&self, is_pull: bool) -> Result<(), SessionError> {
+        if let Some(access_control) = &self.access_control {
+            access_control
+                .check(self.remote_addr.ip(), is_pull)
+                .inspect_err(|_| metrics::inc_acl_rejection("rtsp"))?;
+        }
+        Ok(())
+    }
+
     pub async fn run(&mut self) -> Result<(), SessionError> {
         loop {
             while self.reader.len() < 4 {
-                let data = self.io.lock().await.read().await?;
+                let data = self.read_data().await?;
                 self.reader.extend_from_slice(&data[..]);
             }
             // If delivering media data using RTP over RTSP(TCP), then it should use InterleavedBinaryData
@@ -164,7 +250,7 @@ impl RtspServerSession {
                 match data {
                     Some(a) => {
                         while self.reader.len() < a.length as usize {
-                            let data = self.io.lock().await.read().await?;
+                            let data = self.read_data().await?;
                             self.reader.extend_from_slice(&data[..]);
                         }
                         self.on_rtp_over_rtsp_message(a.channel_identifier, a.length as usize)
@@ -178,6 +264,81 @@ impl RtspServerSession {
         }
     }
 
+    //reads the data of the RTSP connection, the subscribed frames are forwarded and
+    //the idle session is reaped while waiting.
+    async fn read_data(&mut self) -> Result<BytesMut, SessionError> {
+        loop {
+            let io = self.io.clone();
+            let deadline = self.last_active_time + self.session_timeout;
+
+            let event = tokio::select! {
+                data = async { io.lock().await.read().await } => SessionEvent::Data(data?),
+                frame = async {
+                    match self.frame_receiver.as_mut() {
+                        Some(receiver) => receiver.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => SessionEvent::Frame(frame),
+                _ = async {
+                    match self.multicast_end_receiver.as_mut() {
+                        Some(receiver) => receiver.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => SessionEvent::MulticastEnd,
+                _ = tokio::time::sleep_until(deadline) => SessionEvent::Timeout,
+            };
+
+            match event {
+                SessionEvent::Data(data) => {
+                    self.last_active_time = Instant::now();
+                    return Ok(data);
+                }
+                SessionEvent::Frame(Some(frame_data)) => {
+                    //the frames are dropped while paused, so the play resumes from the live position
+                    if !self.is_paused {
+                        self.send_frame(frame_data).await?;
+                    }
+                }
+                SessionEvent::Frame(None) | SessionEvent::MulticastEnd => {
+                    return Err(SessionError {
+                        value: SessionErrorValue::CannotReceiveFrameData,
+                    });
+                }
+                SessionEvent::Timeout => {
+                    //the UDP RTP/RTCP is received out of the RTSP connection
+                    let udp_received = self
+                        .tracks
+                        .values()
+                        .any(|track| track.udp_received.swap(false, Ordering::Relaxed));
+                    if !udp_received {
+                        return Err(SessionError {
+                            value: SessionErrorValue::SessionTimeout,
+                        });
+                    }
+                    self.last_active_time = Instant::now();
+                }
+            }
+        }
+    }
+
+    async fn send_frame(&mut self, frame_data: FrameData) -> Result<(), SessionError> {
+        let (track_type, timestamp, mut data) = match frame_data {
+            FrameData::Audio { timestamp, data } => (TrackType::Audio, timestamp, data),
+            FrameData::Video { timestamp, data } => (TrackType::Video, timestamp, data),
+            _ => return Ok(()),
+        };
+
+        if let Some(track) = self.tracks.get_mut(&track_type) {
+            track
+                .rtp_channel
+                .lock()
+                .await
+                .on_frame(&mut data, timestamp)
+                .await?;
+        }
+        Ok(())
+    }
+
     async fn on_rtp_over_rtsp_message(
         &mut self,
         channel_identifier: u8,
@@ -226,7 +387,7 @@ impl RtspServerSession {
                                 return Ok(());
                             }
                             retry_count += 1;
-                            let data_recv = self.io.lock().await.read().await?;
+                            let data_recv = self.read_data().await?;
                             self.reader.extend_from_slice(&data_recv[..]);
                             continue;
                         }
@@ -241,6 +402,12 @@ impl RtspServerSession {
             break;
         }
 
+        if rtsp_request.method != rtsp_method_name::OPTIONS
+            && !self.check_authorization(&rtsp_request).await?
+        {
+            return Ok(());
+        }
+
         match rtsp_request.method.as_str() {
             rtsp_method_name::OPTIONS => {
                 self.handle_options(&rtsp_request).await?;
@@ -265,16 +432,58 @@ impl RtspServerSession {
             rtsp_method_name::TEARDOWN => {
                 self.handle_teardown(&rtsp_request)?;
             }
-            rtsp_method_name::PAUSE => {}
-            rtsp_method_name::GET_PARAMETER => {}
-            rtsp_method_name::SET_PARAMETER => {}
-            rtsp_method_name::REDIRECT => {}
-
-            _ => {}
+            rtsp_method_name::PAUSE => {
+                self.handle_pause(&rtsp_request).await?;
+            }
+            rtsp_method_name::GET_PARAMETER => {
+                self.handle_get_parameter(&rtsp_request).await?;
+            }
+            rtsp_method_name::SET_PARAMETER => {
+                self.handle_set_parameter(&rtsp_request).await?;
+            }
+            //REDIRECT is sent from the server to the client
+            _ => {
+                let response = Self::gen_response(http::StatusCode::NOT_IMPLEMENTED, &rtsp_request);
+                self.send_response(&response).await?;
+            }
         }
         Ok(())
     }
 
+    //the requests without valid credentials are answered by 401 with the challenge
+    async fn check_authorization(
+        &mut self,
+        rtsp_request: &RtspRequest,
+    ) -> Result<bool, SessionError> {
+        let Some(authenticator) = &self.authenticator else {
+            return Ok(true);
+        };
+        let nonce = self
+            .auth_nonce
+            .get_or_insert_with(ServerAuthenticator::gen_nonce);
+
+        if let Some(authorization) = rtsp_request.get_header(&"Authorization".to_string()) {
+            if authenticator.verify(
+                authorization,
+                &rtsp_request.method,
+                &rtsp_request.uri.marshal(),
+                nonce,
+            ) {
+                return Ok(true);
+            }
+            metrics::inc_auth_failure("rtsp");
+        }
+
+        let mut response = Self::gen_response(http::StatusCode::UNAUTHORIZED, rtsp_request);
+        response.headers.insert(
+            "WWW-Authenticate".to_string(),
+            authenticator.challenge(nonce),
+        );
+        self.send_response(&response).await?;
+
+        Ok(false)
+    }
+
     async fn handle_options(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
         let status_code = http::StatusCode::OK;
         let mut response = Self::gen_response(status_code, rtsp_request);
@@ -296,6 +505,8 @@ impl RtspServerSession {
             stream_path: rtsp_request.uri.path.clone(),
         };
         self.stream_identifier = Some(identifier.clone());
+        //the describing session is a player, it is unsubscribed on exit
+        self.session_type = define::ServerSessionType::Pull;
 
         let request_event = StreamHubEvent::Request { identifier, sender };
 
@@ -325,9 +536,12 @@ impl RtspServerSession {
     }
 
     async fn handle_announce(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
+        self.check_access(false)?;
         if let Some(auth) = &self.auth {
             let stream_name = rtsp_request.uri.path.clone();
+            let app_name = stream_name.trim_start_matches('/').split('/').next();
             auth.authenticate(
+                app_name.unwrap_or_default(),
                 &stream_name,
                 &rtsp_request
                     .uri
@@ -335,7 +549,8 @@ impl RtspServerSession {
                     .as_ref()
                     .map(|q| SecretCarrier::Query(q.to_string())),
                 false,
-            )?;
+            )
+            .inspect_err(|_| metrics::inc_auth_failure("rtsp"))?;
         }
 
         if let Some(request_body) = &rtsp_request.body {
@@ -419,6 +634,61 @@ impl RtspServerSession {
                     let mut rtp_server_port: Option<u16> = None;
                     let mut rtcp_server_port: Option<u16> = None;
 
+                    if trans.cast_type == CastType::Multicast {
+                        let group = match (&self.multicast_manager, &self.stream_identifier) {
+                            (
+                                Some(multicast_manager),
+                                Some(StreamIdentifier::Rtsp { stream_path }),
+                            ) => {
+                                let viewer = *self
+                                    .multicast_viewer
+                                    .get_or_insert_with(|| multicast_manager.new_viewer());
+                                multicast_manager.join(
+                                    stream_path,
+                                    track.track_type.clone(),
+                                    track.rtp_channel.lock().await.codec_info().clone(),
+                                    viewer,
+                                )
+                            }
+                            _ => {
+                                response = Self::gen_response(
+                                    http::StatusCode::from_u16(
+                                        define::STATUS_CODE_UNSUPPORTED_TRANSPORT,
+                                    )
+                                    .unwrap(),
+                                    rtsp_request,
+                                );
+                                break;
+                            }
+                        };
+                        let Some(group) = group else {
+                            response = Self::gen_response(
+                                http::StatusCode::from_u16(
+                                    define::STATUS_CODE_NOT_ENOUGH_BANDWIDTH,
+                                )
+                                .unwrap(),
+                                rtsp_request,
+                            );
+                            break;
+                        };
+                        trans.protocol_type = ProtocolType::UDP;
+                        trans.client_port = None;
+                        trans.destination = Some(group.address.to_string());
+                        trans.port = Some([group.rtp_port, group.rtcp_port]);
+                        trans.ttl = Some(group.ttl);
+
+                        response
+                            .headers
+                            .insert("Transport".to_string(), trans.marshal());
+                        if let Some(session) =
+                            Self::gen_session_header(&self.session_id, self.session_timeout)
+                        {
+                            response.headers.insert("Session".to_string(), session);
+                        }
+                        track.set_transport(trans).await;
+                        break;
+                    }
+
                     match trans.protocol_type {
                         ProtocolType::TCP => {
                             track.create_packer(self.io.clone()).await;
@@ -471,9 +741,11 @@ impl RtspServerSession {
                     response
                         .headers
                         .insert("Transport".to_string(), new_transport_data);
-                    response
-                        .headers
-                        .insert("Session".to_string(), self.session_id.unwrap().to_string());
+                    if let Some(session) =
+                        Self::gen_session_header(&self.session_id, self.session_timeout)
+                    {
+                        response.headers.insert("Session".to_string(), session);
+                    }
 
                     track.set_transport(trans).await;
                 }
@@ -487,9 +759,21 @@ impl RtspServerSession {
     }
 
     async fn handle_play(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
+        //resume the paused session
+        if self.frame_receiver.is_some() {
+            self.is_paused = false;
+            let mut response = Self::gen_response(http::StatusCode::OK, rtsp_request);
+            self.insert_session_header(&mut response);
+            self.send_response(&response).await?;
+            return Ok(());
+        }
+
+        self.check_access(true)?;
         if let Some(auth) = &self.auth {
             let stream_name = rtsp_request.uri.path.clone();
+            let app_name = stream_name.trim_start_matches('/').split('/').next();
             auth.authenticate(
+                app_name.unwrap_or_default(),
                 &stream_name,
                 &rtsp_request
                     .uri
@@ -497,7 +781,8 @@ impl RtspServerSession {
                     .as_ref()
                     .map(|q| SecretCarrier::Query(q.to_string())),
                 true,
-            )?;
+            )
+            .inspect_err(|_| metrics::inc_auth_failure("rtsp"))?;
         }
 
         for track in self.tracks.values_mut() {
@@ -546,12 +831,35 @@ impl RtspServerSession {
         }
 
         let status_code = http::StatusCode::OK;
-        let response = Self::gen_response(status_code, rtsp_request);
+        let mut response = Self::gen_response(status_code, rtsp_request);
+        self.insert_session_header(&mut response);
 
         self.send_response(&response).await?;
 
         self.session_type = define::ServerSessionType::Pull;
 
+        if self.multicast_viewer.is_some() {
+            if let (Some(multicast_manager), Some(StreamIdentifier::Rtsp { stream_path })) =
+                (&self.multicast_manager, &self.stream_identifier)
+            {
+                self.multicast_end_receiver =
+                    multicast_manager.start(stream_path, self.event_producer.clone());
+            }
+
+            //the frames are subscribed only for the unicast tracks
+            let mut has_unicast_track = false;
+            for track in self.tracks.values() {
+                if track.transport.cast_type == CastType::Unicast
+                    && track.rtp_channel.lock().await.rtp_packer.is_some()
+                {
+                    has_unicast_track = true;
+                }
+            }
+            if !has_unicast_track {
+                return Ok(());
+            }
+        }
+
         let (event_result_sender, event_result_receiver) = oneshot::channel();
 
         let subscribe_event = StreamHubEvent::Subscribe {
@@ -568,54 +876,17 @@ impl RtspServerSession {
             });
         }
 
-        let mut receiver = event_result_receiver.await??.0.frame_receiver.unwrap();
-
-        let mut retry_times = 0;
-        loop {
-            if let Some(frame_data) = receiver.recv().await {
-                match frame_data {
-                    FrameData::Audio {
-                        timestamp,
-                        mut data,
-                    } => {
-                        if let Some(audio_track) = self.tracks.get_mut(&TrackType::Audio) {
-                            audio_track
-                                .rtp_channel
-                                .lock()
-                                .await
-                                .on_frame(&mut data, timestamp)
-                                .await?;
-                        }
-                    }
-                    FrameData::Video {
-                        timestamp,
-                        mut data,
-                    } => {
-                        if let Some(video_track) = self.tracks.get_mut(&TrackType::Video) {
-                            video_track
-                                .rtp_channel
-                                .lock()
-                                .await
-                                .on_frame(&mut data, timestamp)
-                                .await?;
-                        }
-                    }
-                    _ => {}
-                }
-            } else {
-                retry_times += 1;
-                log::info!(
-                    "send_channel_data: no data receives ,retry {} times!",
-                    retry_times
-                );
-
-                if retry_times > 10 {
-                    return Err(SessionError {
-                        value: SessionErrorValue::CannotReceiveFrameData,
-                    });
-                }
-            }
+        //the frames are forwarded in the read loop, so the RTSP requests can still be handled
+        let receiver = event_result_receiver.await??.0.frame_receiver;
+        if receiver.is_none() {
+            return Err(SessionError {
+                value: SessionErrorValue::CannotReceiveFrameData,
+            });
         }
+        self.frame_receiver = receiver;
+        self.is_paused = false;
+
+        Ok(())
     }
 
     async fn handle_record(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
@@ -632,10 +903,54 @@ impl RtspServerSession {
             }
         }
 
-        response
-            .headers
-            .insert("Session".to_string(), self.session_id.unwrap().to_string());
+        self.insert_session_header(&mut response);
+        self.send_response(&response).await?;
+
+        Ok(())
+    }
+
+    //the frame forwarding of a playing session is suspended until the next PLAY
+    async fn handle_pause(&mut self, rtsp_request: &RtspRequest) -> Result<(), SessionError> {
+        let status_code = if self.session_id.is_none() {
+            http::StatusCode::from_u16(define::STATUS_CODE_SESSION_NOT_FOUND).unwrap()
+        } else {
+            self.is_paused = true;
+            http::StatusCode::OK
+        };
 
+        let mut response = Self::gen_response(status_code, rtsp_request);
+        self.insert_session_header(&mut response);
+        self.send_response(&response).await?;
+
+        Ok(())
+    }
+
+    //GET_PARAMETER with an empty body is used as a keepalive by many clients
+    async fn handle_get_parameter(
+        &mut self,
+        rtsp_request: &RtspRequest,
+    ) -> Result<(), SessionError> {
+        let mut response = Self::gen_response(http::StatusCode::OK, rtsp_request);
+        self.insert_session_header(&mut response);
+        self.send_response(&response).await?;
+
+        Ok(())
+    }
+
+    //no parameter can be set, only the keepalive without body is accepted
+    async fn handle_set_parameter(
+        &mut self,
+        rtsp_request: &RtspRequest,
+    ) -> Result<(), SessionError> {
+        let status_code = match &rtsp_request.body {
+            Some(body) if !body.trim().is_empty() => {
+                http::StatusCode::from_u16(define::STATUS_CODE_PARAMETER_NOT_UNDERSTOOD).unwrap()
+            }
+            _ => http::StatusCode::OK,
+        };
+
+        let mut response = Self::gen_response(status_code, rtsp_request);
+        self.insert_session_header(&mut response);
         self.send_response(&response).await?;
 
         Ok(())
@@ -646,10 +961,21 @@ impl RtspServerSession {
             stream_path: rtsp_request.uri.path.clone(),
         };
         log::info!("handle_teardown...");
+        self.frame_receiver = None;
         self.exit(identifier)
     }
 
     pub fn exit(&mut self, identifier: StreamIdentifier) -> Result<(), SessionError> {
+        self.multicast_end_receiver = None;
+        if let Some(viewer) = self.multicast_viewer.take() {
+            //the groups are joined with the path of DESCRIBE
+            if let (Some(multicast_manager), Some(StreamIdentifier::Rtsp { stream_path })) =
+                (&self.multicast_manager, &self.stream_identifier)
+            {
+                multicast_manager.leave(stream_path, viewer);
+            }
+        }
+
         let event = match self.session_type {
             define::ServerSessionType::Pull => StreamHubEvent::UnSubscribe {
                 identifier,
@@ -691,15 +1017,19 @@ impl RtspServerSession {
             log::info!("media_name: {}", media_name);
             match media_name.as_str() {
                 "audio" => {
-                    let codec_id = rtsp_codec::RTSP_CODEC_NAME_2_ID
+                    let Some(codec_id) = rtsp_codec::RTSP_CODEC_NAME_2_ID
                         .get(&media.rtpmap.encoding_name.to_lowercase().as_str())
-                        .unwrap()
-                        .clone();
+                        .cloned()
+                    else {
+                        log::warn!("not supported audio codec: {}", media.rtpmap.encoding_name);
+                        continue;
+                    };
                     let codec_info = RtspCodecInfo {
                         codec_id,
                         payload_type: media.rtpmap.payload_type as u8,
                         sample_rate: media.rtpmap.clock_rate,
-                        channel_count: media.rtpmap.encoding_param.parse().unwrap(),
+                        //the channels are omitted for the mono audio, e.g. PCMA/8000
+                        channel_count: media.rtpmap.encoding_param.parse().unwrap_or(1),
                     };
 
                     log::info!("audio codec info: {:?}", codec_info);
@@ -750,6 +1080,19 @@ impl RtspServerSession {
         response
     }
 
+    //Session: <id>;timeout=<seconds>
+    fn gen_session_header(session_id: &Option<Uuid>, session_timeout: Duration) -> Option<String> {
+        session_id
+            .as_ref()
+            .map(|id| format!("{};timeout={}", id, session_timeout.as_secs()))
+    }
+
+    fn insert_session_header(&self, response: &mut RtspResponse) {
+        if let Some(session) = Self::gen_session_header(&self.session_id, self.session_timeout) {
+            response.headers.insert("Session".to_string(), session);
+        }
+    }
+
     fn get_subscriber_info(&mut self) -> SubscriberInfo {
         let id = if let Some(session_id) = &self.session_id {
             *session_id
@@ -832,8 +1175,26 @@ impl TStreamHandler for RtspStreamHandler {
                 let mut audio_clock_rate: u32 = 0;
 
                 let mut vcodec: VideoCodecType = VideoCodecType::H264;
+                let mut acodec: AudioCodecType = AudioCodecType::AAC;
+                //the media info is sent first so the codec is known before the first frame
+                let mut prior_frames = Vec::new();
 
                 for media in &sdp_info.medias {
+                    //the G.711 samples need no parameters, so there is no fmtp
+                    if media.media_type == "audio" {
+                        if let Some(codec) =
+                            match media.rtpmap.encoding_name.to_lowercase().as_str() {
+                                "pcma" => Some(AudioCodecType::G711A),
+                                "pcmu" => Some(AudioCodecType::G711U),
+                                _ => None,
+                            }
+                        {
+                            acodec = codec;
+                            audio_clock_rate = media.rtpmap.clock_rate;
+                            continue;
+                        }
+                    }
+
                     let mut bytes_writer = BytesWriter::new();
                     if let Some(fmtp) = &media.fmtp {
                         match fmtp {
@@ -843,43 +1204,38 @@ impl TStreamHandler for RtspStreamHandler {
                                 bytes_writer.write(&ANNEXB_NALU_START_CODE)?;
                                 bytes_writer.write(&data.pps)?;
 
-                                let frame_data = FrameData::Video {
+                                prior_frames.push(FrameData::Video {
                                     timestamp: 0,
                                     data: bytes_writer.extract_current_bytes(),
-                                };
-                                if let Err(err) = sender.send(frame_data) {
-                                    log::error!("send sps/pps error: {}", err);
-                                }
+                                });
                                 video_clock_rate = media.rtpmap.clock_rate;
                             }
                             Fmtp::H265(data) => {
-                                bytes_writer.write(&ANNEXB_NALU_START_CODE)?;
-                                bytes_writer.write(&data.sps)?;
-                                bytes_writer.write(&ANNEXB_NALU_START_CODE)?;
-                                bytes_writer.write(&data.pps)?;
-                                bytes_writer.write(&ANNEXB_NALU_START_CODE)?;
-                                bytes_writer.write(&data.vps)?;
+                                //the sprop-vps/sps/pps are saved as base64 strings
+                                for parameter_set in [&data.vps, &data.sps, &data.pps] {
+                                    match general_purpose::STANDARD.decode(&parameter_set[..]) {
+                                        Ok(nalu) => {
+                                            bytes_writer.write(&ANNEXB_NALU_START_CODE)?;
+                                            bytes_writer.write(&nalu)?;
+                                        }
+                                        Err(err) => {
+                                            log::error!("decode vps/sps/pps error: {}", err);
+                                        }
+                                    }
+                                }
 
-                                let frame_data = FrameData::Video {
+                                prior_frames.push(FrameData::Video {
                                     timestamp: 0,
                                     data: bytes_writer.extract_current_bytes(),
-                                };
-                                if let Err(err) = sender.send(frame_data) {
-                                    log::error!("send sps/pps/vps error: {}", err);
-                                }
-
+                                });
+                                video_clock_rate = media.rtpmap.clock_rate;
                                 vcodec = VideoCodecType::H265;
                             }
                             Fmtp::Mpeg4(data) => {
-                                let frame_data = FrameData::Audio {
+                                prior_frames.push(FrameData::Audio {
                                     timestamp: 0,
                                     data: data.asc.clone(),
-                                };
-
-                                if let Err(err) = sender.send(frame_data) {
-                                    log::error!("send asc error: {}", err);
-                                }
-
+                                });
                                 audio_clock_rate = media.rtpmap.clock_rate;
                             }
                         }
@@ -892,10 +1248,17 @@ impl TStreamHandler for RtspStreamHandler {
                         video_clock_rate,
 
                         vcodec,
+                        acodec,
                     },
                 }) {
                     log::error!("send media info error: {}", err);
                 }
+
+                for frame_data in prior_frames {
+                    if let Err(err) = sender.send(frame_data) {
+                        log::error!("send prior data error: {}", err);
+                    }
+                }
             }
             SubscribeType::RtmpRemux2Hls => {}
             _ => {}
@@ -915,3 +1278,202 @@ impl TStreamHandler for RtspStreamHandler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{RtspRequest, RtspServerSession, RtspUnmarshal};
+    use crate::rtp::RtpPacket;
+    use crate::rtsp_codec::{RtspCodecId, RtspCodecInfo};
+    use crate::rtsp_track::{RtspTrack, TrackType};
+    use crate::session::errors::SessionErrorValue;
+    use async_trait::async_trait;
+    use bytes::{Bytes, BytesMut};
+    use bytesio::bytesio::{NetType, TNetIO};
+    use bytesio::bytesio_errors::{BytesIOError, BytesIOErrorValue};
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use streamhub::define::{FrameData, FrameDataSender};
+    use streamhub::queue;
+    use streamhub::utils::{RandomDigitCount, Uuid};
+    use tokio::sync::{mpsc, Mutex};
+
+    //the RTSP connection of the tests, nothing is read until the test sends it
+    struct TestIO {
+        read_receiver: mpsc::UnboundedReceiver<BytesMut>,
+    }
+
+    #[async_trait]
+    impl TNetIO for TestIO {
+        fn get_net_type(&self) -> NetType {
+            NetType::TCP
+        }
+
+        async fn write(&mut self, _bytes: Bytes) -> Result<(), BytesIOError> {
+            Ok(())
+        }
+
+        async fn read_timeout(&mut self, _duration: Duration) -> Result<BytesMut, BytesIOError> {
+            self.read().await
+        }
+
+        async fn read(&mut self) -> Result<BytesMut, BytesIOError> {
+            self.read_receiver.recv().await.ok_or(BytesIOError {
+                value: BytesIOErrorValue::NoneReturn,
+            })
+        }
+    }
+
+    fn test_io() -> (
+        Box<dyn TNetIO + Send + Sync>,
+        mpsc::UnboundedSender<BytesMut>,
+    ) {
+        let (read_sender, read_receiver) = mpsc::unbounded_channel();
+        (Box::new(TestIO { read_receiver }), read_sender)
+    }
+
+    fn request(method: &str, session_id: Uuid) -> RtspRequest {
+        RtspRequest::unmarshal(&format!(
+            "{method} rtsp://127.0.0.1/live/test RTSP/1.0\r\nCSeq: 5\r\nSession: {session_id}\r\n\r\n"
+        ))
+        .unwrap()
+    }
+
+    //a playing session whose RTP packets of the audio track are sent to the returned receiver
+    async fn playing_session() -> (
+        RtspServerSession,
+        FrameDataSender,
+        mpsc::UnboundedReceiver<RtpPacket>,
+        mpsc::UnboundedSender<BytesMut>,
+    ) {
+        let (io, read_sender) = test_io();
+        let (event_producer, _) = mpsc::unbounded_channel();
+        let mut session = RtspServerSession::new_with_io(
+            io,
+            "127.0.0.1:5000".parse().unwrap(),
+            event_producer,
+            None,
+        );
+        session.session_id = Some(Uuid::new(RandomDigitCount::Zero));
+
+        let codec_info = RtspCodecInfo {
+            codec_id: RtspCodecId::G711A,
+            payload_type: 8,
+            sample_rate: 8000,
+            channel_count: 1,
+        };
+        let mut track = RtspTrack::new(TrackType::Audio, codec_info, String::from("track1"));
+        let (test_io, _) = test_io();
+        track.create_packer(Arc::new(Mutex::new(test_io))).await;
+
+        let (packet_sender, packet_receiver) = mpsc::unbounded_channel();
+        track
+            .rtp_channel
+            .lock()
+            .await
+            .on_packet_handler(Box::new(move |_, packet: RtpPacket| {
+                let packet_sender = packet_sender.clone();
+                Box::pin(async move {
+                    let _ = packet_sender.send(packet);
+                    Ok(())
+                })
+            }));
+        session.tracks.insert(TrackType::Audio, track);
+
+        let (frame_sender, frame_receiver) = queue::unbounded();
+        session.frame_receiver = Some(frame_receiver);
+
+        (session, frame_sender, packet_receiver, read_sender)
+    }
+
+    fn audio_frame(timestamp: u32) -> FrameData {
+        FrameData::Audio {
+            timestamp,
+            data: BytesMut::from(&[0xd5; 160][..]),
+        }
+    }
+
+    //forwards the queued frames while waiting for the RTSP requests
+    async fn forward_frames(session: &mut RtspServerSession) {
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), session.read_data())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pause_and_resume() {
+        let (mut session, frame_sender, mut packet_receiver, _read_sender) =
+            playing_session().await;
+        let session_id = session.session_id.unwrap();
+
+        frame_sender.send(audio_frame(0)).unwrap();
+        forward_frames(&mut session).await;
+        assert_eq!(packet_receiver.try_recv().unwrap().header.timestamp, 0);
+
+        //the frames are dropped while paused
+        session
+            .handle_pause(&request("PAUSE", session_id))
+            .await
+            .unwrap();
+        frame_sender.send(audio_frame(160)).unwrap();
+        forward_frames(&mut session).await;
+        assert!(packet_receiver.try_recv().is_err());
+
+        //PLAY resumes from the live position
+        session
+            .handle_play(&request("PLAY", session_id))
+            .await
+            .unwrap();
+        frame_sender.send(audio_frame(320)).unwrap();
+        forward_frames(&mut session).await;
+        assert_eq!(packet_receiver.try_recv().unwrap().header.timestamp, 320);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let (mut session, _frame_sender, _packet_receiver, _read_sender) = playing_session().await;
+        session.set_session_timeout(Duration::from_millis(100));
+
+        //the UDP RTP/RTCP keeps the session alive for one more timeout
+        session.tracks[&TrackType::Audio]
+            .udp_received
+            .store(true, Ordering::Relaxed);
+        let start = tokio::time::Instant::now();
+        let err = session.read_data().await.err().unwrap();
+        assert!(matches!(err.value, SessionErrorValue::SessionTimeout));
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    async fn test_udp_rtp_activity() {
+        let codec_info = RtspCodecInfo {
+            codec_id: RtspCodecId::G711A,
+            payload_type: 8,
+            sample_rate: 8000,
+            channel_count: 1,
+        };
+        let mut track = RtspTrack::new(TrackType::Audio, codec_info, String::from("track1"));
+        let (rtp_io, rtp_sender) = test_io();
+        track.rtp_receive_loop(rtp_io).await;
+
+        rtp_sender.send(BytesMut::from(&[0x80, 0x08][..])).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(track.udp_received.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_gen_session_header() {
+        assert_eq!(
+            RtspServerSession::gen_session_header(&None, Duration::from_secs(60)),
+            None
+        );
+
+        let session_id = Uuid::new(RandomDigitCount::Zero);
+        assert_eq!(
+            RtspServerSession::gen_session_header(&Some(session_id), Duration::from_secs(30)),
+            Some(format!("{session_id};timeout=30"))
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::errors::Aac2OpusError;
+use audiopus::coder::Encoder as OpusEncoder;
+use fdk_aac::dec::{Decoder as AacDecoder, Transport};
+
+//the opus frames are 20ms
+const OPUS_FRAMES_PER_SECOND: usize = 50;
+//the max size of an opus packet is 1275 bytes
+const OPUS_MAX_PACKET_SIZE: usize = 1500;
+
+/*Decodes the ADTS frames to pcm, converts the pcm to the channels and
+sample rate of the opus encoder and encodes it to 20ms opus packets. The pcm
+decoded from other codecs(e.g. G.711) can be encoded by encode_pcm.*/
+pub struct Aac2OpusTranscoder {
+    decoder: AacDecoder,
+    encoder: OpusEncoder,
+    encoder_sample_rate: usize,
+    encoder_channels_size: usize,
+    //the pcm converted to the encoder channels, waiting for resampling
+    resample_data: Vec<i16>,
+    //the position of the next resampled sample in resample_data
+    resample_position: f64,
+    pcm_data: Vec<i16>,
+}
+
+impl Aac2OpusTranscoder {
+    pub fn new(
+        encoder_sample_rate: i32,
+        encoder_channels: audiopus::Channels,
+    ) -> Result<Self, Aac2OpusError> {
+        let encoder_channels_size = match encoder_channels {
+            audiopus::Channels::Stereo | audiopus::Channels::Auto => 2,
+            audiopus::Channels::Mono => 1,
+        };
+
+        let mut decoder = AacDecoder::new(Transport::Adts);
+        decoder.set_max_output_channels(2)?;
+
+        let encoder = OpusEncoder::new(
+            audiopus::SampleRate::try_from(encoder_sample_rate)?,
+            encoder_channels,
+            audiopus::Application::Audio,
+        )?;
+
+        Ok(Aac2OpusTranscoder {
+            decoder,
+            encoder,
+            encoder_sample_rate: encoder_sample_rate as usize,
+            encoder_channels_size,
+            resample_data: Vec::new(),
+            resample_position: 0.,
+            pcm_data: Vec::new(),
+        })
+    }
+
+    //the number of the samples per channel in one opus packet
+    pub fn frame_size(&self) -> usize {
+        self.encoder_sample_rate / OPUS_FRAMES_PER_SECOND
+    }
+
+    pub fn transcode(&mut self, input: &[u8]) -> Result<Vec<Vec<u8>>, Aac2OpusError> {
+        self.decoder.fill(input)?;
+
+        let mut pcm_output: Vec<i16> = vec![0; 1024 * 8];
+        self.decoder.decode_frame(&mut pcm_output)?;
+        let pcm_output_len = self.decoder.decoded_frame_size();
+        let stream_info = self.decoder.stream_info();
+        let decoder_sample_rate = stream_info.sampleRate as usize;
+        let decoder_channels_size = stream_info.numChannels as usize;
+
+        if decoder_channels_size == 0 || decoder_sample_rate == 0 {
+            return Ok(Vec::new());
+        }
+
+        self.encode_pcm(
+            &pcm_output[..pcm_output_len],
+            decoder_sample_rate,
+            decoder_channels_size,
+        )
+    }
+
+    //the pcm samples are interleaved if there are more than one channel
+    pub fn encode_pcm(
+        &mut self,
+        pcm: &[i16],
+        sample_rate: usize,
+        channels_size: usize,
+    ) -> Result<Vec<Vec<u8>>, Aac2OpusError> {
+        for frame in pcm.chunks_exact(channels_size) {
+            match (channels_size, self.encoder_channels_size) {
+                (1, 2) => self.resample_data.extend_from_slice(&[frame[0], frame[0]]),
+                (_, 1) => {
+                    let sum: i32 = frame.iter().map(|sample| *sample as i32).sum();
+                    self.resample_data.push((sum / channels_size as i32) as i16);
+                }
+                _ => self.resample_data.extend_from_slice(&frame[..2]),
+            }
+        }
+        self.resample(sample_rate);
+
+        let frame_size = self.frame_size() * self.encoder_channels_size;
+        let mut opus_output: Vec<u8> = vec![0; OPUS_MAX_PACKET_SIZE];
+        let mut result = Vec::new();
+        while self.pcm_data.len() >= frame_size {
+            let pcm = self.pcm_data.split_off(frame_size);
+            let opus_output_len = self.encoder.encode(&self.pcm_data, &mut opus_output)?;
+            self.pcm_data = pcm;
+            result.push(opus_output[..opus_output_len].to_vec());
+        }
+
+        Ok(result)
+    }
+
+    //converts the sample rate of resample_data using linear interpolation
+    fn resample(&mut self, decoder_sample_rate: usize) {
+        let channels = self.encoder_channels_size;
+
+        if decoder_sample_rate == self.encoder_sample_rate {
+            self.pcm_data.append(&mut self.resample_data);
+            self.resample_position = 0.;
+            return;
+        }
+
+        let step = decoder_sample_rate as f64 / self.encoder_sample_rate as f64;
+        let frames = self.resample_data.len() / channels;
+
+        while self.resample_position + 1. < frames as f64 {
+            let index = self.resample_position as usize;
+            let fraction = self.resample_position - index as f64;
+            for channel in 0..channels {
+                let cur = self.resample_data[index * channels + channel] as f64;
+                let next = self.resample_data[(index + 1) * channels + channel] as f64;
+                self.pcm_data
+                    .push((cur + (next - cur) * fraction).round() as i16);
+            }
+            self.resample_position += step;
+        }
+
+        //keep the frames which are needed by the next interpolation
+        let consumed = self.resample_position as usize;
+        self.resample_data.drain(..consumed * channels);
+        self.resample_position -= consumed as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aac2OpusTranscoder;
+
+    #[test]
+    fn test_resample() {
+        let mut transcoder = Aac2OpusTranscoder::new(48000, audiopus::Channels::Mono).unwrap();
+
+        //1 second of 44.1khz input is fed in small chunks
+        for _ in 0..441 {
+            transcoder.resample_data.extend_from_slice(&[1000; 100]);
+            transcoder.resample(44100);
+        }
+
+        let samples = transcoder.pcm_data.len();
+        assert!((47990..=48000).contains(&samples));
+        assert!(transcoder.pcm_data.iter().all(|sample| *sample == 1000));
+    }
+}
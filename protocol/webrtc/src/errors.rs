@@ -2,6 +2,7 @@
 use {
     audiopus::error::Error as OpusError,
     failure::{Backtrace, Fail},
+    fdk_aac::dec::DecoderError as AacDecoderError,
     fdk_aac::enc::EncoderError as AacEncoderError,
     std::fmt,
     std::num::ParseIntError,
@@ -99,6 +100,33 @@ impl From<AacEncoderError> for Opus2AacError {
     }
 }
 
+#[derive(Debug)]
+pub struct Aac2OpusError {
+    pub value: Aac2OpusErrorValue,
+}
+
+#[derive(Debug)]
+pub enum Aac2OpusErrorValue {
+    OpusError(OpusError),
+    AacDecoderError(AacDecoderError),
+}
+
+impl From<OpusError> for Aac2OpusError {
+    fn from(error: OpusError) -> Self {
+        Aac2OpusError {
+            value: Aac2OpusErrorValue::OpusError(error),
+        }
+    }
+}
+
+impl From<AacDecoderError> for Aac2OpusError {
+    fn from(error: AacDecoderError) -> Self {
+        Aac2OpusError {
+            value: Aac2OpusErrorValue::AacDecoderError(error),
+        }
+    }
+}
+
 // impl fmt::Display for Opus2AacError {
 //     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 //         fmt::Display::fmt(&self.value, f)
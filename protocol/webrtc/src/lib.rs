@@ -6,3 +6,5 @@ pub mod whep;
 pub mod whip;
 pub mod opus2aac;
 pub mod rtp_queue;
+pub mod aac2opus;
+pub mod remuxer;
@@ -0,0 +1,419 @@
+use {
+    super::errors::{WebRTCRemuxerError, WebRTCRemuxerErrorValue},
+    crate::{aac2opus::Aac2OpusTranscoder, session::WebRTCStreamHandler},
+    bytes::{Bytes, BytesMut},
+    g711::{G711Law, G711_SAMPLE_RATE},
+    std::{sync::Arc, time::Duration},
+    streamhub::{
+        define::{
+            FrameData, FrameDataReceiver, NotifyInfo, PacketData, PacketDataSender, PublishType,
+            PublisherInfo, StreamHubEvent, StreamHubEventSender, SubscribeType, SubscriberInfo,
+        },
+        errors::{StreamHubError, StreamHubErrorValue},
+        queue,
+        stream::StreamIdentifier,
+        utils::{RandomDigitCount, Uuid},
+    },
+    tokio::{sync::oneshot, time::sleep},
+    webrtc::{
+        rtp::{
+            codecs::{h264::H264Payloader, opus::OpusPayloader},
+            header::Header,
+            packet::Packet,
+            packetizer::Payloader,
+        },
+        util::Marshal,
+    },
+    xflv::{
+        define::{AvcCodecId, SoundFormat},
+        demuxer::{FlvAudioTagDemuxer, FlvVideoTagDemuxer},
+    },
+};
+
+//the payload types are the same as the ones registered by whep
+const VIDEO_PAYLOAD_TYPE: u8 = 96;
+const AUDIO_PAYLOAD_TYPE: u8 = 111;
+const VIDEO_SSRC: u32 = 0x1000;
+const AUDIO_SSRC: u32 = 0x2000;
+const VIDEO_CLOCK_RATE: i64 = 90000;
+const OPUS_SAMPLE_RATE: i32 = 48000;
+const RTP_MTU: usize = 1200;
+
+//packs the payloads of one frame to the marshalled rtp packets of a track
+pub struct RtpPacker {
+    payload_type: u8,
+    ssrc: u32,
+    sequence_number: u16,
+}
+
+impl RtpPacker {
+    pub fn new(payload_type: u8, ssrc: u32) -> Self {
+        Self {
+            payload_type,
+            ssrc,
+            sequence_number: 0,
+        }
+    }
+
+    pub fn pack(
+        &mut self,
+        payloads: Vec<Bytes>,
+        timestamp: u32,
+    ) -> Result<Vec<BytesMut>, WebRTCRemuxerError> {
+        let count = payloads.len();
+        let mut packets = Vec::with_capacity(count);
+
+        for (index, payload) in payloads.into_iter().enumerate() {
+            let packet = Packet {
+                header: Header {
+                    version: 2,
+                    //the marker is set on the last packet of a frame
+                    marker: index + 1 == count,
+                    payload_type: self.payload_type,
+                    sequence_number: self.sequence_number,
+                    timestamp,
+                    ssrc: self.ssrc,
+                    ..Default::default()
+                },
+                payload,
+            };
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+            packets.push(BytesMut::from(&packet.marshal()?[..]));
+        }
+
+        Ok(packets)
+    }
+}
+
+/*Subscribes the frames of a rtmp stream and publishes them as a webrtc stream
+which can be played by whep. The h264 frames are converted to annexb and
+packetized to rtp directly, the aac frames are transcoded to opus first
+because the browsers do not support aac in webrtc, and the G.711 samples are
+decoded to pcm and encoded to opus.*/
+pub struct Rtmp2WebRTCRemuxerSession {
+    event_producer: StreamHubEventSender,
+    app_name: String,
+    stream_name: String,
+    //RTMP
+    data_receiver: FrameDataReceiver,
+    subscribe_id: Uuid,
+    video_demuxer: FlvVideoTagDemuxer,
+    audio_demuxer: FlvAudioTagDemuxer,
+
+    //WebRTC
+    publish_id: Uuid,
+    data_sender: Option<PacketDataSender>,
+    stream_handler: Arc<WebRTCStreamHandler>,
+    h264_payloader: H264Payloader,
+    opus_payloader: OpusPayloader,
+    video_packer: RtpPacker,
+    audio_packer: RtpPacker,
+    aac2opus: Option<Aac2OpusTranscoder>,
+    //the rtp timestamp of the next opus packet
+    audio_timestamp: Option<u32>,
+}
+
+impl Rtmp2WebRTCRemuxerSession {
+    pub fn new(
+        app_name: String,
+        stream_name: String,
+        event_producer: StreamHubEventSender,
+    ) -> Self {
+        let (_, data_consumer) = queue::unbounded();
+
+        Self {
+            event_producer,
+            app_name,
+            stream_name,
+            data_receiver: data_consumer,
+            subscribe_id: Uuid::new(RandomDigitCount::Four),
+            video_demuxer: FlvVideoTagDemuxer::new(),
+            audio_demuxer: FlvAudioTagDemuxer::new(),
+
+            publish_id: Uuid::new(RandomDigitCount::Four),
+            data_sender: None,
+            stream_handler: Arc::new(WebRTCStreamHandler::default()),
+            h264_payloader: H264Payloader::default(),
+            opus_payloader: OpusPayloader,
+            video_packer: RtpPacker::new(VIDEO_PAYLOAD_TYPE, VIDEO_SSRC),
+            audio_packer: RtpPacker::new(AUDIO_PAYLOAD_TYPE, AUDIO_SSRC),
+            aac2opus: None,
+            audio_timestamp: None,
+        }
+    }
+
+    pub async fn run(&mut self) -> Result<(), WebRTCRemuxerError> {
+        match self.publish_webrtc().await {
+            //the webrtc stream is published by a whip client and remuxed to rtmp
+            Err(WebRTCRemuxerError {
+                value:
+                    WebRTCRemuxerErrorValue::ChannelError(StreamHubError {
+                        value: StreamHubErrorValue::Exists,
+                    }),
+            }) => {
+                log::info!(
+                    "rtmp2webrtc: the webrtc stream {}/{} exists",
+                    self.app_name,
+                    self.stream_name
+                );
+                return Ok(());
+            }
+            result => result?,
+        }
+
+        let result = match self.subscribe_rtmp().await {
+            Ok(()) => {
+                let result = self.receive_rtmp_data().await;
+                self.unsubscribe_rtmp().await?;
+                result
+            }
+            Err(err) => Err(err),
+        };
+        self.unpublish_webrtc().await?;
+
+        result
+    }
+
+    pub async fn publish_webrtc(&mut self) -> Result<(), WebRTCRemuxerError> {
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+        let publish_event = StreamHubEvent::Publish {
+            identifier: StreamIdentifier::WebRTC {
+                app_name: self.app_name.clone(),
+                stream_name: self.stream_name.clone(),
+            },
+            info: self.get_publisher_info(),
+            result_sender: event_result_sender,
+            stream_handler: self.stream_handler.clone(),
+        };
+
+        if self.event_producer.send(publish_event).is_err() {
+            return Err(WebRTCRemuxerError {
+                value: WebRTCRemuxerErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        self.data_sender = event_result_receiver.await??.1;
+        Ok(())
+    }
+
+    pub async fn unpublish_webrtc(&mut self) -> Result<(), WebRTCRemuxerError> {
+        let unpublish_event = StreamHubEvent::UnPublish {
+            identifier: StreamIdentifier::WebRTC {
+                app_name: self.app_name.clone(),
+                stream_name: self.stream_name.clone(),
+            },
+            info: self.get_publisher_info(),
+        };
+        if let Err(err) = self.event_producer.send(unpublish_event) {
+            log::error!("unpublish_webrtc err {}", err);
+        }
+
+        Ok(())
+    }
+
+    pub async fn subscribe_rtmp(&mut self) -> Result<(), WebRTCRemuxerError> {
+        let (event_result_sender, event_result_receiver) = oneshot::channel();
+
+        let subscribe_event = StreamHubEvent::Subscribe {
+            identifier: StreamIdentifier::Rtmp {
+                app_name: self.app_name.clone(),
+                stream_name: self.stream_name.clone(),
+            },
+            info: self.get_subscriber_info(),
+            result_sender: event_result_sender,
+        };
+
+        if self.event_producer.send(subscribe_event).is_err() {
+            return Err(WebRTCRemuxerError {
+                value: WebRTCRemuxerErrorValue::StreamHubEventSendErr,
+            });
+        }
+
+        let receiver = event_result_receiver.await??.0;
+        self.data_receiver = receiver.frame_receiver.unwrap();
+        Ok(())
+    }
+
+    pub async fn unsubscribe_rtmp(&mut self) -> Result<(), WebRTCRemuxerError> {
+        let subscribe_event = StreamHubEvent::UnSubscribe {
+            identifier: StreamIdentifier::Rtmp {
+                app_name: self.app_name.clone(),
+                stream_name: self.stream_name.clone(),
+            },
+            info: self.get_subscriber_info(),
+        };
+        if let Err(err) = self.event_producer.send(subscribe_event) {
+            log::error!("unsubscribe_rtmp err {}", err);
+        }
+
+        Ok(())
+    }
+
+    pub async fn receive_rtmp_data(&mut self) -> Result<(), WebRTCRemuxerError> {
+        let mut retry_count = 0;
+
+        loop {
+            if let Some(data) = self.data_receiver.recv().await {
+                match data {
+                    FrameData::Audio { timestamp, data } => self.on_rtmp_audio(data, timestamp)?,
+                    FrameData::Video { timestamp, data } => self.on_rtmp_video(data, timestamp)?,
+                    _ => continue,
+                };
+                retry_count = 0;
+            } else {
+                sleep(Duration::from_millis(100)).await;
+                retry_count += 1;
+            }
+
+            if retry_count > 10 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_rtmp_audio(&mut self, data: BytesMut, timestamp: u32) -> Result<(), WebRTCRemuxerError> {
+        //the aac frames are demuxed to adts which can be decoded by fdk-aac,
+        //and the G.711 samples are returned as they are
+        let audio_data = self.audio_demuxer.demux(timestamp, data)?;
+        if !audio_data.has_data {
+            return Ok(());
+        }
+
+        if self.aac2opus.is_none() {
+            match Aac2OpusTranscoder::new(OPUS_SAMPLE_RATE, audiopus::Channels::Stereo) {
+                Ok(transcoder) => self.aac2opus = Some(transcoder),
+                Err(err) => {
+                    log::error!("rtmp2webrtc new aac2opus transcoder error: {:?}", err);
+                    return Err(WebRTCRemuxerError {
+                        value: WebRTCRemuxerErrorValue::Aac2OpusError,
+                    });
+                }
+            }
+        }
+
+        let g711_law = match audio_data.sound_format {
+            sound_format if sound_format == SoundFormat::G711A as u8 => Some(G711Law::ALaw),
+            sound_format if sound_format == SoundFormat::G711U as u8 => Some(G711Law::MuLaw),
+            _ => None,
+        };
+
+        let Some(transcoder) = self.aac2opus.as_mut() else {
+            return Ok(());
+        };
+        let result = match g711_law {
+            Some(law) => {
+                transcoder.encode_pcm(&law.decode(&audio_data.data), G711_SAMPLE_RATE as usize, 1)
+            }
+            None => transcoder.transcode(&audio_data.data),
+        };
+        let opus_packets = match result {
+            Ok(opus_packets) => opus_packets,
+            Err(err) => {
+                log::warn!("rtmp2webrtc transcode audio to opus error: {:?}", err);
+                return Ok(());
+            }
+        };
+        let frame_size = transcoder.frame_size() as u32;
+
+        for opus_packet in opus_packets {
+            let audio_timestamp = *self
+                .audio_timestamp
+                .get_or_insert((timestamp as i64 * OPUS_SAMPLE_RATE as i64 / 1000) as u32);
+            self.audio_timestamp = Some(audio_timestamp.wrapping_add(frame_size));
+
+            let payloads = self
+                .opus_payloader
+                .payload(RTP_MTU, &Bytes::from(opus_packet))?;
+            for data in self.audio_packer.pack(payloads, audio_timestamp)? {
+                self.send_packet(PacketData::Audio {
+                    timestamp: audio_timestamp,
+                    data,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_rtmp_video(&mut self, data: BytesMut, timestamp: u32) -> Result<(), WebRTCRemuxerError> {
+        let video_data = match self.video_demuxer.demux(timestamp, data)? {
+            Some(video_data) if video_data.codec_id == AvcCodecId::H264 as u8 => video_data,
+            _ => return Ok(()),
+        };
+
+        let video_timestamp = (video_data.pts * (VIDEO_CLOCK_RATE / 1000)) as u32;
+        let payloads = self
+            .h264_payloader
+            .payload(RTP_MTU, &video_data.data.freeze())?;
+        for data in self.video_packer.pack(payloads, video_timestamp)? {
+            self.send_packet(PacketData::Video {
+                timestamp: video_timestamp,
+                data,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn send_packet(&mut self, packet_data: PacketData) {
+        if let Some(sender) = &self.data_sender {
+            if let Err(err) = sender.send(packet_data) {
+                log::error!("rtmp2webrtc send packet error: {}", err);
+            }
+        }
+    }
+
+    fn get_subscriber_info(&self) -> SubscriberInfo {
+        SubscriberInfo {
+            id: self.subscribe_id,
+            sub_type: SubscribeType::RtmpRemux2WebRTC,
+            sub_data_type: streamhub::define::SubDataType::Frame,
+            notify_info: NotifyInfo {
+                request_url: String::from(""),
+                remote_addr: String::from(""),
+            },
+        }
+    }
+
+    fn get_publisher_info(&self) -> PublisherInfo {
+        PublisherInfo {
+            id: self.publish_id,
+            pub_type: PublishType::WebRtcRemux,
+            pub_data_type: streamhub::define::PubDataType::Packet,
+            notify_info: NotifyInfo {
+                request_url: String::from(""),
+                remote_addr: String::from(""),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::RtpPacker,
+        bytes::Bytes,
+        webrtc::{rtp::packet::Packet, util::Unmarshal},
+    };
+
+    #[test]
+    fn test_rtp_packer() {
+        let mut packer = RtpPacker::new(96, 1);
+        packer.sequence_number = u16::MAX;
+
+        let payloads = vec![Bytes::from_static(&[1, 2]), Bytes::from_static(&[3])];
+        let packets = packer.pack(payloads, 9000).unwrap();
+        assert_eq!(packets.len(), 2);
+
+        let first = Packet::unmarshal(&mut &packets[0][..]).unwrap();
+        let second = Packet::unmarshal(&mut &packets[1][..]).unwrap();
+        assert_eq!(first.header.sequence_number, u16::MAX);
+        assert_eq!(second.header.sequence_number, 0);
+        assert!(!first.header.marker);
+        assert!(second.header.marker);
+        assert_eq!(second.header.timestamp, 9000);
+        assert_eq!(&second.payload[..], &[3]);
+    }
+}
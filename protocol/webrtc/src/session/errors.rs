@@ -3,7 +3,7 @@ use streamhub::errors::StreamHubError;
 use {
     bytesio::bytes_errors::BytesReadError,
     bytesio::{bytes_errors::BytesWriteError, bytesio_errors::BytesIOError},
-    commonlib::errors::AuthError,
+    commonlib::errors::{AclError, AuthError},
     failure::{Backtrace, Fail},
     std::fmt,
     std::str::Utf8Error,
@@ -34,6 +34,8 @@ pub enum SessionErrorValue {
     RecvError(#[cause] RecvError),
     #[fail(display = "Auth err: {}", _0)]
     AuthError(#[cause] AuthError),
+    #[fail(display = "Acl err: {}", _0)]
+    AclError(#[cause] AclError),
     #[fail(display = "stream hub event send error")]
     StreamHubEventSendErr,
     #[fail(display = "cannot receive frame data from stream hub")]
@@ -114,6 +116,14 @@ impl From<AuthError> for SessionError {
     }
 }
 
+impl From<AclError> for SessionError {
+    fn from(error: AclError) -> Self {
+        SessionError {
+            value: SessionErrorValue::AclError(error),
+        }
+    }
+}
+
 impl fmt::Display for SessionError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(&self.value, f)
@@ -5,7 +5,7 @@ use streamhub::{
         StreamHubEventSender, SubscribeType, SubscriberInfo, TStreamHandler,
     },
     errors::StreamHubError,
-    statistics::StatisticsStream,
+    statistics::{metrics, StatisticsStream},
     stream::StreamIdentifier,
     utils::{RandomDigitCount, Uuid},
 };
@@ -15,7 +15,7 @@ use tokio::sync::{broadcast, oneshot};
 use bytesio::bytesio::TNetIO;
 use bytesio::bytesio::TcpIO;
 use std::io::Read;
-use std::{collections::HashMap, fs::File, sync::Arc};
+use std::{collections::HashMap, fs::File, net::SocketAddr, sync::Arc};
 use tokio::net::TcpStream;
 
 use commonlib::http::{parse_content_length, HttpRequest, HttpResponse};
@@ -24,7 +24,7 @@ use commonlib::{auth::SecretCarrier, define::http_method_name};
 use commonlib::http::Marshal as HttpMarshal;
 use commonlib::http::Unmarshal as HttpUnmarshal;
 
-use commonlib::auth::Auth;
+use commonlib::{acl::AccessControl, auth::Auth};
 
 use super::whep::handle_whep;
 use super::whip::handle_whip;
@@ -52,6 +52,9 @@ pub struct WebRTCServerSession {
     pub peer_connection: Option<Arc<RTCPeerConnection>>,
 
     auth: Option<Auth>,
+    //the publish/play directions of the remote ip are checked if set
+    access_control: Option<Arc<AccessControl>>,
+    remote_addr: Option<SocketAddr>,
 }
 
 impl WebRTCServerSession {
@@ -60,6 +63,7 @@ impl WebRTCServerSession {
         event_producer: StreamHubEventSender,
         auth: Option<Auth>,
     ) -> Self {
+        let remote_addr = stream.peer_addr().ok();
         let net_io: Box<dyn TNetIO + Send + Sync> = Box::new(TcpIO::new(stream));
         let io = Arc::new(Mutex::new(net_io));
 
@@ -73,9 +77,25 @@ impl WebRTCServerSession {
             http_request_data: None,
             peer_connection: None,
             auth,
+            access_control: None,
+            remote_addr,
         }
     }
 
+    pub fn set_access_control(&mut self, access_control: Arc<AccessControl>) {
+        self.access_control = Some(access_control);
+    }
+
+    fn check_access(&self, is_pull: bool) -> Result<(), SessionError> {
+        if let (Some(access_control), Some(remote_addr)) = (&self.access_control, self.remote_addr)
+        {
+            access_control
+                .check(remote_addr.ip(), is_pull)
+                .inspect_err(|_| metrics::inc_acl_rejection("webrtc"))?;
+        }
+        Ok(())
+    }
+
     pub async fn close_peer_connection(&self) -> Result<(), SessionError> {
         if let Some(pc) = &self.peer_connection {
             pc.close().await?;
@@ -197,15 +217,19 @@ impl WebRTCServerSession {
 
                     match t.to_lowercase().as_str() {
                         "whip" => {
+                            self.check_access(false)?;
                             if let Some(auth) = &self.auth {
-                                auth.authenticate(&stream_name, &token_carrier, false)?;
+                                auth.authenticate(&app_name, &stream_name, &token_carrier, false)
+                                    .inspect_err(|_| metrics::inc_auth_failure("webrtc"))?;
                             }
                             self.publish_whip(app_name, stream_name, path, offer)
                                 .await?;
                         }
                         "whep" => {
+                            self.check_access(true)?;
                             if let Some(auth) = &self.auth {
-                                auth.authenticate(&stream_name, &token_carrier, true)?;
+                                auth.authenticate(&app_name, &stream_name, &token_carrier, true)
+                                    .inspect_err(|_| metrics::inc_auth_failure("webrtc"))?;
                             }
                             self.subscribe_whep(app_name, stream_name, path, offer)
                                 .await?;
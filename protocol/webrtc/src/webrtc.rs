@@ -2,11 +2,12 @@ use streamhub::define::StreamHubEventSender;
 
 use super::session::WebRTCServerSession;
 
-use commonlib::auth::Auth;
 use commonlib::define::http_method_name;
+use commonlib::{acl::AccessControl, auth::Auth};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
+use streamhub::statistics::metrics;
 use streamhub::utils::Uuid;
 use tokio::io::Error;
 use tokio::net::TcpListener;
@@ -17,6 +18,7 @@ pub struct WebRTCServer {
     event_producer: StreamHubEventSender,
     uuid_2_sessions: Arc<Mutex<HashMap<Uuid, Arc<Mutex<WebRTCServerSession>>>>>,
     auth: Option<Auth>,
+    access_control: Option<Arc<AccessControl>>,
 }
 
 impl WebRTCServer {
@@ -26,23 +28,49 @@ impl WebRTCServer {
             event_producer,
             uuid_2_sessions: Arc::new(Mutex::new(HashMap::new())),
             auth,
+            access_control: None,
         }
     }
 
+    pub fn set_access_control(&mut self, access_control: Arc<AccessControl>) {
+        self.access_control = Some(access_control);
+    }
+
     pub async fn run(&mut self) -> Result<(), Error> {
         let socket_addr: &SocketAddr = &self.address.parse().unwrap();
         let listener = TcpListener::bind(socket_addr).await?;
 
         log::info!("WebRTC server listening on tcp://{}", socket_addr);
         loop {
-            let (tcp_stream, _) = listener.accept().await?;
-            let session = Arc::new(Mutex::new(WebRTCServerSession::new(
+            let (tcp_stream, remote_addr) = listener.accept().await?;
+            let connection_guard = match &self.access_control {
+                Some(access_control) => match access_control.check_connection(remote_addr.ip()) {
+                    Ok(guard) => Some(guard),
+                    Err(err) => {
+                        log::warn!(
+                            "webrtc connection from {} is rejected: {}",
+                            remote_addr,
+                            err
+                        );
+                        metrics::inc_acl_rejection("webrtc");
+                        continue;
+                    }
+                },
+                None => None,
+            };
+            let mut session = WebRTCServerSession::new(
                 tcp_stream,
                 self.event_producer.clone(),
                 self.auth.clone(),
-            )));
+            );
+            if let Some(access_control) = &self.access_control {
+                session.set_access_control(access_control.clone());
+            }
+            let session = Arc::new(Mutex::new(session));
             let uuid_2_sessions = self.uuid_2_sessions.clone();
             tokio::spawn(async move {
+                //the connection is counted until the http request is handled
+                let _connection_guard = connection_guard;
                 let mut session_unlock = session.lock().await;
                 if let Err(err) = session_unlock.run(uuid_2_sessions.clone()).await {
                     log::error!("session run error, err: {}", err);
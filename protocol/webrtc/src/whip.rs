@@ -5,8 +5,7 @@ use super::errors::WebRTCErrorValue;
 use bytes::BytesMut;
 use std::sync::Arc;
 use streamhub::define::VideoCodecType;
-use streamhub::define::{FrameData, PacketData};
-use tokio::sync::mpsc::UnboundedSender;
+use streamhub::define::{FrameData, FrameDataSender, PacketData, PacketDataSender};
 use webrtc::rtp::codecs::opus::OpusPacket;
 
 use tokio::time::Duration;
@@ -88,8 +87,8 @@ pub(crate) fn parse_rtpmap(rtpmap: &str) -> Result<Codec> {
 
 pub async fn handle_whip(
     offer: RTCSessionDescription,
-    frame_sender: Option<UnboundedSender<FrameData>>,
-    packet_sender: Option<UnboundedSender<PacketData>>,
+    frame_sender: Option<FrameDataSender>,
+    packet_sender: Option<PacketDataSender>,
 ) -> Result<(RTCSessionDescription, Arc<RTCPeerConnection>)> {
     // Create a MediaEngine object to configure the supported codec
     let mut m = MediaEngine::default();
@@ -240,6 +239,7 @@ pub async fn handle_whip(
                     audio_clock_rate: audio_codec.clock_rate,
                     video_clock_rate: video_codec.clock_rate,
                     vcodec,
+                    acodec: streamhub::define::AudioCodecType::Opus,
                 },
             };
 